@@ -0,0 +1,43 @@
+use super::flatten_book;
+use hvmc::ast::Book;
+use std::fmt::Write;
+
+/// The C runtime, with a `/* BOOK */` placeholder for the compiled definitions.
+const RUNTIME: &str = include_str!("runtime.c");
+
+/// Generates a standalone C program that normalizes the entrypoint of an hvmc book and prints the result.
+pub fn book_to_c(book: &Book) -> Result<String, String> {
+  let book = flatten_book(book)?;
+
+  let mut code = String::new();
+  let mut defs = String::new();
+  for (id, def) in book.defs.iter().enumerate() {
+    let nodes = write_array(&mut code, &format!("DEF_{id}_NODES"), &def.nodes);
+    let rbag = write_array(&mut code, &format!("DEF_{id}_RBAG"), &def.rbag);
+    writeln!(
+      defs,
+      "  {{\"{}\", {}, 0x{:x}, {}, {nodes}, {}, {}, {rbag}}},",
+      def.name,
+      def.safe as u8,
+      def.root,
+      def.nodes.len() / 2,
+      def.vars,
+      def.rbag.len() / 2,
+    )
+    .unwrap();
+  }
+  writeln!(code, "\nstatic const Def BOOK[] = {{\n{defs}}};").unwrap();
+  writeln!(code, "\n#define MAIN_ID {}", book.main).unwrap();
+
+  Ok(RUNTIME.replace("/* BOOK */", code.trim()))
+}
+
+/// Writes the ports as a constant array, returning the expression that refers to it.
+fn write_array(code: &mut String, name: &str, ports: &[u32]) -> String {
+  if ports.is_empty() {
+    return "NULL".to_string();
+  }
+  let ports = ports.iter().map(|port| format!("0x{port:x}")).collect::<Vec<_>>().join(", ");
+  writeln!(code, "static const Port {name}[] = {{{ports}}};").unwrap();
+  name.to_string()
+}
@@ -0,0 +1,128 @@
+use crate::maybe_grow;
+use hvmc::ast::{Book, Net, Tree};
+use indexmap::IndexMap;
+use std::fmt::Write;
+
+/// Renders the net of each definition of an hvmc book as a Graphviz graph in the DOT language,
+/// with one cluster per definition.
+///
+/// Agents are colored by kind and the redexes are drawn in bold red.
+/// The auxiliary ports of each agent are laid out from left to right, in order.
+pub fn book_to_dot(book: &Book) -> String {
+  let mut out = header("book");
+  for (id, (name, net)) in book.iter().enumerate() {
+    writeln!(out, "  subgraph cluster_{id} {{\n    label=\"{}\";", escape(name)).unwrap();
+    NetWriter::new(&mut out, format!("d{id}_"), "    ").net(net);
+    writeln!(out, "  }}").unwrap();
+  }
+  out.push('}');
+  out
+}
+
+/// Renders a single net, like the result of a run, as a Graphviz graph in the DOT language.
+pub fn net_to_dot(net: &Net) -> String {
+  let mut out = header("net");
+  NetWriter::new(&mut out, String::new(), "  ").net(net);
+  out.push('}');
+  out
+}
+
+fn header(name: &str) -> String {
+  let mut out = format!("graph {name} {{\n");
+  out.push_str("  graph [ordering=out, fontname=monospace];\n");
+  out.push_str("  node [shape=circle, style=filled, fontname=monospace];\n");
+  out
+}
+
+/// What a tree is connected to: an agent, by the id of its node, or a wire, by its name.
+enum Port<'a> {
+  Node(String),
+  Var(&'a str),
+}
+
+struct NetWriter<'a, 'n> {
+  out: &'a mut String,
+  /// Prepended to the node ids, to keep the ones of different nets apart.
+  prefix: String,
+  indent: &'a str,
+  next: usize,
+  /// The node connected to the first end found of each wire.
+  vars: IndexMap<&'n str, String>,
+}
+
+impl<'a, 'n> NetWriter<'a, 'n> {
+  fn new(out: &'a mut String, prefix: String, indent: &'a str) -> Self {
+    NetWriter { out, prefix, indent, next: 0, vars: IndexMap::new() }
+  }
+
+  fn net(mut self, net: &'n Net) {
+    let root = self.node("root", "shape=point");
+    let tree = self.tree(&net.root);
+    self.link(Port::Node(root), tree, "");
+    for (_, a, b) in &net.redexes {
+      let (a, b) = (self.tree(a), self.tree(b));
+      self.link(a, b, " [style=bold, color=red]");
+    }
+    // Wires with a single end are the free variables of the net.
+    for (nam, node) in std::mem::take(&mut self.vars) {
+      let var = self.node(&escape(nam), "shape=plaintext, style=\"\"");
+      self.edge(&node, &var, "");
+    }
+  }
+
+  /// Writes the agents of a tree, returning what its principal port is connected to.
+  fn tree(&mut self, tree: &'n Tree) -> Port<'n> {
+    maybe_grow(|| {
+      let (label, color, children): (String, _, Vec<&Tree>) = match tree {
+        Tree::Var { nam } => return Port::Var(nam),
+        Tree::Era => ("*".to_string(), "gray80", vec![]),
+        Tree::Num { .. } => (tree.to_string(), "palegreen", vec![]),
+        Tree::Ref { nam } => (format!("@{nam}"), "lightyellow", vec![]),
+        Tree::Ctr { lab: 0, ports } => ("CON".to_string(), "lightblue", ports.iter().collect()),
+        Tree::Ctr { ports, .. } => ("DUP".to_string(), "lightpink", ports.iter().collect()),
+        Tree::Op { fst, snd } => ("OPR".to_string(), "orange", vec![fst, snd]),
+        Tree::Mat { zero, succ, out } => ("SWI".to_string(), "plum", vec![zero, succ, out]),
+      };
+      let node = self.node(&escape(&label), &format!("fillcolor={color}"));
+      for child in children {
+        let child = self.tree(child);
+        self.link(Port::Node(node.clone()), child, "");
+      }
+      Port::Node(node)
+    })
+  }
+
+  /// Connects two ports, which for a wire waits until both of its ends are found.
+  fn link(&mut self, a: Port<'n>, b: Port<'n>, attrs: &str) {
+    match (a, b) {
+      (Port::Node(a), Port::Node(b)) => self.edge(&a, &b, attrs),
+      (Port::Var(nam), Port::Node(node)) | (Port::Node(node), Port::Var(nam)) => {
+        match self.vars.shift_remove(nam) {
+          Some(other) => self.edge(&other, &node, attrs),
+          None => _ = self.vars.insert(nam, node),
+        }
+      }
+      // Two wires connected directly meet at a point.
+      (Port::Var(a), Port::Var(b)) => {
+        let point = self.node("", "shape=point");
+        self.link(Port::Var(a), Port::Node(point.clone()), attrs);
+        self.link(Port::Var(b), Port::Node(point), attrs);
+      }
+    }
+  }
+
+  fn node(&mut self, label: &str, attrs: &str) -> String {
+    let id = format!("{}{}", self.prefix, self.next);
+    self.next += 1;
+    writeln!(self.out, "{}{id} [label=\"{label}\", {attrs}];", self.indent).unwrap();
+    id
+  }
+
+  fn edge(&mut self, a: &str, b: &str, attrs: &str) {
+    writeln!(self.out, "{}{a} -- {b}{attrs};", self.indent).unwrap();
+  }
+}
+
+fn escape(label: &str) -> String {
+  label.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,212 @@
+pub mod c;
+pub mod dot;
+
+use crate::{maybe_grow, ENTRY_POINT};
+use hvmc::ast::{Book, Net, Tree};
+use std::collections::HashMap;
+
+// Port tags, shared by the runtimes.
+pub const VAR: u32 = 0x0;
+pub const REF: u32 = 0x1;
+pub const ERA: u32 = 0x2;
+pub const NUM: u32 = 0x3;
+pub const CON: u32 = 0x4;
+pub const DUP: u32 = 0x5;
+pub const OPR: u32 = 0x6;
+pub const SWI: u32 = 0x7;
+
+/// A book flattened into arrays of ports, which is how the runtimes store the definitions.
+///
+/// A port is a 32-bit value with a 3-bit tag and a 29-bit value.
+/// The value of a node or variable port is its index inside the definition,
+/// the value of a reference is the index of the definition and the value of a number is its bits.
+pub struct FlatBook {
+  pub defs: Vec<FlatDef>,
+  /// The index of the entrypoint definition.
+  pub main: u32,
+}
+
+pub struct FlatDef {
+  pub name: String,
+  /// Whether the definition can be copied by a duplicator instead of expanded.
+  pub safe: bool,
+  pub root: u32,
+  /// The two auxiliary ports of each node.
+  pub nodes: Vec<u32>,
+  pub vars: u32,
+  /// The two ports of each redex.
+  pub rbag: Vec<u32>,
+}
+
+pub fn flatten_book(book: &Book) -> Result<FlatBook, String> {
+  let Some(main) = book.keys().position(|name| name == ENTRY_POINT) else {
+    return Err(format!("File has no '{ENTRY_POINT}' definition."));
+  };
+
+  let ids: HashMap<&str, u32> =
+    book.keys().enumerate().map(|(id, name)| (name.as_str(), id as u32)).collect();
+  let safe = safe_defs(book);
+
+  let mut defs = vec![];
+  for (name, net) in book.iter() {
+    let def = DefEncoder::encode(net, &ids).map_err(|e| format!("In definition '{name}': {e}"))?;
+    defs.push(FlatDef {
+      name: name.clone(),
+      safe: safe[name],
+      root: def.root,
+      nodes: def.nodes,
+      vars: def.vars,
+      rbag: def.rbag,
+    });
+  }
+  Ok(FlatBook { defs, main: main as u32 })
+}
+
+struct DefEncoder<'a> {
+  ids: &'a HashMap<&'a str, u32>,
+  nodes: Vec<u32>,
+  vars: u32,
+  var_ids: HashMap<&'a str, u32>,
+  rbag: Vec<u32>,
+  root: u32,
+}
+
+impl<'a> DefEncoder<'a> {
+  fn encode(net: &'a Net, ids: &'a HashMap<&'a str, u32>) -> Result<Self, String> {
+    let mut def = DefEncoder { ids, nodes: vec![], vars: 0, var_ids: HashMap::new(), rbag: vec![], root: 0 };
+    def.root = def.encode_tree(&net.root)?;
+    for (_, a, b) in &net.redexes {
+      let a = def.encode_tree(a)?;
+      let b = def.encode_tree(b)?;
+      def.rbag.extend([a, b]);
+    }
+    Ok(def)
+  }
+
+  fn encode_tree(&mut self, tree: &'a Tree) -> Result<u32, String> {
+    maybe_grow(|| match tree {
+      Tree::Era => Ok(port(ERA, 0)),
+      Tree::Num { val } => Ok(port(NUM, *val)),
+      Tree::Ref { nam } => match self.ids.get(nam.as_str()) {
+        Some(id) => Ok(port(REF, *id)),
+        None => Err(format!("Reference to unknown definition '{nam}'.")),
+      },
+      Tree::Var { nam } => {
+        let next = self.vars;
+        let id = *self.var_ids.entry(nam).or_insert(next);
+        if id == next {
+          self.vars += 1;
+        }
+        Ok(port(VAR, id))
+      }
+      Tree::Ctr { lab, ports } => {
+        let tag = match lab {
+          0 => CON,
+          1 => DUP,
+          _ => return Err(format!("Unsupported node label {lab}.")),
+        };
+        self.encode_ctr(tag, ports)
+      }
+      Tree::Op { fst, snd } => self.encode_node(OPR, fst, snd),
+      Tree::Mat { zero, succ, out } => {
+        let loc = self.alloc_node();
+        let arms = self.encode_node(CON, zero, succ)?;
+        let out = self.encode_tree(out)?;
+        self.set_node(loc, arms, out);
+        Ok(port(SWI, loc))
+      }
+    })
+  }
+
+  /// Encodes an n-ary constructor as a chain of binary nodes.
+  fn encode_ctr(&mut self, tag: u32, ports: &'a [Tree]) -> Result<u32, String> {
+    match ports {
+      [] => Ok(port(ERA, 0)),
+      [port] => self.encode_tree(port),
+      [fst, rest @ ..] => {
+        let loc = self.alloc_node();
+        let fst = self.encode_tree(fst)?;
+        let snd = self.encode_ctr(tag, rest)?;
+        self.set_node(loc, fst, snd);
+        Ok(port(tag, loc))
+      }
+    }
+  }
+
+  fn encode_node(&mut self, tag: u32, fst: &'a Tree, snd: &'a Tree) -> Result<u32, String> {
+    let loc = self.alloc_node();
+    let fst = self.encode_tree(fst)?;
+    let snd = self.encode_tree(snd)?;
+    self.set_node(loc, fst, snd);
+    Ok(port(tag, loc))
+  }
+
+  fn alloc_node(&mut self) -> u32 {
+    self.nodes.extend([0, 0]);
+    (self.nodes.len() / 2 - 1) as u32
+  }
+
+  fn set_node(&mut self, loc: u32, fst: u32, snd: u32) {
+    self.nodes[2 * loc as usize] = fst;
+    self.nodes[2 * loc as usize + 1] = snd;
+  }
+}
+
+pub fn port(tag: u32, val: u32) -> u32 {
+  (val << 3) | tag
+}
+
+/// A definition is safe to copy instead of expanding when it has no duplicators
+/// and only refers to safe definitions.
+fn safe_defs(book: &Book) -> HashMap<&String, bool> {
+  let mut safe: HashMap<&String, bool> = book.iter().map(|(name, net)| (name, !net_has_dup(net))).collect();
+  let mut changed = true;
+  while changed {
+    changed = false;
+    for (name, net) in book.iter() {
+      if safe[name] && net_refs(net).iter().any(|nam| !safe.get(nam).copied().unwrap_or(false)) {
+        safe.insert(name, false);
+        changed = true;
+      }
+    }
+  }
+  safe
+}
+
+fn net_trees(net: &Net) -> impl Iterator<Item = &Tree> {
+  std::iter::once(&net.root).chain(net.redexes.iter().flat_map(|(_, a, b)| [a, b]))
+}
+
+fn net_has_dup(net: &Net) -> bool {
+  fn go(tree: &Tree) -> bool {
+    maybe_grow(|| match tree {
+      Tree::Ctr { lab, ports } => *lab != 0 || ports.iter().any(go),
+      Tree::Op { fst, snd } => go(fst) || go(snd),
+      Tree::Mat { zero, succ, out } => go(zero) || go(succ) || go(out),
+      Tree::Era | Tree::Num { .. } | Tree::Ref { .. } | Tree::Var { .. } => false,
+    })
+  }
+  net_trees(net).any(go)
+}
+
+fn net_refs(net: &Net) -> Vec<&String> {
+  fn go<'a>(tree: &'a Tree, refs: &mut Vec<&'a String>) {
+    maybe_grow(|| match tree {
+      Tree::Ref { nam } => refs.push(nam),
+      Tree::Ctr { ports, .. } => ports.iter().for_each(|port| go(port, refs)),
+      Tree::Op { fst, snd } => {
+        go(fst, refs);
+        go(snd, refs);
+      }
+      Tree::Mat { zero, succ, out } => {
+        go(zero, refs);
+        go(succ, refs);
+        go(out, refs);
+      }
+      Tree::Era | Tree::Num { .. } | Tree::Var { .. } => {}
+    })
+  }
+  let mut refs = vec![];
+  net_trees(net).for_each(|tree| go(tree, &mut refs));
+  refs
+}
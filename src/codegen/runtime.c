@@ -0,0 +1,681 @@
+// Sequential interaction combinator runtime for programs compiled by `bend gen-c`.
+//
+// Define `BEND_NO_MAIN` to leave out `main` and link the program into other software,
+// calling `bend_run` to normalize the entrypoint.
+
+#define _POSIX_C_SOURCE 199309L
+
+#include <math.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+
+// Types
+// -----
+
+typedef uint8_t Tag;
+typedef uint32_t Val;
+typedef uint32_t Port;
+typedef uint32_t Numb;
+
+// Port tags
+#define VAR 0x0
+#define REF 0x1
+#define ERA 0x2
+#define NUM 0x3
+#define CON 0x4
+#define DUP 0x5
+#define OPR 0x6
+#define SWI 0x7
+
+// An empty variable
+#define NONE 0xFFFFFFFF
+
+// Number types
+#define TY_SYM 0x0
+#define TY_U24 0x1
+#define TY_I24 0x2
+#define TY_F24 0x3
+
+// Number operations
+#define OP_ADD 0x4
+#define OP_SUB 0x5
+#define OP_MUL 0x6
+#define OP_DIV 0x7
+#define OP_REM 0x8
+#define OP_EQ  0x9
+#define OP_NEQ 0xA
+#define OP_LT  0xB
+#define OP_GT  0xC
+#define OP_AND 0xD
+#define OP_OR  0xE
+#define OP_XOR 0xF
+
+// Swaps the operands of a number operation
+#define FLIP 0x10000000
+
+// A closed net, instantiated every time its reference is called.
+// Node and var ports are indices local to the definition.
+typedef struct {
+  const char* name;
+  int safe;
+  Port root;
+  Val nodes_len;
+  const Port* nodes;
+  Val vars_len;
+  Val rbag_len;
+  const Port* rbag;
+} Def;
+
+static inline Port new_port(Tag tag, Val val) {
+  return (val << 3) | tag;
+}
+
+static inline Tag get_tag(Port port) {
+  return port & 0x7;
+}
+
+static inline Val get_val(Port port) {
+  return port >> 3;
+}
+
+static inline int is_node(Port port) {
+  return get_tag(port) >= CON;
+}
+
+// Book
+// ----
+
+/* BOOK */
+
+// Numbers
+// -------
+
+static inline Tag numb_tag(Numb numb) {
+  return numb & 0xF;
+}
+
+static inline uint32_t numb_u24(Numb numb) {
+  return (numb >> 4) & 0xFFFFFF;
+}
+
+static inline int32_t numb_i24(Numb numb) {
+  return ((int32_t)(numb_u24(numb) << 8)) >> 8;
+}
+
+// 24-bit floats have a sign bit, a 7-bit exponent and a 16-bit mantissa.
+static inline float numb_f24(Numb numb) {
+  uint32_t bits = numb_u24(numb);
+  uint32_t sign = (bits >> 23) & 0x1;
+  uint32_t expo = (bits >> 16) & 0x7F;
+  uint32_t mant = bits & 0xFFFF;
+  uint32_t f32 = sign << 31;
+  if (expo != 0 || mant != 0) {
+    f32 |= ((expo + 64) << 23) | (mant << 7);
+  }
+  float val;
+  memcpy(&val, &f32, sizeof(val));
+  return val;
+}
+
+static inline Numb new_u24(uint32_t val) {
+  return ((val & 0xFFFFFF) << 4) | TY_U24;
+}
+
+static inline Numb new_i24(int32_t val) {
+  return (((uint32_t)val & 0xFFFFFF) << 4) | TY_I24;
+}
+
+static inline Numb new_f24(float val) {
+  uint32_t f32;
+  memcpy(&f32, &val, sizeof(f32));
+  uint32_t sign = f32 >> 31;
+  int32_t expo = (int32_t)((f32 >> 23) & 0xFF) - 64;
+  uint32_t mant = (f32 & 0x7FFFFF) >> 7;
+  uint32_t bits;
+  if (expo < 0) {
+    bits = sign << 23;
+  } else if (expo > 0x7F) {
+    bits = (sign << 23) | (0x7F << 16) | mant;
+  } else {
+    bits = (sign << 23) | ((uint32_t)expo << 16) | mant;
+  }
+  return (bits << 4) | TY_F24;
+}
+
+static Numb operate_u24(Tag op, uint32_t a, uint32_t b) {
+  switch (op) {
+    case OP_ADD: return new_u24(a + b);
+    case OP_SUB: return new_u24(a - b);
+    case OP_MUL: return new_u24(a * b);
+    case OP_DIV: return new_u24(b == 0 ? 0 : a / b);
+    case OP_REM: return new_u24(b == 0 ? 0 : a % b);
+    case OP_EQ:  return new_u24(a == b);
+    case OP_NEQ: return new_u24(a != b);
+    case OP_LT:  return new_u24(a < b);
+    case OP_GT:  return new_u24(a > b);
+    case OP_AND: return new_u24(a & b);
+    case OP_OR:  return new_u24(a | b);
+    case OP_XOR: return new_u24(a ^ b);
+    default:     return new_u24(0);
+  }
+}
+
+static Numb operate_i24(Tag op, int32_t a, int32_t b) {
+  switch (op) {
+    case OP_ADD: return new_i24(a + b);
+    case OP_SUB: return new_i24(a - b);
+    case OP_MUL: return new_i24(a * b);
+    case OP_DIV: return new_i24(b == 0 ? 0 : a / b);
+    case OP_REM: return new_i24(b == 0 ? 0 : a % b);
+    case OP_EQ:  return new_u24(a == b);
+    case OP_NEQ: return new_u24(a != b);
+    case OP_LT:  return new_u24(a < b);
+    case OP_GT:  return new_u24(a > b);
+    case OP_AND: return new_i24(a & b);
+    case OP_OR:  return new_i24(a | b);
+    case OP_XOR: return new_i24(a ^ b);
+    default:     return new_i24(0);
+  }
+}
+
+static Numb operate_f24(Tag op, float a, float b) {
+  switch (op) {
+    case OP_ADD: return new_f24(a + b);
+    case OP_SUB: return new_f24(a - b);
+    case OP_MUL: return new_f24(a * b);
+    case OP_DIV: return new_f24(a / b);
+    case OP_REM: return new_f24(fmodf(a, b));
+    case OP_EQ:  return new_u24(a == b);
+    case OP_NEQ: return new_u24(a != b);
+    case OP_LT:  return new_u24(a < b);
+    case OP_GT:  return new_u24(a > b);
+    case OP_AND: return new_f24(atan2f(a, b));
+    case OP_OR:  return new_f24(logf(b) / logf(a));
+    case OP_XOR: return new_f24(powf(a, b));
+    default:     return new_f24(0);
+  }
+}
+
+// Applies a number to another.
+// An operation symbol applied to a number makes a partial operation,
+// and a partial operation applied to a number computes the result.
+static Numb operate(Numb a, Numb b) {
+  if ((a ^ b) & FLIP) {
+    Numb t = a; a = b; b = t;
+  }
+  Tag at = numb_tag(a);
+  Tag bt = numb_tag(b);
+  if (at == TY_SYM && bt == TY_SYM) {
+    return new_u24(0);
+  }
+  if (at == TY_SYM) {
+    return (b & ~0xF) | (numb_u24(a) & 0xF);
+  }
+  if (bt == TY_SYM) {
+    return (a & ~0xF) | (numb_u24(b) & 0xF);
+  }
+  if ((at >= OP_ADD) == (bt >= OP_ADD)) {
+    return new_u24(0);
+  }
+  Tag op = at >= OP_ADD ? at : bt;
+  Tag ty = at >= OP_ADD ? bt : at;
+  switch (ty) {
+    case TY_U24: return operate_u24(op, numb_u24(a), numb_u24(b));
+    case TY_I24: return operate_i24(op, numb_i24(a), numb_i24(b));
+    case TY_F24: return operate_f24(op, numb_f24(a), numb_f24(b));
+    default:     return new_u24(0);
+  }
+}
+
+// Net
+// ---
+
+// Each node has two auxiliary ports, stored side by side.
+static Port* nodes;
+static Val nodes_cap;
+static Val nodes_len;
+static Val* nodes_free;
+static Val nodes_free_len;
+
+static Port* vars;
+static Val vars_cap;
+static Val vars_len;
+static Val* vars_free;
+static Val vars_free_len;
+
+// Pairs of principal ports waiting to interact.
+static Port* rbag;
+static Val rbag_cap;
+static Val rbag_len;
+
+static uint64_t itrs;
+
+static void* grow(void* buf, Val* cap, size_t size) {
+  *cap = *cap == 0 ? 1024 : *cap * 2;
+  buf = realloc(buf, (size_t)*cap * size);
+  if (buf == NULL) {
+    fprintf(stderr, "Out of memory\n");
+    exit(1);
+  }
+  return buf;
+}
+
+static Val alloc_node(void) {
+  if (nodes_free_len > 0) {
+    return nodes_free[--nodes_free_len];
+  }
+  if (nodes_len == nodes_cap) {
+    nodes = grow(nodes, &nodes_cap, 2 * sizeof(Port));
+    nodes_free = realloc(nodes_free, (size_t)nodes_cap * sizeof(Val));
+  }
+  return nodes_len++;
+}
+
+static void free_node(Val loc) {
+  nodes_free[nodes_free_len++] = loc;
+}
+
+static Val alloc_var(void) {
+  Val loc;
+  if (vars_free_len > 0) {
+    loc = vars_free[--vars_free_len];
+  } else {
+    if (vars_len == vars_cap) {
+      vars = grow(vars, &vars_cap, sizeof(Port));
+      vars_free = realloc(vars_free, (size_t)vars_cap * sizeof(Val));
+    }
+    loc = vars_len++;
+  }
+  vars[loc] = NONE;
+  return loc;
+}
+
+static void free_var(Val loc) {
+  vars[loc] = NONE;
+  vars_free[vars_free_len++] = loc;
+}
+
+static void push_redex(Port a, Port b) {
+  if (rbag_len == rbag_cap) {
+    rbag = grow(rbag, &rbag_cap, 2 * sizeof(Port));
+  }
+  rbag[2 * rbag_len + 0] = a;
+  rbag[2 * rbag_len + 1] = b;
+  rbag_len++;
+}
+
+static Val new_node(Port fst, Port snd) {
+  Val loc = alloc_node();
+  nodes[2 * loc + 0] = fst;
+  nodes[2 * loc + 1] = snd;
+  return loc;
+}
+
+// Follows a variable to what was linked to its other end, consuming it.
+static Port enter(Port port) {
+  while (get_tag(port) == VAR && vars[get_val(port)] != NONE) {
+    Port got = vars[get_val(port)];
+    free_var(get_val(port));
+    port = got;
+  }
+  return port;
+}
+
+// Connects two ports, creating a redex if both are principal.
+static void link(Port a, Port b) {
+  for (;;) {
+    if (get_tag(a) != VAR && get_tag(b) == VAR) {
+      Port t = a; a = b; b = t;
+    }
+    if (get_tag(a) != VAR) {
+      push_redex(a, b);
+      return;
+    }
+    b = enter(b);
+    Val loc = get_val(a);
+    Port got = vars[loc];
+    if (got == NONE) {
+      vars[loc] = b;
+      return;
+    }
+    free_var(loc);
+    a = got;
+  }
+}
+
+// Interactions
+// ------------
+
+static Val* call_nodes;
+static Val call_nodes_cap;
+static Val* call_vars;
+static Val call_vars_cap;
+
+static inline Port adjust(Port port) {
+  if (get_tag(port) == VAR) {
+    return new_port(VAR, call_vars[get_val(port)]);
+  }
+  if (is_node(port)) {
+    return new_port(get_tag(port), call_nodes[get_val(port)]);
+  }
+  return port;
+}
+
+// Expands a definition, connecting its root to `port`.
+static void call(Val ref, Port port) {
+  const Def* def = &BOOK[ref];
+  while (call_nodes_cap < def->nodes_len) {
+    call_nodes = grow(call_nodes, &call_nodes_cap, sizeof(Val));
+  }
+  while (call_vars_cap < def->vars_len) {
+    call_vars = grow(call_vars, &call_vars_cap, sizeof(Val));
+  }
+  for (Val i = 0; i < def->nodes_len; i++) {
+    call_nodes[i] = alloc_node();
+  }
+  for (Val i = 0; i < def->vars_len; i++) {
+    call_vars[i] = alloc_var();
+  }
+  for (Val i = 0; i < def->nodes_len; i++) {
+    nodes[2 * call_nodes[i] + 0] = adjust(def->nodes[2 * i + 0]);
+    nodes[2 * call_nodes[i] + 1] = adjust(def->nodes[2 * i + 1]);
+  }
+  for (Val i = 0; i < def->rbag_len; i++) {
+    link(adjust(def->rbag[2 * i + 0]), adjust(def->rbag[2 * i + 1]));
+  }
+  link(adjust(def->root), port);
+}
+
+static void interact(Port a, Port b) {
+  if (get_tag(a) > get_tag(b)) {
+    Port t = a; a = b; b = t;
+  }
+  Tag at = get_tag(a);
+  Tag bt = get_tag(b);
+  itrs++;
+
+  // References, erasers and numbers meeting each other are simply discarded.
+  if (!is_node(b)) {
+    return;
+  }
+
+  // References are expanded, except safe ones that meet a duplicator, which are copied.
+  if (at == REF) {
+    if (bt == DUP && BOOK[get_val(a)].safe) {
+      Val bl = get_val(b);
+      link(nodes[2 * bl + 0], a);
+      link(nodes[2 * bl + 1], a);
+      free_node(bl);
+    } else {
+      call(get_val(a), b);
+    }
+    return;
+  }
+
+  Val bl = get_val(b);
+  Port b1 = nodes[2 * bl + 0];
+  Port b2 = nodes[2 * bl + 1];
+
+  if (at == NUM && bt == OPR) {
+    free_node(bl);
+    Port fst = enter(b1);
+    if (get_tag(fst) == NUM) {
+      link(b2, new_port(NUM, operate(get_val(a), get_val(fst))));
+    } else {
+      // The number waits for the other operand, with the order of the operation flipped.
+      link(fst, new_port(OPR, new_node(new_port(NUM, get_val(a) ^ FLIP), b2)));
+    }
+    return;
+  }
+
+  if (at == NUM && bt == SWI) {
+    free_node(bl);
+    Numb numb = get_val(a);
+    uint32_t val = numb_u24(numb);
+    if (val == 0) {
+      link(b1, new_port(CON, new_node(b2, new_port(ERA, 0))));
+    } else {
+      Port pred = new_port(NUM, (numb & ~(0xFFFFFF << 4)) | ((val - 1) << 4));
+      Port succ = new_port(CON, new_node(pred, b2));
+      link(b1, new_port(CON, new_node(new_port(ERA, 0), succ)));
+    }
+    return;
+  }
+
+  // Erasers and numbers are copied into both auxiliary ports.
+  if (!is_node(a)) {
+    free_node(bl);
+    link(b1, a);
+    link(b2, a);
+    return;
+  }
+
+  Val al = get_val(a);
+  Port a1 = nodes[2 * al + 0];
+  Port a2 = nodes[2 * al + 1];
+  free_node(al);
+  free_node(bl);
+
+  if (at == bt) {
+    link(a1, b1);
+    link(a2, b2);
+    return;
+  }
+
+  Val x1 = alloc_var(), x2 = alloc_var(), y1 = alloc_var(), y2 = alloc_var();
+  Port ta1 = new_port(bt, new_node(new_port(VAR, x1), new_port(VAR, x2)));
+  Port ta2 = new_port(bt, new_node(new_port(VAR, y1), new_port(VAR, y2)));
+  Port tb1 = new_port(at, new_node(new_port(VAR, x1), new_port(VAR, y1)));
+  Port tb2 = new_port(at, new_node(new_port(VAR, x2), new_port(VAR, y2)));
+  link(a1, ta1);
+  link(a2, ta2);
+  link(b1, tb1);
+  link(b2, tb2);
+}
+
+static void reduce(void) {
+  while (rbag_len > 0) {
+    rbag_len--;
+    interact(rbag[2 * rbag_len + 0], rbag[2 * rbag_len + 1]);
+  }
+}
+
+// Normalization
+// -------------
+
+// A location holding a port, either a variable or the side of a node.
+typedef struct {
+  int is_var;
+  Val idx;
+} Loc;
+
+static Port* loc_ptr(Loc loc) {
+  return loc.is_var ? &vars[loc.idx] : &nodes[loc.idx];
+}
+
+// Follows the variables of a location to the port it holds.
+static Loc resolve(Loc loc) {
+  Port port = *loc_ptr(loc);
+  while (get_tag(port) == VAR && vars[get_val(port)] != NONE) {
+    loc = (Loc){1, get_val(port)};
+    port = *loc_ptr(loc);
+  }
+  return loc;
+}
+
+// Expands the root while it is a reference, like when the entrypoint is an alias.
+// Returns whether it was expanded.
+static int expand_root(Val root) {
+  Loc loc = resolve((Loc){1, root});
+  Port port = *loc_ptr(loc);
+  if (get_tag(port) != REF) {
+    return 0;
+  }
+  Val var = alloc_var();
+  *loc_ptr(loc) = new_port(VAR, var);
+  call(get_val(port), new_port(VAR, var));
+  return 1;
+}
+
+// Output
+// ------
+
+typedef struct {
+  char* buf;
+  size_t len;
+  size_t cap;
+} Text;
+
+static void text_push(Text* text, const char* str) {
+  size_t len = strlen(str);
+  while (text->len + len + 1 > text->cap) {
+    text->cap = text->cap == 0 ? 1024 : text->cap * 2;
+    text->buf = realloc(text->buf, text->cap);
+  }
+  memcpy(text->buf + text->len, str, len + 1);
+  text->len += len;
+}
+
+static const char* op_symbol(Tag op) {
+  static const char* symbols[] = {"+", "-", "*", "/", "%", "=", "!", "<", ">", "&", "|", "^"};
+  return symbols[op - OP_ADD];
+}
+
+static void show_numb(Text* text, Numb numb) {
+  char buf[64];
+  if (numb & FLIP) {
+    text_push(text, ":");
+  }
+  Tag tag = numb_tag(numb);
+  switch (tag) {
+    case TY_SYM:
+      snprintf(buf, sizeof(buf), "[%s]", op_symbol(numb_u24(numb) & 0xF));
+      break;
+    case TY_U24:
+      snprintf(buf, sizeof(buf), "%u", numb_u24(numb));
+      break;
+    case TY_I24:
+      snprintf(buf, sizeof(buf), "%+d", numb_i24(numb));
+      break;
+    case TY_F24:
+      snprintf(buf, sizeof(buf), "%.3f", numb_f24(numb));
+      break;
+    default:
+      snprintf(buf, sizeof(buf), "[%s%u]", op_symbol(tag), numb_u24(numb));
+      break;
+  }
+  text_push(text, buf);
+}
+
+// Writes the net connected to the root in the textual format of HVM.
+static void show_net(Text* text, Val root) {
+  // Each entry is either a location to show or a string literal.
+  typedef struct {
+    Loc loc;
+    const char* str;
+  } Item;
+  Item* stack = NULL;
+  Val stack_cap = 0, stack_len = 0;
+  char buf[32];
+
+  stack = grow(stack, &stack_cap, sizeof(Item));
+  stack[stack_len++] = (Item){{1, root}, NULL};
+  while (stack_len > 0) {
+    Item item = stack[--stack_len];
+    if (item.str != NULL) {
+      text_push(text, item.str);
+      continue;
+    }
+    Loc loc = resolve(item.loc);
+    Port port = *loc_ptr(loc);
+    switch (get_tag(port)) {
+      case VAR: {
+        // Variables are named by their slot, written in letters.
+        Val val = get_val(port);
+        char* name = buf + sizeof(buf) - 1;
+        *name = '\0';
+        do {
+          *--name = 'a' + val % 26;
+          val /= 26;
+        } while (val > 0);
+        text_push(text, name);
+        break;
+      }
+      case REF:
+        text_push(text, "@");
+        text_push(text, BOOK[get_val(port)].name);
+        break;
+      case ERA:
+        text_push(text, "*");
+        break;
+      case NUM:
+        show_numb(text, get_val(port));
+        break;
+      default: {
+        static const char* open[] = {"(", "{", "$(", "?("};
+        static const char* close[] = {")", "}", ")", ")"};
+        Tag tag = get_tag(port);
+        while (stack_len + 4 > stack_cap) {
+          stack = grow(stack, &stack_cap, sizeof(Item));
+        }
+        text_push(text, open[tag - CON]);
+        stack[stack_len++] = (Item){{0, 0}, close[tag - CON]};
+        stack[stack_len++] = (Item){{0, 2 * get_val(port) + 1}, NULL};
+        stack[stack_len++] = (Item){{0, 0}, " "};
+        stack[stack_len++] = (Item){{0, 2 * get_val(port) + 0}, NULL};
+        break;
+      }
+    }
+  }
+  free(stack);
+}
+
+// API
+// ---
+
+// Normalizes the entrypoint of the program and returns the resulting net in the
+// textual format of HVM. The caller must free the returned string.
+// If `interactions` is not NULL, it receives the number of interactions performed.
+char* bend_run(uint64_t* interactions) {
+  nodes_len = nodes_free_len = 0;
+  vars_len = vars_free_len = 0;
+  rbag_len = 0;
+  itrs = 0;
+
+  Val root = alloc_var();
+  call(MAIN_ID, new_port(VAR, root));
+  do {
+    reduce();
+  } while (expand_root(root));
+
+  Text text = {NULL, 0, 0};
+  text_push(&text, "");
+  show_net(&text, root);
+  if (interactions != NULL) {
+    *interactions = itrs;
+  }
+  return text.buf;
+}
+
+#ifndef BEND_NO_MAIN
+int main(void) {
+  struct timespec start, end;
+  clock_gettime(CLOCK_MONOTONIC, &start);
+  uint64_t interactions;
+  char* result = bend_run(&interactions);
+  clock_gettime(CLOCK_MONOTONIC, &end);
+  double time = (double)(end.tv_sec - start.tv_sec) + (double)(end.tv_nsec - start.tv_nsec) / 1e9;
+
+  printf("Result: %s\n", result);
+  printf("- ITRS: %llu\n", (unsigned long long)interactions);
+  printf("- TIME: %.2fs\n", time);
+  printf("- MIPS: %.2f\n", time > 0 ? (double)interactions / time / 1e6 : 0.0);
+  // The buffers only grow, so their lengths are the most that was used at once.
+  printf("- MEMO: %llu bytes\n", (unsigned long long)((2 * (uint64_t)nodes_len + vars_len) * sizeof(Port)));
+  free(result);
+  return 0;
+}
+#endif
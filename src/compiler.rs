@@ -0,0 +1,546 @@
+use crate::{
+  compile_book,
+  diagnostics::{DiagnosticOrigin, Diagnostics, DiagnosticsConfig, JsonStr, Severity},
+  embed::Value,
+  fun::{display::DisplayFn, Book, Name, Term},
+  hvm::{
+    interpreter::{self, DefProfile, Limit, Limits, Rewrites},
+    source_map::SourceMap,
+  },
+  load_file_to_book, load_source_to_book, readback_hvm_net, run_hvm_book, CompileOpts, CompileResult,
+  RunOpts,
+};
+use std::{borrow::Cow, path::PathBuf};
+
+/// Builder for the compile and run pipeline, for using Bend as a library.
+///
+/// ```no_run
+/// use bend::compiler::{Compiler, Runtime};
+///
+/// let res = Compiler::new().entrypoint("main").load_path("program.bend").runtime(Runtime::C).run()?;
+/// println!("{}", res.term.unwrap());
+/// # Ok::<(), bend::diagnostics::Diagnostics>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Compiler {
+  opts: CompileOpts,
+  warnings: DiagnosticsConfig,
+  run_opts: RunOpts,
+  runtime: Runtime,
+  entrypoint: Option<Name>,
+  args: Option<Vec<Term>>,
+  source: Option<Source>,
+  profile: bool,
+  cache_dir: Option<PathBuf>,
+}
+
+/// Where a program is evaluated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Runtime {
+  /// The Rust implementation of hvm.
+  #[default]
+  Rust,
+  /// The C implementation of hvm.
+  C,
+  /// The Cuda implementation of hvm.
+  Cuda,
+  /// The sequential evaluator built into the compiler, which doesn't need hvm to be installed.
+  Interpreter,
+}
+
+#[derive(Clone, Debug)]
+enum Source {
+  Path(PathBuf),
+  Code { name: String, code: String },
+  Book(Book),
+  Hvm(hvmc::ast::Book),
+}
+
+#[derive(Debug)]
+pub struct RunResult {
+  /// The normal form of the program, or `None` when it ran with IO.
+  pub term: Option<Term>,
+  /// The statistics of the evaluation, as printed by the runtime.
+  pub stats: String,
+  /// The statistics of the evaluation, as far as the runtime reports them.
+  pub info: RunInfo,
+  pub diagnostics: Diagnostics,
+  /// The rewrites of each definition, sorted by their number, when the run was profiled.
+  pub profile: Option<Vec<DefProfile>>,
+  /// The definitions of the program that each net came from, to report the profile by their names.
+  pub source_map: SourceMap,
+}
+
+impl RunResult {
+  /// Displays the whole outcome of a run as a single JSON object, for tools that use the CLI as a backend:
+  /// the result as data, like [`crate::embed::Value::display_json`], and also as a term,
+  /// the diagnostics that are not allowed, and the statistics of [`RunInfo::display_json`].
+  pub fn display_json<'a>(&'a self, book: &'a Book) -> impl std::fmt::Display + 'a {
+    DisplayFn(move |f| {
+      match &self.term {
+        Some(term) => write!(
+          f,
+          "{{\"result\":{},\"term\":{},",
+          Value::from_term(term, book).display_json(book),
+          JsonStr(&term.to_string())
+        )?,
+        None => write!(f, "{{\"result\":null,\"term\":null,")?,
+      }
+      write!(f, "\"diagnostics\":[")?;
+      let diags =
+        self.diagnostics.diagnostics.iter().flat_map(|(orig, errs)| errs.iter().map(move |err| (orig, err)));
+      for (i, (orig, err)) in diags.filter(|(_, err)| err.severity != Severity::Allow).enumerate() {
+        if i != 0 {
+          write!(f, ",")?;
+        }
+        write!(f, "{}", err.display_json(orig))?;
+      }
+      write!(f, "],\"stats\":{}}}", self.info.display_json())
+    })
+  }
+}
+
+/// The statistics of an evaluation, for tools that consume them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunInfo {
+  /// The total number of rewrites.
+  pub rewrites: Option<u64>,
+  /// The rewrites of each kind, which only the interpreter counts.
+  pub breakdown: Option<Rewrites>,
+  /// How long the evaluation took in seconds, as measured by the runtime.
+  pub time: Option<f64>,
+  /// The rewrites per second.
+  pub rps: Option<f64>,
+  /// The most bytes of memory used by the net at once, which only the interpreter and the programs
+  /// built with `gen-c` measure.
+  pub memory: Option<usize>,
+  /// The number of problems found while reading back the result.
+  pub readback_errors: usize,
+  /// The limit that stopped the evaluation before the result was fully reduced, if any.
+  pub stopped: Option<Limit>,
+}
+
+impl RunInfo {
+  /// Reads the statistics printed by hvm, like `- ITRS: 23068656`, `- TIME: 0.47s` and `- MIPS: 48.82`,
+  /// and the `- MEMO: 1024 bytes` printed by the programs built with `gen-c`.
+  pub fn from_hvm_stats(stats: &str) -> Self {
+    let mut info = RunInfo::default();
+    for line in stats.lines() {
+      let Some((key, val)) = line.trim().trim_start_matches("- ").split_once(": ") else { continue };
+      match key {
+        "ITRS" => info.rewrites = val.parse().ok(),
+        "TIME" => info.time = val.trim_end_matches('s').parse().ok(),
+        "MIPS" => info.rps = val.parse::<f64>().ok().map(|mips| mips * 1_000_000.0),
+        "MEMO" => info.memory = val.trim_end_matches(" bytes").parse().ok(),
+        _ => (),
+      }
+    }
+    info
+  }
+
+  fn from_interpreter_stats(stats: &interpreter::Stats) -> Self {
+    RunInfo {
+      rewrites: Some(stats.rewrites.total()),
+      breakdown: Some(stats.rewrites),
+      memory: Some(stats.memory),
+      stopped: stats.stopped,
+      ..RunInfo::default()
+    }
+  }
+
+  /// Displays the statistics as a single JSON object, with `null` for the ones that weren't reported.
+  pub fn display_json(&self) -> impl std::fmt::Display + '_ {
+    fn opt<T: std::fmt::Display>(val: &Option<T>) -> String {
+      val.as_ref().map_or("null".to_string(), T::to_string)
+    }
+    DisplayFn(move |f| {
+      write!(f, "{{\"rewrites\":{{\"total\":{}", opt(&self.rewrites))?;
+      if let Some(Rewrites { anni, comm, eras, dref, oper }) = self.breakdown {
+        write!(f, ",\"anni\":{anni},\"comm\":{comm},\"eras\":{eras},\"dref\":{dref},\"oper\":{oper}")?;
+      }
+      write!(f, "}},\"time\":{},\"rps\":{},", opt(&self.time), opt(&self.rps))?;
+      write!(
+        f,
+        "\"memory\":{},\"readback_errors\":{},\"stopped\":",
+        opt(&self.memory),
+        self.readback_errors
+      )?;
+      match self.stopped {
+        Some(Limit::Rewrites(_)) => write!(f, "\"rewrites\"}}"),
+        Some(Limit::Timeout(_)) => write!(f, "\"timeout\"}}"),
+        Some(Limit::Memory(_)) => write!(f, "\"memory\"}}"),
+        None => write!(f, "null}}"),
+      }
+    })
+  }
+
+  fn count_readback_errors(mut self, diagnostics: &Diagnostics) -> Self {
+    self.readback_errors = diagnostics.diagnostics.get(&DiagnosticOrigin::Readback).map_or(0, Vec::len);
+    self
+  }
+}
+
+impl Compiler {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn opts(mut self, opts: CompileOpts) -> Self {
+    self.opts = opts;
+    self
+  }
+
+  pub fn warnings(mut self, warnings: DiagnosticsConfig) -> Self {
+    self.warnings = warnings;
+    self
+  }
+
+  pub fn run_opts(mut self, run_opts: RunOpts) -> Self {
+    self.run_opts = run_opts;
+    self
+  }
+
+  pub fn runtime(mut self, runtime: Runtime) -> Self {
+    self.runtime = runtime;
+    self
+  }
+
+  /// Starts the program from this definition, taking precedence over the entrypoint in the options.
+  pub fn entrypoint<'a>(mut self, entrypoint: impl Into<Cow<'a, str>>) -> Self {
+    self.entrypoint = Some(Name::new(entrypoint));
+    self
+  }
+
+  /// Caches the compiled definitions in this directory, reusing the ones that didn't change
+  /// since the last compilation. See [`crate::incremental`].
+  pub fn incremental(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+    self.cache_dir = Some(cache_dir.into());
+    self
+  }
+
+  /// Counts the rewrites performed in each definition and the time spent on them.
+  /// Only the interpreter runtime can profile a run. See [`interpreter::profile`].
+  pub fn profile(mut self, profile: bool) -> Self {
+    self.profile = profile;
+    self
+  }
+
+  /// Applies these arguments to the entrypoint.
+  pub fn args(mut self, args: Vec<Term>) -> Self {
+    self.args = Some(args);
+    self
+  }
+
+  /// Loads the program from a file, together with its imports.
+  pub fn load_path(mut self, path: impl Into<PathBuf>) -> Self {
+    self.source = Some(Source::Path(path.into()));
+    self
+  }
+
+  /// Loads the program from source code, using `name` to refer to it in diagnostics.
+  pub fn load_source(mut self, name: impl Into<String>, code: impl Into<String>) -> Self {
+    self.source = Some(Source::Code { name: name.into(), code: code.into() });
+    self
+  }
+
+  /// Uses an already loaded book as the program.
+  pub fn book(mut self, book: Book) -> Self {
+    self.source = Some(Source::Book(book));
+    self
+  }
+
+  /// Uses a program that was already compiled to hvm, which is run as it is.
+  /// Its result is read back with the builtin datatypes, since the ones it was compiled from are unknown.
+  pub fn hvm_book(mut self, book: hvmc::ast::Book) -> Self {
+    self.source = Some(Source::Hvm(book));
+    self
+  }
+
+  /// Compiles the program to hvm.
+  pub fn compile(self) -> Result<CompileResult, Diagnostics> {
+    let (mut book, opts) = self.prepare()?;
+    self.compile_prepared(&mut book, opts)
+  }
+
+  /// Compiles the program and evaluates it with the selected runtime.
+  pub fn run(self) -> Result<RunResult, Diagnostics> {
+    if self.profile && self.run_opts.lazy {
+      return Err("A lazy evaluation can't be profiled.".to_string().into());
+    }
+    if self.run_opts.threads.is_some_and(|threads| threads > 1) && self.runtime != Runtime::C {
+      return Err("Only the C runtime can run on more than one thread.".to_string().into());
+    }
+    let (mut book, opts) = self.prepare()?;
+    let cmd = match self.runtime {
+      Runtime::Interpreter => {
+        let limits = self.limits();
+        let res = self.compile_prepared(&mut book, opts)?;
+        let (net, stats, profile) = if self.profile {
+          let (net, stats, profile) = interpreter::profile(&res.core_book, limits)?;
+          (net, stats, Some(profile))
+        } else if self.run_opts.lazy {
+          let (net, stats) = interpreter::normalize_lazy(&res.core_book, limits)?;
+          (net, stats, None)
+        } else {
+          let (net, stats) = interpreter::normalize_with_stats(&res.core_book, limits)?;
+          (net, stats, None)
+        };
+        let (term, mut diags) = readback_hvm_net(
+          &net,
+          &book,
+          &res.labels,
+          self.run_opts.linear_readback,
+          self.run_opts.raw_readback,
+        );
+        diags.config = self.warnings;
+        warn_stopped(&mut diags, &stats);
+        let info = RunInfo::from_interpreter_stats(&stats).count_readback_errors(&diags);
+        let stats = display_interpreter_stats(&stats);
+        let source_map = res.source_map;
+        return Ok(RunResult { term: Some(term), stats, info, diagnostics: diags, profile, source_map });
+      }
+      _ if self.profile => return Err("Only the interpreter runtime can profile a run.".to_string().into()),
+      _ if self.run_opts.lazy => {
+        return Err("Only the interpreter runtime can evaluate lazily.".to_string().into());
+      }
+      _ if self.run_opts.max_rewrites.is_some() => {
+        return Err("Only the interpreter runtime can limit the number of rewrites.".to_string().into());
+      }
+      Runtime::Rust => "run",
+      Runtime::C => "run-c",
+      Runtime::Cuda => "run-cu",
+    };
+
+    let io = opts.io;
+    if io && self.run_opts.threads.is_some_and(|threads| threads > 1) {
+      return Err("A program with IO can't be run on more than one thread.".to_string().into());
+    }
+    let compiled = self.compile_prepared(&mut book, opts)?;
+    // Printed before running, so that the run can be cancelled if there's a problem with the program.
+    eprint!("{}", compiled.diagnostics);
+    let res =
+      run_hvm_book(&compiled.core_book, &book, &compiled.labels, self.run_opts, self.warnings, cmd, io)?;
+    let source_map = compiled.source_map;
+    Ok(match res {
+      Some((term, stats, diagnostics)) => {
+        let info = RunInfo::from_hvm_stats(&stats).count_readback_errors(&diagnostics);
+        RunResult { term: Some(term), stats, info, diagnostics, profile: None, source_map }
+      }
+      None => RunResult {
+        term: None,
+        stats: String::new(),
+        info: RunInfo::default(),
+        diagnostics: Diagnostics::new(self.warnings),
+        profile: None,
+        source_map,
+      },
+    })
+  }
+
+  /// Compiles the program and evaluates it with the built-in interpreter, passing each element of a
+  /// list result to `on_elem` as soon as it's computed, together with the diagnostics of its readback.
+  ///
+  /// The term of the result is what remains after the streamed elements, an empty list if the whole
+  /// result was streamed. See [`interpreter::normalize_stream`].
+  pub fn stream(self, mut on_elem: impl FnMut(Term, Diagnostics)) -> Result<RunResult, Diagnostics> {
+    if self.run_opts.lazy {
+      return Err("The elements of a lazy evaluation can't be streamed.".to_string().into());
+    }
+    if self.run_opts.threads.is_some_and(|threads| threads > 1) {
+      return Err("A streamed evaluation runs on a single thread.".to_string().into());
+    }
+    let (mut book, opts) = self.prepare()?;
+    let limits = self.limits();
+    let res = self.compile_prepared(&mut book, opts)?;
+    let RunOpts { linear_readback: linear, raw_readback: raw, .. } = self.run_opts;
+    let (net, stats) = interpreter::normalize_stream(&res.core_book, limits, |elem| {
+      let (term, mut diags) = readback_hvm_net(&elem, &book, &res.labels, linear, raw);
+      diags.config = self.warnings;
+      on_elem(term, diags);
+    })?;
+    let (term, mut diags) = readback_hvm_net(&net, &book, &res.labels, linear, raw);
+    diags.config = self.warnings;
+    warn_stopped(&mut diags, &stats);
+    let info = RunInfo::from_interpreter_stats(&stats).count_readback_errors(&diags);
+    let stats = display_interpreter_stats(&stats);
+    let source_map = res.source_map;
+    Ok(RunResult { term: Some(term), stats, info, diagnostics: diags, profile: None, source_map })
+  }
+
+  fn limits(&self) -> Limits {
+    Limits { max_rewrites: self.run_opts.max_rewrites, timeout: self.run_opts.timeout, max_memory: None }
+  }
+
+  fn prepare(&self) -> Result<(Book, CompileOpts), Diagnostics> {
+    let book = match &self.source {
+      Some(Source::Path(path)) => load_file_to_book(path)?,
+      Some(Source::Code { name, code }) => load_source_to_book(name, code)?,
+      Some(Source::Book(book)) => book.clone(),
+      Some(Source::Hvm(_)) => Book::builtins(),
+      None => return Err("No program was given to the compiler.".to_string().into()),
+    };
+    let mut opts = self.opts.clone();
+    if self.entrypoint.is_some() {
+      opts.entrypoint = self.entrypoint.clone();
+    }
+    if self.cache_dir.is_some() {
+      opts.cache_dir = self.cache_dir.clone();
+    }
+    Ok((book, opts))
+  }
+
+  /// Compiles the book made by [`Compiler::prepare`], or takes the program that was already compiled to hvm.
+  fn compile_prepared(&self, book: &mut Book, opts: CompileOpts) -> Result<CompileResult, Diagnostics> {
+    let Some(Source::Hvm(core_book)) = &self.source else {
+      return compile_book(book, opts, self.warnings, self.args.clone());
+    };
+    if self.args.is_some() {
+      return Err(
+        "Arguments can't be applied to a program that was already compiled to hvm.".to_string().into(),
+      );
+    }
+    Ok(CompileResult {
+      diagnostics: Diagnostics::new(self.warnings),
+      core_book: core_book.clone(),
+      labels: Default::default(),
+      timings: vec![],
+      // Without the source, the nets can still be traced back to the definitions named after them.
+      source_map: SourceMap::new(core_book, book, &Default::default()),
+    })
+  }
+}
+
+/// The statistics of the interpreter in the format printed by hvm.
+fn display_interpreter_stats(stats: &interpreter::Stats) -> String {
+  format!("- ITRS: {}\n- MEMO: {} bytes", stats.rewrites.total(), stats.memory)
+}
+
+/// Warns that the result is only partially reduced when the evaluation was stopped by a limit.
+fn warn_stopped(diags: &mut Diagnostics, stats: &interpreter::Stats) {
+  if let Some(limit) = stats.stopped {
+    let msg =
+      format!("The evaluation was stopped after reaching {limit}, so the result is only partially reduced.");
+    diags.add_diagnostic(msg, Severity::Warning, DiagnosticOrigin::Book);
+  }
+}
+
+#[test]
+fn interpreter_profile() {
+  let code = r#"
+fib 0 = 0
+fib 1 = 1
+fib n = (+ (fib (- n 1)) (fib (- n 2)))
+main = (fib 10)
+"#;
+  let res =
+    Compiler::new().load_source("profile", code).runtime(Runtime::Interpreter).profile(true).run().unwrap();
+  assert_eq!(res.term.unwrap().to_string(), "55");
+  let profile = res.profile.unwrap();
+  assert_eq!(Some(profile.iter().map(|def| def.rewrites.total()).sum::<u64>()), res.info.rewrites);
+  assert!(profile.windows(2).all(|defs| defs[0].rewrites.total() >= defs[1].rewrites.total()));
+  assert!(profile.iter().any(|def| def.name == "fib" && def.rewrites.dref > 0 && def.rewrites.oper > 0));
+
+  assert!(Compiler::new().load_source("profile", code).profile(true).run().is_err());
+}
+
+#[test]
+fn run_info_json() {
+  let info = RunInfo::from_hvm_stats("- ITRS: 10\n- TIME: 0.50s\n- MIPS: 2.00\n- MEMO: 64 bytes");
+  assert_eq!(info.rewrites, Some(10));
+  assert_eq!(info.memory, Some(64));
+  assert_eq!(info.time, Some(0.5));
+  assert_eq!(info.rps, Some(2_000_000.0));
+  assert_eq!(
+    info.display_json().to_string(),
+    r#"{"rewrites":{"total":10},"time":0.5,"rps":2000000,"memory":64,"readback_errors":0,"stopped":null}"#
+  );
+
+  let res =
+    Compiler::new().load_source("info", "main = (+ 1 2)").runtime(Runtime::Interpreter).run().unwrap();
+  let json = res.info.display_json().to_string();
+  assert!(
+    json.starts_with(r#"{"rewrites":{"total":1,"anni":0,"comm":0,"eras":0,"dref":0,"oper":1}"#),
+    "{json}"
+  );
+  assert!(json.ends_with(r#""readback_errors":0,"stopped":null}"#), "{json}");
+}
+
+#[test]
+fn run_limits() {
+  use std::time::Duration;
+
+  let run = |code: &str, run_opts: RunOpts| {
+    Compiler::new()
+      .load_source("limits", code)
+      .runtime(Runtime::Interpreter)
+      .run_opts(run_opts)
+      .run()
+      .unwrap()
+  };
+  let code = "loop = λn switch n { 0: (loop 1); _: (loop (+ n 1)) }\nmain = (loop 0)";
+
+  let res = run(code, RunOpts { max_rewrites: Some(100), ..RunOpts::default() });
+  assert_eq!(res.info.stopped, Some(Limit::Rewrites(100)));
+  assert_eq!(res.info.rewrites, Some(100));
+  assert!(res.diagnostics.has_severity(Severity::Warning));
+  assert!(res.term.is_some());
+
+  let timeout = Duration::from_millis(10);
+  let res = run(code, RunOpts { timeout: Some(timeout), ..RunOpts::default() });
+  assert_eq!(res.info.stopped, Some(Limit::Timeout(timeout)));
+
+  let res = run("main = (+ 1 2)", RunOpts { max_rewrites: Some(100), ..RunOpts::default() });
+  assert_eq!(res.term.unwrap().to_string(), "3");
+  assert_eq!(res.info.stopped, None);
+  assert!(!res.diagnostics.has_severity(Severity::Warning));
+
+  let limited = RunOpts { max_rewrites: Some(100), ..RunOpts::default() };
+  assert!(Compiler::new().load_source("limits", code).run_opts(limited).run().is_err());
+}
+
+#[test]
+fn run_lazy_interpreter() {
+  let run = |code: &str, run_opts: RunOpts| {
+    Compiler::new()
+      .load_source("lazy", code)
+      .warnings(DiagnosticsConfig::new(Severity::Allow, false))
+      .runtime(Runtime::Interpreter)
+      .run_opts(run_opts)
+      .run()
+      .unwrap()
+  };
+  let lazy = RunOpts { lazy: true, ..RunOpts::default() };
+
+  let code = "
+nats n = (List/Cons n (nats (+ n 1)))
+take n xs = switch n {
+  0: []
+  _: match xs {
+    List/Cons: (List/Cons xs.head (take n-1 xs.tail))
+    List/Nil: []
+  }
+}
+main = (take 3 (nats 0))
+";
+  let res = run(code, lazy);
+  assert_eq!(res.term.unwrap().to_string(), "[0, 1, 2]");
+  assert_eq!(res.info.stopped, None);
+  let res = run(code, RunOpts { max_rewrites: Some(10_000), ..RunOpts::default() });
+  assert!(res.info.stopped.is_some());
+
+  let code = "
+fib 0 = 0
+fib 1 = 1
+fib n = (+ (fib (- n 1)) (fib (- n 2)))
+main = (fib 10, λx (x (fib 5)))
+";
+  let strict = run(code, RunOpts::default());
+  let res = run(code, lazy);
+  assert_eq!(res.term.unwrap().to_string(), strict.term.unwrap().to_string());
+  assert!(res.info.rewrites <= strict.info.rewrites);
+
+  assert!(Compiler::new().load_source("lazy", code).run_opts(lazy).run().is_err());
+  let interpreter = || Compiler::new().load_source("lazy", code).runtime(Runtime::Interpreter).run_opts(lazy);
+  assert!(interpreter().profile(true).run().is_err());
+  assert!(interpreter().stream(|_, _| ()).is_err());
+}
@@ -0,0 +1,143 @@
+//! The configuration of a project, read from the `bend.toml` file at its root.
+//!
+//! ```toml
+//! entrypoint = "start"
+//! opts = ["inline", "no-merge"]
+//! pre-reduce-memory = 1048576
+//!
+//! [warnings]
+//! unused-definition = "allow"
+//! ```
+//!
+//! Only the subset of TOML needed for these options is supported: strings, integers,
+//! arrays of strings, and the `[warnings]` table, each on their own line.
+
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE: &str = "bend.toml";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+  /// The file the configuration was read from.
+  pub path: PathBuf,
+  /// The optimizations to enable or disable, with the names of the `-O` options, applied in order.
+  pub opts: Vec<String>,
+  /// The level of each warning, with the names of the `-W` options, applied in order.
+  pub warnings: Vec<(String, String)>,
+  pub entrypoint: Option<String>,
+  pub float_threshold: Option<usize>,
+  pub inline_threshold: Option<usize>,
+  pub pre_reduce_rewrite_limit: Option<u64>,
+  pub pre_reduce_memory: Option<usize>,
+}
+
+impl Config {
+  /// Finds the configuration of the project that `dir` is in, looking for a `bend.toml`
+  /// in it and in each of its parent directories.
+  pub fn find(dir: &Path) -> Result<Option<Config>, String> {
+    let dir = dir.canonicalize().map_err(|e| format!("While looking for '{CONFIG_FILE}': {e}"))?;
+    let Some(path) = dir.ancestors().map(|dir| dir.join(CONFIG_FILE)).find(|path| path.is_file()) else {
+      return Ok(None);
+    };
+    let code =
+      std::fs::read_to_string(&path).map_err(|e| format!("While reading '{}': {e}", path.display()))?;
+    let config = Config::parse(&code).map_err(|e| format!("In {} :\n{e}", path.display()))?;
+    Ok(Some(Config { path, ..config }))
+  }
+
+  pub fn parse(code: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    let mut in_warnings = false;
+    for (i, line) in code.lines().enumerate() {
+      let err = |msg: String| format!("- At line {}: {msg}", i + 1);
+      let line = strip_comment(line).trim();
+      if line.is_empty() {
+        continue;
+      }
+      if let Some(table) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+        match table.trim() {
+          "warnings" => in_warnings = true,
+          table => return Err(err(format!("Unknown table '{table}'. Expected 'warnings'."))),
+        }
+        continue;
+      }
+
+      let Some((key, val)) = line.split_once('=') else {
+        return Err(err(format!("Expected 'key = value', found '{line}'.")));
+      };
+      let (key, val) = (key.trim(), val.trim());
+      let invalid = |expected: &str| err(format!("Expected {expected} for '{key}', found '{val}'."));
+      if in_warnings {
+        let level = parse_str(val).ok_or_else(|| invalid("a string"))?;
+        config.warnings.push((key.to_string(), level));
+        continue;
+      }
+      match key {
+        "opts" => config.opts = parse_str_array(val).ok_or_else(|| invalid("an array of strings"))?,
+        "entrypoint" => config.entrypoint = Some(parse_str(val).ok_or_else(|| invalid("a string"))?),
+        "float-threshold" => {
+          config.float_threshold = Some(parse_int(val).ok_or_else(|| invalid("a number"))?)
+        }
+        "inline-threshold" => {
+          config.inline_threshold = Some(parse_int(val).ok_or_else(|| invalid("a number"))?)
+        }
+        "pre-reduce-rewrite-limit" => {
+          config.pre_reduce_rewrite_limit = Some(parse_int(val).ok_or_else(|| invalid("a number"))?)
+        }
+        "pre-reduce-memory" => {
+          config.pre_reduce_memory = Some(parse_int(val).ok_or_else(|| invalid("a number"))?)
+        }
+        _ => return Err(err(format!("Unknown option '{key}'."))),
+      }
+    }
+    Ok(config)
+  }
+}
+
+/// Removes the comment at the end of a line, if it's not inside a string.
+fn strip_comment(line: &str) -> &str {
+  let mut in_str = false;
+  let mut escaped = false;
+  for (i, c) in line.char_indices() {
+    match c {
+      _ if escaped => escaped = false,
+      '\\' if in_str => escaped = true,
+      '"' => in_str = !in_str,
+      '#' if !in_str => return &line[.. i],
+      _ => (),
+    }
+  }
+  line
+}
+
+fn parse_str(val: &str) -> Option<String> {
+  let val = val.strip_prefix('"')?.strip_suffix('"')?;
+  let mut str = String::new();
+  let mut chars = val.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => match chars.next()? {
+        'n' => str.push('\n'),
+        't' => str.push('\t'),
+        c @ ('"' | '\\') => str.push(c),
+        _ => return None,
+      },
+      '"' => return None,
+      c => str.push(c),
+    }
+  }
+  Some(str)
+}
+
+fn parse_str_array(val: &str) -> Option<Vec<String>> {
+  let val = val.strip_prefix('[')?.strip_suffix(']')?.trim();
+  let val = val.strip_suffix(',').unwrap_or(val);
+  if val.trim().is_empty() {
+    return Some(vec![]);
+  }
+  val.split(',').map(|el| parse_str(el.trim())).collect()
+}
+
+fn parse_int<T: std::str::FromStr>(val: &str) -> Option<T> {
+  val.replace('_', "").parse().ok()
+}
@@ -1,6 +1,6 @@
 use crate::fun::{display::DisplayFn, Name};
 use std::{
-  collections::BTreeMap,
+  collections::{BTreeMap, HashMap, HashSet},
   fmt::{Display, Formatter},
 };
 
@@ -11,6 +11,10 @@ pub struct Diagnostics {
   err_counter: usize,
   pub diagnostics: BTreeMap<DiagnosticOrigin, Vec<Diagnostic>>,
   pub config: DiagnosticsConfig,
+  /// Where each definition is in the source code, used to locate the diagnostics of its rules.
+  pub def_spans: HashMap<Name, Span>,
+  /// The warning levels set for single definitions, which take precedence over the config.
+  pub def_lints: HashMap<Name, Vec<(WarningType, Severity)>>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -19,15 +23,51 @@ pub struct DiagnosticsConfig {
   pub irrefutable_match: Severity,
   pub redundant_match: Severity,
   pub unreachable_match: Severity,
+  pub unreachable_arm: Severity,
+  pub shadowed_variable: Severity,
+  pub unused_vars: Severity,
+  pub implicit_dup: Severity,
+  pub unused_dup: Severity,
   pub unused_definition: Severity,
   pub repeated_bind: Severity,
   pub recursion_cycle: Severity,
+  pub unguarded_recursion: Severity,
+  pub message_format: MessageFormat,
+  /// Whether the human format is colored with terminal escape codes.
+  pub color: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct Diagnostic {
-  message: String,
-  severity: Severity,
+  pub message: String,
+  pub severity: Severity,
+  /// A stable identifier for the kind of diagnostic, like the name of the warning that caused it.
+  pub code: Option<String>,
+  /// The location in the source code that the diagnostic refers to.
+  pub span: Option<Span>,
+  /// Additional information related to the diagnostic.
+  pub notes: Vec<String>,
+}
+
+/// A region of a source file, with 1-based lines and columns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+  /// The file the region is in, or `None` if the source didn't come from a file.
+  pub file: Option<String>,
+  pub line: usize,
+  pub column: usize,
+  /// The number of characters in the region.
+  pub length: usize,
+}
+
+/// How diagnostics are rendered when displayed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+  /// Colored text meant to be read in a terminal.
+  #[default]
+  Human,
+  /// One JSON object per diagnostic, separated by newlines.
+  Json,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -49,66 +89,131 @@ pub enum Severity {
   Error,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl Severity {
+  /// The terminal escape code of the color that diagnostics of this severity are highlighted with.
+  fn color(self) -> &'static str {
+    match self {
+      Severity::Allow => "",
+      Severity::Warning => "\x1b[33m",
+      Severity::Error => "\x1b[31m",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorType {
+  NameClash,
+  Entrypoint,
+  InvalidPattern,
+  NonExhaustiveMatch,
+  MatchTypeMismatch,
+  UnscopedExtraction,
+  InvalidOpen,
+  InvalidRecord,
+  InvalidDoBlock,
+  UnboundVariable,
+  TypeMismatch,
+  NetTooLarge,
+  ViciousCycle,
+  Inlining,
+  ConstEval,
+  UnsupportedOperator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WarningType {
   IrrefutableMatch,
   RedundantMatch,
   UnreachableMatch,
+  UnreachableArm,
+  ShadowedVariable,
+  UnusedVars,
+  ImplicitDup,
+  UnusedDup,
   UnusedDefinition,
   RepeatedBind,
   RecursionCycle,
+  UnguardedRecursion,
 }
 
 impl Diagnostics {
   pub fn new(config: DiagnosticsConfig) -> Self {
-    Self { err_counter: 0, diagnostics: Default::default(), config }
+    Self {
+      err_counter: 0,
+      diagnostics: Default::default(),
+      config,
+      def_spans: Default::default(),
+      def_lints: Default::default(),
+    }
   }
 
-  pub fn add_book_error(&mut self, err: impl std::fmt::Display) {
-    self.err_counter += 1;
-    self.add_diagnostic(err, Severity::Error, DiagnosticOrigin::Book);
+  pub fn add_book_error(&mut self, err: impl std::fmt::Display, err_type: ErrorType) {
+    self.add_error(err, err_type, DiagnosticOrigin::Book);
   }
 
-  pub fn add_rule_error(&mut self, err: impl std::fmt::Display, def_name: Name) {
-    self.err_counter += 1;
-    self.add_diagnostic(err, Severity::Error, DiagnosticOrigin::Rule(def_name.def_name_from_generated()));
+  pub fn add_rule_error(&mut self, err: impl std::fmt::Display, err_type: ErrorType, def_name: Name) {
+    self.add_error(err, err_type, DiagnosticOrigin::Rule(def_name.def_name_from_generated()));
   }
 
-  pub fn add_inet_error(&mut self, err: impl std::fmt::Display, def_name: String) {
+  pub fn add_inet_error(&mut self, err: impl std::fmt::Display, err_type: ErrorType, def_name: String) {
+    self.add_error(err, err_type, DiagnosticOrigin::Inet(def_name));
+  }
+
+  fn add_error(&mut self, err: impl std::fmt::Display, err_type: ErrorType, orig: DiagnosticOrigin) {
     self.err_counter += 1;
-    self.add_diagnostic(err, Severity::Error, DiagnosticOrigin::Inet(def_name));
+    let mut diag = Diagnostic::new(err, Severity::Error);
+    diag.code = Some(err_type.code().to_string());
+    self.push_diagnostic(diag, orig);
   }
 
   pub fn add_rule_warning(&mut self, warn: impl std::fmt::Display, warn_type: WarningType, def_name: Name) {
-    let severity = self.config.warning_severity(warn_type);
-    if severity == Severity::Error {
-      self.err_counter += 1;
-    }
-    self.add_diagnostic(warn, severity, DiagnosticOrigin::Rule(def_name.def_name_from_generated()));
+    self.add_warning(warn, warn_type, DiagnosticOrigin::Rule(def_name.def_name_from_generated()));
   }
 
   pub fn add_book_warning(&mut self, warn: impl std::fmt::Display, warn_type: WarningType) {
-    let severity = self.config.warning_severity(warn_type);
+    self.add_warning(warn, warn_type, DiagnosticOrigin::Book);
+  }
+
+  fn add_warning(&mut self, warn: impl std::fmt::Display, warn_type: WarningType, orig: DiagnosticOrigin) {
+    let def_lints = match &orig {
+      DiagnosticOrigin::Rule(nam) => self.def_lints.get(nam).map(Vec::as_slice).unwrap_or_default(),
+      _ => &[],
+    };
+    let severity = match def_lints.iter().rev().find(|(warn, _)| *warn == warn_type) {
+      Some((_, severity)) => *severity,
+      None => self.config.warning_severity(warn_type),
+    };
     if severity == Severity::Error {
       self.err_counter += 1;
     }
-    self.add_diagnostic(warn, severity, DiagnosticOrigin::Book);
+    let mut diag = Diagnostic::new(warn, severity);
+    diag.code = Some(warn_type.code().to_string());
+    self.push_diagnostic(diag, orig);
   }
 
   pub fn add_diagnostic(&mut self, msg: impl ToString, severity: Severity, orig: DiagnosticOrigin) {
-    let diag = Diagnostic { message: msg.to_string(), severity };
+    self.push_diagnostic(Diagnostic::new(msg, severity), orig)
+  }
+
+  pub fn push_diagnostic(&mut self, mut diag: Diagnostic, orig: DiagnosticOrigin) {
+    if let DiagnosticOrigin::Rule(nam) = &orig
+      && diag.span.is_none()
+    {
+      diag.span = self.def_spans.get(nam).cloned();
+    }
     self.diagnostics.entry(orig).or_default().push(diag)
   }
 
   pub fn take_rule_err<T, E: std::fmt::Display>(
     &mut self,
     result: Result<T, E>,
+    err_type: ErrorType,
     def_name: Name,
   ) -> Option<T> {
     match result {
       Ok(t) => Some(t),
       Err(e) => {
-        self.add_rule_error(e, def_name);
+        self.add_rule_error(e, err_type, def_name);
         None
       }
     }
@@ -117,12 +222,13 @@ impl Diagnostics {
   pub fn take_inet_err<T, E: std::fmt::Display>(
     &mut self,
     result: Result<T, E>,
+    err_type: ErrorType,
     def_name: String,
   ) -> Option<T> {
     match result {
       Ok(t) => Some(t),
       Err(e) => {
-        self.add_inet_error(e, def_name);
+        self.add_inet_error(e, err_type, def_name);
         None
       }
     }
@@ -136,6 +242,15 @@ impl Diagnostics {
     self.has_severity(Severity::Error)
   }
 
+  /// Returns the names of the definitions that have errors.
+  pub fn failed_defs(&self) -> HashSet<Name> {
+    let failed = self.diagnostics.iter().filter_map(|(orig, diags)| match orig {
+      DiagnosticOrigin::Rule(nam) if diags.iter().any(|d| d.severity == Severity::Error) => Some(nam.clone()),
+      _ => None,
+    });
+    failed.collect()
+  }
+
   /// Resets the internal counter
   pub fn start_pass(&mut self) {
     self.err_counter = 0;
@@ -145,7 +260,28 @@ impl Diagnostics {
   /// Returning all the current information as a `Err(Info)`, replacing `&mut self` with an empty one.
   /// Otherwise, returns the given arg as an `Ok(T)`.
   pub fn fatal<T>(&mut self, t: T) -> Result<T, Diagnostics> {
-    if self.err_counter == 0 { Ok(t) } else { Err(std::mem::take(self)) }
+    if self.err_counter == 0 {
+      Ok(t)
+    } else {
+      let empty = Diagnostics {
+        def_spans: self.def_spans.clone(),
+        def_lints: self.def_lints.clone(),
+        ..Diagnostics::new(self.config)
+      };
+      Err(std::mem::replace(self, empty))
+    }
+  }
+
+  /// Returns a Display that prints each diagnostic that is not allowed as a JSON object in its own line.
+  pub fn display_json(&self) -> impl std::fmt::Display + '_ {
+    DisplayFn(move |f| {
+      for (orig, errs) in &self.diagnostics {
+        for err in errs.iter().filter(|err| err.severity != Severity::Allow) {
+          writeln!(f, "{}", err.display_json(orig))?;
+        }
+      }
+      Ok(())
+    })
   }
 
   /// Returns a Display that prints the diagnostics with one of the given severities.
@@ -170,7 +306,15 @@ impl Diagnostics {
             }
             DiagnosticOrigin::Rule(nam) => {
               writeln!(f, "\x1b[1mIn definition '\x1b[4m{}\x1b[0m\x1b[1m':\x1b[0m", nam)?;
+              // The code frame is shown again only when the diagnostics point to a different place.
+              let mut last_span = None;
               for err in errs {
+                if let Some(span) = &err.span
+                  && last_span != Some(span)
+                {
+                  write!(f, "{}", span.display_frame(ERR_INDENT_SIZE, severity))?;
+                  last_span = Some(span);
+                }
                 writeln!(f, "{:ERR_INDENT_SIZE$}{err}", "")?;
               }
             }
@@ -196,27 +340,61 @@ impl Diagnostics {
       Ok(())
     })
   }
+
+  /// Returns a Display that prints the warnings and then the errors, each group under a header,
+  /// with the code frames of the diagnostics that point to the source.
+  pub fn display_human(&self) -> impl std::fmt::Display + '_ {
+    DisplayFn(move |f| {
+      for severity in [Severity::Warning, Severity::Error] {
+        if self.has_severity(severity) {
+          let header = if severity == Severity::Warning { "Warnings:" } else { "Errors:" };
+          writeln!(f, "\x1b[4m\x1b[1m{}{header}\x1b[0m", severity.color())?;
+          write!(f, "{}", self.display_with_severity(severity))?;
+        }
+      }
+      Ok(())
+    })
+  }
 }
 
 impl Display for Diagnostics {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-    if self.has_severity(Severity::Warning) {
-      write!(f, "\x1b[4m\x1b[1m\x1b[33mWarnings:\x1b[0m\n{}", self.display_with_severity(Severity::Warning))?;
+    match self.config.message_format {
+      MessageFormat::Json => write!(f, "{}", self.display_json()),
+      MessageFormat::Human if self.config.color => write!(f, "{}", self.display_human()),
+      // Some messages, like the ones of the parser, come already colored, so the codes are removed at the end.
+      MessageFormat::Human => write!(f, "{}", strip_ansi(&self.display_human().to_string())),
     }
-    if self.has_severity(Severity::Error) {
-      write!(f, "\x1b[4m\x1b[1m\x1b[31mErrors:\x1b[0m\n{}", self.display_with_severity(Severity::Error))?;
+  }
+}
+
+/// Removes the terminal escape codes, like the ones that set the colors, from a string.
+pub fn strip_ansi(s: &str) -> String {
+  let mut out = String::with_capacity(s.len());
+  let mut chars = s.chars();
+  while let Some(c) = chars.next() {
+    if c == '\x1b' {
+      if chars.next() == Some('[') {
+        for c in chars.by_ref() {
+          if c.is_ascii_alphabetic() {
+            break;
+          }
+        }
+      }
+    } else {
+      out.push(c);
     }
-    Ok(())
   }
+  out
 }
 
 impl From<String> for Diagnostics {
   fn from(value: String) -> Self {
     Self {
-      diagnostics: BTreeMap::from_iter([(DiagnosticOrigin::Book, vec![Diagnostic {
-        message: value,
-        severity: Severity::Error,
-      }])]),
+      diagnostics: BTreeMap::from_iter([(DiagnosticOrigin::Book, vec![Diagnostic::new(
+        value,
+        Severity::Error,
+      )])]),
       ..Default::default()
     }
   }
@@ -228,10 +406,20 @@ impl DiagnosticsConfig {
       irrefutable_match: severity,
       redundant_match: severity,
       unreachable_match: severity,
+      unreachable_arm: severity,
+      // Opt-in, since rebinding a name is idiomatic in the imperative syntax,
+      // ignoring arguments is idiomatic in lambda encodings and most duplications are cheap.
+      shadowed_variable: Severity::Allow,
+      unused_vars: Severity::Allow,
+      implicit_dup: Severity::Allow,
+      unused_dup: severity,
       unused_definition: severity,
       repeated_bind: severity,
       recursion_cycle: severity,
+      unguarded_recursion: severity,
       verbose,
+      message_format: MessageFormat::Human,
+      color: true,
     }
   }
 
@@ -240,9 +428,32 @@ impl DiagnosticsConfig {
       WarningType::UnusedDefinition => self.unused_definition,
       WarningType::RepeatedBind => self.repeated_bind,
       WarningType::RecursionCycle => self.recursion_cycle,
+      WarningType::UnguardedRecursion => self.unguarded_recursion,
       WarningType::IrrefutableMatch => self.irrefutable_match,
       WarningType::RedundantMatch => self.redundant_match,
       WarningType::UnreachableMatch => self.unreachable_match,
+      WarningType::UnreachableArm => self.unreachable_arm,
+      WarningType::ShadowedVariable => self.shadowed_variable,
+      WarningType::UnusedVars => self.unused_vars,
+      WarningType::ImplicitDup => self.implicit_dup,
+      WarningType::UnusedDup => self.unused_dup,
+    }
+  }
+
+  pub fn set_warning_severity(&mut self, warn: WarningType, severity: Severity) {
+    match warn {
+      WarningType::UnusedDefinition => self.unused_definition = severity,
+      WarningType::RepeatedBind => self.repeated_bind = severity,
+      WarningType::RecursionCycle => self.recursion_cycle = severity,
+      WarningType::UnguardedRecursion => self.unguarded_recursion = severity,
+      WarningType::IrrefutableMatch => self.irrefutable_match = severity,
+      WarningType::RedundantMatch => self.redundant_match = severity,
+      WarningType::UnreachableMatch => self.unreachable_match = severity,
+      WarningType::UnreachableArm => self.unreachable_arm = severity,
+      WarningType::ShadowedVariable => self.shadowed_variable = severity,
+      WarningType::UnusedVars => self.unused_vars = severity,
+      WarningType::ImplicitDup => self.implicit_dup = severity,
+      WarningType::UnusedDup => self.unused_dup = severity,
     }
   }
 }
@@ -255,8 +466,308 @@ impl Default for DiagnosticsConfig {
   }
 }
 
+impl Diagnostic {
+  pub fn new(msg: impl ToString, severity: Severity) -> Self {
+    Diagnostic { message: msg.to_string(), severity, code: None, span: None, notes: vec![] }
+  }
+
+  pub fn display_json<'a>(&'a self, orig: &'a DiagnosticOrigin) -> impl std::fmt::Display + 'a {
+    DisplayFn(move |f| {
+      let severity = match self.severity {
+        Severity::Allow => "allow",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+      };
+      write!(f, "{{\"severity\":\"{severity}\",\"code\":")?;
+      match &self.code {
+        Some(code) => write!(f, "{}", JsonStr(code))?,
+        None => write!(f, "null")?,
+      }
+      write!(f, ",\"name\":")?;
+      match self.code.as_deref().and_then(code_name) {
+        Some(name) => write!(f, "{}", JsonStr(name))?,
+        None => write!(f, "null")?,
+      }
+      write!(f, ",\"message\":{},\"origin\":", JsonStr(&self.message))?;
+      match orig {
+        DiagnosticOrigin::Book => write!(f, "{{\"kind\":\"book\"}}")?,
+        DiagnosticOrigin::Rule(nam) => write!(f, "{{\"kind\":\"rule\",\"name\":{}}}", JsonStr(nam))?,
+        DiagnosticOrigin::Inet(nam) => write!(f, "{{\"kind\":\"inet\",\"name\":{}}}", JsonStr(nam))?,
+        DiagnosticOrigin::Readback => write!(f, "{{\"kind\":\"readback\"}}")?,
+      }
+      write!(f, ",\"span\":")?;
+      match &self.span {
+        Some(span) => write!(f, "{}", span.display_json())?,
+        None => write!(f, "null")?,
+      }
+      write!(f, ",\"notes\":[")?;
+      for (i, note) in self.notes.iter().enumerate() {
+        if i != 0 {
+          write!(f, ",")?;
+        }
+        write!(f, "{}", JsonStr(note))?;
+      }
+      write!(f, "]}}")
+    })
+  }
+}
+
 impl Display for Diagnostic {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-    write!(f, "{}", self.message)
+    if let Some(code) = &self.code {
+      write!(f, "{}[{code}]\x1b[0m ", self.severity.color())?;
+    }
+    write!(f, "{}", self.message)?;
+    for note in &self.notes {
+      write!(f, "\n{:ERR_INDENT_SIZE$}\x1b[1m\x1b[36mnote:\x1b[0m {note}", "")?;
+    }
+    Ok(())
+  }
+}
+
+impl Span {
+  /// Displays the span as a JSON object with its file, line, column and length.
+  pub fn display_json(&self) -> impl std::fmt::Display + '_ {
+    DisplayFn(move |f| {
+      let Span { file, line, column, length } = self;
+      write!(f, "{{\"file\":")?;
+      match file {
+        Some(file) => write!(f, "{}", JsonStr(file))?,
+        None => write!(f, "null")?,
+      }
+      write!(f, ",\"line\":{line},\"column\":{column},\"length\":{length}}}")
+    })
+  }
+
+  /// Returns a Display with the location of the span followed by the first line of the span in
+  /// the source code, underlined with carets of the color of the given severity.
+  ///
+  /// The source is read from the file when displaying, so the code frame is omitted if it's not available.
+  pub fn display_frame(&self, indent: usize, severity: Severity) -> impl std::fmt::Display + '_ {
+    DisplayFn(move |f| {
+      let Span { file, line, column, length } = self;
+      let Some(file) = file else {
+        return writeln!(f, "{:indent$}At line {line}, column {column}", "");
+      };
+      writeln!(f, "{:indent$}At {file}:{line}:{column}", "")?;
+      let code = std::fs::read_to_string(file).ok();
+      let Some(code_line) = code.as_ref().and_then(|code| code.lines().nth(line - 1)) else {
+        return Ok(());
+      };
+      let gutter = line.to_string().len();
+      let before = code_line.chars().take(column - 1).map(|c| if c == '\t' { '\t' } else { ' ' });
+      let before = before.collect::<String>();
+      let carets = (*length).min(code_line.chars().count().saturating_sub(column - 1)).max(1);
+      writeln!(f, "{:indent$}{line} | {code_line}", "")?;
+      let color = severity.color();
+      writeln!(f, "{:indent$}{:gutter$} | {before}{color}{}\x1b[0m", "", "", "^".repeat(carets))
+    })
+  }
+}
+
+impl ErrorType {
+  pub const ALL: [ErrorType; 16] = [
+    ErrorType::NameClash,
+    ErrorType::Entrypoint,
+    ErrorType::InvalidPattern,
+    ErrorType::NonExhaustiveMatch,
+    ErrorType::MatchTypeMismatch,
+    ErrorType::UnscopedExtraction,
+    ErrorType::InvalidOpen,
+    ErrorType::InvalidRecord,
+    ErrorType::InvalidDoBlock,
+    ErrorType::UnboundVariable,
+    ErrorType::TypeMismatch,
+    ErrorType::NetTooLarge,
+    ErrorType::ViciousCycle,
+    ErrorType::Inlining,
+    ErrorType::ConstEval,
+    ErrorType::UnsupportedOperator,
+  ];
+
+  /// The stable code of the error, as accepted by `bend explain`.
+  pub fn code(&self) -> &'static str {
+    match self {
+      ErrorType::NameClash => "BEND0001",
+      ErrorType::Entrypoint => "BEND0002",
+      ErrorType::InvalidPattern => "BEND0003",
+      ErrorType::NonExhaustiveMatch => "BEND0004",
+      ErrorType::MatchTypeMismatch => "BEND0005",
+      ErrorType::UnscopedExtraction => "BEND0006",
+      ErrorType::InvalidOpen => "BEND0007",
+      ErrorType::InvalidRecord => "BEND0008",
+      ErrorType::InvalidDoBlock => "BEND0009",
+      ErrorType::UnboundVariable => "BEND0010",
+      ErrorType::TypeMismatch => "BEND0011",
+      ErrorType::NetTooLarge => "BEND0012",
+      ErrorType::ViciousCycle => "BEND0013",
+      ErrorType::Inlining => "BEND0014",
+      ErrorType::ConstEval => "BEND0015",
+      ErrorType::UnsupportedOperator => "BEND0016",
+    }
+  }
+
+  /// A short name describing the error.
+  pub fn name(&self) -> &'static str {
+    match self {
+      ErrorType::NameClash => "name-clash",
+      ErrorType::Entrypoint => "entrypoint",
+      ErrorType::InvalidPattern => "invalid-pattern",
+      ErrorType::NonExhaustiveMatch => "non-exhaustive-match",
+      ErrorType::MatchTypeMismatch => "match-type-mismatch",
+      ErrorType::UnscopedExtraction => "unscoped-extraction",
+      ErrorType::InvalidOpen => "invalid-open",
+      ErrorType::InvalidRecord => "invalid-record",
+      ErrorType::InvalidDoBlock => "invalid-do-block",
+      ErrorType::UnboundVariable => "unbound-variable",
+      ErrorType::TypeMismatch => "type-mismatch",
+      ErrorType::NetTooLarge => "net-too-large",
+      ErrorType::ViciousCycle => "vicious-cycle",
+      ErrorType::Inlining => "inlining",
+      ErrorType::ConstEval => "const-eval",
+      ErrorType::UnsupportedOperator => "unsupported-operator",
+    }
+  }
+
+  /// The extended description of the error, with examples and common fixes.
+  pub fn explanation(&self) -> &'static str {
+    match self {
+      ErrorType::NameClash => include_str!("explanations/BEND0001.md"),
+      ErrorType::Entrypoint => include_str!("explanations/BEND0002.md"),
+      ErrorType::InvalidPattern => include_str!("explanations/BEND0003.md"),
+      ErrorType::NonExhaustiveMatch => include_str!("explanations/BEND0004.md"),
+      ErrorType::MatchTypeMismatch => include_str!("explanations/BEND0005.md"),
+      ErrorType::UnscopedExtraction => include_str!("explanations/BEND0006.md"),
+      ErrorType::InvalidOpen => include_str!("explanations/BEND0007.md"),
+      ErrorType::InvalidRecord => include_str!("explanations/BEND0008.md"),
+      ErrorType::InvalidDoBlock => include_str!("explanations/BEND0009.md"),
+      ErrorType::UnboundVariable => include_str!("explanations/BEND0010.md"),
+      ErrorType::TypeMismatch => include_str!("explanations/BEND0011.md"),
+      ErrorType::NetTooLarge => include_str!("explanations/BEND0012.md"),
+      ErrorType::ViciousCycle => include_str!("explanations/BEND0013.md"),
+      ErrorType::Inlining => include_str!("explanations/BEND0014.md"),
+      ErrorType::ConstEval => include_str!("explanations/BEND0015.md"),
+      ErrorType::UnsupportedOperator => include_str!("explanations/BEND0016.md"),
+    }
+  }
+}
+
+impl WarningType {
+  pub const ALL: [WarningType; 12] = [
+    WarningType::IrrefutableMatch,
+    WarningType::RedundantMatch,
+    WarningType::UnreachableMatch,
+    WarningType::UnreachableArm,
+    WarningType::ShadowedVariable,
+    WarningType::UnusedVars,
+    WarningType::ImplicitDup,
+    WarningType::UnusedDefinition,
+    WarningType::RepeatedBind,
+    WarningType::RecursionCycle,
+    WarningType::UnguardedRecursion,
+    WarningType::UnusedDup,
+  ];
+
+  /// The stable code of the warning, as accepted by `bend explain`.
+  pub fn code(&self) -> &'static str {
+    match self {
+      WarningType::IrrefutableMatch => "BEND1001",
+      WarningType::RedundantMatch => "BEND1002",
+      WarningType::UnreachableMatch => "BEND1003",
+      WarningType::UnreachableArm => "BEND1004",
+      WarningType::ShadowedVariable => "BEND1005",
+      WarningType::UnusedVars => "BEND1006",
+      WarningType::ImplicitDup => "BEND1007",
+      WarningType::UnusedDefinition => "BEND1008",
+      WarningType::RepeatedBind => "BEND1009",
+      WarningType::RecursionCycle => "BEND1010",
+      WarningType::UnguardedRecursion => "BEND1011",
+      WarningType::UnusedDup => "BEND1012",
+    }
+  }
+
+  /// The name of the warning, as accepted by the `-W`, `-D` and `-A` cli options.
+  pub fn name(&self) -> &'static str {
+    match self {
+      WarningType::IrrefutableMatch => "irrefutable-match",
+      WarningType::RedundantMatch => "redundant-match",
+      WarningType::UnreachableMatch => "unreachable-match",
+      WarningType::UnreachableArm => "unreachable-arm",
+      WarningType::ShadowedVariable => "shadowed-variable",
+      WarningType::UnusedVars => "unused-vars",
+      WarningType::ImplicitDup => "implicit-dup",
+      WarningType::UnusedDefinition => "unused-definition",
+      WarningType::RepeatedBind => "repeated-bind",
+      WarningType::RecursionCycle => "recursion-cycle",
+      WarningType::UnguardedRecursion => "unguarded-recursion",
+      WarningType::UnusedDup => "unused-dup",
+    }
+  }
+
+  /// The extended description of the warning, with examples and common fixes.
+  pub fn explanation(&self) -> &'static str {
+    match self {
+      WarningType::IrrefutableMatch => include_str!("explanations/BEND1001.md"),
+      WarningType::RedundantMatch => include_str!("explanations/BEND1002.md"),
+      WarningType::UnreachableMatch => include_str!("explanations/BEND1003.md"),
+      WarningType::UnreachableArm => include_str!("explanations/BEND1004.md"),
+      WarningType::ShadowedVariable => include_str!("explanations/BEND1005.md"),
+      WarningType::UnusedVars => include_str!("explanations/BEND1006.md"),
+      WarningType::ImplicitDup => include_str!("explanations/BEND1007.md"),
+      WarningType::UnusedDefinition => include_str!("explanations/BEND1008.md"),
+      WarningType::RepeatedBind => include_str!("explanations/BEND1009.md"),
+      WarningType::RecursionCycle => include_str!("explanations/BEND1010.md"),
+      WarningType::UnguardedRecursion => include_str!("explanations/BEND1011.md"),
+      WarningType::UnusedDup => include_str!("explanations/BEND1012.md"),
+    }
+  }
+}
+
+/// Returns the short name of the diagnostic with the given code.
+fn code_name(code: &str) -> Option<&'static str> {
+  let errs = ErrorType::ALL.iter().map(|err| (err.code(), err.name()));
+  let warns = WarningType::ALL.iter().map(|warn| (warn.code(), warn.name()));
+  errs.chain(warns).find(|(c, _)| *c == code).map(|(_, name)| name)
+}
+
+/// Returns the extended description of a diagnostic, given its code or its name, as printed by `bend explain`.
+pub fn explain(code: &str) -> Option<String> {
+  let matches = |c: &str, name: &str| c.eq_ignore_ascii_case(code) || name == code;
+  if let Some(err) = ErrorType::ALL.iter().find(|err| matches(err.code(), err.name())) {
+    return Some(format!("{} ({}): error\n\n{}", err.code(), err.name(), err.explanation()));
+  }
+  let warn = WarningType::ALL.iter().find(|warn| matches(warn.code(), warn.name()))?;
+  Some(format!("{} ({}): warning\n\n{}", warn.code(), warn.name(), warn.explanation()))
+}
+
+/// Returns a list of all the diagnostic codes with their names, as printed by `bend explain` without a code.
+pub fn list_codes() -> String {
+  let errs = ErrorType::ALL.iter().map(|err| (err.code(), err.name()));
+  let warns = WarningType::ALL.iter().map(|warn| (warn.code(), warn.name()));
+  let mut out = String::new();
+  for (code, name) in errs.chain(warns) {
+    out.push_str(&format!("{code} {name}\n"));
+  }
+  out
+}
+
+/// Displays a string as a quoted and escaped JSON string.
+pub(crate) struct JsonStr<'a>(pub(crate) &'a str);
+
+impl Display for JsonStr<'_> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for c in self.0.chars() {
+      match c {
+        '"' => write!(f, "\\\"")?,
+        '\\' => write!(f, "\\\\")?,
+        '\n' => write!(f, "\\n")?,
+        '\r' => write!(f, "\\r")?,
+        '\t' => write!(f, "\\t")?,
+        c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+        c => write!(f, "{c}")?,
+      }
+    }
+    write!(f, "\"")
   }
 }
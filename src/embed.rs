@@ -0,0 +1,359 @@
+//! Calling the definitions of a Bend program from Rust, for using it as an embedded scripting language.
+//!
+//! ```
+//! use bend::embed::{EmbeddedBook, Value};
+//!
+//! let book = EmbeddedBook::from_source("<script>", "double x = (* x 2)").unwrap();
+//! assert_eq!(book.call("double", &[Value::Num(21)]).unwrap(), Value::Num(42));
+//! ```
+
+use crate::{
+  compiler::{Compiler, Runtime},
+  diagnostics::{Diagnostics, DiagnosticsConfig, JsonStr},
+  fun::{
+    display::DisplayFn, term_to_net::Labels, transform::resugar_adts::scott_ctr, Book, Definition, FanKind,
+    Name, Num, Rule, Term,
+  },
+  load_file_to_book, load_source_to_book, readback_hvm_net, CompileOpts, ENTRY_POINT, HVM1_ENTRY_POINT,
+};
+use hvmc::ast::Net;
+use std::path::Path;
+
+/// A program loaded once, whose definitions can then be called any number of times.
+#[derive(Debug, Clone)]
+pub struct EmbeddedBook {
+  book: Book,
+  opts: CompileOpts,
+  warnings: DiagnosticsConfig,
+  runtime: Runtime,
+}
+
+/// A value passed to or returned from a Bend definition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  /// An unsigned 24-bit number.
+  Num(u32),
+  /// A signed 24-bit number.
+  Int(i32),
+  /// A 24-bit float.
+  Float(f32),
+  Str(String),
+  List(Vec<Value>),
+  Tuple(Vec<Value>),
+  /// A constructor of a datatype, with its full name and its fields.
+  Ctr(String, Vec<Value>),
+  /// A result that has no corresponding Rust value, like a lambda.
+  Term(Term),
+}
+
+impl EmbeddedBook {
+  /// By default, the definitions are run with the evaluator built into the compiler.
+  pub fn new(book: Book) -> Self {
+    Self {
+      book,
+      opts: CompileOpts::default(),
+      warnings: DiagnosticsConfig::default(),
+      runtime: Runtime::Interpreter,
+    }
+  }
+
+  pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Diagnostics> {
+    Ok(Self::new(load_file_to_book(path.as_ref())?))
+  }
+
+  pub fn from_source(name: &str, code: &str) -> Result<Self, Diagnostics> {
+    Ok(Self::new(load_source_to_book(name, code)?))
+  }
+
+  pub fn opts(mut self, opts: CompileOpts) -> Self {
+    self.opts = opts;
+    self
+  }
+
+  pub fn warnings(mut self, warnings: DiagnosticsConfig) -> Self {
+    self.warnings = warnings;
+    self
+  }
+
+  pub fn runtime(mut self, runtime: Runtime) -> Self {
+    self.runtime = runtime;
+    self
+  }
+
+  /// Calls a definition with the given arguments and returns its normal form.
+  ///
+  /// The program's own entrypoint is not used, so any definition of the book can be called.
+  pub fn call(&self, name: &str, args: &[Value]) -> Result<Value, Diagnostics> {
+    let name = Name::new(name);
+    if !self.book.defs.contains_key(&name) {
+      return Err(format!("Definition '{name}' not found.").into());
+    }
+
+    let mut book = self.book.clone();
+    let body = Term::call(Term::Ref { nam: name }, args.iter().map(Value::to_term));
+    set_main(&mut book, body);
+
+    let mut opts = self.opts.clone();
+    opts.entrypoint = None;
+    let res =
+      Compiler::new().opts(opts).warnings(self.warnings).runtime(self.runtime).book(book.clone()).run()?;
+    if res.diagnostics.has_errors() {
+      return Err(res.diagnostics);
+    }
+    match res.term {
+      Some(term) => Ok(Value::from_term(&term, &book)),
+      None => Err("The program didn't return a result.".to_string().into()),
+    }
+  }
+}
+
+impl Value {
+  pub fn to_term(&self) -> Term {
+    match self {
+      Value::Num(val) => Term::u24(*val),
+      Value::Int(val) => Term::i24(*val),
+      Value::Float(val) => Term::f24(*val),
+      Value::Str(val) => Term::str(val),
+      Value::List(els) => Term::List { els: els.iter().map(Value::to_term).collect() },
+      Value::Tuple(els) => Term::Fan {
+        fan: FanKind::Tup,
+        tag: Default::default(),
+        els: els.iter().map(Value::to_term).collect(),
+      },
+      Value::Ctr(nam, fields) => Term::call(Term::r#ref(nam), fields.iter().map(Value::to_term)),
+      Value::Term(term) => term.clone(),
+    }
+  }
+
+  /// Converts a term read back from a net, keeping it as a term when it isn't data.
+  pub fn from_term(term: &Term, book: &Book) -> Value {
+    let from_terms = |els: &[Term]| els.iter().map(|el| Value::from_term(el, book)).collect();
+    match term {
+      Term::Num { val: Num::U24(val) } => Value::Num(*val),
+      Term::Num { val: Num::I24(val) } => Value::Int(*val),
+      Term::Num { val: Num::F24(val) } => Value::Float(*val),
+      Term::Str { val } => Value::Str(val.to_string()),
+      Term::List { els } => Value::List(from_terms(els)),
+      Term::Fan { fan: FanKind::Tup, els, .. } => Value::Tuple(from_terms(els)),
+      Term::Ref { nam } if book.ctrs.contains_key(nam) => Value::Ctr(nam.to_string(), vec![]),
+      _ => {
+        let (head, fields) = spine(term);
+        match head {
+          Term::Ref { nam } if book.ctrs.contains_key(nam) => Value::Ctr(
+            nam.to_string(),
+            fields.into_iter().map(|field| Value::from_term(field, book)).collect(),
+          ),
+          _ => Value::from_scott(term, book).unwrap_or_else(|| Value::Term(term.clone())),
+        }
+      }
+    }
+  }
+
+  /// Reads back a constructor from its Scott encoding, `λc1 .. λcn (ci field1 .. fieldk)`,
+  /// looking at the datatypes of the program before the builtin ones. See [`scott_ctr`].
+  fn from_scott(term: &Term, book: &Book) -> Option<Value> {
+    let adts =
+      book.adts.values().filter(|adt| !adt.builtin).chain(book.adts.values().filter(|adt| adt.builtin));
+    let (ctr, arity) = scott_ctr(term, adts)?;
+    let mut bod = term;
+    for _ in 0 .. arity {
+      let Term::Lam { bod: nxt, .. } = bod else { unreachable!() };
+      bod = nxt;
+    }
+    let (_, fields) = spine(bod);
+    Some(Value::Ctr(ctr.to_string(), fields.into_iter().map(|field| Value::from_term(field, book)).collect()))
+  }
+}
+
+impl Value {
+  /// Displays the value as JSON, in the same format as [`Value::to_json`],
+  /// without depending on `serde`. Floats that aren't finite are shown as `null`.
+  pub fn display_json<'a>(&'a self, book: &'a Book) -> impl std::fmt::Display + 'a {
+    DisplayFn(move |f| match self {
+      Value::Num(val) => write!(f, "{val}"),
+      Value::Int(val) => write!(f, "{val}"),
+      Value::Float(val) if val.is_finite() => write!(f, "{val}"),
+      Value::Float(_) => write!(f, "null"),
+      Value::Str(val) => write!(f, "{}", JsonStr(val)),
+      Value::List(els) | Value::Tuple(els) => {
+        write!(f, "[")?;
+        for (i, el) in els.iter().enumerate() {
+          if i != 0 {
+            write!(f, ",")?;
+          }
+          write!(f, "{}", el.display_json(book))?;
+        }
+        write!(f, "]")
+      }
+      Value::Ctr(nam, fields) => {
+        let nam = Name::new(nam);
+        let fld_nams = book.ctrs.get(&nam).and_then(|adt| book.adts[adt].ctrs.get(&nam));
+        write!(f, "{{{}:{{", JsonStr(&nam))?;
+        for (i, field) in fields.iter().enumerate() {
+          if i != 0 {
+            write!(f, ",")?;
+          }
+          let fld_nam =
+            fld_nams.and_then(|flds| flds.get(i)).map_or(i.to_string(), |fld| fld.nam.to_string());
+          write!(f, "{}:{}", JsonStr(&fld_nam), field.display_json(book))?;
+        }
+        write!(f, "}}}}")
+      }
+      Value::Term(term) => write!(f, "{{\"$term\":{}}}", JsonStr(&term.to_string())),
+    })
+  }
+}
+
+/// Reads back the result of a program as a value, instead of as a term.
+pub fn readback_to_value(net: &Net, book: &Book, labels: &Labels) -> (Value, Diagnostics) {
+  let (term, diags) = readback_hvm_net(net, book, labels, false, false);
+  (Value::from_term(&term, book), diags)
+}
+
+/// Reads back the result of a program as JSON. See [`Value::to_json`].
+#[cfg(feature = "serde")]
+pub fn readback_to_json(net: &Net, book: &Book, labels: &Labels) -> (serde_json::Value, Diagnostics) {
+  let (value, diags) = readback_to_value(net, book, labels);
+  (value.to_json(book), diags)
+}
+
+#[cfg(feature = "serde")]
+impl Value {
+  /// Converts the value to JSON, in the format that `serde` uses for Rust data.
+  ///
+  /// A constructor is an object with its name as the only key, mapped to an object with its
+  /// fields, like `{"Tree/Leaf": {"val": 1}}`. This is how `serde` represents an enum variant
+  /// with named fields, so the result can be deserialized to a Rust enum whose variants are
+  /// renamed to the constructors. Terms that aren't data are given as `{"$term": "<term>"}`.
+  pub fn to_json(&self, book: &Book) -> serde_json::Value {
+    use serde_json::{json, Map, Value as Json};
+
+    match self {
+      Value::Num(val) => json!(val),
+      Value::Int(val) => json!(val),
+      Value::Float(val) => json!(val),
+      Value::Str(val) => json!(val),
+      Value::List(els) | Value::Tuple(els) => Json::Array(els.iter().map(|el| el.to_json(book)).collect()),
+      Value::Ctr(nam, fields) => {
+        let nam = Name::new(nam);
+        let fld_nams = book.ctrs.get(&nam).and_then(|adt| book.adts[adt].ctrs.get(&nam));
+        let fields = fields.iter().enumerate().map(|(i, field)| {
+          let fld_nam =
+            fld_nams.and_then(|flds| flds.get(i)).map_or(i.to_string(), |fld| fld.nam.to_string());
+          (fld_nam, field.to_json(book))
+        });
+        json!({ nam.to_string(): Map::from_iter(fields) })
+      }
+      Value::Term(term) => json!({ "$term": term.to_string() }),
+    }
+  }
+}
+
+/// Splits an application into the function being called and its arguments.
+fn spine(term: &Term) -> (&Term, Vec<&Term>) {
+  let mut args = vec![];
+  let mut fun = term;
+  while let Term::App { fun: nxt, arg, .. } = fun {
+    args.push(arg.as_ref());
+    fun = nxt;
+  }
+  args.reverse();
+  (fun, args)
+}
+
+impl From<u32> for Value {
+  fn from(val: u32) -> Self {
+    Value::Num(val)
+  }
+}
+
+impl From<i32> for Value {
+  fn from(val: i32) -> Self {
+    Value::Int(val)
+  }
+}
+
+impl From<f32> for Value {
+  fn from(val: f32) -> Self {
+    Value::Float(val)
+  }
+}
+
+impl From<&str> for Value {
+  fn from(val: &str) -> Self {
+    Value::Str(val.to_string())
+  }
+}
+
+/// Replaces the entrypoint of the book with a `main` definition with the given body.
+pub(crate) fn set_main(book: &mut Book, body: Term) {
+  book.entrypoint = None;
+  book.defs.shift_remove(&Name::new(HVM1_ENTRY_POINT));
+  let main = Name::new(ENTRY_POINT);
+  let rule = Rule { pats: vec![], guard: None, body };
+  let def =
+    Definition { name: main.clone(), typ: None, rules: vec![rule], builtin: false, span: None, doc: None };
+  book.defs.insert(main, def);
+}
+
+#[test]
+fn embed_call() {
+  let code = r#"
+data Tree = (Node ~left ~right) | (Leaf val)
+fib 0 = 0
+fib 1 = 1
+fib n = (+ (fib (- n 1)) (fib (- n 2)))
+capitalize (String/Cons h t) = (String/Cons (- h 32) t)
+capitalize (String/Nil) = (String/Nil)
+mirror (Tree/Node l r) = (Tree/Node (mirror r) (mirror l))
+mirror (Tree/Leaf v) = (Tree/Leaf v)
+len (List/Cons * t) = (+ 1 (len t))
+len (List/Nil) = 0
+pair xs = ((len xs), xs)
+"#;
+  let book = EmbeddedBook::from_source("embed", code).unwrap();
+  assert_eq!(book.call("fib", &[Value::Num(20)]).unwrap(), Value::Num(6765));
+  assert_eq!(book.call("capitalize", &["bend".into()]).unwrap(), Value::Str("Bend".to_string()));
+
+  let leaf = |v| Value::Ctr("Tree/Leaf".to_string(), vec![Value::Num(v)]);
+  let node = |l, r| Value::Ctr("Tree/Node".to_string(), vec![l, r]);
+  let tree = node(leaf(1), node(leaf(2), leaf(3)));
+  assert_eq!(book.call("mirror", &[tree]).unwrap(), node(node(leaf(3), leaf(2)), leaf(1)));
+
+  let list = Value::List(vec![Value::Num(1), Value::Num(2), Value::Num(3)]);
+  assert_eq!(book.call("pair", &[list.clone()]).unwrap(), Value::Tuple(vec![Value::Num(3), list]));
+
+  assert!(book.call("missing", &[]).is_err());
+  assert!(book.call("fib", &[]).is_ok_and(|res| matches!(res, Value::Term(_))));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn readback_json() {
+  use crate::{compile_book, fun::load_book::do_parse_book};
+  use serde_json::json;
+
+  let code = r#"
+data Tree = (Node ~left ~right) | (Leaf val) | Empty
+main = ((Tree/Node (Tree/Leaf 1) Tree/Empty), [-2, 1.5], "hi", λx x)
+"#;
+  let mut book = do_parse_book(code, Path::new("readback_json"), Book::builtins()).unwrap();
+  let res = compile_book(&mut book, CompileOpts::default(), DiagnosticsConfig::default(), None).unwrap();
+  let (net, _) = crate::hvm::interpreter::normalize(&res.core_book).unwrap();
+  let (json, diags) = readback_to_json(&net, &book, &res.labels);
+  assert!(!diags.has_errors());
+  assert_eq!(
+    json[0],
+    json!({ "Tree/Node": { "left": { "Tree/Leaf": { "val": 1 } }, "right": { "Tree/Empty": {} } } })
+  );
+  assert_eq!(json[1], json!([-2, 1.5]));
+  assert_eq!(json[2], json!("hi"));
+  assert!(json[3]["$term"].as_str().is_some_and(|term| term.starts_with('λ')));
+
+  // The JSON displayed without serde is the same.
+  let (value, _) = readback_to_value(&net, &book, &res.labels);
+  assert_eq!(
+    serde_json::from_str::<serde_json::Value>(&value.display_json(&book).to_string()).unwrap(),
+    json
+  );
+}
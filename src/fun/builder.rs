@@ -0,0 +1,137 @@
+use crate::fun::{Adt, Book, CtrField, Definition, Name, Pattern, Rule, Term};
+
+/// Builds a book from terms constructed in Rust, without going through the parser.
+///
+/// ```
+/// use bend::{fun::{Book, Name}, term};
+///
+/// let book = Book::builder()
+///   .data("Tree", &[("Node", &["~left", "~right"]), ("Leaf", &["val"])])
+///   .def("main", term!("(Tree/Node (Tree/Leaf 1) (Tree/Leaf 2))"))
+///   .build()
+///   .unwrap();
+/// assert!(book.ctrs.contains_key(&Name::new("Tree/Node")));
+/// ```
+#[derive(Debug, Clone)]
+pub struct BookBuilder {
+  book: Book,
+  /// The first error found while building, reported by [`BookBuilder::build`].
+  err: Option<String>,
+}
+
+impl Book {
+  /// Starts building a book that contains the builtin definitions.
+  pub fn builder() -> BookBuilder {
+    BookBuilder { book: Book::builtins(), err: None }
+  }
+}
+
+impl BookBuilder {
+  /// Adds a definition without pattern matching arguments.
+  pub fn def(self, name: &str, body: Term) -> Self {
+    if self.book.defs.contains_key(&Name::new(name)) {
+      return self.fail(format!("Repeated definition '{name}'."));
+    }
+    self.rule(name, vec![], body)
+  }
+
+  /// Adds a pattern matching rule to a definition, creating the definition if it doesn't exist yet.
+  pub fn rule(mut self, name: &str, pats: Vec<Pattern>, body: Term) -> Self {
+    let rule = Rule { pats, guard: None, body };
+    if let Some(def) = self.book.defs.get_mut(&Name::new(name)) {
+      if def.builtin {
+        return self.fail(format!("{name} is a built-in definition and should not be overridden."));
+      }
+      def.rules.push(rule);
+    } else {
+      let name = Name::new(name);
+      let def = Definition {
+        name: name.clone(),
+        typ: None,
+        rules: vec![rule],
+        builtin: false,
+        span: None,
+        doc: None,
+      };
+      self.book.defs.insert(name, def);
+    }
+    self
+  }
+
+  /// Adds a datatype, with each constructor given by its name and its fields.
+  ///
+  /// Like in the source syntax, the constructor names are prefixed with the name
+  /// of the datatype and recursive fields are marked with a leading `~`.
+  pub fn data(mut self, name: &str, ctrs: &[(&str, &[&str])]) -> Self {
+    let ctrs = ctrs.iter().map(|(ctr, fields)| {
+      let fields = fields.iter().map(|field| match field.strip_prefix('~') {
+        Some(nam) => CtrField { nam: Name::new(nam), rec: true },
+        None => CtrField { nam: Name::new(*field), rec: false },
+      });
+      (Name::new(format!("{name}/{ctr}")), fields.collect())
+    });
+    let adt = Adt { ctrs: ctrs.collect(), builtin: false, span: None, doc: None };
+    match self.book.add_adt(Name::new(name), adt) {
+      Ok(()) => self,
+      Err(e) => self.fail(e),
+    }
+  }
+
+  /// Sets the definition that the program starts from, instead of `main`.
+  pub fn entrypoint(mut self, name: &str) -> Self {
+    self.book.entrypoint = Some(Name::new(name));
+    self
+  }
+
+  /// Finishes the book, failing if any of the added items was invalid.
+  pub fn build(self) -> Result<Book, String> {
+    match self.err {
+      Some(err) => Err(err),
+      None => Ok(self.book),
+    }
+  }
+
+  fn fail(mut self, err: String) -> Self {
+    self.err.get_or_insert(err);
+    self
+  }
+}
+
+#[test]
+fn book_builder() {
+  use crate::{
+    compiler::{Compiler, Runtime},
+    fun::Op,
+    term,
+  };
+
+  // sum (Tree/Node l r) = (+ (sum l) (sum r)); sum (Tree/Leaf v) = v
+  let sum = Term::lam_var(
+    "t",
+    Term::mat("t", [
+      (
+        "Tree/Node",
+        Term::oper(
+          Op::ADD,
+          Term::app(Term::r#ref("sum"), Term::var("t.left")),
+          Term::app(Term::r#ref("sum"), Term::var("t.right")),
+        ),
+      ),
+      ("Tree/Leaf", Term::var("t.val")),
+    ]),
+  );
+  let dec = Term::lam_var("n", Term::swt("n", vec![Term::u24(0), Term::var("n-1")]));
+  let book = Book::builder()
+    .data("Tree", &[("Node", &["~left", "~right"]), ("Leaf", &["val"])])
+    .def("sum", sum)
+    .def("dec", dec)
+    .def("main", term!("(dec (sum (Tree/Node (Tree/Leaf 20) (Tree/Node (Tree/Leaf 1) (Tree/Leaf 22)))))"))
+    .build()
+    .unwrap();
+  let res = Compiler::new().book(book).runtime(Runtime::Interpreter).run().unwrap();
+  assert_eq!(res.term.unwrap().to_string(), "42");
+
+  let repeated = Book::builder().def("main", Term::u24(1)).def("main", Term::u24(2)).build();
+  assert_eq!(repeated.unwrap_err(), "Repeated definition 'main'.");
+  assert!(crate::fun::parser::parse_term("(a b) c").is_err());
+}
@@ -1,7 +1,8 @@
-use super::{parser::TermParser, Book, Name, Num, Pattern, Term};
+use super::{parser::TermParser, Book, Name, Num, Op, Pattern, Term, STRINGS};
 use crate::maybe_grow;
 
 const BUILTINS: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/fun/builtins.bend"));
+const PRELUDE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/fun/prelude.bend"));
 
 pub const LIST: &str = "List";
 pub const LCONS: &str = "List/Cons";
@@ -22,6 +23,14 @@ pub const NAT: &str = "Nat";
 pub const NAT_SUCC: &str = "Nat/Succ";
 pub const NAT_ZERO: &str = "Nat/Zero";
 
+pub const NUM_SHL: &str = "Num/shl";
+pub const NUM_SHR: &str = "Num/shr";
+pub const NUM_SHOW: &str = "Num/show";
+
+pub const MK_IO: &str = "MkIO";
+
+pub const RAND_SEED: &str = "Rand/seed";
+
 impl Book {
   pub fn builtins() -> Book {
     TermParser::new(BUILTINS)
@@ -29,10 +38,65 @@ impl Book {
       .expect("Error parsing builtin file, this should not happen")
   }
 
+  /// Adds the datatypes and functions of the prelude to the book.
+  ///
+  /// Unlike the builtins, the prelude is added after the program is parsed, so that
+  /// the program can replace any of its names. A datatype whose name or constructors
+  /// are taken is left out together with the functions of its namespace.
+  pub fn add_prelude(&mut self) {
+    let prelude = TermParser::new(PRELUDE)
+      .parse_book(Book::default(), true)
+      .expect("Error parsing prelude file, this should not happen");
+
+    let mut replaced = vec![];
+    for (nam, adt) in prelude.adts {
+      let is_taken = |nam: &Name| {
+        self.adts.contains_key(nam) || self.ctrs.contains_key(nam) || self.defs.contains_key(nam)
+      };
+      if is_taken(&nam) || adt.ctrs.keys().any(is_taken) {
+        replaced.push(nam);
+        continue;
+      }
+      for ctr in adt.ctrs.keys() {
+        self.ctrs.insert(ctr.clone(), nam.clone());
+      }
+      self.adts.insert(nam, adt);
+    }
+
+    for (nam, def) in prelude.defs {
+      let in_replaced =
+        replaced.iter().any(|adt| nam.strip_prefix(adt.as_ref()).is_some_and(|s| s.starts_with('/')));
+      if !in_replaced && !self.defs.contains_key(&nam) && !self.ctrs.contains_key(&nam) {
+        self.defs.insert(nam, def);
+      }
+    }
+  }
+
+  /// Replaces the seed of the builtin pseudo-random number generator.
+  pub fn set_rand_seed(&mut self, seed: u32) {
+    if let Some(def) = self.defs.get_mut(&Name::new(RAND_SEED))
+      && def.builtin
+    {
+      def.rules[0].body = Term::Num { val: Num::U24(seed & 0x00ff_ffff) };
+    }
+  }
+
+  /// Whether the definitions that aren't builtins use the seed of the pseudo-random number generator.
+  pub fn uses_rand_seed(&self) -> bool {
+    fn go(term: &Term) -> bool {
+      maybe_grow(|| {
+        matches!(term, Term::Var { nam } | Term::Ref { nam } if *nam == RAND_SEED) || term.children().any(go)
+      })
+    }
+    let mut rules = self.defs.values().filter(|def| !def.builtin).flat_map(|def| &def.rules);
+    rules.any(|rule| rule.guard.iter().chain([&rule.body]).any(go))
+  }
+
   pub fn encode_builtins(&mut self) {
     for def in self.defs.values_mut() {
       for rule in def.rules.iter_mut() {
         rule.pats.iter_mut().for_each(Pattern::encode_builtins);
+        rule.guard.iter_mut().for_each(Term::encode_builtins);
         rule.body.encode_builtins();
       }
     }
@@ -45,6 +109,12 @@ impl Term {
       Term::List { els } => *self = Term::encode_list(std::mem::take(els)),
       Term::Str { val } => *self = Term::encode_str(val),
       Term::Nat { val } => *self = Term::encode_nat(*val),
+      Term::Oper { opr: opr @ (Op::SHL | Op::SHR), fst, snd } => {
+        let fun = if *opr == Op::SHL { NUM_SHL } else { NUM_SHR };
+        let mut args = [std::mem::take(fst.as_mut()), std::mem::take(snd.as_mut())];
+        args.iter_mut().for_each(Term::encode_builtins);
+        *self = Term::call(Term::r#ref(fun), args);
+      }
       _ => {
         for child in self.children_mut() {
           child.encode_builtins();
@@ -66,6 +136,22 @@ impl Term {
     })
   }
 
+  /// Builds an interpolated string, like "result: {x}", from the text before each interpolated
+  /// expression and the text after the last one.
+  ///
+  /// The interpolated expressions must be numbers, which are written in decimal by `Num/show`:
+  /// ```bend
+  /// (String/Cons 'r' ... (String/Cons ' ' (Num/show x "")))
+  /// ```
+  pub fn interpolated_str(parts: Vec<(String, Term)>, end: String) -> Term {
+    parts.into_iter().rfold(Term::Str { val: STRINGS.get(end) }, |acc, (text, term)| {
+      let acc = Term::call(Term::r#ref(NUM_SHOW), [term, acc]);
+      text.chars().rfold(acc, |acc, chr| {
+        Term::call(Term::r#ref(SCONS), [Term::Num { val: Num::U24(chr as u32 & 0x00ff_ffff) }, acc])
+      })
+    })
+  }
+
   pub fn encode_nat(val: u32) -> Term {
     (0 .. val).fold(Term::r#ref(NAT_ZERO), |acc, _| Term::app(Term::r#ref(NAT_SUCC), acc))
   }
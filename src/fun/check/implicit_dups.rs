@@ -0,0 +1,154 @@
+use crate::{
+  diagnostics::{Diagnostics, WarningType},
+  fun::{Ctx, Name, Pattern, Term},
+  maybe_grow,
+};
+use std::collections::HashMap;
+
+pub struct ImplicitDupWarn {
+  nam: Name,
+  uses: u64,
+}
+
+/// The variables in scope, with how many times each of their binders was used.
+#[derive(Default)]
+struct Scope<'a> {
+  vars: HashMap<&'a Name, Vec<usize>>,
+  binds: Vec<(&'a Name, u64)>,
+  /// The binders that went out of scope after being used more than once.
+  dups: Vec<ImplicitDupWarn>,
+}
+
+impl Ctx<'_> {
+  /// Warns about the variables that are used more than once, which the compiler
+  /// implicitly duplicates, copying the whole value they are bound to.
+  ///
+  /// The uses in different arms of a match are counted only once, since only one of them runs.
+  ///
+  /// Must run after the local definitions are lifted and the constructor patterns are fixed.
+  pub fn check_implicit_dups(&mut self) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    for (def_name, def) in self.book.defs.iter().filter(|(_, def)| !def.builtin) {
+      let mut scope = Scope::default();
+      for rule in &def.rules {
+        scope.push_pats(&rule.pats);
+        for term in rule.guard.iter().chain([&rule.body]) {
+          term.check_implicit_dups(&mut scope);
+        }
+        scope.pop_to(0);
+      }
+
+      for warn in scope.dups {
+        self.info.add_rule_warning(warn, WarningType::ImplicitDup, def_name.clone());
+      }
+    }
+
+    self.info.fatal(())
+  }
+}
+
+impl Term {
+  fn check_implicit_dups<'a>(&'a self, scope: &mut Scope<'a>) {
+    maybe_grow(|| match self {
+      Term::Var { nam } => scope.add_use(nam),
+      Term::Open { var, bod, .. } => {
+        scope.add_use(var);
+        bod.check_implicit_dups(scope);
+      }
+      Term::PatMat { arg, arms } => {
+        arg.check_implicit_dups(scope);
+        scope.arms(arms, |scope, (pat, guard, body)| {
+          let len = scope.binds.len();
+          scope.push_pats([pat]);
+          for term in guard.iter().chain([body]) {
+            term.check_implicit_dups(scope);
+          }
+          scope.pop_to(len);
+        });
+      }
+      // The matched variable and the `with` variables are bound again in each arm.
+      Term::Mat { arg, bnd, with, arms } | Term::Fold { arg, bnd, with, arms } => {
+        arg.check_implicit_dups(scope);
+        with.iter().for_each(|nam| scope.add_use(nam));
+        scope.arms(arms, |scope, (_, fields, body)| {
+          let len = scope.binds.len();
+          bnd.iter().chain(with).chain(fields.iter().flatten()).for_each(|nam| scope.push(nam));
+          body.check_implicit_dups(scope);
+          scope.pop_to(len);
+        });
+      }
+      Term::Swt { arg, bnd, with, pred, arms } => {
+        arg.check_implicit_dups(scope);
+        with.iter().for_each(|nam| scope.add_use(nam));
+        let n_nums = arms.len() - 1;
+        scope.arms(arms.iter().enumerate(), |scope, (i, arm)| {
+          let len = scope.binds.len();
+          let pred = pred.iter().filter(|_| i == n_nums);
+          bnd.iter().chain(with).chain(pred).for_each(|nam| scope.push(nam));
+          arm.check_implicit_dups(scope);
+          scope.pop_to(len);
+        });
+      }
+      _ => {
+        for (child, binds) in self.children_with_binds() {
+          let len = scope.binds.len();
+          binds.flatten().for_each(|nam| scope.push(nam));
+          child.check_implicit_dups(scope);
+          scope.pop_to(len);
+        }
+      }
+    })
+  }
+}
+
+impl<'a> Scope<'a> {
+  fn push(&mut self, nam: &'a Name) {
+    self.vars.entry(nam).or_default().push(self.binds.len());
+    self.binds.push((nam, 0));
+  }
+
+  fn push_pats(&mut self, pats: impl IntoIterator<Item = &'a Pattern>) {
+    for nam in pats.into_iter().flat_map(|pat| pat.binds().flatten()) {
+      self.push(nam);
+    }
+  }
+
+  fn add_use(&mut self, nam: &Name) {
+    if let Some(&id) = self.vars.get(nam).and_then(|ids| ids.last()) {
+      self.binds[id].1 += 1;
+    }
+  }
+
+  /// Visits the arms of a match, keeping for each variable the most uses of any single arm.
+  fn arms<T>(&mut self, arms: impl IntoIterator<Item = T>, mut visit: impl FnMut(&mut Self, T)) {
+    let before = self.binds.iter().map(|(_, uses)| *uses).collect::<Vec<_>>();
+    let mut most = before.clone();
+    for arm in arms {
+      visit(self, arm);
+      for (id, (_, uses)) in self.binds.iter_mut().enumerate() {
+        most[id] = most[id].max(*uses);
+        *uses = before[id];
+      }
+    }
+    for (id, (_, uses)) in self.binds.iter_mut().enumerate() {
+      *uses = most[id];
+    }
+  }
+
+  /// Removes the binders pushed after the first `len`, collecting the ones used more than once.
+  fn pop_to(&mut self, len: usize) {
+    for (nam, uses) in self.binds.drain(len ..) {
+      self.vars.get_mut(nam).unwrap().pop();
+      if uses > 1 && !self.dups.iter().any(|dup| &dup.nam == nam) {
+        self.dups.push(ImplicitDupWarn { nam: nam.clone(), uses });
+      }
+    }
+  }
+}
+
+impl std::fmt::Display for ImplicitDupWarn {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Variable '{}' is used {} times, so its value is implicitly duplicated.", self.nam, self.uses)
+  }
+}
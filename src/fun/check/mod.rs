@@ -1,3 +1,8 @@
+pub mod implicit_dups;
 pub mod set_entrypoint;
+pub mod shadowed_vars;
 pub mod shared_names;
+pub mod types;
 pub mod unbound_vars;
+pub mod unguarded_recursion;
+pub mod unused_vars;
@@ -1,49 +1,56 @@
 use crate::{
+  diagnostics::ErrorType,
   fun::{Book, Ctx, Definition, Name},
   ENTRY_POINT, HVM1_ENTRY_POINT,
 };
 
 #[derive(Debug, Clone)]
 pub enum EntryErr {
-  NotFound(Name),
+  /// The entrypoint wasn't found, with the names of similar definitions.
+  NotFound(Name, Vec<Name>),
   Multiple(Vec<Name>),
   MultipleRules,
 }
 
 impl Ctx<'_> {
   pub fn set_entrypoint(&mut self) {
-    let mut entrypoint = None;
-
-    let (custom, main, hvm1_main) = self.book.get_possible_entry_points();
-    match (custom, main, hvm1_main) {
-      (Some(entry), None, None) | (None, Some(entry), None) | (None, None, Some(entry)) => {
-        match validate_entry_point(entry) {
-          Ok(name) => entrypoint = Some(name),
-          Err(err) => self.info.add_book_error(err),
+    // An entrypoint chosen by the user takes precedence over `main` and `Main`.
+    if let Some(custom) = self.book.entrypoint.take() {
+      match self.book.defs.get(&custom) {
+        Some(entry) => match validate_entry_point(entry) {
+          Ok(name) => self.book.entrypoint = Some(name),
+          Err(err) => self.info.add_book_error(err, ErrorType::Entrypoint),
+        },
+        None => {
+          let similar = self.book.similar_def_names(&custom, false);
+          self.info.add_book_error(EntryErr::NotFound(custom, similar), ErrorType::Entrypoint);
         }
       }
+      return;
+    }
 
-      (Some(a), Some(b), None) | (None, Some(a), Some(b)) | (Some(a), None, Some(b)) => {
-        self.info.add_book_error(EntryErr::Multiple(vec![a.name.clone(), b.name.clone()]));
+    let mut entrypoint = None;
 
-        match validate_entry_point(a) {
-          Ok(name) => entrypoint = Some(name),
-          Err(err) => self.info.add_book_error(err),
-        }
-      }
+    let (main, hvm1_main) = self.book.get_possible_entry_points();
+    match (main, hvm1_main) {
+      (Some(entry), None) | (None, Some(entry)) => match validate_entry_point(entry) {
+        Ok(name) => entrypoint = Some(name),
+        Err(err) => self.info.add_book_error(err, ErrorType::Entrypoint),
+      },
 
-      (Some(a), Some(b), Some(c)) => {
-        self.info.add_book_error(EntryErr::Multiple(vec![a.name.clone(), b.name.clone(), c.name.clone()]));
+      (Some(a), Some(b)) => {
+        self
+          .info
+          .add_book_error(EntryErr::Multiple(vec![a.name.clone(), b.name.clone()]), ErrorType::Entrypoint);
 
         match validate_entry_point(a) {
           Ok(name) => entrypoint = Some(name),
-          Err(err) => self.info.add_book_error(err),
+          Err(err) => self.info.add_book_error(err, ErrorType::Entrypoint),
         }
       }
 
-      (None, None, None) => {
-        let entrypoint = self.book.entrypoint.clone().unwrap_or(Name::new(ENTRY_POINT));
-        self.info.add_book_error(EntryErr::NotFound(entrypoint))
+      (None, None) => {
+        self.info.add_book_error(EntryErr::NotFound(Name::new(ENTRY_POINT), vec![]), ErrorType::Entrypoint)
       }
     }
 
@@ -56,24 +63,25 @@ fn validate_entry_point(entry: &Definition) -> Result<Name, EntryErr> {
 }
 
 impl Book {
-  fn get_possible_entry_points(&self) -> (Option<&Definition>, Option<&Definition>, Option<&Definition>) {
-    let custom = self.entrypoint.as_ref().and_then(|e| self.defs.get(e));
+  fn get_possible_entry_points(&self) -> (Option<&Definition>, Option<&Definition>) {
     let main = self.defs.get(&Name::new(ENTRY_POINT));
     let hvm1_main = self.defs.get(&Name::new(HVM1_ENTRY_POINT));
-    (custom, main, hvm1_main)
+    (main, hvm1_main)
   }
 }
 
 impl std::fmt::Display for EntryErr {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
-      EntryErr::NotFound(name) => write!(f, "File has no '{name}' definition."),
-      EntryErr::Multiple(fnd) if fnd.len() == 2 => {
-        write!(f, "File has both '{}' and '{}' definitions.", fnd[0], fnd[1])
-      }
-      EntryErr::Multiple(fnd) => {
-        write!(f, "File has '{}', '{}' and '{}' definitions.", fnd[0], fnd[1], fnd[2])
+      EntryErr::NotFound(name, similar) => {
+        write!(f, "File has no '{name}' definition.")?;
+        if !similar.is_empty() {
+          let similar = similar.iter().map(|nam| format!("'{nam}'")).collect::<Vec<_>>();
+          write!(f, " Did you mean {}?", similar.join(", "))?;
+        }
+        Ok(())
       }
+      EntryErr::Multiple(fnd) => write!(f, "File has both '{}' and '{}' definitions.", fnd[0], fnd[1]),
       EntryErr::MultipleRules => write!(f, "Main definition can't have more than one rule."),
     }
   }
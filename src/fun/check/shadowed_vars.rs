@@ -0,0 +1,75 @@
+use crate::{
+  diagnostics::{Diagnostics, WarningType},
+  fun::{Ctx, Name, Term},
+  maybe_grow,
+};
+use std::collections::HashMap;
+
+pub struct ShadowedVarWarn {
+  nam: Name,
+}
+
+impl Ctx<'_> {
+  /// Warns about binders that reuse the name of a variable that is already in scope,
+  /// making the outer variable inaccessible for the rest of the term.
+  ///
+  /// Must run after the local definitions are lifted and the constructor patterns are fixed.
+  pub fn check_shadowed_vars(&mut self) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    for (def_name, def) in self.book.defs.iter().filter(|(_, def)| !def.builtin) {
+      let mut warns = Vec::new();
+      for rule in &def.rules {
+        let mut scope = HashMap::new();
+        for nam in rule.pats.iter().flat_map(|pat| pat.binds().flatten()) {
+          *scope.entry(nam).or_default() += 1;
+        }
+        for term in rule.guard.iter().chain([&rule.body]) {
+          term.check_shadowed_vars(&mut scope, &mut warns);
+        }
+      }
+
+      for nam in warns {
+        self.info.add_rule_warning(ShadowedVarWarn { nam }, WarningType::ShadowedVariable, def_name.clone());
+      }
+    }
+
+    self.info.fatal(())
+  }
+}
+
+impl Term {
+  /// Collects the names of the binders of this term that shadow a variable of the scope,
+  /// reporting each name only once.
+  pub fn check_shadowed_vars<'a>(&'a self, scope: &mut HashMap<&'a Name, u64>, warns: &mut Vec<Name>) {
+    maybe_grow(|| {
+      // The predecessor of a switch is bound implicitly, not by the user.
+      let report = !matches!(self, Term::Swt { .. });
+      for (child, binds) in self.children_with_binds() {
+        let binds = binds.flatten();
+        for nam in binds.clone() {
+          if report && scope.contains_key(nam) && !warns.contains(nam) {
+            warns.push(nam.clone());
+          }
+        }
+        for nam in binds.clone() {
+          *scope.entry(nam).or_default() += 1;
+        }
+        child.check_shadowed_vars(scope, warns);
+        for nam in binds {
+          let count = scope.get_mut(nam).unwrap();
+          *count -= 1;
+          if *count == 0 {
+            scope.remove(nam);
+          }
+        }
+      }
+    })
+  }
+}
+
+impl std::fmt::Display for ShadowedVarWarn {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Variable '{}' shadows a variable of the same name that is already in scope.", self.nam)
+  }
+}
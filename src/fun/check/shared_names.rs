@@ -1,4 +1,7 @@
-use crate::fun::{Ctx, Name};
+use crate::{
+  diagnostics::ErrorType,
+  fun::{Ctx, Name},
+};
 use indexmap::IndexMap;
 use std::fmt::Display;
 
@@ -28,7 +31,7 @@ impl Ctx<'_> {
     }
 
     for err in names.into_errs() {
-      self.info.add_book_error(err);
+      self.info.add_book_error(err, ErrorType::NameClash);
     }
   }
 }
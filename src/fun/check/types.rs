@@ -0,0 +1,515 @@
+use crate::{
+  diagnostics::{Diagnostics, ErrorType},
+  fun::{builtins::STRING, Adt, Book, CtrField, Ctx, FanKind, Name, Num, Op, Pattern, Term, Type},
+  maybe_grow,
+};
+use std::collections::{BTreeSet, HashMap};
+
+impl Ctx<'_> {
+  /// Checks the definitions that have a type signature, inferring the types of their
+  /// terms with Hindley-Milner and checking that they agree with the signature.
+  ///
+  /// The definitions without a signature that are used by the checked ones have their types inferred.
+  /// If that's not possible, they're treated as having an unknown type that can be used as anything.
+  ///
+  /// Precondition: Matches are desugared and fixed, and refs are resolved.
+  pub fn check_types(&mut self) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    let mut checker = TypeChecker::new(self.book);
+    for def in self.book.defs.values() {
+      if let Some(typ) = &def.typ
+        && let Err(err) = checker.check_def(typ, &def.rules[0].body)
+      {
+        self.info.add_rule_error(err, ErrorType::TypeMismatch, def.name.clone());
+      }
+    }
+
+    self.info.fatal(())
+  }
+}
+
+/// A type where the variables in `vars` are replaced by fresh ones each time it's used.
+#[derive(Debug, Clone)]
+struct Scheme {
+  vars: Vec<Name>,
+  typ: Type,
+}
+
+struct TypeChecker<'a> {
+  book: &'a Book,
+  /// The inferred types of the definitions without a signature, or `None` while they're being inferred.
+  inferred: HashMap<Name, Option<Scheme>>,
+  /// The types that the unification variables were solved to.
+  subst: HashMap<Name, Type>,
+  /// The types of the unscoped variables of the current definition.
+  links: HashMap<Name, Type>,
+  fresh: usize,
+}
+
+impl<'a> TypeChecker<'a> {
+  fn new(book: &'a Book) -> Self {
+    TypeChecker { book, inferred: HashMap::new(), subst: HashMap::new(), links: HashMap::new(), fresh: 0 }
+  }
+
+  /// Checks the body of a definition against its signature.
+  /// The type variables of the signature are rigid, so they can't be unified with other types.
+  fn check_def(&mut self, typ: &Type, body: &Term) -> Result<(), String> {
+    let typ = self.resolve_signature(typ)?;
+    self.links.clear();
+    self.check(body, &typ, &mut vec![])
+  }
+
+  /// Infers the type of a definition without a signature.
+  fn infer_def(&mut self, body: &Term) -> Result<Type, String> {
+    let links = std::mem::take(&mut self.links);
+    let res = self.infer(body, &mut vec![]);
+    self.links = links;
+    Ok(self.apply(&res?))
+  }
+
+  fn check(&mut self, term: &Term, typ: &Type, env: &mut Vec<(Name, Scheme)>) -> Result<(), String> {
+    maybe_grow(|| match (term, self.shallow(typ)) {
+      (Term::Lam { pat, bod, .. }, Type::Arr(arg, ret)) => {
+        let len = env.len();
+        self.bind_pattern(pat, &arg, env)?;
+        self.check(bod, &ret, env)?;
+        env.truncate(len);
+        Ok(())
+      }
+      (Term::Let { pat, val, nxt }, _) => {
+        let len = env.len();
+        self.bind_let(pat, val, env)?;
+        self.check(nxt, typ, env)?;
+        env.truncate(len);
+        Ok(())
+      }
+      (Term::Use { nam, val, nxt }, _) => {
+        let len = env.len();
+        self.bind_use(nam, val, env)?;
+        self.check(nxt, typ, env)?;
+        env.truncate(len);
+        Ok(())
+      }
+      (Term::Mat { arg, arms, .. }, _) => {
+        for (fields, body) in self.match_arms(arg, arms, env)? {
+          let len = env.len();
+          env.extend(fields);
+          self.check(body, typ, env)?;
+          env.truncate(len);
+        }
+        Ok(())
+      }
+      (Term::Swt { arg, pred, arms, .. }, _) => {
+        let arg_typ = self.infer(arg, env)?;
+        self.unify(&Type::U24, &arg_typ)?;
+        let (succ, nums) = arms.split_last().unwrap();
+        for arm in nums {
+          self.check(arm, typ, env)?;
+        }
+        let len = env.len();
+        env.extend(pred.clone().map(|pred| (pred, Scheme::mono(Type::U24))));
+        self.check(succ, typ, env)?;
+        env.truncate(len);
+        Ok(())
+      }
+      _ => {
+        let found = self.infer(term, env)?;
+        self.unify(typ, &found)
+      }
+    })
+  }
+
+  fn infer(&mut self, term: &Term, env: &mut Vec<(Name, Scheme)>) -> Result<Type, String> {
+    maybe_grow(|| match term {
+      Term::Var { nam } => match env.iter().rev().find(|(var, _)| var == nam) {
+        Some((_, scheme)) => Ok(self.instantiate(&scheme.clone())),
+        None => Ok(self.fresh_var()),
+      },
+      Term::Link { nam } => Ok(self.link(nam)),
+      Term::Lam { pat, bod, .. } => {
+        let len = env.len();
+        let arg = self.fresh_var();
+        self.bind_pattern(pat, &arg, env)?;
+        let ret = self.infer(bod, env)?;
+        env.truncate(len);
+        Ok(Type::Arr(Box::new(arg), Box::new(ret)))
+      }
+      Term::Let { pat, val, nxt } => {
+        let len = env.len();
+        self.bind_let(pat, val, env)?;
+        let typ = self.infer(nxt, env)?;
+        env.truncate(len);
+        Ok(typ)
+      }
+      Term::Use { nam, val, nxt } => {
+        let len = env.len();
+        self.bind_use(nam, val, env)?;
+        let typ = self.infer(nxt, env)?;
+        env.truncate(len);
+        Ok(typ)
+      }
+      Term::App { fun, arg, .. } => {
+        let fun_typ = self.infer(fun, env)?;
+        match self.shallow(&fun_typ) {
+          Type::Arr(arg_typ, ret) => {
+            self.check(arg, &arg_typ, env)?;
+            Ok(*ret)
+          }
+          Type::Var(nam) if is_unification_var(&nam) => {
+            let arg_typ = self.infer(arg, env)?;
+            let ret = self.fresh_var();
+            self.unify(&fun_typ, &Type::Arr(Box::new(arg_typ), Box::new(ret.clone())))?;
+            Ok(ret)
+          }
+          fun_typ => Err(format!("Applying a value of type '{}' as a function.", self.apply(&fun_typ))),
+        }
+      }
+      Term::Fan { fan: FanKind::Tup, els, .. } => {
+        let els = els.iter().map(|el| self.infer(el, env)).collect::<Result<_, _>>()?;
+        Ok(Type::Tup(els))
+      }
+      Term::Fan { fan: FanKind::Dup, els, .. } => {
+        let typ = self.fresh_var();
+        for el in els {
+          self.check(el, &typ, env)?;
+        }
+        Ok(typ)
+      }
+      Term::Num { val: Num::U24(_) } => Ok(Type::U24),
+      Term::Num { val: Num::I24(_) } => Ok(Type::I24),
+      Term::Num { val: Num::F24(_) } => Ok(Type::F24),
+      Term::Oper { opr, fst, snd } => {
+        let typ = self.infer(fst, env)?;
+        if let Type::Ctr(..) | Type::Arr(..) | Type::Tup(..) = self.shallow(&typ) {
+          return Err(format!("Numeric operation on a value of type '{}'.", self.apply(&typ)));
+        }
+        self.check(snd, &typ, env)?;
+        match opr {
+          Op::EQL | Op::NEQ | Op::LTN | Op::GTN => Ok(Type::U24),
+          _ => Ok(typ),
+        }
+      }
+      Term::Mat { .. } | Term::Swt { .. } => {
+        let typ = self.fresh_var();
+        self.check(term, &typ, env)?;
+        Ok(typ)
+      }
+      Term::Ref { nam } => self.infer_ref(nam),
+      Term::Era | Term::Err => Ok(self.fresh_var()),
+      Term::Nat { .. } | Term::Str { .. } | Term::List { .. } => {
+        unreachable!("Should have been encoded in encode_builtins")
+      }
+      Term::Do { .. }
+      | Term::Ask { .. }
+      | Term::PatMat { .. }
+      | Term::Fold { .. }
+      | Term::Bend { .. }
+      | Term::Open { .. }
+      | Term::Def { .. }
+      | Term::Get { .. }
+      | Term::Upd { .. } => unreachable!("Should have been removed in earlier pass"),
+    })
+  }
+
+  fn infer_ref(&mut self, nam: &Name) -> Result<Type, String> {
+    if let Some(adt) = self.book.ctrs.get(nam) {
+      return Ok(self.ctr_type(adt, nam));
+    }
+    let Some(def) = self.book.defs.get(nam) else {
+      return Ok(self.fresh_var());
+    };
+    if let Some(typ) = &def.typ {
+      // Errors in the signature are reported when checking the definition.
+      let typ = self.resolve_signature(typ).unwrap_or_else(|_| self.fresh_var());
+      return Ok(self.instantiate(&Scheme::poly(typ)));
+    }
+    match self.inferred.get(nam) {
+      Some(Some(scheme)) => Ok(self.instantiate(&scheme.clone())),
+      // A recursive use of a definition that is still being inferred.
+      Some(None) => Ok(self.fresh_var()),
+      None => {
+        self.inferred.insert(nam.clone(), None);
+        let typ = self.infer_def(&def.rules[0].body).unwrap_or_else(|_| self.fresh_var());
+        let scheme = self.generalize(typ, &[]);
+        self.inferred.insert(nam.clone(), Some(scheme.clone()));
+        Ok(self.instantiate(&scheme))
+      }
+    }
+  }
+
+  /// Binds the variables of a lambda or `let` pattern, matching a value of the given type.
+  fn bind_pattern(&mut self, pat: &Pattern, typ: &Type, env: &mut Vec<(Name, Scheme)>) -> Result<(), String> {
+    maybe_grow(|| match pat {
+      Pattern::Var(nam) => {
+        env.extend(nam.clone().map(|nam| (nam, Scheme::mono(typ.clone()))));
+        Ok(())
+      }
+      Pattern::Chn(nam) => {
+        let link = self.link(nam);
+        self.unify(&link, typ)
+      }
+      Pattern::Fan(FanKind::Tup, _, els) => {
+        let els_typ = els.iter().map(|_| self.fresh_var()).collect::<Vec<_>>();
+        self.unify(&Type::Tup(els_typ.clone()), typ)?;
+        for (el, el_typ) in els.iter().zip(els_typ) {
+          self.bind_pattern(el, &el_typ, env)?;
+        }
+        Ok(())
+      }
+      Pattern::Fan(FanKind::Dup, _, els) => {
+        for el in els {
+          self.bind_pattern(el, typ, env)?;
+        }
+        Ok(())
+      }
+      Pattern::Ctr(..) | Pattern::Num(..) | Pattern::Lst(..) | Pattern::Str(..) => {
+        unreachable!("Should have been removed in earlier pass")
+      }
+    })
+  }
+
+  fn bind_let(&mut self, pat: &Pattern, val: &Term, env: &mut Vec<(Name, Scheme)>) -> Result<(), String> {
+    if let Pattern::Var(nam) = pat {
+      self.bind_use(nam, val, env)
+    } else {
+      let typ = self.infer(val, env)?;
+      self.bind_pattern(pat, &typ, env)
+    }
+  }
+
+  /// Binds a variable to a value, generalizing its type so that it can be used with different types.
+  fn bind_use(
+    &mut self,
+    nam: &Option<Name>,
+    val: &Term,
+    env: &mut Vec<(Name, Scheme)>,
+  ) -> Result<(), String> {
+    let typ = self.infer(val, env)?;
+    if let Some(nam) = nam {
+      let scheme = self.generalize(typ, env);
+      env.push((nam.clone(), scheme));
+    }
+    Ok(())
+  }
+
+  /// Checks the matched value against the type of the constructors in the arms,
+  /// returning the variables bound by each arm.
+  #[allow(clippy::type_complexity)]
+  fn match_arms<'t>(
+    &mut self,
+    arg: &Term,
+    arms: &'t [(Option<Name>, Vec<Option<Name>>, Term)],
+    env: &mut Vec<(Name, Scheme)>,
+  ) -> Result<Vec<(Vec<(Name, Scheme)>, &'t Term)>, String> {
+    let Some(Some(fst)) = arms.first().map(|arm| &arm.0) else { unreachable!() };
+    let adt_nam = &self.book.ctrs[fst];
+    let adt = &self.book.adts[adt_nam];
+    let params = self.fresh_vars(adt_params(adt_nam, adt).count());
+    let adt_typ = Type::Ctr(adt_nam.clone(), params.clone());
+    let arg_typ = self.infer(arg, env)?;
+    self.unify(&adt_typ, &arg_typ)?;
+
+    let mut res = vec![];
+    for (ctr, binds, body) in arms {
+      let Some(ctr) = ctr else { unreachable!() };
+      let fields = field_types(adt_nam, adt, ctr, &adt_typ, &params);
+      let binds = binds.iter().zip(fields).filter_map(|(bind, typ)| Some((bind.clone()?, Scheme::mono(typ))));
+      res.push((binds.collect(), body));
+    }
+    Ok(res)
+  }
+
+  /// The type of a constructor, a function from its fields to its datatype.
+  fn ctr_type(&mut self, adt_nam: &Name, ctr: &Name) -> Type {
+    let adt = &self.book.adts[adt_nam];
+    let params = self.fresh_vars(adt_params(adt_nam, adt).count());
+    let adt_typ = Type::Ctr(adt_nam.clone(), params.clone());
+    let fields = field_types(adt_nam, adt, ctr, &adt_typ, &params);
+    fields.into_iter().rev().fold(adt_typ, |ret, field| Type::Arr(Box::new(field), Box::new(ret)))
+  }
+
+  /// Resolves the datatypes used in a type signature.
+  /// A datatype used without its parameters has them filled with unification variables.
+  fn resolve_signature(&mut self, typ: &Type) -> Result<Type, String> {
+    maybe_grow(|| match typ {
+      Type::Var(nam) => match self.book.adts.get(nam) {
+        Some(adt) => Ok(Type::Ctr(nam.clone(), self.fresh_vars(adt_params(nam, adt).count()))),
+        None => Ok(Type::Var(nam.clone())),
+      },
+      Type::Ctr(nam, args) => {
+        let Some(adt) = self.book.adts.get(nam) else {
+          return Err(format!("Unknown datatype '{nam}' in type signature."));
+        };
+        let arity = adt_params(nam, adt).count();
+        if args.len() != arity {
+          return Err(format!(
+            "Incorrect number of parameters for datatype '{nam}' in type signature. Expected {arity}, found {}.",
+            args.len()
+          ));
+        }
+        let args = args.iter().map(|arg| self.resolve_signature(arg)).collect::<Result<_, _>>()?;
+        Ok(Type::Ctr(nam.clone(), args))
+      }
+      Type::Arr(arg, ret) => {
+        Ok(Type::Arr(Box::new(self.resolve_signature(arg)?), Box::new(self.resolve_signature(ret)?)))
+      }
+      Type::Tup(els) => {
+        Ok(Type::Tup(els.iter().map(|el| self.resolve_signature(el)).collect::<Result<_, _>>()?))
+      }
+      Type::U24 | Type::I24 | Type::F24 => Ok(typ.clone()),
+    })
+  }
+
+  fn unify(&mut self, expected: &Type, found: &Type) -> Result<(), String> {
+    if self.unify_types(expected, found) {
+      Ok(())
+    } else {
+      Err(format!("Expected type '{}', found '{}'.", self.apply(expected), self.apply(found)))
+    }
+  }
+
+  fn unify_types(&mut self, a: &Type, b: &Type) -> bool {
+    maybe_grow(|| match (self.shallow(a), self.shallow(b)) {
+      (Type::Var(a), Type::Var(b)) if a == b => true,
+      (Type::Var(var), typ) if is_unification_var(&var) => self.solve(var, typ),
+      (typ, Type::Var(var)) if is_unification_var(&var) => self.solve(var, typ),
+      (Type::Ctr(a, a_args), Type::Ctr(b, b_args)) => {
+        a == b
+          && a_args.len() == b_args.len()
+          && a_args.iter().zip(&b_args).all(|(a, b)| self.unify_types(a, b))
+      }
+      (Type::Arr(a_arg, a_ret), Type::Arr(b_arg, b_ret)) => {
+        self.unify_types(&a_arg, &b_arg) && self.unify_types(&a_ret, &b_ret)
+      }
+      (Type::Tup(a_els), Type::Tup(b_els)) => {
+        a_els.len() == b_els.len() && a_els.iter().zip(&b_els).all(|(a, b)| self.unify_types(a, b))
+      }
+      (Type::U24, Type::U24) | (Type::I24, Type::I24) | (Type::F24, Type::F24) => true,
+      _ => false,
+    })
+  }
+
+  /// Solves a unification variable to a type, unless it would create an infinite type.
+  fn solve(&mut self, var: Name, typ: Type) -> bool {
+    let typ = self.apply(&typ);
+    if free_vars(&typ).contains(&var) {
+      return false;
+    }
+    self.subst.insert(var, typ);
+    true
+  }
+
+  /// Follows the solved unification variables at the root of the type.
+  fn shallow(&self, typ: &Type) -> Type {
+    let mut typ = typ;
+    while let Type::Var(nam) = typ
+      && let Some(solved) = self.subst.get(nam)
+    {
+      typ = solved;
+    }
+    typ.clone()
+  }
+
+  /// Replaces all the solved unification variables in the type.
+  fn apply(&self, typ: &Type) -> Type {
+    maybe_grow(|| {
+      let mut typ = self.shallow(typ);
+      for child in typ.children_mut() {
+        *child = self.apply(child);
+      }
+      typ
+    })
+  }
+
+  fn generalize(&self, typ: Type, env: &[(Name, Scheme)]) -> Scheme {
+    let typ = self.apply(&typ);
+    let mut env_vars = BTreeSet::new();
+    for (_, scheme) in env {
+      let scheme_vars = free_vars(&self.apply(&scheme.typ));
+      env_vars.extend(scheme_vars.into_iter().filter(|var| !scheme.vars.contains(var)));
+    }
+    let vars = free_vars(&typ).into_iter().filter(|var| is_unification_var(var) && !env_vars.contains(var));
+    Scheme { vars: vars.collect(), typ }
+  }
+
+  fn instantiate(&mut self, scheme: &Scheme) -> Type {
+    let vars = scheme.vars.iter().map(|var| (var.clone(), self.fresh_var())).collect::<HashMap<_, _>>();
+    replace_vars(&scheme.typ, &vars)
+  }
+
+  fn link(&mut self, nam: &Name) -> Type {
+    if let Some(typ) = self.links.get(nam) {
+      return typ.clone();
+    }
+    let typ = self.fresh_var();
+    self.links.insert(nam.clone(), typ.clone());
+    typ
+  }
+
+  fn fresh_var(&mut self) -> Type {
+    self.fresh += 1;
+    Type::Var(Name::new(format!("?{}", self.fresh - 1)))
+  }
+
+  fn fresh_vars(&mut self, n: usize) -> Vec<Type> {
+    (0 .. n).map(|_| self.fresh_var()).collect()
+  }
+}
+
+impl Scheme {
+  fn mono(typ: Type) -> Self {
+    Scheme { vars: vec![], typ }
+  }
+
+  /// Generalizes all the variables of the type.
+  fn poly(typ: Type) -> Self {
+    Scheme { vars: free_vars(&typ).into_iter().collect(), typ }
+  }
+}
+
+/// Unification variables are the ones created by the checker, which can be solved to any type.
+/// The variables written in type signatures are rigid, standing for a type chosen by the caller.
+fn is_unification_var(nam: &Name) -> bool {
+  nam.starts_with('?')
+}
+
+/// The parameters of a datatype are the types of the fields of its constructors,
+/// except for the recursive fields, which have the type of the datatype itself.
+///
+/// Strings have no parameters, since their characters are always `u24`.
+fn adt_params<'a>(nam: &Name, adt: &'a Adt) -> impl Iterator<Item = (&'a Name, &'a Name)> {
+  let fields = adt.ctrs.iter().flat_map(|(ctr, fields)| fields.iter().map(move |f| (ctr, f)));
+  let is_string = nam == STRING;
+  fields.filter(move |(_, f)| !f.rec && !is_string).map(|(ctr, f)| (ctr, &f.nam))
+}
+
+fn field_types(adt_nam: &Name, adt: &Adt, ctr: &Name, adt_typ: &Type, params: &[Type]) -> Vec<Type> {
+  let param_idx = |fld: &Name| adt_params(adt_nam, adt).position(|(c, f)| c == ctr && f == fld).unwrap();
+  let field_type = |f: &CtrField| match () {
+    _ if f.rec => adt_typ.clone(),
+    _ if adt_nam == STRING => Type::U24,
+    _ => params[param_idx(&f.nam)].clone(),
+  };
+  adt.ctrs[ctr].iter().map(field_type).collect()
+}
+
+fn free_vars(typ: &Type) -> BTreeSet<Name> {
+  maybe_grow(|| match typ {
+    Type::Var(nam) => BTreeSet::from([nam.clone()]),
+    _ => typ.children().flat_map(free_vars).collect(),
+  })
+}
+
+fn replace_vars(typ: &Type, vars: &HashMap<Name, Type>) -> Type {
+  maybe_grow(|| match typ {
+    Type::Var(nam) => vars.get(nam).cloned().unwrap_or_else(|| typ.clone()),
+    _ => {
+      let mut typ = typ.clone();
+      for child in typ.children_mut() {
+        *child = replace_vars(child, vars);
+      }
+      typ
+    }
+  })
+}
@@ -1,5 +1,5 @@
 use crate::{
-  diagnostics::Diagnostics,
+  diagnostics::{Diagnostics, ErrorType},
   fun::{Ctx, Name, Pattern, Term},
   maybe_grow,
 };
@@ -7,8 +7,13 @@ use std::collections::{hash_map::Entry, HashMap};
 
 #[derive(Debug, Clone)]
 pub enum UnboundVarErr {
-  Local(Name),
-  Global { var: Name, declared: usize, used: usize },
+  /// A variable that isn't bound, with the names of similar definitions.
+  Local(Name, Vec<Name>),
+  Global {
+    var: Name,
+    declared: usize,
+    used: usize,
+  },
 }
 
 impl Ctx<'_> {
@@ -16,6 +21,7 @@ impl Ctx<'_> {
   pub fn check_unbound_vars(&mut self) -> Result<(), Diagnostics> {
     self.info.start_pass();
 
+    let mut def_errs = Vec::new();
     for (def_name, def) in self.book.defs.iter_mut() {
       let mut errs = Vec::new();
       for rule in &mut def.rules {
@@ -27,8 +33,16 @@ impl Ctx<'_> {
         rule.body.check_unbound_vars(&mut scope, &mut errs);
       }
 
-      for err in errs {
-        self.info.add_rule_error(err, def_name.clone());
+      def_errs.push((def_name.clone(), errs));
+    }
+
+    for (def_name, errs) in def_errs {
+      for mut err in errs {
+        // A name that is neither a variable nor a definition is probably a misspelled definition.
+        if let UnboundVarErr::Local(var, similar) = &mut err {
+          *similar = self.book.similar_def_names(var, true);
+        }
+        self.info.add_rule_error(err, ErrorType::UnboundVariable, def_name.clone());
       }
     }
 
@@ -66,7 +80,7 @@ pub fn check_uses<'a>(
   maybe_grow(move || match term {
     Term::Var { nam } => {
       if !scope.contains_key(nam) {
-        errs.push(UnboundVarErr::Local(nam.clone()));
+        errs.push(UnboundVarErr::Local(nam.clone(), vec![]));
         *term = Term::Err;
       }
     }
@@ -123,7 +137,14 @@ fn pop_scope<'a>(nam: Option<&'a Name>, scope: &mut HashMap<&'a Name, u64>) {
 impl std::fmt::Display for UnboundVarErr {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
-      UnboundVarErr::Local(var) => write!(f, "Unbound variable '{var}'."),
+      UnboundVarErr::Local(var, similar) => {
+        write!(f, "Unbound variable '{var}'.")?;
+        if !similar.is_empty() {
+          let similar = similar.iter().map(|nam| format!("'{nam}'")).collect::<Vec<_>>();
+          write!(f, " Did you mean {}?", similar.join(", "))?;
+        }
+        Ok(())
+      }
       UnboundVarErr::Global { var, declared, used } => match (declared, used) {
         (0, _) => write!(f, "Unbound unscoped variable '${var}'."),
         (_, 0) => write!(f, "Unscoped variable from lambda 'λ${var}' is never used."),
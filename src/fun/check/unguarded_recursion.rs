@@ -0,0 +1,94 @@
+use crate::{
+  diagnostics::{Diagnostics, WarningType},
+  fun::{Ctx, Name, Term},
+  maybe_grow,
+};
+
+pub struct UnguardedRecursionWarn {
+  call: Term,
+}
+
+impl Ctx<'_> {
+  /// Warns about the calls of a definition to itself that are not inside the arm of a match.
+  ///
+  /// Under strict evaluation, such a call is expanded as soon as the definition is,
+  /// so it loops forever or makes the net grow without bound.
+  /// Recursive calls inside match arms are fine, since the arms are lifted to lazy references,
+  /// and so are the ones inside lambdas other than the parameters of the definition,
+  /// which is how lambda-encoded data is matched on, and the closed ones, which are
+  /// floated out to their own definitions.
+  ///
+  /// Must run after the pattern matching definitions and the `bend`s and `fold`s are desugared.
+  pub fn check_unguarded_recursion(&mut self) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    for (def_name, def) in self.book.defs.iter().filter(|(_, def)| !def.builtin) {
+      let mut calls = Vec::new();
+      for rule in &def.rules {
+        let mut body = &rule.body;
+        while let Term::Lam { bod, .. } = body {
+          body = bod;
+        }
+        body.unguarded_calls(def_name, &mut calls);
+      }
+
+      for call in calls {
+        self.info.add_rule_warning(
+          UnguardedRecursionWarn { call: call.clone() },
+          WarningType::UnguardedRecursion,
+          def_name.clone(),
+        );
+      }
+    }
+
+    self.info.fatal(())
+  }
+}
+
+impl Term {
+  /// Collects the calls to `def_name` that would be evaluated without going through a match arm.
+  fn unguarded_calls<'a>(&'a self, def_name: &Name, calls: &mut Vec<&'a Term>) {
+    maybe_grow(|| match self {
+      Term::Mat { arg, .. } | Term::Swt { arg, .. } | Term::Fold { arg, .. } => {
+        arg.unguarded_calls(def_name, calls)
+      }
+      Term::Lam { .. } => (),
+      // The value of a `use` is only evaluated where it's inlined.
+      Term::Use { nxt, .. } => nxt.unguarded_calls(def_name, calls),
+      Term::App { .. } => {
+        let mut fun = self;
+        let mut args = vec![];
+        while let Term::App { fun: f, arg, .. } = fun {
+          args.push(arg.as_ref());
+          fun = f;
+        }
+        match fun {
+          Term::Ref { nam } if nam == def_name => {
+            if !self.free_vars().is_empty() {
+              calls.push(self);
+            }
+          }
+          _ => fun.unguarded_calls(def_name, calls),
+        }
+        for arg in args.into_iter().rev() {
+          arg.unguarded_calls(def_name, calls);
+        }
+      }
+      _ => {
+        for child in self.children() {
+          child.unguarded_calls(def_name, calls);
+        }
+      }
+    })
+  }
+}
+
+impl std::fmt::Display for UnguardedRecursionWarn {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "Recursive call '{}' is not inside a match arm, so it may loop forever under strict evaluation.",
+      self.call
+    )
+  }
+}
@@ -0,0 +1,172 @@
+use crate::{
+  diagnostics::{Diagnostics, WarningType},
+  fun::{Ctx, FanKind, Name, Pattern, Term},
+  maybe_grow,
+};
+use std::collections::HashMap;
+
+pub struct UnusedVarWarn {
+  nam: Name,
+}
+
+pub struct UnusedDupWarn {
+  nam: Name,
+}
+
+/// The variables in scope, with the binders that introduced them.
+#[derive(Default)]
+struct Scope<'a> {
+  vars: HashMap<&'a Name, Vec<usize>>,
+  binds: Vec<Bind<'a>>,
+  /// The reported binders that went out of scope without being used.
+  unused: Vec<Name>,
+  /// The copies of explicit duplications that went out of scope without being used.
+  unused_dups: Vec<Name>,
+}
+
+struct Bind<'a> {
+  nam: &'a Name,
+  used: bool,
+  /// Whether it's reported as an unused variable when not used.
+  report: bool,
+  /// Whether it's one of the copies of an explicit duplication, like `a` in `let {a b} = x`.
+  dup: bool,
+}
+
+impl Ctx<'_> {
+  /// Warns about the parameters of lambdas and functions and the variables bound by
+  /// `let`s and pattern matching that are never used, and so are silently erased.
+  ///
+  /// The unused copies of an explicit duplication, like `let {a b} = x`, are reported
+  /// with their own warning, since the duplication is built for nothing.
+  ///
+  /// Names starting with `_` are considered intentionally unused.
+  ///
+  /// Must run after the local definitions are lifted and the constructor patterns are fixed.
+  pub fn check_unused_vars(&mut self) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    // The encoded constructors are skipped, since they always ignore the other cases.
+    let defs = self.book.defs.iter().filter(|(nam, def)| !def.builtin && !self.book.ctrs.contains_key(*nam));
+    for (def_name, def) in defs {
+      let mut scope = Scope::default();
+      for rule in &def.rules {
+        scope.push_pats(&rule.pats);
+        for term in rule.guard.iter().chain([&rule.body]) {
+          term.check_unused_vars(&mut scope);
+        }
+        scope.pop_to(0);
+      }
+
+      for nam in scope.unused {
+        self.info.add_rule_warning(UnusedVarWarn { nam }, WarningType::UnusedVars, def_name.clone());
+      }
+      for nam in scope.unused_dups {
+        self.info.add_rule_warning(UnusedDupWarn { nam }, WarningType::UnusedDup, def_name.clone());
+      }
+    }
+
+    self.info.fatal(())
+  }
+}
+
+impl Term {
+  fn check_unused_vars<'a>(&'a self, scope: &mut Scope<'a>) {
+    maybe_grow(|| match self {
+      Term::Var { nam } => scope.mark_used(nam),
+      Term::Open { var, .. } => scope.mark_used(var),
+      // The guard and the body of an arm share its binds.
+      Term::PatMat { arg, arms } => {
+        arg.check_unused_vars(scope);
+        for (pat, guard, body) in arms {
+          let len = scope.binds.len();
+          scope.push_pats([pat]);
+          for term in guard.iter().chain([body]) {
+            term.check_unused_vars(scope);
+          }
+          scope.pop_to(len);
+        }
+      }
+      _ => {
+        if let Term::Mat { with, .. } | Term::Swt { with, .. } | Term::Fold { with, .. } = self {
+          with.iter().for_each(|nam| scope.mark_used(nam));
+        }
+        // Other binders, like `use` and the implicit ones of matches and bends, are not reported.
+        let report = matches!(self, Term::Lam { .. } | Term::Let { .. } | Term::Ask { .. });
+        let dups = match self {
+          Term::Lam { pat, .. } | Term::Let { pat, .. } => dup_copies(pat),
+          _ => vec![],
+        };
+        for (child, binds) in self.children_with_binds() {
+          let len = scope.binds.len();
+          binds.flatten().for_each(|nam| scope.push(nam, report, dups.contains(&nam)));
+          child.check_unused_vars(scope);
+          scope.pop_to(len);
+        }
+      }
+    })
+  }
+}
+
+/// The variables that directly receive a copy of a duplication in the pattern.
+fn dup_copies(pat: &Pattern) -> Vec<&Name> {
+  let dups = pat.iter().filter_map(|pat| match pat {
+    Pattern::Fan(FanKind::Dup, _, els) => Some(els),
+    _ => None,
+  });
+  dups.flatten().filter_map(|el| if let Pattern::Var(Some(nam)) = el { Some(nam) } else { None }).collect()
+}
+
+impl<'a> Scope<'a> {
+  fn push(&mut self, nam: &'a Name, report: bool, dup: bool) {
+    self.vars.entry(nam).or_default().push(self.binds.len());
+    let intentional = nam.starts_with('_');
+    self.binds.push(Bind { nam, used: false, report: report && !intentional, dup: dup && !intentional });
+  }
+
+  fn push_pats(&mut self, pats: impl IntoIterator<Item = &'a Pattern>) {
+    for pat in pats {
+      let dups = dup_copies(pat);
+      for nam in pat.binds().flatten() {
+        self.push(nam, true, dups.contains(&nam));
+      }
+    }
+  }
+
+  fn mark_used(&mut self, nam: &Name) {
+    if let Some(&id) = self.vars.get(nam).and_then(|ids| ids.last()) {
+      self.binds[id].used = true;
+    }
+  }
+
+  /// Removes the binders pushed after the first `len`, collecting the unused ones that are reported.
+  fn pop_to(&mut self, len: usize) {
+    for Bind { nam, used, report, dup } in self.binds.drain(len ..) {
+      self.vars.get_mut(nam).unwrap().pop();
+      if used {
+        continue;
+      }
+      if dup && !self.unused_dups.contains(nam) {
+        self.unused_dups.push(nam.clone());
+      } else if !dup && report && !self.unused.contains(nam) {
+        self.unused.push(nam.clone());
+      }
+    }
+  }
+}
+
+impl std::fmt::Display for UnusedVarWarn {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "Variable '{}' is never used. Erase it with '*' or prefix it with '_' if this is intentional.",
+      self.nam
+    )
+  }
+}
+
+impl std::fmt::Display for UnusedDupWarn {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Copy '{}' of the duplication is never used. Erase it with '*' or make fewer copies.", self.nam)
+  }
+}
@@ -1,4 +1,4 @@
-use super::{Book, Definition, FanKind, Name, Num, Op, Pattern, Rule, Tag, Term};
+use super::{format::FormatOpts, Book, Definition, FanKind, Name, Num, Op, Pattern, Rule, Tag, Term, Type};
 use crate::maybe_grow;
 use std::{fmt, ops::Deref};
 
@@ -55,6 +55,9 @@ impl fmt::Display for Term {
         let Some(nam) = nam else { unreachable!() };
         write!(f, "use {} = {}; {}", nam, val, nxt)
       }
+      Term::Def { nam, rules, nxt } => {
+        write!(f, "def {}; {}", DisplayJoin(|| rules.iter().map(|rule| rule.display(nam)), "; "), nxt)
+      }
       Term::Ref { nam: def_name } => write!(f, "{def_name}"),
       Term::App { tag, fun, arg } => {
         write!(f, "{}({} {})", tag.display_padded(), fun.display_app(tag), arg)
@@ -78,6 +81,17 @@ impl fmt::Display for Term {
         }
         write!(f, "}}")
       }
+      Term::PatMat { arg, arms } => {
+        write!(f, "match {} {{ ", arg)?;
+        for (pat, guard, bod) in arms {
+          write!(f, "{}", pat)?;
+          if let Some(guard) = guard {
+            write!(f, " | {}", guard)?;
+          }
+          write!(f, ": {}; ", bod)?;
+        }
+        write!(f, "}}")
+      }
       Term::Swt { arg, bnd, with, pred, arms } => {
         write!(f, "switch ")?;
         if let Some(bnd) = bnd {
@@ -143,6 +157,14 @@ impl fmt::Display for Term {
       }
       Term::List { els } => write!(f, "[{}]", DisplayJoin(|| els.iter(), ", "),),
       Term::Open { typ, var, bod } => write!(f, "open {typ} {var}; {bod}"),
+      Term::Get { rec, fld } => write!(f, "({rec}).{fld}"),
+      Term::Upd { rec, flds } => {
+        write!(
+          f,
+          "{{{rec} | {}}}",
+          DisplayJoin(|| flds.iter().map(|(fld, val)| display!("{fld} = {val}")), ", ")
+        )
+      }
       Term::Err => write!(f, "<Invalid>"),
     })
   }
@@ -172,17 +194,36 @@ impl fmt::Display for Pattern {
       Pattern::Fan(FanKind::Tup, tag, pats) => write!(f, "{}({})", tag, DisplayJoin(|| pats, ", ")),
       Pattern::Fan(FanKind::Dup, tag, pats) => write!(f, "{}{{{}}}", tag, DisplayJoin(|| pats, " ")),
       Pattern::Lst(pats) => write!(f, "[{}]", DisplayJoin(|| pats, ", ")),
-      Pattern::Str(str) => write!(f, "\"{str}\""),
+      Pattern::Str(str) => write!(f, "{:?}", str.as_ref()),
     }
   }
 }
 
+impl fmt::Display for Type {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    maybe_grow(|| match self {
+      Type::Var(nam) => write!(f, "{nam}"),
+      Type::Ctr(nam, args) if args.is_empty() => write!(f, "{nam}"),
+      Type::Ctr(nam, args) => {
+        write!(f, "({}{})", nam, DisplayJoin(|| args.iter().map(|a| display!(" {a}")), ""))
+      }
+      Type::Arr(arg, ret) if matches!(arg.as_ref(), Type::Arr(..)) => write!(f, "({arg}) -> {ret}"),
+      Type::Arr(arg, ret) => write!(f, "{arg} -> {ret}"),
+      Type::Tup(els) => write!(f, "({})", DisplayJoin(|| els, ", ")),
+      Type::U24 => write!(f, "u24"),
+      Type::I24 => write!(f, "i24"),
+      Type::F24 => write!(f, "f24"),
+    })
+  }
+}
+
 impl Rule {
   pub fn display<'a>(&'a self, def_name: &'a Name) -> impl fmt::Display + 'a {
     display!(
-      "({}{}) = {}",
+      "({}{}){} = {}",
       def_name,
       DisplayJoin(|| self.pats.iter().map(|x| display!(" {x}")), ""),
+      DisplayJoin(|| self.guard.iter().map(|x| display!(" | {x}")), ""),
       self.body
     )
   }
@@ -234,6 +275,8 @@ impl fmt::Display for Op {
       Op::AND => write!(f, "&"),
       Op::OR => write!(f, "|"),
       Op::XOR => write!(f, "^"),
+      Op::SHL => write!(f, "<<"),
+      Op::SHR => write!(f, ">>"),
       Op::POW => write!(f, "**"),
       Op::LOG => todo!(),
       Op::ATN => todo!(),
@@ -273,9 +316,10 @@ impl Definition {
 impl Rule {
   pub fn display_pretty<'a>(&'a self, def_name: &'a Name) -> impl fmt::Display + 'a {
     display!(
-      "({}{}) =\n  {}",
+      "({}{}){} =\n  {}",
       def_name,
       DisplayJoin(|| self.pats.iter().map(|x| display!(" {x}")), ""),
+      DisplayJoin(|| self.guard.iter().map(|x| display!(" | {}", x.display_pretty(2))), ""),
       self.body.display_pretty(2)
     )
   }
@@ -311,6 +355,15 @@ impl Term {
             nxt.display_pretty(tab)
           )
         }
+        Term::Def { nam, rules, nxt } => {
+          write!(
+            f,
+            "def {};\n{:tab$}{}",
+            DisplayJoin(|| rules.iter().map(|rule| rule.display(nam)), "; "),
+            "",
+            nxt.display_pretty(tab)
+          )
+        }
         Term::App { tag, fun, arg } => {
           write!(
             f,
@@ -356,6 +409,17 @@ impl Term {
           }
           write!(f, "\n{:tab$}}}", "")
         }
+        Term::PatMat { arg, arms } => {
+          write!(f, "match {} {{ ", arg.display_pretty(tab))?;
+          for (pat, guard, bod) in arms {
+            write!(f, "\n{:tab$}{}", "", pat, tab = tab + 2)?;
+            if let Some(guard) = guard {
+              write!(f, " | {}", guard.display_pretty(tab + 4))?;
+            }
+            write!(f, ": {}; ", bod.display_pretty(tab + 4))?;
+          }
+          write!(f, "\n{:tab$}}}", "")
+        }
         Term::Swt { bnd, arg, with, pred, arms } => {
           write!(f, "switch ")?;
           if let Some(bnd) = bnd {
@@ -416,6 +480,15 @@ impl Term {
         Term::Open { typ, var, bod } => {
           write!(f, "open {typ} {var};\n{:tab$}{}", "", bod.display_pretty(tab))
         }
+        Term::Get { rec, fld } => write!(f, "({}).{fld}", rec.display_pretty(tab)),
+        Term::Upd { rec, flds } => {
+          write!(f, "{{{} | ", rec.display_pretty(tab))?;
+          let flds = DisplayJoin(
+            || flds.iter().map(|(fld, val)| display!("{fld} = {}", val.display_pretty(tab))),
+            ", ",
+          );
+          write!(f, "{flds}}}")
+        }
         Term::Nat { val } => write!(f, "#{val}"),
         Term::Num { val: Num::U24(val) } => write!(f, "{val}"),
         Term::Num { val: Num::I24(val) } => write!(f, "{}{}", if *val < 0 { "-" } else { "+" }, val.abs()),
@@ -439,3 +512,115 @@ impl Term {
     })
   }
 }
+
+/* Displaying results */
+
+/// Options for showing the result of a program, which can be too big to read in a single line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DisplayOpts {
+  /// Terms nested deeper than this in applications, tuples and lists are shown as `...`.
+  pub max_depth: Option<usize>,
+  /// Only the first elements of longer lists are shown, followed by `...`.
+  pub max_list: Option<usize>,
+  /// Results wider than this are broken into multiple lines, like `bend fmt` does.
+  pub width: Option<usize>,
+  pub numbers: NumDisplay,
+}
+
+/// How the unsigned and signed numbers of a result are shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NumDisplay {
+  #[default]
+  Decimal,
+  Hex,
+  /// As the character with that code point, for the ones that are printable.
+  Char,
+}
+
+impl std::str::FromStr for DisplayOpts {
+  type Err = String;
+
+  /// Parses a list of options like `depth=5,list=10,width=100,numbers=hex`.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut opts = DisplayOpts::default();
+    for opt in s.split(',').map(str::trim).filter(|opt| !opt.is_empty()) {
+      let Some((key, val)) = opt.split_once('=') else {
+        return Err(format!("Expected '=' after display option '{opt}'."));
+      };
+      let (key, val) = (key.trim(), val.trim());
+      let invalid = || format!("Invalid value '{val}' for display option '{key}'.");
+      match key {
+        "depth" => opts.max_depth = Some(val.parse().map_err(|_| invalid())?),
+        "list" => opts.max_list = Some(val.parse().map_err(|_| invalid())?),
+        "width" => opts.width = Some(val.parse().map_err(|_| invalid())?),
+        "numbers" => {
+          opts.numbers = match val {
+            "dec" => NumDisplay::Decimal,
+            "hex" => NumDisplay::Hex,
+            "char" => NumDisplay::Char,
+            _ => return Err(invalid()),
+          }
+        }
+        _ => {
+          return Err(format!(
+            "Unknown display option '{key}'. Expected 'depth', 'list', 'width' or 'numbers'."
+          ));
+        }
+      }
+    }
+    Ok(opts)
+  }
+}
+
+impl Term {
+  /// Shows a term with the given options, for printing the results of programs.
+  pub fn display_with(&self, opts: &DisplayOpts) -> String {
+    let mut term = self.clone();
+    term.abbreviate(opts, 0);
+    match opts.width {
+      Some(max_width) => term.format(FormatOpts { max_width, ..FormatOpts::default() }),
+      None => term.to_string(),
+    }
+  }
+
+  /// Replaces the parts of the term that the options hide, and the numbers shown in another base,
+  /// with variables named after what should be shown in their place.
+  fn abbreviate(&mut self, opts: &DisplayOpts, depth: usize) {
+    maybe_grow(|| {
+      let shown = |text: String| Term::Var { nam: Name::new(text) };
+      if opts.max_depth.is_some_and(|max| depth > max) {
+        *self = shown("...".to_string());
+        return;
+      }
+      match self {
+        Term::Num { val: Num::U24(val) } => match opts.numbers {
+          NumDisplay::Decimal => (),
+          NumDisplay::Hex => *self = shown(format!("{val:#x}")),
+          NumDisplay::Char => {
+            if let Some(chr) = char::from_u32(*val).filter(|chr| !chr.is_control()) {
+              *self = shown(format!("{chr:?}"));
+            }
+          }
+        },
+        Term::Num { val: Num::I24(val) } if opts.numbers == NumDisplay::Hex => {
+          *self = shown(format!("{}{:#x}", if *val < 0 { "-" } else { "+" }, val.unsigned_abs()));
+        }
+        Term::List { els } => {
+          if let Some(max) = opts.max_list.filter(|max| els.len() > *max) {
+            els.truncate(max);
+            els.push(shown("...".to_string()));
+          }
+          els.iter_mut().for_each(|el| el.abbreviate(opts, depth + 1));
+        }
+        // The arguments of an application are one level deeper than it, and its function at the same level.
+        Term::App { fun, arg, .. } => {
+          arg.abbreviate(opts, depth + 1);
+          fun.abbreviate(opts, depth);
+        }
+        // A chain of lambdas doesn't add to the depth of its body.
+        Term::Lam { bod, .. } => bod.abbreviate(opts, depth),
+        _ => self.children_mut().for_each(|child| child.abbreviate(opts, depth + 1)),
+      }
+    })
+  }
+}
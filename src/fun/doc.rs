@@ -0,0 +1,206 @@
+use super::{
+  format::{pattern, FormatOpts},
+  Book, Definition, Name, Pattern, Term, Type,
+};
+use crate::maybe_grow;
+use std::collections::BTreeSet;
+
+/// The output formats of the documentation generated by `bend doc`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocFormat {
+  Markdown,
+  Html,
+}
+
+/// A documented datatype or definition.
+struct DocItem<'a> {
+  name: &'a Name,
+  doc: Option<&'a str>,
+  /// The source code that declares the item, without its body.
+  decl: String,
+  /// The other documented items that this one mentions.
+  refs: BTreeSet<Name>,
+}
+
+impl Book {
+  /// Generates the reference documentation of the non-builtin datatypes and definitions of the book.
+  ///
+  /// Each item gets its own section, with its `##` documentation, its declaration
+  /// and links to the sections of the other items that it references.
+  pub fn document(&self, title: &str, format: DocFormat) -> String {
+    let adts = self.adts.iter().filter(|(_, adt)| !adt.builtin);
+    let adts = adts.map(|(nam, adt)| DocItem {
+      name: nam,
+      doc: adt.doc.as_deref(),
+      decl: adt.format(nam, FormatOpts::default()),
+      refs: BTreeSet::new(),
+    });
+    let adts = adts.collect::<Vec<_>>();
+
+    let defs = self.defs.values().filter(|def| !def.builtin);
+    let defs = defs.map(|def| DocItem {
+      name: &def.name,
+      doc: def.doc.as_deref(),
+      decl: def_decl(def),
+      refs: self.documented_refs(def),
+    });
+    let defs = defs.collect::<Vec<_>>();
+
+    let sections = [("Datatypes", adts), ("Definitions", defs)];
+    match format {
+      DocFormat::Markdown => markdown(title, &sections),
+      DocFormat::Html => html(title, &sections),
+    }
+  }
+
+  /// Returns the documented items that the definition mentions in its signature and rules.
+  /// Constructors are replaced by the datatype that declares them.
+  fn documented_refs(&self, def: &Definition) -> BTreeSet<Name> {
+    let mut refs = BTreeSet::new();
+    if let Some(typ) = &def.typ {
+      type_refs(typ, &mut refs);
+    }
+    for rule in &def.rules {
+      let mut rule_refs = BTreeSet::new();
+      for pat in &rule.pats {
+        pattern_refs(pat, &mut rule_refs);
+      }
+      for term in rule.guard.iter().chain([&rule.body]) {
+        term_refs(term, &mut rule_refs);
+      }
+      for bind in rule.pats.iter().flat_map(|pat| pat.binds().flatten()) {
+        rule_refs.remove(bind);
+      }
+      refs.extend(rule_refs);
+    }
+
+    let refs = refs.into_iter().map(|nam| self.ctrs.get(&nam).cloned().unwrap_or(nam));
+    let refs = refs.filter(|nam| {
+      self.defs.get(nam).is_some_and(|def| !def.builtin) || self.adts.get(nam).is_some_and(|adt| !adt.builtin)
+    });
+    refs.filter(|nam| nam != &def.name).collect()
+  }
+}
+
+/// The signature of the definition if it has one, otherwise the left-hand side of its rules.
+fn def_decl(def: &Definition) -> String {
+  if let Some(typ) = &def.typ {
+    return format!("{} : {typ}", def.name);
+  }
+  let rules = def.rules.iter().map(|rule| {
+    let pats = rule.pats.iter().map(|pat| format!(" {}", pattern(pat)));
+    format!("{}{}", def.name, pats.collect::<String>())
+  });
+  rules.collect::<Vec<_>>().join("\n")
+}
+
+fn type_refs(typ: &Type, refs: &mut BTreeSet<Name>) {
+  maybe_grow(|| match typ {
+    Type::Var(nam) => {
+      refs.insert(nam.clone());
+    }
+    Type::Ctr(nam, args) => {
+      refs.insert(nam.clone());
+      args.iter().for_each(|arg| type_refs(arg, refs));
+    }
+    Type::Arr(arg, ret) => {
+      type_refs(arg, refs);
+      type_refs(ret, refs);
+    }
+    Type::Tup(els) => els.iter().for_each(|el| type_refs(el, refs)),
+    Type::U24 | Type::I24 | Type::F24 => {}
+  })
+}
+
+fn pattern_refs(pat: &Pattern, refs: &mut BTreeSet<Name>) {
+  for pat in pat.iter() {
+    if let Pattern::Ctr(nam, _) = pat {
+      refs.insert(nam.clone());
+    }
+  }
+}
+
+/// Collects the names mentioned by a term: variables, references, matched constructors and opened types.
+///
+/// Doesn't keep track of which variables are bound, since local variables
+/// rarely have the same name as a top-level item.
+fn term_refs(term: &Term, refs: &mut BTreeSet<Name>) {
+  maybe_grow(|| {
+    match term {
+      Term::Var { nam } | Term::Ref { nam } | Term::Open { typ: nam, .. } => {
+        refs.insert(nam.clone());
+      }
+      Term::Mat { arms, .. } | Term::Fold { arms, .. } => {
+        refs.extend(arms.iter().filter_map(|(ctr, ..)| ctr.clone()));
+      }
+      Term::PatMat { arms, .. } => arms.iter().for_each(|(pat, ..)| pattern_refs(pat, refs)),
+      Term::Lam { pat, .. } | Term::Let { pat, .. } | Term::Ask { pat, .. } => pattern_refs(pat, refs),
+      _ => {}
+    }
+    for child in term.children() {
+      term_refs(child, refs);
+    }
+  })
+}
+
+fn markdown(title: &str, sections: &[(&str, Vec<DocItem>)]) -> String {
+  let mut out = format!("# {title}\n");
+  for (section, items) in sections.iter().filter(|(_, items)| !items.is_empty()) {
+    out.push_str(&format!("\n## {section}\n"));
+    for item in items {
+      out.push_str(&format!("\n<a id=\"{}\"></a>\n\n### `{}`\n\n", item.name, item.name));
+      if let Some(doc) = item.doc {
+        out.push_str(&format!("{doc}\n\n"));
+      }
+      out.push_str(&format!("```\n{}\n```\n", item.decl));
+      if !item.refs.is_empty() {
+        let links = item.refs.iter().map(|nam| format!("[`{nam}`](#{nam})"));
+        out.push_str(&format!("\nReferences: {}\n", links.collect::<Vec<_>>().join(", ")));
+      }
+    }
+  }
+  out
+}
+
+fn html(title: &str, sections: &[(&str, Vec<DocItem>)]) -> String {
+  let title = escape_html(title);
+  let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+  out.push_str(&format!("<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n"));
+  for (section, items) in sections.iter().filter(|(_, items)| !items.is_empty()) {
+    out.push_str(&format!("<h2>{section}</h2>\n"));
+    for item in items {
+      let name = escape_html(item.name);
+      out.push_str(&format!("<section id=\"{name}\">\n<h3><code>{name}</code></h3>\n"));
+      // Doc comments are split into paragraphs at their blank lines.
+      let paragraphs = item.doc.iter().flat_map(|doc| doc.split("\n\n"));
+      for paragraph in paragraphs.filter(|p| !p.trim().is_empty()) {
+        out.push_str(&format!("<p>{}</p>\n", escape_html(paragraph.trim())));
+      }
+      out.push_str(&format!("<pre><code>{}</code></pre>\n", escape_html(&item.decl)));
+      if !item.refs.is_empty() {
+        let links = item.refs.iter().map(|nam| {
+          let nam = escape_html(nam);
+          format!("<a href=\"#{nam}\"><code>{nam}</code></a>")
+        });
+        out.push_str(&format!("<p>References: {}</p>\n", links.collect::<Vec<_>>().join(", ")));
+      }
+      out.push_str("</section>\n");
+    }
+  }
+  out.push_str("</body>\n</html>\n");
+  out
+}
+
+fn escape_html(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  for chr in text.chars() {
+    match chr {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      _ => out.push(chr),
+    }
+  }
+  out
+}
@@ -0,0 +1,853 @@
+use super::{
+  builtins::{NUM_SHOW, SCONS},
+  parser::{is_name_char, ItemKind, TermParser},
+  Adt, Book, FanKind, Name, Num, Pattern, Rule, Tag, Term,
+};
+use crate::maybe_grow;
+use std::{collections::HashMap, ops::Range, path::Path};
+
+/// Options for the canonical source printer used by `bend fmt`.
+#[derive(Clone, Copy, Debug)]
+pub struct FormatOpts {
+  /// How many spaces each nesting level is indented by.
+  pub indent: usize,
+  /// The line width after which terms are broken into multiple lines.
+  pub max_width: usize,
+}
+
+impl Default for FormatOpts {
+  fn default() -> Self {
+    Self { indent: 2, max_width: 100 }
+  }
+}
+
+/// Formats the source code of a file in the canonical style.
+///
+/// The datatypes and the definitions with the functional syntax are printed again from what was parsed,
+/// while the comments, the imports, the operator declarations, the items with the imperative syntax
+/// and the items with comments inside are kept as they were written.
+/// The char literals and symbols, which the parser turns into numbers, are written back as they were.
+/// Consecutive blank lines are joined, and the definitions are separated by a blank line.
+pub fn format_source(code: &str, path: &Path, opts: FormatOpts) -> Result<String, String> {
+  let (book, items) = TermParser::new(code)
+    .parse_book_items(Book::builtins(), false)
+    .map_err(|e| format!("In {} :\n{}", path.display(), e))?;
+  let mut fmt = Formatter { opts, out: String::new() };
+  // How many rules of each definition were already written.
+  let mut rules = HashMap::<&Name, usize>::new();
+  // Where the code of the previous item ends, and the definition it belongs to.
+  let mut prev: Option<(usize, Option<&Name>)> = None;
+  for item in &items {
+    let src = &code[item.ini_idx .. item.end_idx];
+    let (len, has_comments) = code_len(src);
+    let def = match &item.kind {
+      ItemKind::Signature(nam) | ItemKind::Rule(nam, _) => Some(nam),
+      _ => None,
+    };
+    let (gap_ini, separate) = match prev {
+      Some((end, prev_def)) => (end, def.is_none() || def != prev_def),
+      None => (0, true),
+    };
+    fmt.gap(&code[gap_ini .. item.ini_idx], separate, true);
+
+    let start = fmt.out.len();
+    let idx = def.and_then(|nam| rules.get(nam)).copied().unwrap_or(0);
+    match &item.kind {
+      ItemKind::Data(nam) if !has_comments => fmt.adt(nam, &book.adts[nam]),
+      ItemKind::Signature(nam) if !has_comments => {
+        let typ = book.defs[nam].typ.as_ref().unwrap();
+        fmt.out.push_str(&format!("{nam} : {typ}"));
+      }
+      // Or-patterns are expanded into a rule for each alternative, so those rules are kept as written.
+      ItemKind::Rule(nam, 1) if !has_comments => fmt.rule(nam, &book.defs[nam].rules[idx], 0),
+      ItemKind::Const(nam) if !has_comments => {
+        fmt.out.push_str("const ");
+        fmt.rule(nam, &book.defs[nam].rules[0], 0);
+      }
+      _ => fmt.out.push_str(&src[.. len]),
+    }
+    let formatted = restore_literals(&src[.. len], &fmt.out[start ..]);
+    fmt.out.truncate(start);
+    fmt.out.push_str(&formatted);
+    if let ItemKind::Rule(nam, count) = &item.kind {
+      *rules.entry(nam).or_default() += count;
+    }
+    prev = Some((item.ini_idx + len, def));
+  }
+  fmt.gap(&code[prev.map_or(0, |(end, _)| end) ..], true, false);
+  fmt.out.push('\n');
+  Ok(fmt.out)
+}
+
+impl Adt {
+  /// Formats the declaration of the datatype in the canonical style, without its documentation.
+  pub fn format(&self, nam: &Name, opts: FormatOpts) -> String {
+    let mut fmt = Formatter { opts, out: String::new() };
+    fmt.adt(nam, self);
+    fmt.out
+  }
+}
+
+impl Term {
+  /// Formats a term in the canonical style, breaking it into multiple lines when it doesn't fit in the width.
+  pub fn format(&self, opts: FormatOpts) -> String {
+    let mut fmt = Formatter { opts, out: String::new() };
+    fmt.term(self, 0);
+    fmt.out
+  }
+}
+
+struct Formatter {
+  opts: FormatOpts,
+  out: String,
+}
+
+impl Formatter {
+  fn adt(&mut self, nam: &Name, adt: &Adt) {
+    // Records are declared with the imperative syntax, since the functional
+    // syntax always prefixes the constructor names with the type name.
+    if let Some((ctr, fields)) = adt.ctrs.first()
+      && adt.ctrs.len() == 1
+      && ctr == nam
+    {
+      let fields = fields.iter().map(|f| format!("{}{}", if f.rec { "~" } else { "" }, f.nam));
+      let fields = fields.collect::<Vec<_>>();
+      if fields.is_empty() {
+        self.out.push_str(&format!("object {nam}"));
+      } else {
+        self.out.push_str(&format!("object {nam} {{ {} }}", fields.join(", ")));
+      }
+      return;
+    }
+
+    let ctrs = adt.ctrs.iter().map(|(ctr, fields)| {
+      let ctr = ctr.strip_prefix(&format!("{nam}/")).unwrap_or(ctr);
+      if fields.is_empty() {
+        ctr.to_string()
+      } else {
+        let fields = fields.iter().map(|f| format!(" {}{}", if f.rec { "~" } else { "" }, f.nam));
+        format!("({ctr}{})", fields.collect::<String>())
+      }
+    });
+    let ctrs = ctrs.collect::<Vec<_>>();
+
+    let flat = format!("data {nam} = {}", ctrs.join(" | "));
+    if flat.chars().count() <= self.opts.max_width {
+      self.out.push_str(&flat);
+    } else {
+      self.out.push_str(&format!("data {nam}"));
+      for (i, ctr) in ctrs.iter().enumerate() {
+        self.newline(self.opts.indent);
+        self.out.push_str(if i == 0 { "= " } else { "| " });
+        self.out.push_str(ctr);
+      }
+    }
+  }
+
+  /// Writes the comments between two items, or before the first or after the last one,
+  /// and starts the line of the next item if there's one.
+  ///
+  /// The parts of the same definition aren't separated, and the items of different definitions are
+  /// separated by the blank lines between them, at most one in a row, or by one right after the previous item.
+  fn gap(&mut self, gap: &str, separate: bool, next: bool) {
+    let mut lines = gap.split('\n').collect::<Vec<_>>();
+    if !self.out.is_empty() {
+      // The rest of the line of the previous item, which can only have a comment.
+      let rest = lines.remove(0).trim();
+      if !rest.is_empty() {
+        self.out.push(' ');
+        self.out.push_str(rest);
+      }
+    }
+    if next {
+      // The indentation before the next item.
+      lines.pop();
+    }
+    let mut blank = separate && next && lines.iter().all(|line| !line.trim().is_empty());
+    for line in lines {
+      if line.trim().is_empty() {
+        blank |= separate;
+      } else {
+        self.line(line.trim_end(), blank);
+        blank = false;
+      }
+    }
+    if next {
+      self.line("", blank);
+    }
+  }
+
+  /// Writes a new line, after a blank one if `blank`, unless it's the first line of the output.
+  fn line(&mut self, text: &str, blank: bool) {
+    if !self.out.is_empty() {
+      self.out.push_str(if blank { "\n\n" } else { "\n" });
+    }
+    self.out.push_str(text);
+  }
+
+  fn rule(&mut self, nam: &Name, rule: &Rule, indent: usize) {
+    self.out.push_str(nam);
+    for pat in &rule.pats {
+      self.out.push(' ');
+      self.out.push_str(&pattern(pat));
+    }
+    self.out.push_str(" = ");
+    self.body(&rule.body, indent);
+  }
+
+  /// Writes a term that follows a `=`, `:` or lambda binder on a line indented by `indent`.
+  ///
+  /// Terms that fit are kept on the same line, sequences of bindings start a new
+  /// indented line, and everything else is broken starting from the current column.
+  fn body(&mut self, term: &Term, indent: usize) {
+    if let Some(flat) = self.fitting(term) {
+      self.out.push_str(&flat);
+    } else if term.is_binding_sequence() {
+      self.newline(indent + self.opts.indent);
+      self.term(term, indent + self.opts.indent);
+    } else {
+      self.term(term, indent);
+    }
+  }
+
+  /// Writes a term starting at the current column on a line indented by `indent`.
+  fn term(&mut self, term: &Term, indent: usize) {
+    maybe_grow(|| {
+      if let Some(flat) = self.fitting(term) {
+        self.out.push_str(&flat);
+        return;
+      }
+      // Strings are never broken into multiple lines.
+      if let Some(str) = interpolated_str(term) {
+        self.out.push_str(&str);
+        return;
+      }
+      let inner = indent + self.opts.indent;
+      match term {
+        Term::Lam { tag, pat, bod } => {
+          self.out.push_str(&format!("{}λ{} ", tag.display_padded(), pattern(pat)));
+          self.body(bod, indent);
+        }
+        Term::Let { pat, val, nxt } => {
+          self.out.push_str(&format!("let {} = ", pattern(pat)));
+          self.body(val, indent);
+          self.out.push(';');
+          self.newline(indent);
+          self.term(nxt, indent);
+        }
+        Term::Ask { pat, val, nxt } => {
+          self.out.push_str(&format!("ask {} = ", pattern(pat)));
+          self.body(val, indent);
+          self.out.push(';');
+          self.newline(indent);
+          self.term(nxt, indent);
+        }
+        Term::Use { nam, val, nxt } => {
+          self.out.push_str(&format!("use {} = ", var_or_era(nam)));
+          self.body(val, indent);
+          self.out.push(';');
+          self.newline(indent);
+          self.term(nxt, indent);
+        }
+        Term::Def { nam, rules, nxt } => {
+          // The rules after the first are aligned with it.
+          for (i, rule) in rules.iter().enumerate() {
+            if i != 0 {
+              self.newline(indent);
+            }
+            self.out.push_str(if i == 0 { "def " } else { "    " });
+            self.rule(nam, rule, indent);
+          }
+          self.out.push(';');
+          self.newline(indent);
+          self.term(nxt, indent);
+        }
+        Term::Open { typ, var, bod } => {
+          self.out.push_str(&format!("open {typ} {var};"));
+          self.newline(indent);
+          self.term(bod, indent);
+        }
+        Term::Do { typ, bod } => {
+          self.out.push_str(&format!("do {typ} {{"));
+          self.newline(inner);
+          self.term(bod, inner);
+          self.newline(indent);
+          self.out.push('}');
+        }
+        Term::App { tag, .. } => {
+          let (fun, args) = term.app_spine(tag);
+          self.out.push_str(&format!("{}(", tag.display_padded()));
+          self.term(fun, inner);
+          for arg in args {
+            self.newline(inner);
+            self.term(arg, inner);
+          }
+          self.out.push(')');
+        }
+        Term::Oper { opr, fst, snd } => {
+          self.out.push_str(&format!("({opr} "));
+          self.term(fst, inner);
+          self.newline(inner);
+          self.term(snd, inner);
+          self.out.push(')');
+        }
+        Term::Fan { fan, tag, els } => {
+          let (open, sep, close) = match fan {
+            FanKind::Tup => ("(", ",", ")"),
+            FanKind::Dup => ("{", "", "}"),
+          };
+          self.out.push_str(&format!("{tag}{open}"));
+          self.elements(els, sep, inner);
+          self.newline(indent);
+          self.out.push_str(close);
+        }
+        Term::List { els } => {
+          self.out.push('[');
+          self.elements(els, ",", inner);
+          self.newline(indent);
+          self.out.push(']');
+        }
+        Term::Get { rec, fld } => {
+          self.record(rec, indent);
+          self.out.push_str(&format!(".{fld}"));
+        }
+        Term::Upd { rec, flds } => {
+          self.out.push('{');
+          self.term(rec, inner);
+          self.out.push_str(" |");
+          for (i, (fld, val)) in flds.iter().enumerate() {
+            self.newline(inner);
+            self.out.push_str(&format!("{fld} = "));
+            self.body(val, inner);
+            if i != flds.len() - 1 {
+              self.out.push(',');
+            }
+          }
+          self.newline(indent);
+          self.out.push('}');
+        }
+        Term::Mat { arg, bnd, with, arms } | Term::Fold { arg, bnd, with, arms } => {
+          let kw = if matches!(term, Term::Mat { .. }) { "match" } else { "fold" };
+          self.out.push_str(&format!("{kw} "));
+          self.header(bnd, arg, with, indent);
+          self.out.push('{');
+          for (ctr, fields, bod) in arms {
+            self.newline(inner);
+            self.out.push_str(var_or_era(ctr));
+            for field in fields {
+              self.out.push_str(&format!(" {}", var_or_era(field)));
+            }
+            self.out.push_str(": ");
+            self.body(bod, inner);
+          }
+          self.newline(indent);
+          self.out.push('}');
+        }
+        Term::PatMat { arg, arms } => {
+          self.out.push_str("match ");
+          self.header(&None, arg, &[], indent);
+          self.out.push('{');
+          for (pat, guard, bod) in arms {
+            self.newline(inner);
+            self.out.push_str(&pattern(pat));
+            if let Some(guard) = guard {
+              self.out.push_str(" | ");
+              self.term(guard, inner);
+            }
+            self.out.push_str(": ");
+            self.body(bod, inner);
+          }
+          self.newline(indent);
+          self.out.push('}');
+        }
+        Term::Swt { arg, bnd, with, pred: _, arms }
+          if bnd.as_ref().is_some_and(|b| b == "%cond" || b == "%pred") =>
+        {
+          // `if` expressions are parsed directly into a switch on the condition.
+          let [els, thn] = arms.as_slice() else { unreachable!() };
+          self.out.push_str("if ");
+          self.header(&None, arg, with, indent);
+          self.out.push('{');
+          self.newline(inner);
+          self.term(thn, inner);
+          self.newline(indent);
+          // Chained conditions are written as `else if`.
+          if matches!(els, Term::Swt { bnd: Some(b), .. } if b == "%cond") {
+            self.out.push_str("} else ");
+            self.term(els, indent);
+            return;
+          }
+          self.out.push_str("} else {");
+          self.newline(inner);
+          self.term(els, inner);
+          self.newline(indent);
+          self.out.push('}');
+        }
+        Term::Swt { arg, bnd, with, pred: _, arms } => {
+          self.out.push_str("switch ");
+          self.header(bnd, arg, with, indent);
+          self.out.push('{');
+          for (i, arm) in arms.iter().enumerate() {
+            self.newline(inner);
+            if i == arms.len() - 1 {
+              self.out.push_str("_: ");
+            } else {
+              self.out.push_str(&format!("{i}: "));
+            }
+            self.body(arm, inner);
+          }
+          self.newline(indent);
+          self.out.push('}');
+        }
+        Term::Bend { bind, init, cond, step, base } => {
+          self.out.push_str("bend ");
+          for (i, (bind, init)) in bind.iter().zip(init).enumerate() {
+            if i != 0 {
+              self.out.push_str(", ");
+            }
+            match (bind, init) {
+              (Some(bind), Term::Var { nam }) if bind == nam => self.out.push_str(bind),
+              (bind, init) => {
+                self.out.push_str(&format!("{} = ", var_or_era(bind)));
+                self.term(init, indent);
+              }
+            }
+          }
+          self.out.push_str(" {");
+          self.newline(inner);
+          self.out.push_str("when ");
+          self.term(cond, inner);
+          self.out.push_str(": ");
+          self.body(step, inner);
+          self.newline(inner);
+          self.out.push_str("else: ");
+          self.body(base, inner);
+          self.newline(indent);
+          self.out.push('}');
+        }
+        Term::Var { .. }
+        | Term::Link { .. }
+        | Term::Ref { .. }
+        | Term::Num { .. }
+        | Term::Nat { .. }
+        | Term::Str { .. }
+        | Term::Era
+        | Term::Err => self.out.push_str(&flat(term).unwrap()),
+      }
+    })
+  }
+
+  fn header(&mut self, bnd: &Option<Name>, arg: &Term, with: &[Name], indent: usize) {
+    match (bnd, arg) {
+      (Some(bnd), Term::Var { nam }) if bnd == nam => (),
+      (Some(bnd), _) if !bnd.starts_with('%') => self.out.push_str(&format!("{bnd} = ")),
+      _ => (),
+    }
+    self.term(arg, indent);
+    self.out.push(' ');
+    if !with.is_empty() {
+      let with = with.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+      self.out.push_str(&format!("with {} ", with.join(", ")));
+    }
+  }
+
+  /// Writes the record of a field access, which must be between parentheses.
+  fn record(&mut self, rec: &Term, indent: usize) {
+    if matches!(rec, Term::App { .. }) {
+      self.term(rec, indent);
+    } else {
+      self.out.push('(');
+      self.term(rec, indent + self.opts.indent);
+      self.out.push(')');
+    }
+  }
+
+  fn elements(&mut self, els: &[Term], sep: &str, indent: usize) {
+    for (i, el) in els.iter().enumerate() {
+      self.newline(indent);
+      self.term(el, indent);
+      if i != els.len() - 1 {
+        self.out.push_str(sep);
+      }
+    }
+  }
+
+  /// Returns the single-line rendering of the term if it fits in the current line.
+  fn fitting(&self, term: &Term) -> Option<String> {
+    let flat = flat(term)?;
+    let col = self.out.rsplit('\n').next().unwrap_or_default().chars().count();
+    (col + flat.chars().count() <= self.opts.max_width).then_some(flat)
+  }
+
+  fn newline(&mut self, indent: usize) {
+    let len = self.out.trim_end_matches(' ').len();
+    self.out.truncate(len);
+    self.out.push('\n');
+    self.out.push_str(&" ".repeat(indent));
+  }
+}
+
+/// Renders a term in a single line.
+///
+/// Returns `None` for terms that always span multiple lines,
+/// like binding sequences and pattern matching.
+fn flat(term: &Term) -> Option<String> {
+  maybe_grow(|| {
+    if let Some(str) = interpolated_str(term) {
+      return Some(str);
+    }
+    if let Some(section) = operator_section(term) {
+      return Some(section);
+    }
+    let res = match term {
+      Term::Lam { tag, pat, bod } => format!("{}λ{} {}", tag.display_padded(), pattern(pat), flat(bod)?),
+      Term::Var { nam } => var(nam),
+      Term::Link { nam } => format!("${nam}"),
+      Term::Ref { nam } => nam.to_string(),
+      Term::App { tag, .. } => {
+        let (fun, args) = term.app_spine(tag);
+        let mut res = format!("{}({}", tag.display_padded(), flat(fun)?);
+        for arg in args {
+          res.push(' ');
+          res.push_str(&flat(arg)?);
+        }
+        res.push(')');
+        res
+      }
+      Term::Oper { opr, fst, snd } => format!("({opr} {} {})", flat(fst)?, flat(snd)?),
+      Term::Fan { fan: FanKind::Tup, tag, els } => format!("{tag}({})", flat_join(els, ", ")?),
+      Term::Fan { fan: FanKind::Dup, tag, els } => format!("{tag}{{{}}}", flat_join(els, " ")?),
+      Term::List { els } => format!("[{}]", flat_join(els, ", ")?),
+      Term::Get { rec, fld } if matches!(rec.as_ref(), Term::App { .. }) => format!("{}.{fld}", flat(rec)?),
+      Term::Get { rec, fld } => format!("({}).{fld}", flat(rec)?),
+      Term::Upd { rec, flds } => {
+        let flds = flds.iter().map(|(fld, val)| Some(format!("{fld} = {}", flat(val)?)));
+        format!("{{{} | {}}}", flat(rec)?, flds.collect::<Option<Vec<_>>>()?.join(", "))
+      }
+      Term::Num { val: Num::U24(val) } => format!("{val}"),
+      Term::Num { val: Num::I24(val) } => {
+        format!("{}{}", if *val < 0 { "-" } else { "+" }, val.unsigned_abs())
+      }
+      Term::Num { val: Num::F24(val) } if val.fract() == 0.0 => format!("{val:.1}"),
+      Term::Num { val: Num::F24(val) } => format!("{val}"),
+      Term::Nat { val } => format!("#{val}"),
+      Term::Str { val } => format!("\"{}\"", str_text(val)),
+      Term::Era => "*".to_string(),
+      Term::Err => "<Invalid>".to_string(),
+      Term::Let { .. }
+      | Term::Ask { .. }
+      | Term::Use { .. }
+      | Term::Def { .. }
+      | Term::Open { .. }
+      | Term::Do { .. }
+      | Term::Mat { .. }
+      | Term::PatMat { .. }
+      | Term::Swt { .. }
+      | Term::Fold { .. }
+      | Term::Bend { .. } => return None,
+    };
+    Some(res)
+  })
+}
+
+fn flat_join(els: &[Term], sep: &str) -> Option<String> {
+  Some(els.iter().map(flat).collect::<Option<Vec<_>>>()?.join(sep))
+}
+
+/// Renders a string that had expressions interpolated, which the parser turns
+/// into calls to `String/Cons` and `Num/show` ending in a string literal.
+fn interpolated_str(term: &Term) -> Option<String> {
+  let mut res = String::new();
+  let mut term = term;
+  let mut interpolated = false;
+  loop {
+    match term {
+      Term::Str { val } => {
+        res.push_str(&str_text(val));
+        break;
+      }
+      Term::App { tag: Tag::Static, .. } => {
+        let (fun, args) = term.app_spine(&Tag::Static);
+        let (Term::Ref { nam } | Term::Var { nam }) = fun else { return None };
+        match (nam.as_ref(), args.as_slice()) {
+          (SCONS, [Term::Num { val: Num::U24(chr) }, rest]) => {
+            res.push_str(&str_text(&char::from_u32(*chr)?.to_string()));
+            term = rest;
+          }
+          (NUM_SHOW, [val, rest]) => {
+            res.push_str(&format!("{{{}}}", flat(val)?));
+            interpolated = true;
+            term = rest;
+          }
+          _ => return None,
+        }
+      }
+      _ => return None,
+    }
+  }
+  interpolated.then(|| format!("\"{res}\""))
+}
+
+/// Renders the lambdas that the parser creates for operator sections, like `(+ 1)`.
+fn operator_section(term: &Term) -> Option<String> {
+  let binds = |pat: &Pattern, nam: &str| matches!(pat, Pattern::Var(Some(bind)) if bind == nam);
+  let is_var = |term: &Term, nam: &str| matches!(term, Term::Var { nam: var } if var == nam);
+  let Term::Lam { tag: Tag::Static, pat, bod } = term else { return None };
+  match bod.as_ref() {
+    Term::Lam { tag: Tag::Static, pat: pat2, bod } if binds(pat, "%lhs") && binds(pat2, "%rhs") => {
+      match bod.as_ref() {
+        Term::Oper { opr, fst, snd } if is_var(fst, "%lhs") && is_var(snd, "%rhs") => {
+          Some(format!("({opr})"))
+        }
+        _ => None,
+      }
+    }
+    Term::Oper { opr, fst, snd } if binds(pat, "%lhs") && is_var(fst, "%lhs") => {
+      Some(format!("({opr} {})", flat(snd)?))
+    }
+    Term::Oper { opr, fst, snd } if binds(pat, "%rhs") && is_var(snd, "%rhs") => {
+      Some(format!("({} {opr})", flat(fst)?))
+    }
+    _ => None,
+  }
+}
+
+/// Escapes the text of a string literal, including the braces used for interpolation.
+fn str_text(str: &str) -> String {
+  let escaped = format!("{str:?}");
+  let escaped = &escaped[1 .. escaped.len() - 1];
+  let mut res = String::new();
+  let mut chars = escaped.chars();
+  while let Some(chr) = chars.next() {
+    match chr {
+      // Keep the braces of unicode escapes, like `\u{1F600}`.
+      '\\' => {
+        res.push(chr);
+        if let Some(nxt) = chars.next() {
+          res.push(nxt);
+          if nxt == 'u' {
+            for chr in chars.by_ref() {
+              res.push(chr);
+              if chr == '}' {
+                break;
+              }
+            }
+          }
+        }
+      }
+      '{' | '}' => {
+        res.push(chr);
+        res.push(chr);
+      }
+      _ => res.push(chr),
+    }
+  }
+  res
+}
+
+pub(super) fn pattern(pat: &Pattern) -> String {
+  maybe_grow(|| match pat {
+    Pattern::Var(nam) => nam.as_ref().map_or("*".to_string(), var),
+    Pattern::Chn(nam) => format!("${nam}"),
+    Pattern::Ctr(nam, pats) if pats.is_empty() => format!("({nam})"),
+    Pattern::Ctr(nam, pats) => {
+      format!("({nam} {})", pats.iter().map(pattern).collect::<Vec<_>>().join(" "))
+    }
+    Pattern::Num(num) => num.to_string(),
+    Pattern::Fan(FanKind::Tup, tag, pats) => {
+      format!("{tag}({})", pats.iter().map(pattern).collect::<Vec<_>>().join(", "))
+    }
+    Pattern::Fan(FanKind::Dup, tag, pats) => {
+      format!("{tag}{{{}}}", pats.iter().map(pattern).collect::<Vec<_>>().join(" "))
+    }
+    Pattern::Lst(pats) => format!("[{}]", pats.iter().map(pattern).collect::<Vec<_>>().join(", ")),
+    Pattern::Str(str) => format!("\"{}\"", str_text(str)),
+  })
+}
+
+/// Variables generated while parsing the imperative syntax contain a `%`,
+/// which is not a valid name character, so they're renamed to a parseable form.
+fn var(nam: &Name) -> String {
+  nam.replace('%', ".")
+}
+
+fn var_or_era(nam: &Option<Name>) -> &str {
+  nam.as_ref().map_or("*", |nam| nam.as_ref())
+}
+
+/// A piece of source code, as far as the formatter needs to tell them apart.
+enum Token {
+  Comment,
+  Str,
+  /// A char literal or a symbol between backticks, with the number that the parser turns it into.
+  Quoted(Option<u32>),
+  /// A name or a number.
+  Word,
+  Punct,
+}
+
+/// Splits source code into its tokens, skipping the whitespace.
+fn tokens(src: &str) -> Vec<(Range<usize>, Token)> {
+  let mut tokens = vec![];
+  let mut chars = src.char_indices().peekable();
+  while let Some((ini, chr)) = chars.next() {
+    let token = match chr {
+      _ if chr.is_whitespace() => continue,
+      '#' if chars.next_if(|(_, c)| *c == '{').is_some() => {
+        let mut prev = '{';
+        for (_, c) in chars.by_ref() {
+          if prev == '}' && c == '#' {
+            break;
+          }
+          prev = c;
+        }
+        Token::Comment
+      }
+      '#' => {
+        while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+        Token::Comment
+      }
+      '"' | '\'' | '`' => {
+        while let Some((_, c)) = chars.next() {
+          match c {
+            '\\' => _ = chars.next(),
+            _ if c == chr => break,
+            _ => (),
+          }
+        }
+        let end = chars.peek().map_or(src.len(), |(idx, _)| *idx);
+        match chr {
+          '\'' => Token::Quoted(char_value(&src[ini .. end])),
+          '`' => Token::Quoted(symbol_value(&src[ini .. end])),
+          _ => Token::Str,
+        }
+      }
+      _ if is_name_char(chr) => {
+        while chars.next_if(|(_, c)| is_name_char(*c)).is_some() {}
+        Token::Word
+      }
+      _ => Token::Punct,
+    };
+    let end = chars.peek().map_or(src.len(), |(idx, _)| *idx);
+    tokens.push((ini .. end, token));
+  }
+  tokens
+}
+
+/// Returns the length of the code of an item, without the comments and whitespace after it,
+/// and whether there are comments inside of it.
+fn code_len(src: &str) -> (usize, bool) {
+  let tokens = tokens(src);
+  let len =
+    tokens.iter().rfind(|(_, token)| !matches!(token, Token::Comment)).map_or(0, |(range, _)| range.end);
+  let has_comments = tokens.iter().any(|(range, token)| matches!(token, Token::Comment) && range.start < len);
+  (len, has_comments)
+}
+
+/// Writes the char literals and the symbols of the source of an item back into its formatted code,
+/// where the parser turned them into numbers. The numbers of both are matched in order,
+/// and the formatted code is returned as it is if they don't match.
+fn restore_literals(src: &str, formatted: &str) -> String {
+  let numbers = |code: &str| -> Vec<(Range<usize>, Option<u32>, bool)> {
+    let tokens = tokens(code).into_iter().filter_map(|(range, token)| match token {
+      Token::Quoted(val) => Some((range, val, true)),
+      Token::Word => u24_value(&code[range.clone()]).map(|val| (range, Some(val), false)),
+      _ => None,
+    });
+    tokens.collect()
+  };
+  let (src_nums, fmt_nums) = (numbers(src), numbers(formatted));
+  let same = src_nums.len() == fmt_nums.len() && src_nums.iter().zip(&fmt_nums).all(|(a, b)| a.1 == b.1);
+  if !same {
+    return formatted.to_string();
+  }
+  let mut res = String::new();
+  let mut last = 0;
+  for ((src_range, _, quoted), (fmt_range, ..)) in src_nums.into_iter().zip(fmt_nums) {
+    if quoted {
+      res.push_str(&formatted[last .. fmt_range.start]);
+      res.push_str(&src[src_range]);
+      last = fmt_range.end;
+    }
+  }
+  res.push_str(&formatted[last ..]);
+  res
+}
+
+/// The number of a char literal, like `'a'` or `'\n'`.
+fn char_value(lit: &str) -> Option<u32> {
+  let text = lit.strip_prefix('\'')?.strip_suffix('\'')?;
+  let mut chars = text.chars();
+  let chr = match chars.next()? {
+    '\\' => match chars.next()? {
+      'n' => '\n',
+      'r' => '\r',
+      't' => '\t',
+      '0' => '\0',
+      'u' => {
+        let hex = chars.as_str().strip_prefix('{')?.strip_suffix('}')?;
+        return u32::from_str_radix(hex, 16).ok();
+      }
+      chr => chr,
+    },
+    chr => chr,
+  };
+  chars.next().is_none().then_some(chr as u32)
+}
+
+/// The number of a symbol, like `` `foo` ``, whose characters are base64 digits.
+fn symbol_value(lit: &str) -> Option<u32> {
+  let text = lit.strip_prefix('`')?.strip_suffix('`')?;
+  if text.len() > 4 {
+    return None;
+  }
+  text.bytes().try_fold(0, |val, chr| {
+    let digit = match chr {
+      b'A' ..= b'Z' => chr - b'A',
+      b'a' ..= b'z' => chr - b'a' + 26,
+      b'0' ..= b'9' => chr - b'0' + 52,
+      b'+' => 62,
+      b'/' => 63,
+      _ => return None,
+    };
+    Some((val << 6) | digit as u32)
+  })
+}
+
+/// The value of an unsigned integer literal, like `42` or `0xFF`.
+fn u24_value(word: &str) -> Option<u32> {
+  if !word.starts_with(|c: char| c.is_ascii_digit()) {
+    return None;
+  }
+  let word = word.replace('_', "");
+  let (digits, radix) = match word.get(.. 2) {
+    Some("0x") => (&word[2 ..], 16),
+    Some("0b") => (&word[2 ..], 2),
+    Some("0o") => (&word[2 ..], 8),
+    _ => (word.as_str(), 10),
+  };
+  u32::from_str_radix(digits, radix).ok()
+}
+
+impl Term {
+  fn is_binding_sequence(&self) -> bool {
+    matches!(
+      self,
+      Term::Let { .. } | Term::Ask { .. } | Term::Use { .. } | Term::Def { .. } | Term::Open { .. }
+    )
+  }
+
+  /// Splits a chain of applications with the same tag into the applied function and its arguments.
+  fn app_spine<'a>(&'a self, tag: &Tag) -> (&'a Term, Vec<&'a Term>) {
+    let mut args = vec![];
+    let mut fun = self;
+    while let Term::App { tag: app_tag, fun: f, arg } = fun
+      && app_tag == tag
+    {
+      args.push(arg.as_ref());
+      fun = f;
+    }
+    args.reverse();
+    (fun, args)
+  }
+}
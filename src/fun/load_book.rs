@@ -1,15 +1,128 @@
-use crate::fun::{self, parser::TermParser};
-use std::path::Path;
+use crate::fun::{self, parser::TermParser, Name};
+use std::{
+  collections::HashSet,
+  path::{Path, PathBuf},
+};
 
 // TODO: Refactor so that we don't mix the two syntaxes here.
 
-/// Reads a file and parses to a definition book.
+/// Reads a file and parses to a definition book, together with the files it imports.
 pub fn load_file_to_book(path: &Path) -> Result<fun::Book, String> {
-  let builtins = fun::Book::builtins();
+  load_file_to_book_with_root(path, path.parent().unwrap_or(Path::new("")))
+}
+
+/// Reads a file and parses it to a definition book, resolving its imports relative to `root`
+/// instead of to the directory of the file, like for the files of a project checked all at once.
+pub fn load_file_to_book_with_root(path: &Path, root: &Path) -> Result<fun::Book, String> {
   let code = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
-  do_parse_book(&code, path, builtins)
+  load_code_to_book(&code, path, root)
+}
+
+/// Parses the source code of a file that may not be saved yet, like one open in an editor,
+/// to a definition book, resolving its imports relative to `root`.
+pub fn load_code_to_book(code: &str, path: &Path, root: &Path) -> Result<fun::Book, String> {
+  let builtins = fun::Book::builtins();
+  let book = do_parse_book(code, path, builtins)?;
+  let mut loader = ImportLoader { root, loaded: HashSet::new(), stack: vec![] };
+  if let Ok(canonical) = path.canonicalize() {
+    loader.stack.push((canonical, path.to_path_buf()));
+  }
+  loader.load_imports(book)
 }
 
+/// Parses source code that doesn't come from a file, like the standard input, to a definition book.
+///
+/// The name is used in place of the file path in error messages,
+/// and imports are resolved relative to the current directory.
+pub fn load_source_to_book(name: &str, code: &str) -> Result<fun::Book, String> {
+  let builtins = fun::Book::builtins();
+  let book = do_parse_book(code, Path::new(name), builtins)?;
+  let loader = ImportLoader { root: Path::new(""), loaded: HashSet::new(), stack: vec![] };
+  loader.load_imports(book)
+}
+
+/// Parses a single file to a definition book, leaving its imports unresolved.
 pub fn do_parse_book(code: &str, path: &Path, builtins: fun::Book) -> Result<fun::Book, String> {
-  TermParser::new(code).parse_book(builtins, false).map_err(|e| format!("In {} :\n{}", path.display(), e))
+  let mut book = TermParser::new(code)
+    .parse_book(builtins, false)
+    .map_err(|e| format!("In {} :\n{}", path.display(), e))?;
+  let def_spans = book.defs.values_mut().filter_map(|def| def.span.as_mut());
+  let adt_spans = book.adts.values_mut().filter_map(|adt| adt.span.as_mut());
+  for span in def_spans.chain(adt_spans) {
+    span.file = Some(path.display().to_string());
+  }
+  Ok(book)
+}
+
+/// Resolves the imports of a program.
+///
+/// Import paths are relative to the directory of the main file, so that
+/// a file gets the same qualified names no matter where it's imported from.
+struct ImportLoader<'a> {
+  root: &'a Path,
+  /// The files that were already imported, which are not loaded again.
+  loaded: HashSet<PathBuf>,
+  /// The chain of files being imported, used to detect cycles.
+  /// Stores the canonical path of each file, along with the path used to read it.
+  stack: Vec<(PathBuf, PathBuf)>,
+}
+
+impl ImportLoader<'_> {
+  fn load_imports(mut self, mut book: fun::Book) -> Result<fun::Book, String> {
+    for import in std::mem::take(&mut book.imports) {
+      self.load_import(&import, &mut book)?;
+    }
+    Ok(book)
+  }
+
+  /// Loads the file of the import, along with its own imports, and adds their
+  /// contents to the book with their names qualified by the import path.
+  fn load_import(&mut self, import: &Name, book: &mut fun::Book) -> Result<(), String> {
+    let path = self.root.join(format!("{import}.bend"));
+    let canonical = path.canonicalize().map_err(|e| format!("While importing '{import}': {e}"))?;
+
+    if let Some(pos) = self.stack.iter().position(|(p, _)| *p == canonical) {
+      let cycle = self.stack[pos ..].iter().map(|(_, p)| p).chain([&path]).map(|p| p.display().to_string());
+      return Err(format!("Import cycle detected: {}", cycle.collect::<Vec<_>>().join(" -> ")));
+    }
+    if !self.loaded.insert(canonical.clone()) {
+      return Ok(());
+    }
+
+    let code = std::fs::read_to_string(&path).map_err(|e| format!("While importing '{import}': {e}"))?;
+    let mut module = do_parse_book(&code, &path, fun::Book::default())?;
+
+    self.stack.push((canonical, path.clone()));
+    for sub_import in std::mem::take(&mut module.imports) {
+      self.load_import(&sub_import, book)?;
+    }
+    self.stack.pop();
+
+    module.qualify_names(import);
+
+    // Imported files are treated like the builtins, so that
+    // their unused definitions don't generate warnings.
+    for (nam, mut adt) in module.adts {
+      if book.adts.contains_key(&nam) {
+        return Err(format!("Repeated datatype '{nam}' when importing '{import}'."));
+      }
+      adt.builtin = true;
+      book.adts.insert(nam, adt);
+    }
+    for (ctr, adt) in module.ctrs {
+      if book.ctrs.contains_key(&ctr) {
+        return Err(format!("Repeated constructor '{ctr}' when importing '{import}'."));
+      }
+      book.ctrs.insert(ctr, adt);
+    }
+    for (nam, mut def) in module.defs {
+      if book.defs.contains_key(&nam) {
+        return Err(format!("Repeated definition '{nam}' when importing '{import}'."));
+      }
+      def.builtin = true;
+      book.defs.insert(nam, def);
+    }
+    book.consts.extend(module.consts);
+    Ok(())
+  }
 }
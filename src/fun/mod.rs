@@ -1,16 +1,24 @@
 use crate::{
-  diagnostics::{Diagnostics, DiagnosticsConfig},
+  diagnostics::{Diagnostics, DiagnosticsConfig, Severity, Span, WarningType},
   fun::builtins::*,
-  maybe_grow, ENTRY_POINT,
+  maybe_grow,
 };
 use indexmap::{IndexMap, IndexSet};
 use interner::global::{GlobalPool, GlobalString};
 use itertools::Itertools;
-use std::{borrow::Cow, collections::HashMap, hash::Hash, ops::Deref};
+use std::{
+  borrow::Cow,
+  collections::{HashMap, HashSet},
+  hash::Hash,
+  ops::Deref,
+};
 
+pub mod builder;
 pub mod builtins;
 pub mod check;
 pub mod display;
+pub mod doc;
+pub mod format;
 pub mod load_book;
 pub mod net_to_term;
 pub mod parser;
@@ -29,7 +37,31 @@ pub struct Ctx<'book> {
 
 impl Ctx<'_> {
   pub fn new(book: &mut Book, diagnostics_cfg: DiagnosticsConfig) -> Ctx {
-    Ctx { book, info: Diagnostics::new(diagnostics_cfg) }
+    let mut info = Diagnostics::new(diagnostics_cfg);
+    info.def_spans =
+      book.defs.values().filter_map(|def| Some((def.name.clone(), def.span.clone()?))).collect();
+    for &(warn, severity) in &book.lints.file {
+      info.config.set_warning_severity(warn, severity);
+    }
+    info.def_lints = book.lints.defs.clone();
+    Ctx { book, info }
+  }
+
+  /// Runs a pass that fails if it finds any errors, recovering from its failure so
+  /// that the following passes can still check the rest of the definitions.
+  ///
+  /// The definitions that have errors are replaced by an erased placeholder, since
+  /// otherwise the following passes could stumble on them and report spurious errors.
+  pub fn recover(&mut self, pass: impl FnOnce(&mut Self) -> Result<(), Diagnostics>) {
+    if let Err(info) = pass(self) {
+      self.info = info;
+      let failed = self.info.failed_defs();
+      for def in self.book.defs.values_mut() {
+        if failed.contains(&def.name.def_name_from_generated()) {
+          def.rules = vec![Rule { pats: vec![], guard: None, body: Term::Era }];
+        }
+      }
+    }
   }
 }
 
@@ -47,6 +79,103 @@ pub struct Book {
 
   /// A custom or default "main" entrypoint.
   pub entrypoint: Option<Name>,
+
+  /// The paths of the files imported by the program, relative to the main file and without extension.
+  pub imports: Vec<Name>,
+
+  /// The warning levels set by the pragmas in the source code.
+  pub lints: Lints,
+
+  /// How the combinators of each definition are floated, for the ones that set it with a pragma.
+  pub float_modes: HashMap<Name, transform::float_combinators::FloatMode>,
+
+  /// The optimizations set by the pragmas of single definitions.
+  pub def_opts: DefOpts,
+
+  /// The definitions declared with `const`, which are evaluated when compiling
+  /// and replaced by their value where they're used.
+  pub consts: HashSet<Name>,
+}
+
+/// The warning levels set with `#!allow(..)`, `#!warn(..)` and `#!deny(..)` pragmas,
+/// which take precedence over the ones of the [`DiagnosticsConfig`].
+#[derive(Debug, Clone, Default)]
+pub struct Lints {
+  /// The levels that apply to the whole program, in the order they were written.
+  pub file: Vec<(WarningType, Severity)>,
+  /// The levels that apply only to the warnings of one definition.
+  pub defs: HashMap<Name, Vec<(WarningType, Severity)>>,
+}
+
+/// The optimizations enabled or disabled with `#!opt(..)` pragmas, which take precedence
+/// over the [`CompileOpts`](crate::CompileOpts) for the definitions they're right above
+/// and for the ones generated from them.
+#[derive(Debug, Clone, Default)]
+pub struct DefOpts {
+  /// The optimizations set for each definition, in the order they were written.
+  pub defs: HashMap<Name, Vec<(DefOpt, bool)>>,
+}
+
+/// An optimization that can be set for a single definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DefOpt {
+  Eta,
+  Prune,
+  LinearizeMatches,
+  ConstFold,
+  ShareCommonTerms,
+  FloatCombinators,
+  Merge,
+  MergeDefs,
+  Inline,
+  TailCalls,
+  PreReduce,
+}
+
+impl DefOpt {
+  pub const ALL: [DefOpt; 11] = [
+    DefOpt::Eta,
+    DefOpt::Prune,
+    DefOpt::LinearizeMatches,
+    DefOpt::ConstFold,
+    DefOpt::ShareCommonTerms,
+    DefOpt::FloatCombinators,
+    DefOpt::Merge,
+    DefOpt::MergeDefs,
+    DefOpt::Inline,
+    DefOpt::TailCalls,
+    DefOpt::PreReduce,
+  ];
+
+  /// The name of the optimization, the same as the one of its `-O` option.
+  pub fn name(self) -> &'static str {
+    match self {
+      DefOpt::Eta => "eta",
+      DefOpt::Prune => "prune",
+      DefOpt::LinearizeMatches => "linearize-matches",
+      DefOpt::ConstFold => "const-fold",
+      DefOpt::ShareCommonTerms => "share-common-terms",
+      DefOpt::FloatCombinators => "float-combinators",
+      DefOpt::Merge => "merge",
+      DefOpt::MergeDefs => "merge-defs",
+      DefOpt::Inline => "inline",
+      DefOpt::TailCalls => "tail-calls",
+      DefOpt::PreReduce => "pre-reduce",
+    }
+  }
+}
+
+impl DefOpts {
+  /// Whether the definition, or the one it was generated from, enables or disables the optimization.
+  pub fn get(&self, def_name: &Name, opt: DefOpt) -> Option<bool> {
+    let opts = self.defs.get(&def_name.def_name_from_generated())?;
+    opts.iter().rev().find(|(o, _)| *o == opt).map(|(_, enabled)| *enabled)
+  }
+
+  /// Whether the optimization can be applied to the definition, when it's enabled for the whole program.
+  pub fn allows(&self, def_name: &Name, opt: DefOpt) -> bool {
+    self.get(def_name, opt) != Some(false)
+  }
 }
 
 pub type Adts = IndexMap<Name, Adt>;
@@ -56,14 +185,23 @@ pub type Constructors = IndexMap<Name, Name>;
 #[derive(Debug, Clone)]
 pub struct Definition {
   pub name: Name,
+  /// The type signature written for the definition, which makes it be type checked.
+  pub typ: Option<Type>,
   pub rules: Vec<Rule>,
   pub builtin: bool,
+  /// Where the definition was written in the source code, if it came from a parsed file.
+  pub span: Option<Span>,
+  /// The `##` documentation comment written right before the definition, without the markers.
+  pub doc: Option<String>,
 }
 
 /// A pattern matching rule of a definition.
-#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub struct Rule {
   pub pats: Vec<Pattern>,
+  /// A condition that must also hold for the rule to be taken.
+  /// If it fails, the next matching rule is tried instead.
+  pub guard: Option<Term>,
   pub body: Term,
 }
 
@@ -99,6 +237,13 @@ pub enum Term {
     val: Box<Term>,
     nxt: Box<Term>,
   },
+  /// A local function, visible in its own rules and in `nxt`.
+  /// Lifted to a new top-level definition during desugaring.
+  Def {
+    nam: Name,
+    rules: Vec<Rule>,
+    nxt: Box<Term>,
+  },
   App {
     tag: Tag,
     fun: Box<Term>,
@@ -135,6 +280,12 @@ pub enum Term {
     with: Vec<Name>,
     arms: Vec<MatchRule>,
   },
+  /// Pattern matching with nested patterns in the arms.
+  /// Each arm is tried in order, like the rules of a pattern matching function.
+  PatMat {
+    arg: Box<Term>,
+    arms: Vec<PatMatArm>,
+  },
   /// Native pattern matching on numbers
   Swt {
     arg: Box<Term>,
@@ -161,6 +312,16 @@ pub enum Term {
     var: Name,
     bod: Box<Term>,
   },
+  /// Access to a field of a record: `(rec).fld`
+  Get {
+    rec: Box<Term>,
+    fld: Name,
+  },
+  /// Copy of a record with some of its fields replaced: `{rec | fld = val, ...}`
+  Upd {
+    rec: Box<Term>,
+    flds: Vec<(Name, Term)>,
+  },
   Ref {
     nam: Name,
   },
@@ -171,6 +332,9 @@ pub enum Term {
 
 pub type MatchRule = (Option<Name>, Vec<Option<Name>>, Term);
 
+/// An arm of a `match` with nested patterns: the pattern, an optional guard and the body.
+pub type PatMatArm = (Pattern, Option<Term>, Term);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FanKind {
   Tup,
@@ -191,6 +355,8 @@ pub enum Op {
   AND,
   OR,
   XOR,
+  SHL,
+  SHR,
   /// atan(a, b)
   ATN,
   /// log_a(b)
@@ -218,6 +384,21 @@ pub enum Pattern {
   Str(GlobalString),
 }
 
+/// The type of a term, as written in the type signatures of definitions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+  /// A type variable, or a datatype used without its parameters.
+  Var(Name),
+  /// A datatype applied to its parameters, like `(List u24)`.
+  Ctr(Name, Vec<Type>),
+  /// A function from the first type to the second.
+  Arr(Box<Type>, Box<Type>),
+  Tup(Vec<Type>),
+  U24,
+  I24,
+  F24,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub enum Tag {
   Named(Name),
@@ -232,6 +413,10 @@ pub enum Tag {
 pub struct Adt {
   pub ctrs: IndexMap<Name, Vec<CtrField>>,
   pub builtin: bool,
+  /// Where the datatype was declared in the source code, if it came from a parsed file.
+  pub span: Option<Span>,
+  /// The `##` documentation comment written right before the datatype, without the markers.
+  pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -332,6 +517,43 @@ pub fn num_to_name(mut num: u64) -> String {
   name
 }
 
+/// The Levenshtein distance between two strings, counted in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b = b.chars().collect::<Vec<_>>();
+  let mut prev = (0 ..= b.len()).collect::<Vec<_>>();
+  for (i, ca) in a.chars().enumerate() {
+    let mut curr = vec![i + 1];
+    for (j, cb) in b.iter().enumerate() {
+      let subst = prev[j] + usize::from(ca != *cb);
+      curr.push(subst.min(prev[j + 1] + 1).min(curr[j] + 1));
+    }
+    prev = curr;
+  }
+  prev[b.len()]
+}
+
+impl Book {
+  /// Returns the definitions whose names are close to `nam`, from the closest to the farthest,
+  /// to be suggested when `nam` isn't found.
+  pub fn similar_def_names(&self, nam: &Name, with_builtins: bool) -> Vec<Name> {
+    const MAX_SUGGESTIONS: usize = 3;
+    let max_dist = nam.len().max(3) / 3;
+    let mut similar = self
+      .defs
+      .values()
+      .filter(|def| (with_builtins || !def.builtin) && !def.name.contains("__"))
+      .map(|def| {
+        // Compares also with the last part of the name, so that `Foo` matches `Type/Foo`.
+        let last = def.name.rsplit('/').next().unwrap();
+        (edit_distance(nam, &def.name).min(edit_distance(nam, last)), &def.name)
+      })
+      .filter(|(dist, _)| *dist <= max_dist)
+      .collect::<Vec<_>>();
+    similar.sort();
+    similar.into_iter().take(MAX_SUGGESTIONS).map(|(_, nam)| nam.clone()).collect()
+  }
+}
+
 impl Tag {
   pub fn adt_name(name: &Name) -> Self {
     Self::Named(name.clone())
@@ -348,6 +570,7 @@ impl Clone for Term {
       Self::Do { typ, bod } => Self::Do { typ: typ.clone(), bod: bod.clone() },
       Self::Ask { pat, val, nxt } => Self::Ask { pat: pat.clone(), val: val.clone(), nxt: nxt.clone() },
       Self::Use { nam, val, nxt } => Self::Use { nam: nam.clone(), val: val.clone(), nxt: nxt.clone() },
+      Self::Def { nam, rules, nxt } => Self::Def { nam: nam.clone(), rules: rules.clone(), nxt: nxt.clone() },
       Self::App { tag, fun, arg } => Self::App { tag: tag.clone(), fun: fun.clone(), arg: arg.clone() },
       Self::Fan { fan, tag, els } => Self::Fan { fan: *fan, tag: tag.clone(), els: els.clone() },
       Self::Num { val } => Self::Num { val: *val },
@@ -358,6 +581,7 @@ impl Clone for Term {
       Self::Mat { arg, bnd, with, arms } => {
         Self::Mat { arg: arg.clone(), bnd: bnd.clone(), with: with.clone(), arms: arms.clone() }
       }
+      Self::PatMat { arg, arms } => Self::PatMat { arg: arg.clone(), arms: arms.clone() },
       Self::Swt { arg, bnd, with, pred, arms } => Self::Swt {
         arg: arg.clone(),
         bnd: bnd.clone(),
@@ -378,6 +602,8 @@ impl Clone for Term {
       Self::Open { typ, var, bod: nxt } => {
         Self::Open { typ: typ.clone(), var: var.clone(), bod: nxt.clone() }
       }
+      Self::Get { rec, fld } => Self::Get { rec: rec.clone(), fld: fld.clone() },
+      Self::Upd { rec, flds } => Self::Upd { rec: rec.clone(), flds: flds.clone() },
       Self::Ref { nam } => Self::Ref { nam: nam.clone() },
       Self::Era => Self::Era,
       Self::Err => Self::Err,
@@ -437,6 +663,15 @@ impl Term {
     Term::Lam { tag, pat: Box::new(pat), bod: Box::new(bod) }
   }
 
+  /// A lambda that binds its argument to a variable.
+  pub fn lam_var(nam: &str, bod: Term) -> Self {
+    Self::lam(Pattern::Var(Some(Name::new(nam))), bod)
+  }
+
+  pub fn var(nam: &str) -> Self {
+    Term::Var { nam: Name::new(nam) }
+  }
+
   pub fn var_or_era(nam: Option<Name>) -> Self {
     if let Some(nam) = nam { Term::Var { nam } } else { Term::Era }
   }
@@ -471,6 +706,36 @@ impl Term {
     Term::Str { val: STRINGS.get(str) }
   }
 
+  pub fn u24(val: u32) -> Self {
+    Term::Num { val: Num::U24(val) }
+  }
+
+  pub fn i24(val: i32) -> Self {
+    Term::Num { val: Num::I24(val) }
+  }
+
+  pub fn f24(val: f32) -> Self {
+    Term::Num { val: Num::F24(val) }
+  }
+
+  pub fn oper(opr: Op, fst: Term, snd: Term) -> Self {
+    Term::Oper { opr, fst: Box::new(fst), snd: Box::new(snd) }
+  }
+
+  /// A match on the constructors of the datatype of a variable, with one body for each constructor.
+  /// The fields of each constructor are bound as `var.field` in its arm.
+  pub fn mat<'a>(var: &str, arms: impl IntoIterator<Item = (&'a str, Term)>) -> Self {
+    let arms = arms.into_iter().map(|(ctr, bod)| (Some(Name::new(ctr)), vec![], bod));
+    Term::Mat { arg: Box::new(Term::var(var)), bnd: Some(Name::new(var)), with: vec![], arms: arms.collect() }
+  }
+
+  /// A switch on the number in a variable, with one body for each number starting from 0.
+  /// The last arm matches every remaining number, binding `var-n` to the number minus `n`.
+  pub fn swt(var: &str, arms: Vec<Term>) -> Self {
+    let pred = Some(Name::new(format!("{var}-{}", arms.len() - 1)));
+    Term::Swt { arg: Box::new(Term::var(var)), bnd: Some(Name::new(var)), with: vec![], pred, arms }
+  }
+
   pub fn sub_num(arg: Term, val: Num) -> Term {
     if val.is_zero() {
       arg
@@ -503,11 +768,20 @@ impl Term {
 
   /* Iterators */
   pub fn children(&self) -> impl DoubleEndedIterator<Item = &Term> + Clone {
-    multi_iterator!(ChildrenIter { Zero, One, Two, Vec, Mat, Swt, Bend, Fold });
+    multi_iterator!(ChildrenIter { Zero, One, Two, Vec, Mat, PatMat, Swt, Bend, Fold, Upd, Def });
     match self {
+      Term::Def { nam: _, rules, nxt } => {
+        ChildrenIter::Def(rules.iter().flat_map(|r| r.guard.iter().chain([&r.body])).chain([nxt.as_ref()]))
+      }
       Term::Mat { arg, bnd: _, with: _, arms } => {
         ChildrenIter::Mat([arg.as_ref()].into_iter().chain(arms.iter().map(|r| &r.2)))
       }
+      Term::Upd { rec, flds } => {
+        ChildrenIter::Upd([rec.as_ref()].into_iter().chain(flds.iter().map(|f| &f.1)))
+      }
+      Term::PatMat { arg, arms } => ChildrenIter::PatMat(
+        [arg.as_ref()].into_iter().chain(arms.iter().flat_map(|r| r.1.iter().chain([&r.2]))),
+      ),
       Term::Swt { arg, bnd: _, with: _, pred: _, arms } => {
         ChildrenIter::Swt([arg.as_ref()].into_iter().chain(arms))
       }
@@ -523,7 +797,7 @@ impl Term {
       | Term::Use { val: fst, nxt: snd, .. }
       | Term::App { fun: fst, arg: snd, .. }
       | Term::Oper { fst, snd, .. } => ChildrenIter::Two([fst.as_ref(), snd.as_ref()]),
-      Term::Lam { bod, .. } | Term::Do { bod, .. } | Term::Open { bod, .. } => {
+      Term::Lam { bod, .. } | Term::Do { bod, .. } | Term::Open { bod, .. } | Term::Get { rec: bod, .. } => {
         ChildrenIter::One([bod.as_ref()])
       }
       Term::Var { .. }
@@ -538,11 +812,20 @@ impl Term {
   }
 
   pub fn children_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut Term> {
-    multi_iterator!(ChildrenIter { Zero, One, Two, Vec, Mat, Swt, Bend, Fold });
+    multi_iterator!(ChildrenIter { Zero, One, Two, Vec, Mat, PatMat, Swt, Bend, Fold, Upd, Def });
     match self {
+      Term::Def { nam: _, rules, nxt } => ChildrenIter::Def(
+        rules.iter_mut().flat_map(|r| r.guard.iter_mut().chain([&mut r.body])).chain([nxt.as_mut()]),
+      ),
       Term::Mat { arg, bnd: _, with: _, arms } => {
         ChildrenIter::Mat([arg.as_mut()].into_iter().chain(arms.iter_mut().map(|r| &mut r.2)))
       }
+      Term::Upd { rec, flds } => {
+        ChildrenIter::Upd([rec.as_mut()].into_iter().chain(flds.iter_mut().map(|f| &mut f.1)))
+      }
+      Term::PatMat { arg, arms } => ChildrenIter::PatMat(
+        [arg.as_mut()].into_iter().chain(arms.iter_mut().flat_map(|r| r.1.iter_mut().chain([&mut r.2]))),
+      ),
       Term::Swt { arg, bnd: _, with: _, pred: _, arms } => {
         ChildrenIter::Swt([arg.as_mut()].into_iter().chain(arms))
       }
@@ -558,7 +841,7 @@ impl Term {
       | Term::Use { val: fst, nxt: snd, .. }
       | Term::App { fun: fst, arg: snd, .. }
       | Term::Oper { fst, snd, .. } => ChildrenIter::Two([fst.as_mut(), snd.as_mut()]),
-      Term::Lam { bod, .. } | Term::Do { bod, .. } | Term::Open { bod, .. } => {
+      Term::Lam { bod, .. } | Term::Do { bod, .. } | Term::Open { bod, .. } | Term::Get { rec: bod, .. } => {
         ChildrenIter::One([bod.as_mut()])
       }
       Term::Var { .. }
@@ -584,7 +867,7 @@ impl Term {
     &self,
   ) -> impl DoubleEndedIterator<Item = (&Term, impl DoubleEndedIterator<Item = &Option<Name>> + Clone)> + Clone
   {
-    multi_iterator!(ChildrenIter { Zero, One, Two, Vec, Mat, Swt, Bend, Fold });
+    multi_iterator!(ChildrenIter { Zero, One, Two, Vec, Mat, PatMat, Swt, Bend, Fold, Upd });
     multi_iterator!(BindsIter { Zero, One, Mat, Pat, Bend });
     match self {
       Term::Mat { arg, bnd: _, with: _, arms } => ChildrenIter::Mat(
@@ -592,6 +875,11 @@ impl Term {
           .into_iter()
           .chain(arms.iter().map(move |r| (&r.2, BindsIter::Mat(r.1.iter())))),
       ),
+      Term::PatMat { arg, arms } => {
+        ChildrenIter::PatMat([(arg.as_ref(), BindsIter::Zero([]))].into_iter().chain(arms.iter().flat_map(
+          move |r| r.1.iter().chain([&r.2]).map(move |child| (child, BindsIter::Pat(r.0.binds()))),
+        )))
+      }
       Term::Swt { arg, bnd: _, with: _, pred, arms } => {
         let (succ, nums) = arms.split_last().unwrap();
         ChildrenIter::Swt(
@@ -626,7 +914,14 @@ impl Term {
         ChildrenIter::Two([(fst.as_ref(), BindsIter::Zero([])), (snd.as_ref(), BindsIter::Zero([]))])
       }
       Term::Lam { pat, bod, .. } => ChildrenIter::One([(bod.as_ref(), BindsIter::Pat(pat.binds()))]),
-      Term::Do { bod, .. } => ChildrenIter::One([(bod.as_ref(), BindsIter::Zero([]))]),
+      Term::Do { bod, .. } | Term::Open { bod, .. } | Term::Get { rec: bod, .. } => {
+        ChildrenIter::One([(bod.as_ref(), BindsIter::Zero([]))])
+      }
+      Term::Upd { rec, flds } => ChildrenIter::Upd(
+        [(rec.as_ref(), BindsIter::Zero([]))]
+          .into_iter()
+          .chain(flds.iter().map(|f| (&f.1, BindsIter::Zero([])))),
+      ),
       Term::Var { .. }
       | Term::Link { .. }
       | Term::Num { .. }
@@ -635,7 +930,7 @@ impl Term {
       | Term::Ref { .. }
       | Term::Era
       | Term::Err => ChildrenIter::Zero([]),
-      Term::Open { .. } => unreachable!("Open should be removed in earlier pass"),
+      Term::Def { .. } => unreachable!("Def should be removed in earlier pass"),
     }
   }
 
@@ -644,7 +939,7 @@ impl Term {
     &mut self,
   ) -> impl DoubleEndedIterator<Item = (&mut Term, impl DoubleEndedIterator<Item = &Option<Name>> + Clone)>
   {
-    multi_iterator!(ChildrenIter { Zero, One, Two, Vec, Mat, Swt, Bend, Fold });
+    multi_iterator!(ChildrenIter { Zero, One, Two, Vec, Mat, PatMat, Swt, Bend, Fold, Upd });
     multi_iterator!(BindsIter { Zero, One, Mat, Pat, Bend });
     match self {
       Term::Mat { arg, bnd: _, with: _, arms: rules } => ChildrenIter::Mat(
@@ -652,6 +947,14 @@ impl Term {
           .into_iter()
           .chain(rules.iter_mut().map(move |r| (&mut r.2, BindsIter::Mat(r.1.iter())))),
       ),
+      Term::PatMat { arg, arms } => {
+        ChildrenIter::PatMat([(arg.as_mut(), BindsIter::Zero([]))].into_iter().chain(
+          arms.iter_mut().flat_map(move |(pat, guard, body)| {
+            let pat = &*pat;
+            guard.iter_mut().chain([body]).map(move |child| (child, BindsIter::Pat(pat.binds())))
+          }),
+        ))
+      }
       Term::Swt { arg, bnd: _, with: _, pred, arms: rules } => {
         let (succ, nums) = rules.split_last_mut().unwrap();
         ChildrenIter::Swt(
@@ -686,7 +989,14 @@ impl Term {
         ChildrenIter::Two([(fst.as_mut(), BindsIter::Zero([])), (snd.as_mut(), BindsIter::Zero([]))])
       }
       Term::Lam { pat, bod, .. } => ChildrenIter::One([(bod.as_mut(), BindsIter::Pat(pat.binds()))]),
-      Term::Do { bod, .. } => ChildrenIter::One([(bod.as_mut(), BindsIter::Zero([]))]),
+      Term::Do { bod, .. } | Term::Open { bod, .. } | Term::Get { rec: bod, .. } => {
+        ChildrenIter::One([(bod.as_mut(), BindsIter::Zero([]))])
+      }
+      Term::Upd { rec, flds } => ChildrenIter::Upd(
+        [(rec.as_mut(), BindsIter::Zero([]))]
+          .into_iter()
+          .chain(flds.iter_mut().map(|f| (&mut f.1, BindsIter::Zero([])))),
+      ),
       Term::Var { .. }
       | Term::Link { .. }
       | Term::Num { .. }
@@ -695,7 +1005,7 @@ impl Term {
       | Term::Ref { .. }
       | Term::Era
       | Term::Err => ChildrenIter::Zero([]),
-      Term::Open { .. } => unreachable!("Open should be removed in earlier pass"),
+      Term::Def { .. } => unreachable!("Def should be removed in earlier pass"),
     }
   }
 
@@ -703,7 +1013,7 @@ impl Term {
   pub fn children_mut_with_binds_mut(
     &mut self,
   ) -> impl DoubleEndedIterator<Item = (&mut Term, impl DoubleEndedIterator<Item = &mut Option<Name>>)> {
-    multi_iterator!(ChildrenIter { Zero, One, Two, Vec, Mat, Swt, Fold });
+    multi_iterator!(ChildrenIter { Zero, One, Two, Vec, Mat, Swt, Fold, Upd });
     multi_iterator!(BindsIter { Zero, One, Mat, Pat });
     match self {
       Term::Mat { arg, bnd: _, with: _, arms: rules } => ChildrenIter::Mat(
@@ -723,6 +1033,9 @@ impl Term {
       Term::Bend { .. } => {
         unreachable!("Term::Bend can't implement children_mut_with_binds_mut")
       }
+      Term::PatMat { .. } => {
+        unreachable!("Term::PatMat can't implement children_mut_with_binds_mut")
+      }
       Term::Fold { bnd: _, arg, with: _, arms } => ChildrenIter::Fold(
         [(arg.as_mut(), BindsIter::Zero([]))]
           .into_iter()
@@ -742,7 +1055,14 @@ impl Term {
         ChildrenIter::Two([(fst.as_mut(), BindsIter::Zero([])), (snd.as_mut(), BindsIter::Zero([]))])
       }
       Term::Lam { pat, bod, .. } => ChildrenIter::One([(bod.as_mut(), BindsIter::Pat(pat.binds_mut()))]),
-      Term::Do { bod, .. } => ChildrenIter::One([(bod.as_mut(), BindsIter::Zero([]))]),
+      Term::Do { bod, .. } | Term::Get { rec: bod, .. } => {
+        ChildrenIter::One([(bod.as_mut(), BindsIter::Zero([]))])
+      }
+      Term::Upd { rec, flds } => ChildrenIter::Upd(
+        [(rec.as_mut(), BindsIter::Zero([]))]
+          .into_iter()
+          .chain(flds.iter_mut().map(|f| (&mut f.1, BindsIter::Zero([])))),
+      ),
       Term::Var { .. }
       | Term::Link { .. }
       | Term::Num { .. }
@@ -751,6 +1071,7 @@ impl Term {
       | Term::Ref { .. }
       | Term::Era
       | Term::Err => ChildrenIter::Zero([]),
+      Term::Def { .. } => unreachable!("Def should be removed in earlier pass"),
       Term::Open { .. } => unreachable!("Open should be removed in earlier pass"),
     }
   }
@@ -1010,7 +1331,8 @@ impl Pattern {
       Pattern::Fan(fan, tag, args) => {
         Term::Fan { fan: *fan, tag: tag.clone(), els: args.iter().map(|p| p.to_term()).collect() }
       }
-      Pattern::Lst(_) | Pattern::Str(_) => todo!(),
+      Pattern::Lst(els) => Term::List { els: els.iter().map(|p| p.to_term()).collect() },
+      Pattern::Str(val) => Term::Str { val: val.clone() },
     }
   }
 
@@ -1023,6 +1345,26 @@ impl Pattern {
   }
 }
 
+impl Type {
+  pub fn children(&self) -> impl DoubleEndedIterator<Item = &Type> + Clone {
+    multi_iterator!(ChildrenIter { Zero, Vec, Two });
+    match self {
+      Type::Ctr(_, els) | Type::Tup(els) => ChildrenIter::Vec(els.iter()),
+      Type::Arr(arg, ret) => ChildrenIter::Two([arg.as_ref(), ret.as_ref()]),
+      Type::Var(_) | Type::U24 | Type::I24 | Type::F24 => ChildrenIter::Zero([]),
+    }
+  }
+
+  pub fn children_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut Type> {
+    multi_iterator!(ChildrenIter { Zero, Vec, Two });
+    match self {
+      Type::Ctr(_, els) | Type::Tup(els) => ChildrenIter::Vec(els.iter_mut()),
+      Type::Arr(arg, ret) => ChildrenIter::Two([arg.as_mut(), ret.as_mut()]),
+      Type::Var(_) | Type::U24 | Type::I24 | Type::F24 => ChildrenIter::Zero([]),
+    }
+  }
+}
+
 impl Rule {
   pub fn arity(&self) -> usize {
     self.pats.len()
@@ -1100,15 +1442,6 @@ impl AsRef<str> for Name {
   }
 }
 
-impl Book {
-  pub fn hvmc_entrypoint(&self) -> &str {
-    match self.entrypoint.as_ref().map(|e| e.as_ref()) {
-      Some("main" | "Main") | None => ENTRY_POINT,
-      Some(nam) => nam,
-    }
-  }
-}
-
 #[test]
 fn num_to_from_bits() {
   let a = [
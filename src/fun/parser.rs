@@ -1,52 +1,119 @@
 use crate::{
+  diagnostics::{Severity, Span, WarningType},
   fun::{
-    display::DisplayFn, Adt, Book, CtrField, Definition, FanKind, MatchRule, Name, Num, Op, Pattern, Rule,
-    Tag, Term, STRINGS,
+    display::DisplayFn, transform::float_combinators::FloatMode, Adt, Book, CtrField, DefOpt, Definition,
+    FanKind, MatchRule, Name, Num, Op, PatMatArm, Pattern, Rule, Tag, Term, Type, STRINGS,
   },
-  imp::parser::PyParser,
+  imp::parser::{Assoc, InfixOp, PyParser, INFIX_OP_CHARS, INFIX_PREC_LEVELS},
   maybe_grow,
 };
 use highlight_error::highlight_error;
+use std::collections::BTreeSet;
 use TSPL::Parser;
 
 // Bend grammar description:
-// <Book>       ::= (<Data> | <Rule>)*
+// <Book>       ::= (<Import> | <Data> | <InfixDecl> | <Signature> | <Const> | <Rule>)*
+// <Import>     ::= "import" <Name>
 // <Data>       ::= "data" <Name> "=" ( <Name> | "(" <Name> (<Name>)* ")" )+
-// <Rule>       ::= ("(" <Name> <Pattern>* ")" | <Name> <Pattern>*) "=" <Term>
+// <Const>      ::= "const" <Name> "=" <Term>
+// <Rule>       ::= ("(" <Name> <OrPattern>* ")" | <Name> <OrPattern>*) "=" <Term>
+// <InfixDecl>  ::= ("infixl" | "infixr" | "infix") <Number> <InfixSym> "=" <Name>
+// <InfixSym>   ::= [+\-*/%<>=!&|^~?@]+
+// <Signature>  ::= <Name> ":" <Type>
+// <Type>       ::= <TypeAtom> ("->" <Type>)?
+// <TypeAtom>   ::= "u24" | "i24" | "f24" | <Name> | "(" <Name> <TypeAtom>+ ")" | "(" <Type> ("," <Type>)* ")"
+// <OrPattern>  ::= "(" <Pattern> ("|" <Pattern>)+ ")" | <Pattern>
 // <Pattern>    ::= "(" <Name> <Pattern>* ")" | <NameEra> | <Number> | "(" <Pattern> ("," <Pattern>)+ ")"
 // <Term>       ::=
-//   <Number> | <NumOp> | <Tup> | <App> | <Group> | <Nat> | <Lam> | <UnscopedLam> | <Bend> | <Fold> |
-//   <Use> | <Dup> | <LetTup> | <Let> | <Bind> | <Match> | <Switch> | <Era> | <UnscopedVar> | <Var>
+//   <Number> | <NumOp> | <Section> | <Tup> | <App> | <Group> | <Nat> | <Lam> | <UnscopedLam> | <Bend> | <Fold> |
+//   <Use> | <LocalDef> | <Dup> | <LetTup> | <LetCtr> | <Let> | <Bind> | <Match> | <Switch> | <If> | <Update> | <Era> | <UnscopedVar> | <Var>
 // <Lam>        ::= <Tag>? ("λ"|"@") <NameEra> <Term>
 // <UnscopedLam>::= <Tag>? ("λ"|"@") "$" <Name> <Term>
-// <NumOp>      ::= "(" <Operator> <Term> <Term> ")"
+// <NumOp>      ::= "(" <Operator> <Term> <Term> ")" | "(" "~" <Term> ")"
+// <Section>    ::= "(" <Operator> <Term>? ")" | "(" <Term> <Operator> ")"
 // <Tup>        ::= "(" <Term> ("," <Term>)+ ")"
-// <App>        ::= <Tag>? "(" <Term> (<Term>)+ ")"
-// <Group>      ::= "(" <Term> ")"
+// <App>        ::= <Tag>? "(" <Term> (<Term>)+ ")" <Access>?
+// <Group>      ::= "(" <Term> ")" <Access>?
+// <Access>     ::= "." <Name>
+// <Update>     ::= "{" <Term> "|" <Name> "=" <Term> ("," <Name> "=" <Term>)* "}"
 // <Use>        ::= "use" <Name> "=" <Term> ";"? <Term>
+// <LocalDef>   ::= "def" <Rule> (";"? <Rule>)* ";"? <Term>, where all the rules define the same name
 // <Let>        ::= "let" <NameEra> "=" <Term> ";"? <Term>
+// <LetCtr>     ::= "let" "(" <Name> <Pattern>* ")" "=" <Term> ";"? <Term>
 // <Bind>       ::= "do" <Name> "{" <Ask> "}"
-// <Ask>        ::= "ask" <Pattern> "=" <Term> ";" <Term> | <Term>
+// <Ask>        ::= ("ask" <Pattern> "=" | <Pattern> "<-") <Term> ";" <Term> | <Term>
 // <LetTup>     ::= "let" "(" <NameEra> ("," <NameEra>)+ ")" "=" <Term> ";"? <Term>
 // <Dup>        ::= "let" <Tag>? "{" <NameEra> (","? <NameEra>)+ "}" "=" <Term> ";"? <Term>
 // <List>       ::= "[" (<Term> ","?)* "]"
 // <String>     ::= "\"" (escape sequence | [^"])* "\""
 // <Char>       ::= "'" (escape sequence | [^']) "'"
 // <Match>      ::= "match" <Name> ("=" <Term>)? ("with" <Var> (","? <Var>)*)? "{" <MatchArm>+ "}"
-// <MatchArm>   ::= "|"? <Pattern> ":" <Term> ";"?
+// <MatchArm>   ::= "|"? (<NameEra> | <Number> | <Char> | <String> | <OrPattern>) ("|" <Term>)? ":" <Term> ";"?
 // <Switch>     ::= "switch" <Name> ("=" <Term>)? ("with" <Var> (","? <Var>)*)? "{" <SwitchArm>+ "}"
-// <SwitchArm>  ::= "|"? (<Num>|"_") ":" <Term> ";"?
+// <SwitchArm>  ::= "|"? (<Num> (".." <Num>)? | "_") ":" <Term> ";"?
+// <If>         ::= "if" <Term> ("{" <Term> "}" | "then" <Term>) "else" (<If> | "{" <Term> "}" | <Term>)
 // <Var>        ::= <Name>
 // <UnscopedVar>::= "$" <Name>
 // <NameEra>    ::= <Name> | "*"
 // <Era>        ::= "*"
 // <Tag>        ::= "#" <Name>
 // <Name>       ::= [_\-./a-zA-Z0-9]+
-// <Number>     ::= ([0-9]+ | "0x"[0-9a-fA-F]+ | "0b"[01]+)
-// <Operator>   ::= ( "+" | "-" | "*" | "/" | "%" | "==" | "!=" | "<<" | ">>" | "<=" | ">=" | "<" | ">" | "^" )
+// <Number>     ::= ("+" | "-")? ([0-9_]+ | "0x"[0-9a-fA-F_]+ | "0b"[01_]+ | "0o"[0-7_]+) ("." [0-9_]+ (("e" | "E") ("+" | "-")? [0-9]+)?)?
+// <Operator>   ::= ( "+" | "-" | "*" | "/" | "%" | "==" | "!=" | "<<" | ">>" | "<=" | ">=" | "<" | ">" | "&" | "|" | "^" )
+//
+// Comments:     "#" until the end of the line, or "#{" ... "}#".
+// Doc comments: consecutive lines starting with "##" right before a <Data>, <Signature>, <Rule> or imp item.
+// Pragmas:      lines like "#!allow(<Warning> ("," <Warning>)*)", also with "warn" or "deny", that apply to the
+//               function defined right below them, or to the whole file if they're not right above a function,
+//               and "#!inline", "#!no_float", "#!no_prereduce" or "#!opt(<Opt> = <Bool>, ...)",
+//               that must be right above a function.
 
 pub type ParseResult<T> = std::result::Result<T, String>;
 
+/// The bitwise not is a xor with a number with every bit set, which only means the same for u24 numbers.
+pub(crate) const BITWISE_NOT_ERR: &str = "The bitwise not '~' can only be applied to u24 numbers.";
+
+/// Parses a term that must span the whole input.
+pub fn parse_term(input: &str) -> ParseResult<Term> {
+  let mut parser = TermParser::new(input);
+  let term = parser.parse_term()?;
+  parser.skip_trivia();
+  if parser.is_eof() { Ok(term) } else { parser.expected("end of input") }
+}
+
+/// Parses a term written in the Bend syntax, panicking if it's not valid.
+///
+/// ```
+/// let id = bend::term!("λx x");
+/// ```
+#[macro_export]
+macro_rules! term {
+  ($code:expr) => {
+    $crate::fun::parser::parse_term($code).unwrap_or_else(|e| panic!("Invalid term: {e}"))
+  };
+}
+
+/// A top-level item of a file, with where its source starts and ends.
+#[derive(Clone, Debug)]
+pub struct Item {
+  pub kind: ItemKind,
+  pub ini_idx: usize,
+  pub end_idx: usize,
+}
+
+#[derive(Clone, Debug)]
+pub enum ItemKind {
+  /// A `data` declaration.
+  Data(Name),
+  /// The type signature of a function with the functional syntax.
+  Signature(Name),
+  /// A rule of a function with the functional syntax, with the number of rules its or-patterns expand to.
+  Rule(Name, usize),
+  Const(Name),
+  /// An item with the imperative syntax, an import or an operator declaration.
+  Other,
+}
+
 pub struct TermParser<'i> {
   input: &'i str,
   index: usize,
@@ -60,55 +127,358 @@ impl<'a> TermParser<'a> {
   /* AST parsing functions */
 
   pub fn parse_book(&mut self, default_book: Book, builtin: bool) -> ParseResult<Book> {
+    self.parse_book_items(default_book, builtin).map(|(book, _)| book)
+  }
+
+  /// Parses a book like [`TermParser::parse_book`], also returning its top-level items in order.
+  pub fn parse_book_items(&mut self, default_book: Book, builtin: bool) -> ParseResult<(Book, Vec<Item>)> {
     let mut book = default_book;
+    let mut items = vec![];
+    let mut signatures = vec![];
+    let mut infix_ops = vec![];
+    // Where each function definition and signature starts, to find the pragmas that apply to them.
+    let mut def_starts = vec![];
     let mut indent = self.advance_newlines();
     while !self.is_eof() {
       let ini_idx = *self.index();
       // Imp type definition
       if self.try_parse_keyword("type") {
-        let mut prs = PyParser { input: self.input, index: *self.index() };
+        let mut prs = PyParser { input: self.input, index: *self.index(), infix_ops: infix_ops.clone() };
         let (enum_, nxt_indent) = prs.parse_type(indent)?;
         self.index = prs.index;
         let end_idx = *self.index();
+        let name = enum_.name.clone();
         prs.add_type(enum_, &mut book, ini_idx, end_idx, builtin)?;
+        let adt = book.adts.get_mut(&name).unwrap();
+        adt.span = (!builtin).then(|| self.span(ini_idx, end_idx));
+        adt.doc = doc_comment(self.input, ini_idx);
+        items.push(Item { kind: ItemKind::Other, ini_idx, end_idx });
         indent = nxt_indent;
         continue;
       }
       // Imp record type definition
       if self.try_parse_keyword("object") {
-        let mut prs = PyParser { input: self.input, index: *self.index() };
+        let mut prs = PyParser { input: self.input, index: *self.index(), infix_ops: infix_ops.clone() };
         let (obj, nxt_indent) = prs.parse_object(indent)?;
         self.index = prs.index;
         let end_idx = *self.index();
+        let name = obj.name.clone();
         prs.add_object(obj, &mut book, ini_idx, end_idx, builtin)?;
+        let adt = book.adts.get_mut(&name).unwrap();
+        adt.span = (!builtin).then(|| self.span(ini_idx, end_idx));
+        adt.doc = doc_comment(self.input, ini_idx);
+        items.push(Item { kind: ItemKind::Other, ini_idx, end_idx });
         indent = nxt_indent;
         continue;
       }
       // Imp function definition
       if self.try_parse_keyword("def") {
-        let mut prs = PyParser { input: self.input, index: *self.index() };
+        let mut prs = PyParser { input: self.input, index: *self.index(), infix_ops: infix_ops.clone() };
         let (def, nxt_indent) = prs.parse_def(indent)?;
         self.index = prs.index;
         let end_idx = *self.index();
+        let name = def.name.clone();
         prs.add_def(def, &mut book, ini_idx, end_idx)?;
+        book.defs.get_mut(&name).unwrap().doc = doc_comment(self.input, ini_idx);
+        def_starts.push((name, ini_idx));
+        items.push(Item { kind: ItemKind::Other, ini_idx, end_idx });
         indent = nxt_indent;
         continue;
       }
+      // Import of another file
+      if self.try_parse_keyword("import") {
+        self.skip_trivia();
+        let path = self.labelled(|p| p.parse_bend_name(), "import path")?;
+        book.imports.push(path);
+        items.push(Item { kind: ItemKind::Other, ini_idx, end_idx: *self.index() });
+        indent = self.advance_newlines();
+        continue;
+      }
+      // Infix operator declaration
+      if let Some(assoc) = self.try_parse_fixity() {
+        let op = self.parse_infix_op(assoc, &infix_ops)?;
+        infix_ops.push(op);
+        items.push(Item { kind: ItemKind::Other, ini_idx, end_idx: *self.index() });
+        indent = self.advance_newlines();
+        continue;
+      }
       // Fun type definition
       if self.try_parse_keyword("data") {
-        let (nam, adt) = self.parse_datatype(builtin)?;
+        let (nam, mut adt) = self.parse_datatype(builtin)?;
+        let end_idx = *self.index();
+        adt.span = (!builtin).then(|| self.span(ini_idx, end_idx));
+        adt.doc = doc_comment(self.input, ini_idx);
+        self.with_ctx(book.add_adt(nam.clone(), adt), ini_idx, end_idx)?;
+        items.push(Item { kind: ItemKind::Data(nam), ini_idx, end_idx });
+        indent = self.advance_newlines();
+        continue;
+      }
+      // Constant evaluated at compile time
+      if let Some(name) = self.try_parse_const_name() {
+        let body = self.parse_term()?;
+        let end_idx = *self.index();
+        if book.defs.contains_key(&name) {
+          let msg = format!("Redefinition of function '{name}'.");
+          return self.with_ctx(Err(msg), ini_idx, end_idx);
+        }
+        let rule = Rule { pats: vec![], guard: None, body };
+        book.add_rule(name.clone(), rule, builtin, self.span(ini_idx, end_idx));
+        book.defs.get_mut(&name).unwrap().doc = doc_comment(self.input, ini_idx);
+        book.consts.insert(name.clone());
+        def_starts.push((name.clone(), ini_idx));
+        items.push(Item { kind: ItemKind::Const(name), ini_idx, end_idx });
+        indent = self.advance_newlines();
+        continue;
+      }
+      // Fun type signature
+      if let Some(name) = self.try_parse_signature_name() {
+        let typ = self.parse_type()?;
         let end_idx = *self.index();
-        self.with_ctx(book.add_adt(nam, adt), ini_idx, end_idx)?;
+        def_starts.push((name.clone(), ini_idx));
+        items.push(Item { kind: ItemKind::Signature(name.clone()), ini_idx, end_idx });
+        signatures.push((name, typ, doc_comment(self.input, ini_idx), ini_idx, end_idx));
         indent = self.advance_newlines();
         continue;
       }
       // Fun function definition
-      let (name, rule) = self.parse_rule()?;
-      book.add_rule(name, rule, builtin);
+      let (name, rules) = self.parse_rule()?;
+      let end_idx = *self.index();
+      let is_new_def = !book.defs.contains_key(&name);
+      items.push(Item { kind: ItemKind::Rule(name.clone(), rules.len()), ini_idx, end_idx });
+      for rule in rules {
+        book.add_rule(name.clone(), rule, builtin, self.span(ini_idx, end_idx));
+      }
+      if is_new_def {
+        book.defs.get_mut(&name).unwrap().doc = doc_comment(self.input, ini_idx);
+      }
+      def_starts.push((name, ini_idx));
       indent = self.advance_newlines();
     }
 
-    Ok(book)
+    self.parse_pragmas(&def_starts, &mut book)?;
+
+    for (name, typ, doc, ini_idx, end_idx) in signatures {
+      match book.defs.get_mut(&name) {
+        Some(def) if def.typ.is_none() => {
+          def.typ = Some(typ);
+          // The documentation of a function goes before its signature.
+          if doc.is_some() {
+            def.doc = doc;
+          }
+        }
+        Some(_) => {
+          let msg = format!("Repeated type signature for '{name}'.");
+          return self.with_ctx(Err(msg), ini_idx, end_idx);
+        }
+        None => {
+          let msg = format!("Type signature for '{name}' without a definition.");
+          return self.with_ctx(Err(msg), ini_idx, end_idx);
+        }
+      }
+    }
+
+    Ok((book, items))
+  }
+
+  /// Parses the pragmas of the whole input into the book.
+  ///
+  /// Warning level pragmas, like `#!allow(unused-definition)`, apply only to the function they're
+  /// right above, in the comments before its definition or signature, or to the whole file if there's
+  /// none. Floating pragmas, `#!inline` and `#!no_float`, and optimization pragmas, like
+  /// `#!opt(prune = false)` or `#!no_prereduce`, its shorthand for `pre-reduce`, must be right above a function.
+  /// Comments that start with `#!` but are not pragmas, like a shebang, are ignored.
+  fn parse_pragmas(&mut self, def_starts: &[(Name, usize)], book: &mut Book) -> ParseResult<()> {
+    let mut line_idx = 0;
+    for line in self.input.split_inclusive('\n') {
+      let ini_idx = line_idx + line.len() - line.trim_start().len();
+      let end_idx = line_idx + line.trim_end().len();
+      line_idx += line.len();
+      let Some(pragma) = line.trim().strip_prefix("#!") else { continue };
+
+      let def = def_starts
+        .iter()
+        .find(|(_, start)| comments_start(self.input, *start) <= ini_idx && ini_idx < *start)
+        .map(|(nam, _)| nam);
+      let float_mode = match pragma.trim() {
+        "inline" => Some(FloatMode::Inline),
+        "no_float" => Some(FloatMode::NoFloat),
+        _ => None,
+      };
+
+      if let Some(mode) = float_mode {
+        let Some(def) = def else {
+          let msg = format!("The '{}' pragma must be right above a function definition.", pragma.trim());
+          return self.with_ctx(Err(msg), ini_idx, end_idx);
+        };
+        book.float_modes.insert(def.clone(), mode);
+      } else if pragma.trim() == "no_prereduce" {
+        let Some(def) = def else {
+          let msg = "The 'no_prereduce' pragma must be right above a function definition.".to_string();
+          return self.with_ctx(Err(msg), ini_idx, end_idx);
+        };
+        book.def_opts.defs.entry(def.clone()).or_default().push((DefOpt::PreReduce, false));
+      } else if let Some(opts) = self.with_ctx(parse_opt_pragma(pragma), ini_idx, end_idx)? {
+        let Some(def) = def else {
+          let msg = "The 'opt' pragma must be right above a function definition.".to_string();
+          return self.with_ctx(Err(msg), ini_idx, end_idx);
+        };
+        book.def_opts.defs.entry(def.clone()).or_default().extend(opts);
+      } else if let Some(levels) = self.with_ctx(parse_lint_pragma(pragma), ini_idx, end_idx)? {
+        match def {
+          Some(nam) => book.lints.defs.entry(nam.clone()).or_default().extend(levels),
+          None => book.lints.file.extend(levels),
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Parses the fixity keyword of an infix operator declaration, backtracking
+  /// if it's not followed by a precedence, since it could be a definition name.
+  fn try_parse_fixity(&mut self) -> Option<Assoc> {
+    let ini_idx = *self.index();
+    let assoc = if self.try_parse_keyword("infixl") {
+      Assoc::Left
+    } else if self.try_parse_keyword("infixr") {
+      Assoc::Right
+    } else if self.try_parse_keyword("infix") {
+      Assoc::None
+    } else {
+      return None;
+    };
+    self.skip_trivia_inline();
+    if self.peek_one().is_some_and(|c| c.is_ascii_digit()) {
+      Some(assoc)
+    } else {
+      *self.index() = ini_idx;
+      None
+    }
+  }
+
+  /// Parses the rest of an infix operator declaration, like `infixl 7 <+> = My/add`.
+  fn parse_infix_op(&mut self, assoc: Assoc, declared: &[InfixOp]) -> ParseResult<InfixOp> {
+    let ini_idx = *self.index();
+    let prec = self.parse_u32()? as usize;
+    if prec >= INFIX_PREC_LEVELS {
+      let msg = format!("Operator precedence must be between 0 and {}.", INFIX_PREC_LEVELS - 1);
+      let end_idx = *self.index();
+      return self.with_ctx(Err(msg), ini_idx, end_idx);
+    }
+
+    self.skip_trivia_inline();
+    let ini_idx = *self.index();
+    let sym = self.take_while(|c| INFIX_OP_CHARS.contains(c)).to_string();
+    let end_idx = *self.index();
+    if sym.is_empty() {
+      return self.expected("operator symbol");
+    }
+    let is_builtin = self.peek_oper_str(&sym);
+    if is_builtin || sym == "=" {
+      let msg = format!("Can't declare the builtin operator '{sym}'.");
+      return self.with_ctx(Err(msg), ini_idx, end_idx);
+    }
+    if declared.iter().any(|op| op.sym == sym) {
+      let msg = format!("Repeated declaration of the operator '{sym}'.");
+      return self.with_ctx(Err(msg), ini_idx, end_idx);
+    }
+
+    self.consume("=")?;
+    self.skip_trivia_inline();
+    let fun = self.labelled(|p| p.parse_bend_name(), "function name")?;
+    Ok(InfixOp { sym, assoc, prec, fun })
+  }
+
+  /// Checks whether the text is exactly one of the builtin operators.
+  fn peek_oper_str(&self, sym: &str) -> bool {
+    let mut prs = TermParser::new(sym);
+    prs.parse_oper().is_ok() && prs.is_eof()
+  }
+
+  /// Parses the start of a constant, `const <Name> =`, backtracking if it's not one,
+  /// since `const` can also be the name of a function, like in `const x y = x`.
+  fn try_parse_const_name(&mut self) -> Option<Name> {
+    let ini_idx = *self.index();
+    if self.try_parse_keyword("const") {
+      self.skip_trivia();
+      // `_` is a pattern that matches anything, so `const _ = ..` is a function with one argument.
+      if let Ok(name) = self.parse_top_level_name()
+        && !name.chars().all(|c| c == '_')
+      {
+        self.skip_trivia();
+        if self.starts_with("=") && !self.starts_with("==") {
+          self.advance_one();
+          return Some(name);
+        }
+      }
+    }
+    *self.index() = ini_idx;
+    None
+  }
+
+  /// Parses the name of a type signature, `name :`, backtracking if the input isn't one.
+  fn try_parse_signature_name(&mut self) -> Option<Name> {
+    let ini_idx = *self.index();
+    if let Ok(name) = self.parse_top_level_name() {
+      self.skip_trivia();
+      if self.try_consume_exactly(":") {
+        return Some(name);
+      }
+    }
+    *self.index() = ini_idx;
+    None
+  }
+
+  fn parse_type(&mut self) -> ParseResult<Type> {
+    maybe_grow(|| {
+      let typ = self.parse_type_atom()?;
+      let end_idx = *self.index();
+      self.skip_trivia();
+      if self.try_consume_exactly("->") {
+        let ret = self.parse_type()?;
+        Ok(Type::Arr(Box::new(typ), Box::new(ret)))
+      } else {
+        // Don't include the trailing whitespace in the span of the signature.
+        *self.index() = end_idx;
+        Ok(typ)
+      }
+    })
+  }
+
+  fn parse_type_atom(&mut self) -> ParseResult<Type> {
+    self.skip_trivia();
+    if self.try_consume_exactly("(") {
+      let head_ini_idx = *self.index();
+      let head = self.parse_type()?;
+      let head_end_idx = *self.index();
+      self.skip_trivia();
+
+      // Tuple
+      if self.try_consume(",") {
+        let mut els = self.list_like(|p| p.parse_type(), "", ")", ",", true, 1)?;
+        els.insert(0, head);
+        return Ok(Type::Tup(els));
+      }
+
+      // Group
+      if self.try_consume(")") {
+        return Ok(head);
+      }
+
+      // Datatype application
+      let Type::Var(nam) = head else {
+        return self.expected_spanned("datatype name", head_ini_idx, head_end_idx);
+      };
+      let args = self.list_like(|p| p.parse_type_atom(), "", ")", "", false, 1)?;
+      return Ok(Type::Ctr(nam, args));
+    }
+
+    let nam = self.labelled(|p| p.parse_top_level_name(), "type")?;
+    match nam.as_ref() {
+      "u24" => Ok(Type::U24),
+      "i24" => Ok(Type::I24),
+      "f24" => Ok(Type::F24),
+      _ => Ok(Type::Var(nam)),
+    }
   }
 
   fn parse_datatype(&mut self, builtin: bool) -> ParseResult<(Name, Adt)> {
@@ -121,7 +491,7 @@ impl<'a> TermParser<'a> {
       ctrs.push(self.parse_datatype_ctr(&name)?);
     }
     let ctrs = ctrs.into_iter().collect();
-    let adt = Adt { ctrs, builtin };
+    let adt = Adt { ctrs, builtin, span: None, doc: None };
     Ok((name, adt))
   }
 
@@ -150,20 +520,21 @@ impl<'a> TermParser<'a> {
     }
   }
 
-  fn parse_rule(&mut self) -> ParseResult<(Name, Rule)> {
+  /// Parses a rule, expanding any or-patterns into one rule for each combination of the alternatives.
+  fn parse_rule(&mut self) -> ParseResult<(Name, Vec<Rule>)> {
     // (name pat*) = term
     // name pat* = term
     let (name, pats) = if self.try_consume_exactly("(") {
       self.skip_trivia();
       let name = self.labelled(|p| p.parse_top_level_name(), "function name")?;
-      let pats = self.list_like(|p| p.parse_pattern(false), "", ")", "", false, 0)?;
+      let pats = self.list_like(|p| p.parse_or_pattern(), "", ")", "", false, 0)?;
       self.consume("=")?;
       (name, pats)
     } else {
       let name = self.labelled(|p| p.parse_top_level_name(), "top-level definition")?;
       let mut pats = vec![];
       while !self.try_consume("=") {
-        pats.push(self.parse_pattern(false)?);
+        pats.push(self.parse_or_pattern()?);
         self.skip_trivia();
       }
       (name, pats)
@@ -171,8 +542,40 @@ impl<'a> TermParser<'a> {
 
     let body = self.parse_term()?;
 
-    let rule = Rule { pats, body };
-    Ok((name, rule))
+    let rules = expand_or_patterns(pats)
+      .into_iter()
+      .map(|pats| Rule { pats, guard: None, body: body.clone() })
+      .collect();
+    Ok((name, rules))
+  }
+
+  /// Parses a pattern that can also be a list of alternatives between parentheses, like `(A | B)`.
+  ///
+  /// All the alternatives must bind the same variables.
+  fn parse_or_pattern(&mut self) -> ParseResult<Vec<Pattern>> {
+    self.skip_trivia();
+    let ini_idx = *self.index();
+    if self.try_consume_exactly("(") {
+      let fst = self.parse_pattern(false)?;
+      if self.try_consume("|") {
+        let mut alts = self.list_like(|p| p.parse_pattern(false), "", ")", "|", true, 1)?;
+        alts.insert(0, fst);
+        // Constructors are always qualified by their type name and variables usually aren't,
+        // so names with a `/` are assumed to be constructors and not binds.
+        let binds = |pat: &Pattern| {
+          pat.binds().flatten().filter(|nam| !nam.contains('/')).cloned().collect::<BTreeSet<_>>()
+        };
+        if alts.iter().any(|alt| binds(alt) != binds(&alts[0])) {
+          let msg = "All the alternatives of an or-pattern must bind the same variables.";
+          let end_idx = *self.index();
+          return self.with_ctx(Err(msg), ini_idx, end_idx);
+        }
+        return Ok(alts);
+      }
+      // Not an or-pattern, parse it again as a normal pattern.
+      *self.index() = ini_idx;
+    }
+    Ok(vec![self.parse_pattern(false)?])
   }
 
   fn parse_pattern(&mut self, simple: bool) -> ParseResult<Pattern> {
@@ -221,7 +624,12 @@ impl<'a> TermParser<'a> {
       // String
       if self.starts_with("\"") && !simple {
         unexpected_tag(self)?;
-        let str = self.parse_quoted_string()?;
+        self.skip_trivia();
+        let (_, str) = self.parse_interpolated_string(|p| {
+          let idx = *p.index() - 1;
+          let msg = "String patterns can't have interpolated expressions, write '{{' for a literal brace.";
+          p.with_ctx::<()>(Err(msg), idx, idx + 1)
+        })?;
         return Ok(Pattern::Str(STRINGS.get(str)));
       }
 
@@ -235,7 +643,7 @@ impl<'a> TermParser<'a> {
       // Number
       if self.peek_one().map_or(false, |c| c.is_ascii_digit()) {
         unexpected_tag(self)?;
-        let num = self.parse_u32()?;
+        let num = self.parse_u24()?;
         return Ok(Pattern::Num(num));
       }
 
@@ -273,8 +681,23 @@ impl<'a> TermParser<'a> {
       if self.starts_with("(") {
         self.advance_one();
 
-        // Opr but maybe a tup
+        // Bitwise not
         self.skip_trivia();
+        if self.try_consume_exactly("~") {
+          unexpected_tag(self)?;
+          self.skip_trivia();
+          let ini_idx = *self.index();
+          let val = self.parse_term()?;
+          let end_idx = *self.index();
+          if let Term::Num { val: Num::I24(_) | Num::F24(_) } = val {
+            return self.with_ctx(Err(BITWISE_NOT_ERR), ini_idx, end_idx);
+          }
+          self.consume(")")?;
+          let ones = Term::Num { val: Num::U24(0xFF_FFFF) };
+          return Ok(Term::Oper { opr: Op::XOR, fst: Box::new(val), snd: Box::new(ones) });
+        }
+
+        // Opr but maybe a tup
         let starts_with_oper = self.peek_one().map_or(false, |c| "+-*/%&|<>^=!".contains(c));
         if starts_with_oper {
           let opr = self.parse_oper()?;
@@ -292,7 +715,19 @@ impl<'a> TermParser<'a> {
 
           // Opr
           unexpected_tag(self)?;
+
+          // Operator as a function, like `(+)`
+          if self.try_consume(")") {
+            return Ok(operator_section(opr, None, None));
+          }
+
           let fst = self.parse_term()?;
+
+          // Right section, like `(+ 1)`
+          if self.try_consume(")") {
+            return Ok(operator_section(opr, None, Some(fst)));
+          }
+
           let snd = self.parse_term()?;
           self.consume(")")?;
           return Ok(Term::Oper { opr, fst: Box::new(fst), snd: Box::new(snd) });
@@ -309,7 +744,22 @@ impl<'a> TermParser<'a> {
             els.push(self.parse_term()?);
           }
           self.consume(")")?;
-          return Ok(Term::Fan { fan: FanKind::Tup, tag: tag.unwrap_or(Tag::Static), els });
+          let tup = Term::Fan { fan: FanKind::Tup, tag: tag.unwrap_or(Tag::Static), els };
+          return self.parse_bind_arrow(tup);
+        }
+
+        // Left section, like `(1 +)`.
+        // `(x *)` is still an application to an eraser.
+        let ini_idx = *self.index();
+        if let Some(opr) = self.peek_oper()
+          && opr != Op::MUL
+        {
+          self.parse_oper()?;
+          if self.try_consume(")") {
+            unexpected_tag(self)?;
+            return Ok(operator_section(opr, Some(head), None));
+          }
+          *self.index() = ini_idx;
         }
 
         // App
@@ -319,7 +769,7 @@ impl<'a> TermParser<'a> {
           fun: Box::new(fun),
           arg: Box::new(arg),
         });
-        return Ok(term);
+        return self.parse_field_access(term);
       }
 
       // List
@@ -329,9 +779,33 @@ impl<'a> TermParser<'a> {
         return Ok(Term::List { els });
       }
 
-      // Sup
+      // Sup or record update
       if self.starts_with("{") {
-        let els = self.list_like(|p| p.parse_term(), "{", "}", ",", false, 2)?;
+        self.advance_one();
+        let fst = self.parse_term()?;
+
+        // Record update
+        if self.try_consume("|") {
+          unexpected_tag(self)?;
+          let flds = self.list_like(
+            |p| {
+              let fld = p.parse_bend_name()?;
+              p.consume("=")?;
+              Ok((fld, p.parse_term()?))
+            },
+            "",
+            "}",
+            ",",
+            true,
+            1,
+          )?;
+          return Ok(Term::Upd { rec: Box::new(fst), flds });
+        }
+
+        // Sup
+        self.try_consume(",");
+        let mut els = self.list_like(|p| p.parse_term(), "", "}", ",", false, 1)?;
+        els.insert(0, fst);
         return Ok(Term::Fan { fan: FanKind::Dup, tag: tag.unwrap_or(Tag::Auto), els });
       }
 
@@ -348,7 +822,7 @@ impl<'a> TermParser<'a> {
       if self.starts_with("*") {
         self.advance_one();
         unexpected_tag(self)?;
-        return Ok(Term::Era);
+        return self.parse_bind_arrow(Term::Era);
       }
 
       // Nat
@@ -362,8 +836,9 @@ impl<'a> TermParser<'a> {
       // String
       if self.starts_with("\"") {
         unexpected_tag(self)?;
-        let str = self.parse_quoted_string()?;
-        return Ok(Term::Str { val: STRINGS.get(str) });
+        self.skip_trivia();
+        let (parts, end) = self.parse_interpolated_string(|p| p.parse_term())?;
+        return Ok(Term::interpolated_str(parts, end));
       }
 
       // Char
@@ -399,10 +874,35 @@ impl<'a> TermParser<'a> {
         return Ok(Term::Use { nam: Some(nam), val: Box::new(val), nxt: Box::new(nxt) });
       }
 
+      // Local definition
+      if self.try_parse_keyword("def") {
+        unexpected_tag(self)?;
+        self.skip_trivia();
+        let (nam, mut rules) = self.parse_rule()?;
+        // The following rules of the same function, backtracking at the first thing that isn't one.
+        loop {
+          self.try_consume(";");
+          self.skip_trivia();
+          let ini_idx = *self.index();
+          match self.parse_rule() {
+            Ok((nxt_nam, nxt_rules)) if nxt_nam == nam => rules.extend(nxt_rules),
+            _ => {
+              *self.index() = ini_idx;
+              break;
+            }
+          }
+        }
+        let nxt = self.parse_term()?;
+        return Ok(Term::Def { nam, rules, nxt: Box::new(nxt) });
+      }
+
       // Let
       if self.try_parse_keyword("let") {
         unexpected_tag(self)?;
-        let pat = self.parse_pattern(true)?;
+        // Besides tuples, a `let` can destructure a constructor.
+        self.skip_trivia();
+        let simple = !self.starts_with("(");
+        let pat = self.parse_pattern(simple)?;
         self.consume("=")?;
         let val = self.parse_term()?;
         self.try_consume(";");
@@ -423,29 +923,35 @@ impl<'a> TermParser<'a> {
 
       // If
       if self.try_parse_keyword("if") {
-        let cnd = self.parse_term()?;
-        self.consume("{")?;
-        let thn = self.parse_term()?;
-        self.consume("}")?;
-        self.consume("else")?;
-        self.consume("{")?;
-        let els = self.parse_term()?;
-        self.consume("}")?;
-        return Ok(Term::Swt {
-          arg: Box::new(cnd),
-          bnd: Some(Name::new("%cond")),
-          with: Vec::new(),
-          pred: Some(Name::new("%cond-1")),
-          arms: vec![els, thn],
-        });
+        return self.parse_if();
       }
 
       // Match
       if self.try_parse_keyword("match") {
         unexpected_tag(self)?;
         let (bnd, arg, with) = self.parse_match_header()?;
-        let arms = self.list_like(|p| p.parse_match_arm(), "{", "}", ";", false, 1)?;
-        return Ok(Term::Mat { arg: Box::new(arg), bnd, with, arms });
+        let arms = self.list_like(|p| p.parse_nested_match_arm(), "{", "}", ";", false, 1)?;
+        let arms = arms.into_iter().flatten().collect::<Vec<_>>();
+
+        // If all arms are just constructor names, it's a simple match on the constructors.
+        if arms.iter().all(|(pat, guard, _)| matches!(pat, Pattern::Var(_)) && guard.is_none()) {
+          let arms = arms.into_iter().map(|(pat, _, bod)| (pat.binds().next().unwrap().clone(), vec![], bod));
+          return Ok(Term::Mat { arg: Box::new(arg), bnd, with, arms: arms.collect() });
+        }
+
+        // Otherwise, the arms have nested patterns that will be checked in order.
+        // A match on a value bound to a new name is a `use` followed by a match on the name.
+        let term = match bnd {
+          Some(bnd) if !bnd.starts_with('%') && !matches!(&arg, Term::Var { nam } if *nam == bnd) => {
+            Term::Use {
+              nam: Some(bnd.clone()),
+              val: Box::new(arg),
+              nxt: Box::new(Term::PatMat { arg: Box::new(Term::Var { nam: bnd }), arms }),
+            }
+          }
+          _ => Term::PatMat { arg: Box::new(arg), arms },
+        };
+        return Ok(term);
       }
 
       // Switch
@@ -454,32 +960,31 @@ impl<'a> TermParser<'a> {
         let (bnd, arg, with) = self.parse_match_header()?;
 
         self.consume("{")?;
-        self.try_consume("|");
-        self.consume("0")?;
-        self.consume(":")?;
-        let zero = self.parse_term()?;
-        self.try_consume(";");
-
-        let mut arms = vec![zero];
-        let mut expected_num = 1;
+        let mut arms = vec![];
         loop {
           self.try_consume("|");
           // case _
-          if self.try_consume("_") {
+          if !arms.is_empty() && self.try_consume("_") {
             self.consume(":")?;
             arms.push(self.parse_term()?);
             self.try_consume(";");
             self.consume("}")?;
             break;
           }
-          // case num
-          let val = self.parse_u32()?;
+          // case num, or a range of nums `lo..hi` that share the same body
+          let expected_num = arms.len() as u32;
+          self.skip_trivia();
+          if arms.is_empty() && !self.starts_with("0") {
+            return self.expected("'0'");
+          }
+          let val = self.parse_u24()?;
           if val != expected_num {
             return self.expected(&format!("'{}'", &expected_num.to_string()));
           }
-          expected_num += 1;
+          let end = self.parse_switch_range_end(val)?;
           self.consume(":")?;
-          arms.push(self.parse_term()?);
+          let bod = self.parse_term()?;
+          arms.extend(std::iter::repeat(bod).take((end - val) as usize));
           self.try_consume(";");
         }
         let pred = Some(Name::new(format!("{}-{}", bnd.as_ref().unwrap(), arms.len() - 1)));
@@ -555,22 +1060,62 @@ impl<'a> TermParser<'a> {
       // Var
       unexpected_tag(self)?;
       let nam = self.labelled(|p| p.parse_bend_name(), "term")?;
-      Ok(Term::Var { nam })
+      self.parse_bind_arrow(Term::Var { nam })
     })
   }
 
-  fn parse_name_or_era(&mut self) -> ParseResult<Option<Name>> {
-    self.labelled(
-      |p| {
-        if p.try_consume_exactly("*") {
-          Ok(None)
-        } else {
-          let nam = p.parse_bend_name()?;
-          Ok(Some(nam))
-        }
-      },
-      "name or '*'",
-    )
+  /// Parses the rest of a monadic bind `pat <- val; nxt` if the already parsed term is followed by `<-`.
+  /// The term is read back as the bound pattern, which can be a variable, an eraser or a tuple of those.
+  fn parse_bind_arrow(&mut self, term: Term) -> ParseResult<Term> {
+    self.skip_trivia();
+    if !self.starts_with("<-") {
+      return Ok(term);
+    }
+    let ini_idx = *self.index();
+    self.consume("<-")?;
+    let Some(pat) = bind_pattern(&term) else {
+      let end_idx = *self.index();
+      return self.expected_spanned("variable or tuple pattern before '<-'", ini_idx, end_idx);
+    };
+    let val = self.parse_term()?;
+    self.try_consume(";");
+    let nxt = self.parse_term()?;
+    Ok(Term::Ask { pat: Box::new(pat), val: Box::new(val), nxt: Box::new(nxt) })
+  }
+
+  /// Parses the rest of an `if` expression, after the `if` keyword, into a switch on the condition.
+  /// The branches are either `{ thn } else { els }` or `then thn else els`,
+  /// and an `else` can be directly followed by another `if`.
+  fn parse_if(&mut self) -> ParseResult<Term> {
+    let cnd = self.parse_term()?;
+    self.skip_trivia();
+    let thn = if self.try_parse_keyword("then") {
+      self.parse_term()?
+    } else {
+      self.consume("{")?;
+      let thn = self.parse_term()?;
+      self.consume("}")?;
+      thn
+    };
+    self.skip_trivia();
+    self.parse_keyword("else")?;
+    self.skip_trivia();
+    let els = if self.try_parse_keyword("if") {
+      self.parse_if()?
+    } else if self.try_consume("{") {
+      let els = self.parse_term()?;
+      self.consume("}")?;
+      els
+    } else {
+      self.parse_term()?
+    };
+    Ok(Term::Swt {
+      arg: Box::new(cnd),
+      bnd: Some(Name::new("%cond")),
+      with: Vec::new(),
+      pred: Some(Name::new("%cond-1")),
+      arms: vec![els, thn],
+    })
   }
 
   /// Parses a tag where it may or may not be valid.
@@ -644,6 +1189,40 @@ impl<'a> TermParser<'a> {
     let bod = self.parse_term()?;
     Ok((nam, vec![], bod))
   }
+
+  /// Parses a match arm, which is either a constructor name or a nested pattern in parentheses.
+  /// Parses the field accesses that directly follow a term between parentheses, like `(f x).fld`.
+  fn parse_field_access(&mut self, mut term: Term) -> ParseResult<Term> {
+    if self.starts_with(".") {
+      self.advance_one();
+      // Names can have dots, so `.a.b` is read as a single name.
+      let flds = self.parse_bend_name()?;
+      for fld in flds.split('.') {
+        term = Term::Get { rec: Box::new(term), fld: Name::new(fld) };
+      }
+    }
+    Ok(term)
+  }
+
+  /// Parses an arm of a `match`, with one arm for each alternative of an or-pattern.
+  fn parse_nested_match_arm(&mut self) -> ParseResult<Vec<PatMatArm>> {
+    self.try_consume("|");
+    self.skip_trivia();
+    // Literals are compared with the value, like in the patterns of pattern matching functions.
+    let lit =
+      self.starts_with("'") || self.starts_with("\"") || self.peek_one().is_some_and(|c| c.is_ascii_digit());
+    let pats = if self.starts_with("(") {
+      self.parse_or_pattern()?
+    } else if lit {
+      vec![self.parse_pattern(false)?]
+    } else {
+      vec![Pattern::Var(self.parse_name_or_era()?)]
+    };
+    let guard = if self.try_consume("|") { Some(self.parse_term()?) } else { None };
+    self.consume(":")?;
+    let bod = self.parse_term()?;
+    Ok(pats.into_iter().map(|pat| (pat, guard.clone(), bod.clone())).collect())
+  }
 }
 
 impl<'a> Parser<'a> for TermParser<'a> {
@@ -683,6 +1262,9 @@ impl<'a> Parser<'a> for TermParser<'a> {
         self.advance_one();
         continue;
       }
+      if self.skip_block_comment() > 0 {
+        continue;
+      }
       if c == '#' {
         while let Some(c) = self.peek_one() {
           if c != '\n' {
@@ -699,6 +1281,123 @@ impl<'a> Parser<'a> for TermParser<'a> {
   }
 }
 
+/// Returns the `##` documentation comment in the lines right before `idx`,
+/// with the markers and the space after them removed.
+fn doc_comment(input: &str, idx: usize) -> Option<String> {
+  let (before, line_start) = input[.. idx].rsplit_once('\n')?;
+  if !line_start.trim().is_empty() {
+    return None;
+  }
+  let mut lines = before
+    .lines()
+    .rev()
+    .filter(|line| !line.trim_start().starts_with("#!"))
+    .map_while(|line| line.trim_start().strip_prefix("##"))
+    .map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end())
+    .collect::<Vec<_>>();
+  if lines.is_empty() {
+    return None;
+  }
+  lines.reverse();
+  Some(lines.join("\n"))
+}
+
+/// Returns where the block of comment lines right before `idx` starts, or the start of the line of `idx` if there's none.
+fn comments_start(input: &str, idx: usize) -> usize {
+  let mut start = input[.. idx].rfind('\n').map_or(0, |i| i + 1);
+  while start > 0 {
+    let prev = input[.. start - 1].rfind('\n').map_or(0, |i| i + 1);
+    if !input[prev .. start].trim_start().starts_with('#') {
+      break;
+    }
+    start = prev;
+  }
+  start
+}
+
+/// Parses the text of a pragma after the `#!`, returning the warning levels that it sets,
+/// or `None` if it's not a warning level pragma.
+fn parse_lint_pragma(pragma: &str) -> Result<Option<Vec<(WarningType, Severity)>>, String> {
+  let Some((level, args)) = pragma.split_once('(') else { return Ok(None) };
+  let severity = match level.trim() {
+    "allow" => Severity::Allow,
+    "warn" => Severity::Warning,
+    "deny" => Severity::Error,
+    _ => return Ok(None),
+  };
+  let Some(args) = args.trim_end().strip_suffix(')') else {
+    return Err("Expected ')' at the end of the pragma.".to_string());
+  };
+
+  let mut levels = vec![];
+  for name in args.split(',').map(|name| name.trim().replace('_', "-")) {
+    if name == "all" {
+      levels.extend(WarningType::ALL.map(|warn| (warn, severity)));
+    } else if let Some(warn) = WarningType::ALL.into_iter().find(|warn| warn.name() == name) {
+      levels.push((warn, severity));
+    } else {
+      return Err(format!("Unknown warning '{name}' in pragma."));
+    }
+  }
+  Ok(Some(levels))
+}
+
+/// Parses the text of a pragma after the `#!`, returning the optimizations that it sets,
+/// or `None` if it's not an `opt` pragma.
+fn parse_opt_pragma(pragma: &str) -> Result<Option<Vec<(DefOpt, bool)>>, String> {
+  let Some(args) = pragma.trim().strip_prefix("opt(") else { return Ok(None) };
+  let Some(args) = args.strip_suffix(')') else {
+    return Err("Expected ')' at the end of the pragma.".to_string());
+  };
+
+  let mut opts = vec![];
+  for arg in args.split(',') {
+    let Some((name, value)) = arg.split_once('=') else {
+      return Err(format!("Expected '=' after '{}' in pragma.", arg.trim()));
+    };
+    let name = name.trim().replace('_', "-");
+    let Some(opt) = DefOpt::ALL.into_iter().find(|opt| opt.name() == name) else {
+      return Err(format!("Unknown optimization '{name}' in pragma."));
+    };
+    let enabled = match value.trim() {
+      "true" => true,
+      "false" => false,
+      value => return Err(format!("Expected 'true' or 'false' for '{name}', found '{value}'.")),
+    };
+    opts.push((opt, enabled));
+  }
+  Ok(Some(opts))
+}
+
+/// Returns every combination of the alternatives of the patterns.
+fn expand_or_patterns(pats: Vec<Vec<Pattern>>) -> Vec<Vec<Pattern>> {
+  pats.into_iter().fold(vec![vec![]], |combs, alts| {
+    combs
+      .into_iter()
+      .flat_map(|comb| {
+        alts.iter().map(move |alt| {
+          let mut comb = comb.clone();
+          comb.push(alt.clone());
+          comb
+        })
+      })
+      .collect()
+  })
+}
+
+/// Converts the term on the left of a `<-` into the pattern it binds.
+fn bind_pattern(term: &Term) -> Option<Pattern> {
+  match term {
+    Term::Var { nam } => Some(Pattern::Var(Some(nam.clone()))),
+    Term::Era => Some(Pattern::Var(None)),
+    Term::Fan { fan, tag, els } => {
+      let els = els.iter().map(bind_pattern).collect::<Option<_>>()?;
+      Some(Pattern::Fan(*fan, tag.clone(), els))
+    }
+    _ => None,
+  }
+}
+
 pub fn is_name_char(c: char) -> bool {
   c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' || c == '/'
 }
@@ -731,8 +1430,24 @@ impl Indent {
   }
 }
 
+/// Builds the function of an operator section, like `(+ 1)`, `(1 +)` or `(+)`,
+/// which receives the missing operands as the `%lhs` and `%rhs` variables.
+fn operator_section(opr: Op, fst: Option<Term>, snd: Option<Term>) -> Term {
+  let mut binds = vec![];
+  let mut operand = |term: Option<Term>, nam: &str| {
+    term.unwrap_or_else(|| {
+      binds.push(Name::new(nam));
+      Term::Var { nam: Name::new(nam) }
+    })
+  };
+  let fst = operand(fst, "%lhs");
+  let snd = operand(snd, "%rhs");
+  let bod = Term::Oper { opr, fst: Box::new(fst), snd: Box::new(snd) };
+  binds.into_iter().rfold(bod, |bod, nam| Term::lam(Pattern::Var(Some(nam)), bod))
+}
+
 impl Book {
-  fn add_adt(&mut self, nam: Name, adt: Adt) -> ParseResult<()> {
+  pub(crate) fn add_adt(&mut self, nam: Name, adt: Adt) -> ParseResult<()> {
     if let Some(adt) = self.adts.get(&nam) {
       if adt.builtin {
         return Err(format!("{} is a built-in datatype and should not be overridden.", nam));
@@ -757,11 +1472,19 @@ impl Book {
     Ok(())
   }
 
-  fn add_rule(&mut self, name: Name, rule: Rule, builtin: bool) {
+  fn add_rule(&mut self, name: Name, rule: Rule, builtin: bool, span: Span) {
     if let Some(def) = self.defs.get_mut(&name) {
       def.rules.push(rule);
     } else {
-      self.defs.insert(name.clone(), Definition { name, rules: vec![rule], builtin });
+      let span = if builtin { None } else { Some(span) };
+      self.defs.insert(name.clone(), Definition {
+        name,
+        typ: None,
+        rules: vec![rule],
+        builtin,
+        span,
+        doc: None,
+      });
     }
   }
 }
@@ -776,6 +1499,17 @@ pub trait ParserCommons<'a>: Parser<'a> {
     }
   }
 
+  /// Returns the line and column of the text between the two indices, ignoring trailing whitespace.
+  ///
+  /// The span has no file, since the parser only sees the source code.
+  fn span(&mut self, ini_idx: usize, end_idx: usize) -> Span {
+    let before = &self.input()[.. ini_idx];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    let length = self.input()[ini_idx .. end_idx].trim_end().chars().count();
+    Span { file: None, line, column, length }
+  }
+
   fn parse_top_level_name(&mut self) -> ParseResult<Name> {
     let ini_idx = *self.index();
     let nam = self.parse_bend_name()?;
@@ -793,6 +1527,20 @@ pub trait ParserCommons<'a>: Parser<'a> {
     Ok(Name::new(nam))
   }
 
+  fn parse_name_or_era(&mut self) -> ParseResult<Option<Name>> {
+    self.labelled(
+      |p| {
+        if p.try_consume_exactly("*") {
+          Ok(None)
+        } else {
+          let nam = p.parse_bend_name()?;
+          Ok(Some(nam))
+        }
+      },
+      "name or '*'",
+    )
+  }
+
   fn parse_exactly_name(&mut self) -> ParseResult<String> {
     let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' || c == '/');
     if name.is_empty() { self.expected("name") } else { Ok(name.to_owned()) }
@@ -841,6 +1589,11 @@ pub trait ParserCommons<'a>: Parser<'a> {
         char_count += 1;
         continue;
       }
+      let block_len = self.skip_block_comment();
+      if block_len > 0 {
+        char_count += block_len as isize;
+        continue;
+      }
       if c == '#' {
         while let Some(c) = self.peek_one() {
           if c != '\n' {
@@ -857,6 +1610,25 @@ pub trait ParserCommons<'a>: Parser<'a> {
     char_count
   }
 
+  /// Skips a `#{ ... }#` block comment, if there's one at the current position.
+  /// An unterminated block comment extends until the end of the input.
+  /// Returns how many characters were skipped.
+  fn skip_block_comment(&mut self) -> usize {
+    if !self.try_consume_exactly("#{") {
+      return 0;
+    }
+    let mut char_count = 2;
+    while !self.is_eof() {
+      if self.try_consume_exactly("}#") {
+        char_count += 2;
+        break;
+      }
+      self.advance_one();
+      char_count += 1;
+    }
+    char_count
+  }
+
   /// Skips until the next non-trivia character in the same line.
   fn skip_trivia_inline(&mut self) {
     self.advance_trivia_inline();
@@ -996,6 +1768,10 @@ pub trait ParserCommons<'a>: Parser<'a> {
       Op::DIV
     } else if self.try_consume_exactly("%") {
       Op::REM
+    } else if self.try_consume_exactly("<<") {
+      Op::SHL
+    } else if self.try_consume_exactly(">>") {
+      Op::SHR
     } else if self.try_consume_exactly("<") {
       Op::LTN
     } else if self.try_consume_exactly(">") {
@@ -1029,6 +1805,10 @@ pub trait ParserCommons<'a>: Parser<'a> {
       Op::DIV
     } else if self.starts_with("%") {
       Op::REM
+    } else if self.starts_with("<<") {
+      Op::SHL
+    } else if self.starts_with(">>") {
+      Op::SHR
     } else if self.starts_with("<") {
       Op::LTN
     } else if self.starts_with(">") {
@@ -1050,6 +1830,7 @@ pub trait ParserCommons<'a>: Parser<'a> {
   }
 
   fn parse_u32(&mut self) -> ParseResult<u32> {
+    let ini_idx = *self.index();
     let radix = match self.peek_many(2) {
       Some("0x") => {
         self.advance_many(2);
@@ -1059,6 +1840,10 @@ pub trait ParserCommons<'a>: Parser<'a> {
         self.advance_many(2);
         2
       }
+      Some("0o") => {
+        self.advance_many(2);
+        8
+      }
       _ => 10,
     };
     let num_str = self.take_while(move |c| c.is_digit(radix) || c == '_');
@@ -1066,8 +1851,39 @@ pub trait ParserCommons<'a>: Parser<'a> {
     if num_str.is_empty() {
       self.expected("numeric digit")
     } else {
-      u32::from_str_radix(&num_str, radix).map_err(|e| e.to_string())
+      match u32::from_str_radix(&num_str, radix) {
+        Ok(num) => Ok(num),
+        // Bigger than any native number.
+        Err(_) => self.num_range_err(ini_idx, "U24"),
+      }
+    }
+  }
+
+  /// Parses an unsigned number that must fit in the 24 bits of a native number.
+  fn parse_u24(&mut self) -> ParseResult<u32> {
+    let ini_idx = *self.index();
+    let num = self.parse_u32()?;
+    if num >= 1 << 24 {
+      return self.num_range_err(ini_idx, "U24");
+    }
+    Ok(num)
+  }
+
+  /// Parses the end of a range case of a switch, `..hi`, returning `val + 1` if the case is a single number.
+  /// The end of the range is not included in it.
+  fn parse_switch_range_end(&mut self, val: u32) -> ParseResult<u32> {
+    self.skip_trivia_inline();
+    if !self.try_consume_exactly("..") {
+      return Ok(val + 1);
+    }
+    self.skip_trivia_inline();
+    let ini_idx = *self.index();
+    let end = self.parse_u24()?;
+    let end_idx = *self.index();
+    if end <= val {
+      return self.expected_spanned(&format!("range end greater than {val}"), ini_idx, end_idx);
     }
+    Ok(end)
   }
 
   fn parse_number(&mut self) -> ParseResult<Num> {
@@ -1086,14 +1902,13 @@ pub trait ParserCommons<'a>: Parser<'a> {
     let num = self.parse_u32()?;
 
     // Parses frac value (Float type)
-    // TODO: Will lead to some rounding errors
-    // TODO: Doesn't cover very large/small numbers
     let fra = if let Some('.') = self.peek_one() {
       self.advance_one();
-      let ini_idx = *self.index();
-      let fra = self.parse_u32()? as f32;
-      let end_idx = *self.index();
-      let fra = fra / 10f32.powi((end_idx - ini_idx) as i32);
+      let fra_str = self.take_while(|c| c.is_ascii_digit() || c == '_').replace('_', "");
+      if fra_str.is_empty() {
+        return self.expected("numeric digit");
+      }
+      let fra = format!("0.{fra_str}").parse::<f64>().map_err(|e| e.to_string())?;
       Some(fra)
     } else {
       None
@@ -1102,16 +1917,18 @@ pub trait ParserCommons<'a>: Parser<'a> {
     // F24
     if let Some(fra) = fra {
       let sgn = sgn.unwrap_or(1);
-      return Ok(Num::F24(sgn as f32 * (num as f32 + fra)));
+      let exp = self.parse_float_exponent()?;
+      let val = sgn as f64 * (num as f64 + fra) * 10f64.powi(exp);
+      return Ok(Num::F24(val as f32));
     }
 
     // I24
     if let Some(sgn) = sgn {
-      let num = sgn * num as i32;
+      let num = sgn as i64 * num as i64;
       if !(-0x00800000 ..= 0x007fffff).contains(&num) {
         return self.num_range_err(ini_idx, "I24");
       }
-      return Ok(Num::I24(num));
+      return Ok(Num::I24(num as i32));
     }
 
     // U24
@@ -1121,6 +1938,27 @@ pub trait ParserCommons<'a>: Parser<'a> {
     Ok(Num::U24(num))
   }
 
+  /// Parses the optional exponent of a float literal, like the `e-3` in `1.5e-3`.
+  fn parse_float_exponent(&mut self) -> ParseResult<i32> {
+    if !matches!(self.peek_one(), Some('e' | 'E')) {
+      return Ok(0);
+    }
+    self.advance_one();
+    let ini_idx = *self.index();
+    let sgn = if self.try_consume_exactly("-") {
+      -1
+    } else {
+      self.try_consume_exactly("+");
+      1
+    };
+    let exp = self.take_while(|c| c.is_ascii_digit());
+    match exp.parse::<i32>() {
+      Ok(exp) => Ok(sgn * exp),
+      Err(_) if exp.is_empty() => self.expected("float exponent"),
+      Err(_) => self.num_range_err(ini_idx, "F24 exponent"),
+    }
+  }
+
   fn num_range_err<T>(&mut self, ini_idx: usize, typ: &str) -> ParseResult<T> {
     let msg = format!("\x1b[1mNumber literal outside of range for {}.\x1b[0m", typ);
     let end_idx = *self.index();
@@ -1153,4 +1991,40 @@ pub trait ParserCommons<'a>: Parser<'a> {
     self.consume_exactly("`")?;
     Ok(result)
   }
+
+  /// Parses a string literal where expressions can be interpolated between braces, like "result: {x}".
+  /// Braces are written in the text by doubling them, like "{{" and "}}".
+  ///
+  /// Returns each interpolated expression together with the text before it, and the text after the last one.
+  fn parse_interpolated_string<T>(
+    &mut self,
+    parse_expr: impl Fn(&mut Self) -> ParseResult<T>,
+  ) -> ParseResult<(Vec<(String, T)>, String)> {
+    self.consume_exactly("\"")?;
+    let mut parts = vec![];
+    let mut text = String::new();
+    while let Some(chr) = self.peek_one() {
+      match chr {
+        '"' => break,
+        '{' | '}' if self.peek_many(2).is_some_and(|s| s.starts_with(chr) && s.ends_with(chr)) => {
+          self.advance_many(2);
+          text.push(chr);
+        }
+        '{' => {
+          self.advance_one();
+          let expr = parse_expr(self)?;
+          self.consume("}")?;
+          parts.push((std::mem::take(&mut text), expr));
+        }
+        '}' => {
+          let idx = *self.index();
+          let msg = "Unmatched '}' in string, write '}}' for a literal brace.";
+          return self.with_ctx(Err(msg), idx, idx + 1);
+        }
+        _ => text.push(self.parse_char()?),
+      }
+    }
+    self.consume_exactly("\"")?;
+    Ok((parts, text))
+  }
 }
@@ -1,8 +1,9 @@
 use crate::{
-  diagnostics::Diagnostics,
+  diagnostics::{Diagnostics, ErrorType},
   fun::{Book, Name, Pattern, Tag, Term},
   maybe_grow,
   net::CtrKind::{self, *},
+  ENTRY_POINT,
 };
 use std::{
   collections::{hash_map::Entry, HashMap},
@@ -23,19 +24,45 @@ pub fn book_to_nets(book: &Book, diags: &mut Diagnostics) -> Result<(hvmc::ast::
   let mut hvmc = hvmc::ast::Book::default();
   let mut labels = Labels::default();
 
+  // HVM always starts from `main`, so the entrypoint is renamed to it,
+  // and any other definition called `main` gets a new name.
   let main = book.entrypoint.as_ref().unwrap();
+  let mut renames = HashMap::new();
+  if *main != Name::new(ENTRY_POINT) {
+    renames.insert(main.to_string(), ENTRY_POINT.to_string());
+    if book.defs.contains_key(&Name::new(ENTRY_POINT)) {
+      let mut new_name = format!("{ENTRY_POINT}_");
+      while book.defs.contains_key(&Name::new(&new_name)) {
+        new_name.push('_');
+      }
+      renames.insert(ENTRY_POINT.to_string(), new_name);
+    }
+  }
 
+  // The shifts have no native operation, so they must already be calls to their builtin functions.
+  for def in book.defs.values() {
+    if def.rules.iter().any(|rule| rule.body.has_shift()) {
+      let err = "The shift operators '<<' and '>>' can only be compiled as calls to the builtin functions 'Num/shl' and 'Num/shr', made by the 'encode-builtins' pass.";
+      diags.add_rule_error(err, ErrorType::UnsupportedOperator, def.name.clone());
+    }
+  }
+  diags.fatal(())?;
+
+  // The definitions are encoded one after the other, since they share the same label generators.
   for def in book.defs.values() {
     for rule in def.rules.iter() {
       let net = term_to_net(&rule.body, &mut labels);
 
-      let name = if def.name == *main { book.hvmc_entrypoint().to_string() } else { def.name.0.to_string() };
+      let name = renames.get(&*def.name).cloned().unwrap_or_else(|| def.name.to_string());
 
       match net {
-        Ok(net) => {
+        Ok(mut net) => {
+          if !renames.is_empty() {
+            net.trees_mut().for_each(|tree| rename_refs(tree, &renames));
+          }
           hvmc.insert(name, net);
         }
-        Err(err) => diags.add_inet_error(err, name),
+        Err(err) => diags.add_inet_error(err, ErrorType::ViciousCycle, name),
       }
     }
   }
@@ -46,6 +73,17 @@ pub fn book_to_nets(book: &Book, diags: &mut Diagnostics) -> Result<(hvmc::ast::
   diags.fatal((hvmc, labels))
 }
 
+fn rename_refs(tree: &mut Tree, renames: &HashMap<String, String>) {
+  maybe_grow(|| {
+    if let Tree::Ref { nam } = tree
+      && let Some(new_nam) = renames.get(nam)
+    {
+      *nam = new_nam.clone();
+    }
+    tree.children_mut().for_each(|child| rename_refs(child, renames));
+  })
+}
+
 /// Converts an LC term into an IC net.
 pub fn term_to_net(term: &Term, labels: &mut Labels) -> Result<Net, String> {
   let mut net = Net::default();
@@ -210,7 +248,11 @@ impl<'t, 'l> EncodeTermState<'t, 'l> {
         | Term::Mat { .. } // Removed in earlier pass
         | Term::Bend { .. } // Removed in desugar_bend
         | Term::Fold { .. } // Removed in desugar_fold
+        | Term::PatMat { .. } // Removed in desugar_nested_matches
         | Term::Open { .. } // Removed in desugar_open
+        | Term::Def { .. } // Removed in lift_local_defs
+        | Term::Get { .. } // Removed in desugar_records
+        | Term::Upd { .. } // Removed in desugar_records
         | Term::Nat { .. } // Removed in encode_nat
         | Term::Str { .. } // Removed in encode_str
         | Term::List { .. } // Removed in encode_list
@@ -402,12 +444,22 @@ impl LabelGenerator {
   }
 }
 
+impl Term {
+  /// Whether the term has a shift operation, which can't be encoded as a native operation.
+  fn has_shift(&self) -> bool {
+    maybe_grow(|| match self {
+      Term::Oper { opr: Op::SHL | Op::SHR, .. } => true,
+      _ => self.children().any(Term::has_shift),
+    })
+  }
+}
+
 fn hole<T: Default>() -> T {
   T::default()
 }
 
 impl Op {
-  fn to_native_tag(self) -> u32 {
+  pub(crate) fn to_native_tag(self) -> u32 {
     match self {
       Op::ADD => 0x4,
       Op::SUB => 0x5,
@@ -424,6 +476,9 @@ impl Op {
       Op::ATN => 0xd,
       Op::LOG => 0xe,
       Op::POW => 0xf,
+      // Encoded as calls to builtin functions, since there's no native shift operation.
+      // `book_to_nets` rejects the shifts left in a program, so they never get here.
+      Op::SHL | Op::SHR => unreachable!(),
     }
   }
 }
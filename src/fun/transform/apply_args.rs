@@ -1,5 +1,5 @@
 use crate::{
-  diagnostics::Diagnostics,
+  diagnostics::{Diagnostics, ErrorType},
   fun::{Ctx, Pattern, Rule, Term},
 };
 
@@ -14,6 +14,8 @@ impl Ctx<'_> {
   /// ```hvm
   /// main = (λx1 λx2 λx3 (MainBody x1 x2 x3) arg1 arg2 arg3)
   /// ```
+  /// The number of arguments must match the number of parameters of the main function,
+  /// counting both the patterns of its rule and the lambdas at the start of its body.
   pub fn apply_args(&mut self, args: Option<Vec<Term>>) -> Result<(), Diagnostics> {
     self.info.start_pass();
 
@@ -25,6 +27,7 @@ impl Ctx<'_> {
       if n_rules != 1 {
         self.info.add_rule_error(
           format!("Expected the entrypoint function to have only one rule, found {n_rules}."),
+          ErrorType::Entrypoint,
           entrypoint.clone(),
         );
       }
@@ -37,18 +40,40 @@ impl Ctx<'_> {
         } else {
           self.info.add_rule_error(
             format!("Expected the entrypoint function to only have variable patterns, found '{pat}'."),
+            ErrorType::Entrypoint,
             entrypoint.clone(),
           );
         }
       }
 
       if let Some(args) = args {
+        let arity = main_body.lambda_arity();
+        if args.len() != arity {
+          self.info.add_rule_error(
+            format!("Expected {arity} arguments for the entrypoint function, found {}.", args.len()),
+            ErrorType::Entrypoint,
+            entrypoint.clone(),
+          );
+        }
         main_body = Term::call(main_body, args);
       }
 
-      main_def.rules = vec![Rule { pats: vec![], body: main_body }];
+      main_def.rules = vec![Rule { pats: vec![], guard: None, body: main_body }];
     }
 
     self.info.fatal(())
   }
 }
+
+impl Term {
+  /// The number of lambdas at the start of the term.
+  fn lambda_arity(&self) -> usize {
+    let mut arity = 0;
+    let mut term = self;
+    while let Term::Lam { bod, .. } = term {
+      arity += 1;
+      term = bod;
+    }
+    arity
+  }
+}
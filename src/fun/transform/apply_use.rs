@@ -1,6 +1,6 @@
 use crate::{
   fun::{Book, Term},
-  maybe_grow,
+  map_values_mut, maybe_grow,
 };
 
 impl Book {
@@ -15,11 +15,11 @@ impl Book {
   /// (λx x λx x λx x)
   /// ```
   pub fn apply_use(&mut self) {
-    for def in self.defs.values_mut() {
+    map_values_mut(&mut self.defs, |_, def| {
       for rule in def.rules.iter_mut() {
         rule.body.apply_use();
       }
-    }
+    });
   }
 }
 
@@ -0,0 +1,109 @@
+use crate::{
+  fun::{Book, DefOpt, Num, Op, Term},
+  hvm::interpreter::operate,
+  map_values_mut, maybe_grow,
+};
+
+impl Book {
+  /// Evaluates at compile time the numeric operations on literals and the switches on literal numbers,
+  /// and removes the operations that don't change their other operand, like `x + 0` and `x * 1`.
+  ///
+  /// Like at runtime, an operation on numbers of different types uses the type of the second one.
+  /// The identities assume that the other operand has the same type as the literal.
+  ///
+  /// Must run after [`Ctx::fix_match_terms`](crate::fun::Ctx::fix_match_terms) and before the matches are linearized.
+  pub fn const_fold(&mut self) {
+    map_values_mut(&mut self.defs, |_, def| {
+      if !self.def_opts.allows(&def.name, DefOpt::ConstFold) {
+        return;
+      }
+      for rule in def.rules.iter_mut() {
+        rule.body.const_fold();
+      }
+    });
+  }
+}
+
+impl Term {
+  pub fn const_fold(&mut self) {
+    maybe_grow(|| match self {
+      // The numbers bound by a `use`, like the ones of the switch arms, can make more operations constant.
+      Term::Use { nam, val, nxt } => {
+        val.const_fold();
+        if let Term::Num { .. } = val.as_ref() {
+          if let Some(nam) = nam {
+            nxt.subst(nam, val);
+          }
+          *self = std::mem::take(nxt.as_mut());
+          self.const_fold();
+        } else {
+          nxt.const_fold();
+        }
+      }
+      Term::Swt { arg, bnd: _, with: _, pred, arms } => {
+        arg.const_fold();
+        if let Term::Num { val: Num::U24(val) } = arg.as_ref() {
+          let n_nums = arms.len() - 1;
+          let val = *val as usize;
+          *self = if val < n_nums {
+            std::mem::take(&mut arms[val])
+          } else {
+            Term::Use {
+              nam: pred.take(),
+              val: Box::new(Term::Num { val: Num::U24((val - n_nums) as u32) }),
+              nxt: Box::new(std::mem::take(&mut arms[n_nums])),
+            }
+          };
+          self.const_fold();
+        } else {
+          arms.iter_mut().for_each(Term::const_fold);
+        }
+      }
+      Term::Oper { opr, fst, snd } => {
+        fst.const_fold();
+        snd.const_fold();
+        match (fst.as_ref(), snd.as_ref()) {
+          (Term::Num { val: a }, Term::Num { val: b }) => {
+            if let Some(val) = fold_oper(*opr, *a, *b) {
+              *self = Term::Num { val };
+            }
+          }
+          (_, Term::Num { val }) if is_identity(*opr, *val, false) => *self = std::mem::take(fst.as_mut()),
+          (Term::Num { val }, _) if is_identity(*opr, *val, true) => *self = std::mem::take(snd.as_mut()),
+          _ => {}
+        }
+      }
+      _ => self.children_mut().for_each(Term::const_fold),
+    })
+  }
+}
+
+/// Computes an operation on two numbers, the same way hvm does.
+fn fold_oper(opr: Op, a: Num, b: Num) -> Option<Num> {
+  // Shifts are compiled to calls to builtin functions.
+  if matches!(opr, Op::SHL | Op::SHR) {
+    return None;
+  }
+  let res = operate((a.to_bits() & !0xF) | opr.to_native_tag(), b.to_bits());
+  // Floats too big for the literal syntax are left for the runtime.
+  let is_f24_limit = res & 0xF == 0x3 && (res >> 20) & 0x7F == 0x7F;
+  (!is_f24_limit).then(|| Num::from_bits(res))
+}
+
+/// Whether the operation with this literal returns its other operand.
+fn is_identity(opr: Op, val: Num, lit_is_fst: bool) -> bool {
+  // Zero has the same bits in every type.
+  let is_zero = val.to_bits() >> 4 == 0;
+  let is_one = match val {
+    Num::U24(v) => v == 1,
+    Num::I24(v) => v == 1,
+    Num::F24(v) => v == 1.0,
+  };
+  match opr {
+    Op::ADD => is_zero,
+    Op::MUL => is_one,
+    Op::SUB => is_zero && !lit_is_fst,
+    Op::DIV => is_one && !lit_is_fst,
+    _ => false,
+  }
+}
@@ -1,5 +1,5 @@
 use crate::{
-  fun::{Book, Definition, Name, Rule, Term},
+  fun::{Book, DefOpt, Definition, Name, Rule, Term},
   maybe_grow,
 };
 use indexmap::{IndexMap, IndexSet};
@@ -15,7 +15,8 @@ impl Book {
   /// Ignores origin of the rules when merging,
   /// Should not be preceded by passes that cares about the origins.
   pub fn merge_definitions(&mut self) {
-    let defs: Vec<_> = self.defs.keys().cloned().collect();
+    let defs: Vec<_> =
+      self.defs.keys().filter(|nam| self.def_opts.allows(nam, DefOpt::Merge)).cloned().collect();
     self.merge(defs.into_iter());
   }
 
@@ -40,8 +41,14 @@ impl Book {
       if equal_defs.len() > 1 {
         // Merging some defs
         // Add the merged def
-        let new_def =
-          Definition { name: new_name.clone(), rules: vec![Rule { pats: vec![], body: term }], builtin };
+        let new_def = Definition {
+          name: new_name.clone(),
+          typ: None,
+          rules: vec![Rule { pats: vec![], guard: None, body: term }],
+          builtin,
+          span: None,
+          doc: None,
+        };
         self.defs.insert(new_name.clone(), new_def);
         // Remove the old ones and write the map of old names to new ones.
         for name in equal_defs {
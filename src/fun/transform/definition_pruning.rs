@@ -1,10 +1,10 @@
 use crate::{
   diagnostics::WarningType,
-  fun::{Adt, Book, Ctx, Name, Term, LIST, STRING},
+  fun::{transform::encode_adts::encode_ctr, Adt, Book, Ctx, DefOpt, Name, Term, LIST, STRING},
   maybe_grow,
 };
 use indexmap::IndexSet;
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum Used {
@@ -30,10 +30,12 @@ type Definitions = HashMap<Name, Used>;
 impl Ctx<'_> {
   /// If `prune_all`, removes all unused definitions and adts starting from Main.
   /// Otherwise, prunes only the builtins not accessible from any non-built-in definition
+  ///
+  /// The definitions that disable `prune` with an `#!opt(..)` pragma are kept like Main.
   pub fn prune(&mut self, prune_all: bool) {
     let mut used = Definitions::new();
 
-    if let Some(main) = &self.book.entrypoint {
+    for main in self.book.entrypoint.iter().chain(self.book.kept_defs()) {
       let def = self.book.defs.get(main).unwrap();
       used.insert(main.clone(), Used::Main);
       self.book.find_used_definitions(&def.rule().body, Used::Main, &mut used);
@@ -95,6 +97,89 @@ impl Ctx<'_> {
 }
 
 impl Book {
+  /// Removes the constructors of the user datatypes that are never built by the code reachable
+  /// from the entrypoint, along with the match arms for them, and encodes the remaining
+  /// constructors again without them.
+  ///
+  /// The arms of the removed constructors can never run, so the definitions used only
+  /// by them are not reachable either.
+  /// Datatypes with no constructor built are left untouched, since there's no value to match on.
+  /// The definitions that disable `prune` with an `#!opt(..)` pragma are reachable like the entrypoint.
+  ///
+  /// Must run after the match terms are fixed and before they are encoded.
+  pub fn prune_ctrs(&mut self) {
+    let Some(entrypoint) = self.entrypoint.clone() else { return };
+    let roots = std::iter::once(entrypoint).chain(self.kept_defs().cloned()).collect::<Vec<_>>();
+
+    // Building a constructor can make more arms reachable, which can build more constructors.
+    let mut built = HashSet::new();
+    loop {
+      let mut found = built.clone();
+      let mut reached = roots.iter().cloned().collect::<HashSet<_>>();
+      let mut to_visit =
+        roots.iter().flat_map(|nam| self.defs[nam].rules.iter().map(|rule| &rule.body)).collect::<Vec<_>>();
+      while let Some(term) = to_visit.pop() {
+        match term {
+          Term::Ref { nam } if self.ctrs.contains_key(nam) => _ = found.insert(nam.clone()),
+          Term::Ref { nam } => {
+            if let Some(def) = self.defs.get(nam) {
+              if reached.insert(nam.clone()) {
+                to_visit.extend(def.rules.iter().map(|rule| &rule.body));
+              }
+            }
+          }
+          Term::Mat { arg, arms, .. } => {
+            to_visit.push(arg);
+            let live =
+              arms.iter().filter(|(ctr, _, _)| ctr.as_ref().map_or(true, |ctr| self.is_built(ctr, &built)));
+            to_visit.extend(live.map(|(_, _, body)| body));
+          }
+          _ => to_visit.extend(term.children()),
+        }
+      }
+      if found.len() == built.len() {
+        break;
+      }
+      built = found;
+    }
+
+    let mut removed = HashSet::new();
+    for adt in self.adts.values_mut().filter(|adt| !adt.builtin) {
+      let unbuilt = adt.ctrs.keys().filter(|ctr| !built.contains(*ctr)).cloned().collect::<Vec<_>>();
+      if unbuilt.is_empty() || unbuilt.len() == adt.ctrs.len() {
+        continue;
+      }
+      for ctr in unbuilt {
+        adt.ctrs.shift_remove(&ctr);
+        self.ctrs.shift_remove(&ctr);
+        self.defs.shift_remove(&ctr);
+        removed.insert(ctr);
+      }
+      let ctrs = adt.ctrs.keys().cloned().collect::<Vec<_>>();
+      for (ctr, fields) in &adt.ctrs {
+        self.defs[ctr].rule_mut().body = encode_ctr(fields.iter().map(|f| &f.nam), ctrs.clone(), ctr);
+      }
+    }
+
+    if !removed.is_empty() {
+      for def in self.defs.values_mut() {
+        for rule in def.rules.iter_mut() {
+          rule.body.remove_ctr_arms(&removed);
+        }
+      }
+    }
+  }
+
+  /// Whether a constructor can be matched, assuming the ones of builtin datatypes are always built.
+  fn is_built(&self, ctr: &Name, built: &HashSet<Name>) -> bool {
+    built.contains(ctr) || self.ctrs.get(ctr).is_some_and(|adt| self.adts[adt].builtin)
+  }
+
+  /// The definitions that disable `prune` with an `#!opt(..)` pragma.
+  fn kept_defs(&self) -> impl Iterator<Item = &Name> {
+    self.defs.keys().filter(|nam| self.def_opts.get(nam, DefOpt::Prune) == Some(false))
+  }
+
   /// Finds all used definitions on every term that can have a def_id.
   fn find_used_definitions(&self, term: &Term, used: Used, uses: &mut Definitions) {
     maybe_grow(|| {
@@ -142,3 +227,16 @@ impl Book {
     }
   }
 }
+
+impl Term {
+  fn remove_ctr_arms(&mut self, removed: &HashSet<Name>) {
+    maybe_grow(|| {
+      if let Term::Mat { arms, .. } = self {
+        arms.retain(|(ctr, _, _)| !ctr.as_ref().is_some_and(|ctr| removed.contains(ctr)));
+      }
+      for child in self.children_mut() {
+        child.remove_ctr_arms(removed);
+      }
+    })
+  }
+}
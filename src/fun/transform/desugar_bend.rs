@@ -1,5 +1,5 @@
 use crate::{
-  diagnostics::Diagnostics,
+  diagnostics::{Diagnostics, ErrorType},
   fun::{Ctx, Definition, Name, Pattern, Rule, Term},
   maybe_grow,
 };
@@ -15,7 +15,7 @@ impl Ctx<'_> {
       let mut fresh = 0;
       for rule in def.rules.iter_mut() {
         if let Err(err) = rule.body.desugar_bend(&def.name, &mut fresh, &mut new_defs) {
-          self.info.add_rule_error(err, def.name.clone());
+          self.info.add_rule_error(err, ErrorType::UnscopedExtraction, def.name.clone());
           break;
         }
       }
@@ -77,8 +77,14 @@ impl Term {
         let body = free_vars.iter().rfold(body, |acc, bind| Term::lam(Pattern::Var(Some(bind.clone())), acc));
         let body =
           bind.iter_mut().rfold(body, |acc, bind| Term::lam(Pattern::Var(std::mem::take(bind)), acc));
-        let def =
-          Definition { name: new_nam.clone(), rules: vec![Rule { pats: vec![], body }], builtin: false };
+        let def = Definition {
+          name: new_nam.clone(),
+          typ: None,
+          rules: vec![Rule { pats: vec![], guard: None, body }],
+          builtin: false,
+          span: None,
+          doc: None,
+        };
         new_defs.push(def);
 
         // Call the new function in the original term.
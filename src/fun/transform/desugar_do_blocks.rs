@@ -1,5 +1,5 @@
 use crate::{
-  diagnostics::Diagnostics,
+  diagnostics::{Diagnostics, ErrorType},
   fun::{Ctx, Name, Term},
   maybe_grow,
 };
@@ -15,7 +15,7 @@ impl Ctx<'_> {
     for def in self.book.defs.values_mut() {
       for rule in def.rules.iter_mut() {
         if let Err(e) = rule.body.desugar_do_blocks(None, &def_names) {
-          self.info.add_rule_error(e, def.name.clone());
+          self.info.add_rule_error(e, ErrorType::InvalidDoBlock, def.name.clone());
         }
       }
     }
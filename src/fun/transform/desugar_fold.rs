@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 
 use crate::{
-  diagnostics::Diagnostics,
+  diagnostics::{Diagnostics, ErrorType},
   fun::{Adts, Constructors, Ctx, Definition, Name, Pattern, Rule, Term},
   maybe_grow,
 };
@@ -37,7 +37,7 @@ impl Ctx<'_> {
         let res =
           rule.body.desugar_fold(&def.name, &mut fresh, &mut new_defs, &self.book.ctrs, &self.book.adts);
         if let Err(e) = res {
-          self.info.add_rule_error(e, def.name.clone());
+          self.info.add_rule_error(e, ErrorType::UnscopedExtraction, def.name.clone());
         }
       }
     }
@@ -116,8 +116,14 @@ impl Term {
           body = Term::lam(Pattern::Var(Some(nam.clone())), body);
         }
         body = Term::lam(Pattern::Var(Some(x_nam)), body);
-        let def =
-          Definition { name: new_nam.clone(), rules: vec![Rule { pats: vec![], body }], builtin: false };
+        let def = Definition {
+          name: new_nam.clone(),
+          typ: None,
+          rules: vec![Rule { pats: vec![], guard: None, body }],
+          builtin: false,
+          span: None,
+          doc: None,
+        };
         new_defs.push(def);
 
         // Call the new function
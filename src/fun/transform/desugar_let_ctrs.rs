@@ -0,0 +1,74 @@
+use crate::{
+  diagnostics::{Diagnostics, ErrorType},
+  fun::{Adts, Constructors, Ctx, Pattern, Term},
+  maybe_grow,
+};
+
+impl Ctx<'_> {
+  /// Desugars `let` terms that destructure a constructor into a `match` with a single arm.
+  /// ```bend
+  /// let (Pair/New a b) = x; body
+  /// ```
+  /// Desugars to:
+  /// ```bend
+  /// match x { (Pair/New a b): body }
+  /// ```
+  /// Only constructors of types with a single constructor can be destructured this way,
+  /// since for other types the `let` could fail to match the value.
+  pub fn desugar_let_ctrs(&mut self) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    for def in self.book.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        if let Err(err) = rule.body.desugar_let_ctrs(&self.book.ctrs, &self.book.adts) {
+          self.info.add_rule_error(err, ErrorType::InvalidPattern, def.name.clone());
+        }
+      }
+    }
+
+    self.info.fatal(())
+  }
+}
+
+impl Term {
+  fn desugar_let_ctrs(&mut self, ctrs: &Constructors, adts: &Adts) -> Result<(), String> {
+    maybe_grow(|| {
+      for child in self.children_mut() {
+        child.desugar_let_ctrs(ctrs, adts)?;
+      }
+
+      if let Term::Let { pat, val, nxt } = self
+        && pat.iter().any(|p| !matches!(p, Pattern::Var(_) | Pattern::Chn(_) | Pattern::Fan(..)))
+      {
+        pat.resolve_pat(ctrs);
+        check_irrefutable(pat, ctrs, adts)?;
+        let pat = std::mem::replace(pat.as_mut(), Pattern::Var(None));
+        *self = Term::PatMat { arg: std::mem::take(val), arms: vec![(pat, None, std::mem::take(nxt))] };
+      }
+      Ok(())
+    })
+  }
+}
+
+/// Checks that the pattern of a `let` matches every value, so that it doesn't need other arms.
+fn check_irrefutable(pat: &Pattern, ctrs: &Constructors, adts: &Adts) -> Result<(), String> {
+  for pat in pat.iter() {
+    match pat {
+      Pattern::Ctr(nam, _) => {
+        let Some(typ) = ctrs.get(nam) else {
+          return Err(format!("Unbound constructor '{nam}' in 'let' pattern."));
+        };
+        if adts[typ].ctrs.len() > 1 {
+          return Err(format!(
+            "Can't destructure '{nam}' with a 'let', since the type '{typ}' has more than one constructor. Use a 'match' instead."
+          ));
+        }
+      }
+      Pattern::Num(_) | Pattern::Lst(_) | Pattern::Str(_) => {
+        return Err(format!("Refutable pattern '{pat}' in 'let'. Use a 'match' instead."));
+      }
+      Pattern::Var(_) | Pattern::Chn(_) | Pattern::Fan(..) => (),
+    }
+  }
+  Ok(())
+}
@@ -1,6 +1,7 @@
 use crate::{
-  diagnostics::{Diagnostics, WarningType},
+  diagnostics::{Diagnostics, ErrorType, WarningType},
   fun::{builtins, Adts, Constructors, Ctx, Definition, FanKind, Name, Num, Pattern, Rule, Tag, Term},
+  map_values_mut,
 };
 use std::collections::{BTreeSet, HashSet};
 
@@ -9,6 +10,8 @@ pub enum DesugarMatchDefErr {
   NumMissingDefault,
   TypeMismatch { expected: Type, found: Type, pat: Pattern },
   RepeatedBind { bind: Name },
+  UnreachableRule { pats: Vec<Pattern> },
+  GuardNotExhaustive,
 }
 
 impl Ctx<'_> {
@@ -16,16 +19,25 @@ impl Ctx<'_> {
   pub fn desugar_match_defs(&mut self) -> Result<(), Diagnostics> {
     self.info.start_pass();
 
-    for (def_name, def) in self.book.defs.iter_mut() {
-      let errs = def.desugar_match_def(&self.book.ctrs, &self.book.adts);
+    let (ctrs, adts) = (&self.book.ctrs, &self.book.adts);
+    let errs = map_values_mut(&mut self.book.defs, |_, def| def.desugar_match_def(ctrs, adts));
+    for (def_name, errs) in self.book.defs.keys().zip(errs) {
       for err in errs {
         match err {
           DesugarMatchDefErr::AdtNotExhaustive { .. }
           | DesugarMatchDefErr::NumMissingDefault
-          | DesugarMatchDefErr::TypeMismatch { .. } => self.info.add_rule_error(err, def_name.clone()),
+          | DesugarMatchDefErr::GuardNotExhaustive => {
+            self.info.add_rule_error(err, ErrorType::NonExhaustiveMatch, def_name.clone())
+          }
+          DesugarMatchDefErr::TypeMismatch { .. } => {
+            self.info.add_rule_error(err, ErrorType::MatchTypeMismatch, def_name.clone())
+          }
           DesugarMatchDefErr::RepeatedBind { .. } => {
             self.info.add_rule_warning(err, WarningType::RepeatedBind, def_name.clone())
           }
+          DesugarMatchDefErr::UnreachableRule { .. } => {
+            self.info.add_rule_warning(err, WarningType::UnreachableArm, def_name.clone())
+          }
         }
       }
     }
@@ -41,12 +53,16 @@ impl Definition {
     let repeated_bind_errs = fix_repeated_binds(&mut self.rules);
     errs.extend(repeated_bind_errs);
 
+    let unreachable_errs = find_unreachable_rules(&self.rules, ctrs, adts);
+
     let args = (0 .. self.arity()).map(|i| Name::new(format!("%arg{i}"))).collect::<Vec<_>>();
     let rules = std::mem::take(&mut self.rules);
     match simplify_rule_match(args.clone(), rules, vec![], ctrs, adts) {
       Ok(body) => {
         let body = args.into_iter().rfold(body, |body, arg| Term::lam(Pattern::Var(Some(arg)), body));
-        self.rules = vec![Rule { pats: vec![], body }];
+        self.rules = vec![Rule { pats: vec![], guard: None, body }];
+        // Only report unreachable rules for well-typed matches, since the check assumes it.
+        errs.extend(unreachable_errs);
       }
       Err(e) => errs.push(e),
     }
@@ -84,6 +100,117 @@ fn fix_repeated_binds(rules: &mut [Rule]) -> Vec<DesugarMatchDefErr> {
   errs
 }
 
+/// Finds the rules that can never be reached, because every value they'd
+/// match is already matched by one of the rules that come before them.
+///
+/// Example: In
+/// ```hvm
+/// (Foo Nil  x) = 0
+/// (Foo xs   0) = 1
+/// (Foo Nil  1) = 2
+/// (Foo *    *) = 3
+/// ```
+/// the third rule is unreachable, since `Nil` with any second argument is
+/// already matched by the first rule.
+///
+/// Rules with a guard can fail, so they don't cover the rules after them.
+fn find_unreachable_rules(rules: &[Rule], ctrs: &Constructors, adts: &Adts) -> Vec<DesugarMatchDefErr> {
+  let mut errs = vec![];
+  let mut prev_rows = vec![];
+  for rule in rules {
+    let row = rule.pats.iter().collect::<Vec<_>>();
+    if !is_useful(&prev_rows, &row, ctrs, adts) {
+      errs.push(DesugarMatchDefErr::UnreachableRule { pats: rule.pats.clone() });
+    }
+    if rule.guard.is_none() {
+      prev_rows.push(row);
+    }
+  }
+  errs
+}
+
+/// The constructor that a pattern matches on, used to specialize the rows of a pattern matrix.
+#[derive(Clone, Copy, PartialEq)]
+enum PatCtr<'a> {
+  Ctr(&'a Name),
+  Fan,
+  Num(u32),
+}
+
+static WILDCARD: Pattern = Pattern::Var(None);
+
+/// Checks if a row of patterns matches some value that isn't matched by any of the previous rows.
+///
+/// This is the usefulness check from "Warnings for pattern matching" (Maranget, 2007),
+/// which specializes the rows by each constructor of the first column until no columns are left.
+fn is_useful(rows: &[Vec<&Pattern>], row: &[&Pattern], ctrs: &Constructors, adts: &Adts) -> bool {
+  let Some((fst, rest)) = row.split_first() else {
+    return rows.is_empty();
+  };
+  match fst {
+    Pattern::Ctr(ctr, args) => {
+      is_useful_ctr(rows, PatCtr::Ctr(ctr), args.len(), args.iter().chain(rest.iter().copied()), ctrs, adts)
+    }
+    Pattern::Fan(.., args) => {
+      is_useful_ctr(rows, PatCtr::Fan, args.len(), args.iter().chain(rest.iter().copied()), ctrs, adts)
+    }
+    Pattern::Num(num) => is_useful_ctr(rows, PatCtr::Num(*num), 0, rest.iter().copied(), ctrs, adts),
+    Pattern::Var(_) | Pattern::Chn(_) => {
+      // If the previous rows cover every constructor of the type, the row is useful
+      // if it's useful for one of them. Otherwise, it's useful if it matches a missing one.
+      let col = rows.iter().map(|r| r[0]);
+      if let Some(arity) =
+        col.clone().find_map(|p| if let Pattern::Fan(.., els) = p { Some(els.len()) } else { None })
+      {
+        let args = std::iter::repeat(&WILDCARD).take(arity).chain(rest.iter().copied());
+        return is_useful_ctr(rows, PatCtr::Fan, arity, args, ctrs, adts);
+      }
+      let adt = col.clone().find_map(|p| if let Pattern::Ctr(ctr, _) = p { ctrs.get(ctr) } else { None });
+      if let Some(adt) = adt.and_then(|adt| adts.get(adt)) {
+        let is_complete =
+          adt.ctrs.keys().all(|ctr| col.clone().any(|p| matches!(p, Pattern::Ctr(found, _) if found == ctr)));
+        if is_complete {
+          return adt.ctrs.iter().any(|(ctr, fields)| {
+            let args = std::iter::repeat(&WILDCARD).take(fields.len()).chain(rest.iter().copied());
+            is_useful_ctr(rows, PatCtr::Ctr(ctr), fields.len(), args, ctrs, adts)
+          });
+        }
+      }
+      let default_rows =
+        rows.iter().filter(|r| r[0].is_wildcard()).map(|r| r[1 ..].to_vec()).collect::<Vec<_>>();
+      is_useful(&default_rows, rest, ctrs, adts)
+    }
+    // Lists and strings are encoded as constructors before this pass,
+    // but if not, assume they're useful instead of giving a wrong warning.
+    Pattern::Lst(_) | Pattern::Str(_) => true,
+  }
+}
+
+/// Specializes the rows by the given constructor and checks if the row with the arguments of the constructor is useful.
+fn is_useful_ctr<'a>(
+  rows: &[Vec<&'a Pattern>],
+  ctr: PatCtr,
+  arity: usize,
+  row: impl Iterator<Item = &'a Pattern>,
+  ctrs: &Constructors,
+  adts: &Adts,
+) -> bool {
+  let row = row.collect::<Vec<_>>();
+  let mut new_rows = vec![];
+  for r in rows {
+    let (fst, rest) = r.split_first().unwrap();
+    let args = match fst {
+      Pattern::Ctr(found, args) if ctr == PatCtr::Ctr(found) => args.iter().collect(),
+      Pattern::Fan(.., args) if ctr == PatCtr::Fan => args.iter().collect(),
+      Pattern::Num(found) if ctr == PatCtr::Num(*found) => vec![],
+      Pattern::Var(_) | Pattern::Chn(_) => vec![&WILDCARD; arity],
+      _ => continue,
+    };
+    new_rows.push(args.into_iter().chain(rest.iter().copied()).collect());
+  }
+  is_useful(&new_rows, &row, ctrs, adts)
+}
+
 /// Creates the match tree for a given pattern matching function definition.
 /// For each constructor, a match case is created.
 ///
@@ -106,8 +233,13 @@ fn simplify_rule_match(
   adts: &Adts,
 ) -> Result<Term, DesugarMatchDefErr> {
   if args.is_empty() {
-    Ok(rules.into_iter().next().unwrap().body)
-  } else if rules[0].pats.iter().all(|p| p.is_wildcard()) {
+    let mut rules = rules.into_iter();
+    let rule = rules.next().unwrap();
+    match rule.guard {
+      Some(guard) => guard_rule(guard, rule.body, rules.collect(), with, ctrs, adts),
+      None => Ok(rule.body),
+    }
+  } else if rules[0].guard.is_none() && rules[0].pats.iter().all(|p| p.is_wildcard()) {
     Ok(irrefutable_fst_row_rule(args, rules.into_iter().next().unwrap()))
   } else {
     let typ = Type::infer_from_def_arg(&rules, 0, ctrs)?;
@@ -134,6 +266,33 @@ fn irrefutable_fst_row_rule(args: Vec<Name>, rule: Rule) -> Term {
   term
 }
 
+/// Guard rule.
+/// When no patterns are left, the first rule matches if its guard holds.
+/// Otherwise, the remaining rules are tried.
+/// `case { | guard: Body; rest }`
+/// becomes
+/// `switch guard { 0: case { rest }; _: Body }`
+fn guard_rule(
+  guard: Term,
+  body: Term,
+  rules: Vec<Rule>,
+  with: Vec<Name>,
+  ctrs: &Constructors,
+  adts: &Adts,
+) -> Result<Term, DesugarMatchDefErr> {
+  if rules.is_empty() {
+    return Err(DesugarMatchDefErr::GuardNotExhaustive);
+  }
+  let els = simplify_rule_match(vec![], rules, with.clone(), ctrs, adts)?;
+  Ok(Term::Swt {
+    arg: Box::new(guard),
+    bnd: Some(Name::new("%guard")),
+    with,
+    pred: Some(Name::new("%guard-1")),
+    arms: vec![els, body],
+  })
+}
+
 /// Var rule.
 /// `case x0 ... xN { var p1 ... pN: (Body var p1 ... pN) }`
 /// becomes
@@ -153,16 +312,12 @@ fn var_rule(
     let new_pats = rule.pats.split_off(1);
     let pat = rule.pats.pop().unwrap();
 
-    if let Pattern::Var(Some(nam)) = &pat {
-      rule.body = Term::Use {
-        nam: Some(nam.clone()),
-        val: Box::new(Term::Var { nam: arg.clone() }),
-        nxt: Box::new(std::mem::take(&mut rule.body)),
-      };
+    if let Pattern::Var(Some(nam)) = pat {
+      rule.bind(nam, Term::Var { nam: arg.clone() });
     }
 
-    let new_rule = Rule { pats: new_pats, body: rule.body };
-    new_rules.push(new_rule);
+    rule.pats = new_pats;
+    new_rules.push(rule);
   }
 
   with.push(arg);
@@ -213,8 +368,7 @@ fn fan_rule(
           // Rebuild the tuple if it was a var pattern
           let tup =
             Term::Fan { fan, tag: tag.clone(), els: new_args.clone().map(|nam| Term::Var { nam }).collect() };
-          rule.body =
-            Term::Use { nam: Some(var), val: Box::new(tup), nxt: Box::new(std::mem::take(&mut rule.body)) };
+          rule.bind(var, tup);
         }
         new_args.clone().map(|nam| Pattern::Var(Some(nam))).collect()
       }
@@ -222,8 +376,8 @@ fn fan_rule(
     };
     new_pats.extend(old_pats);
 
-    let new_rule = Rule { pats: new_pats, body: rule.body };
-    new_rules.push(new_rule);
+    rule.pats = new_pats;
+    new_rules.push(rule);
   }
 
   let bnd = new_args.clone().map(|x| Pattern::Var(Some(x))).collect();
@@ -271,20 +425,13 @@ fn num_rule(
     for rule in rules.iter() {
       match &rule.pats[0] {
         Pattern::Num(n) if n == num => {
-          let body = rule.body.clone();
-          let rule = Rule { pats: rule.pats[1 ..].to_vec(), body };
-          new_rules.push(rule);
+          new_rules.push(rule.with_pats(rule.pats[1 ..].to_vec()));
         }
         Pattern::Var(var) => {
-          let mut body = rule.body.clone();
+          let mut rule = rule.with_pats(rule.pats[1 ..].to_vec());
           if let Some(var) = var {
-            body = Term::Use {
-              nam: Some(var.clone()),
-              val: Box::new(Term::Num { val: Num::U24(*num) }),
-              nxt: Box::new(std::mem::take(&mut body)),
-            };
+            rule.bind(var.clone(), Term::Num { val: Num::U24(*num) });
           }
-          let rule = Rule { pats: rule.pats[1 ..].to_vec(), body };
           new_rules.push(rule);
         }
         _ => (),
@@ -298,14 +445,13 @@ fn num_rule(
   let mut new_rules = vec![];
   for rule in rules {
     if let Pattern::Var(var) = &rule.pats[0] {
-      let mut body = rule.body.clone();
+      let mut new_rule = rule.with_pats(rule.pats[1 ..].to_vec());
       if let Some(var) = var {
         let last_num = *nums.last().unwrap();
         let var_recovered = Term::add_num(Term::Var { nam: pred_var.clone() }, Num::U24(1 + last_num));
-        body = Term::Use { nam: Some(var.clone()), val: Box::new(var_recovered), nxt: Box::new(body) };
+        new_rule.bind(var.clone(), var_recovered);
       }
-      let rule = Rule { pats: rule.pats[1 ..].to_vec(), body };
-      new_rules.push(rule);
+      new_rules.push(new_rule);
     }
   }
   let mut default_with = with.clone();
@@ -413,9 +559,7 @@ fn switch_rule(
         // pat0_0 ... pat0_m pat1 ... patN: body
         Pattern::Ctr(found_ctr, new_pats) if ctr == found_ctr => {
           let pats = new_pats.iter().cloned().chain(old_pats).collect();
-          let body = rule.body.clone();
-          let rule = Rule { pats, body };
-          new_rules.push(rule);
+          new_rules.push(rule.with_pats(pats));
         }
         // Var, match and rebuild the constructor.
         // var pat1 ... patN: body
@@ -425,14 +569,12 @@ fn switch_rule(
         Pattern::Var(var) => {
           let new_pats = new_args.clone().map(|n| Pattern::Var(Some(n)));
           let pats = new_pats.chain(old_pats.clone()).collect();
-          let mut body = rule.body.clone();
+          let mut rule = rule.with_pats(pats);
           let reconstructed_var =
             Term::call(Term::Ref { nam: ctr.clone() }, new_args.clone().map(|nam| Term::Var { nam }));
           if let Some(var) = var {
-            body =
-              Term::Use { nam: Some(var.clone()), val: Box::new(reconstructed_var), nxt: Box::new(body) };
+            rule.bind(var.clone(), reconstructed_var);
           }
-          let rule = Rule { pats, body };
           new_rules.push(rule);
         }
         _ => (),
@@ -459,6 +601,26 @@ fn switch_rule(
   Ok(term)
 }
 
+impl Rule {
+  /// Copies the rule with other patterns, keeping its guard and body.
+  fn with_pats(&self, pats: Vec<Pattern>) -> Rule {
+    Rule { pats, guard: self.guard.clone(), body: self.body.clone() }
+  }
+
+  /// Binds a variable to a value in the body of the rule and in its guard.
+  fn bind(&mut self, nam: Name, val: Term) {
+    if let Some(guard) = &mut self.guard {
+      *guard = Term::Use {
+        nam: Some(nam.clone()),
+        val: Box::new(val.clone()),
+        nxt: Box::new(std::mem::take(guard)),
+      };
+    }
+    self.body =
+      Term::Use { nam: Some(nam), val: Box::new(val), nxt: Box::new(std::mem::take(&mut self.body)) };
+  }
+}
+
 /// Pattern types.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Type {
@@ -541,9 +703,19 @@ impl std::fmt::Display for DesugarMatchDefErr {
       DesugarMatchDefErr::NumMissingDefault => {
         write!(f, "Non-exhaustive pattern matching rule. Default case of number type not covered.")
       }
+      DesugarMatchDefErr::GuardNotExhaustive => {
+        write!(f, "Non-exhaustive pattern matching rule. No rule left to try when the last guard fails.")
+      }
       DesugarMatchDefErr::RepeatedBind { bind } => {
         write!(f, "Repeated bind in pattern matching rule: '{bind}'.")
       }
+      DesugarMatchDefErr::UnreachableRule { pats } => {
+        let pats = pats.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(" ");
+        write!(
+          f,
+          "Unreachable pattern matching rule '{pats}'. All its cases are covered by the previous rules."
+        )
+      }
     }
   }
 }
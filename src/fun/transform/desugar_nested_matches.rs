@@ -0,0 +1,148 @@
+use crate::{
+  diagnostics::{Diagnostics, ErrorType},
+  fun::{Ctx, Definition, Name, Pattern, Rule, Term},
+  maybe_grow,
+};
+use std::collections::{BTreeSet, HashSet};
+
+impl Ctx<'_> {
+  /// Desugars `match` expressions with nested patterns in the arms into
+  /// pattern matching functions, which are then compiled like any other definition.
+  /// ```bend
+  /// foo x y = match x {
+  ///   (List/Cons (Pair/New a b) *) | (> a 0): (Bar a b y)
+  ///   *: y
+  /// }
+  /// ```
+  /// Desugars to:
+  /// ```bend
+  /// foo x y = (foo__match0 y x)
+  ///
+  /// (foo__match0 y (List/Cons (Pair/New a b) *)) | (> a 0) = (Bar a b y)
+  /// (foo__match0 y *) = y
+  /// ```
+  pub fn desugar_nested_matches(&mut self) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    let global_names = self.book.defs.keys().chain(self.book.ctrs.keys()).cloned().collect::<HashSet<_>>();
+
+    let mut new_defs = vec![];
+    for def in self.book.defs.values_mut() {
+      let mut fresh = 0;
+      let mut def_new_defs = vec![];
+      for rule in def.rules.iter_mut() {
+        // Global names don't need to be passed to the new functions, unless shadowed by a local variable.
+        let mut local_names = rule.pats.iter().flat_map(|p| p.binds().flatten().cloned()).collect();
+        rule.body.bound_names(&mut local_names);
+        let globals = global_names.difference(&local_names).cloned().collect();
+
+        let res = rule.body.desugar_nested_matches(&def.name, &globals, &mut fresh, &mut def_new_defs);
+        if let Err(e) = res {
+          self.info.add_rule_error(e, ErrorType::UnscopedExtraction, def.name.clone());
+        }
+      }
+      for new_def in def_new_defs.iter_mut() {
+        new_def.span.clone_from(&def.span);
+      }
+      new_defs.extend(def_new_defs);
+    }
+
+    // The new definitions skipped `fix_match_defs`, so we check their patterns here.
+    // Only the matched pattern is resolved, since the free variables may have the name of a constructor.
+    for mut def in new_defs {
+      let mut errs = vec![];
+      for pat in def.rules.iter_mut().filter_map(|rule| rule.pats.last_mut()) {
+        pat.resolve_pat(&self.book.ctrs);
+        pat.check_good_ctr(&self.book.ctrs, &self.book.adts, &mut errs);
+      }
+      for err in errs {
+        self.info.add_rule_error(err, ErrorType::InvalidPattern, def.name.clone());
+      }
+      if let Some(span) = &def.span {
+        self.info.def_spans.insert(def.name.clone(), span.clone());
+      }
+      self.book.defs.insert(def.name.clone(), def);
+    }
+
+    self.info.fatal(())
+  }
+}
+
+impl Term {
+  fn desugar_nested_matches(
+    &mut self,
+    def_name: &Name,
+    globals: &HashSet<Name>,
+    fresh: &mut usize,
+    new_defs: &mut Vec<Definition>,
+  ) -> Result<(), String> {
+    maybe_grow(|| {
+      for child in self.children_mut() {
+        child.desugar_nested_matches(def_name, globals, fresh, new_defs)?;
+      }
+
+      if let Term::PatMat { .. } = self {
+        // Can't have unmatched unscoped because this'll be extracted
+        if self.has_unscoped_diff() {
+          return Err(
+            "Can't have non self-contained unscoped variables in a 'match' with nested patterns".into(),
+          );
+        }
+        let Term::PatMat { arg, arms } = self else { unreachable!() };
+
+        // Gather the free variables, in a fixed order so that the output is deterministic.
+        let mut free_vars = BTreeSet::new();
+        for (pat, guard, bod) in arms.iter() {
+          let mut arm_free_vars = bod.free_vars().into_keys().collect::<BTreeSet<_>>();
+          arm_free_vars.extend(guard.iter().flat_map(|guard| guard.free_vars().into_keys()));
+          for bind in pat.binds().flatten() {
+            arm_free_vars.remove(bind);
+          }
+          free_vars.extend(arm_free_vars);
+        }
+        free_vars.retain(|var| !globals.contains(var));
+
+        let new_nam = Name::new(format!("{}__match{}", def_name, fresh));
+        *fresh += 1;
+
+        // Create the new function, receiving the free variables before the matched value.
+        // If an arm binds a variable with the same name as a free variable, the free one is erased.
+        let rules = std::mem::take(arms)
+          .into_iter()
+          .map(|(pat, guard, body)| {
+            let binds = pat.binds().flatten().cloned().collect::<BTreeSet<_>>();
+            let mut pats = free_vars
+              .iter()
+              .map(|var| Pattern::Var(if binds.contains(var) { None } else { Some(var.clone()) }))
+              .collect::<Vec<_>>();
+            pats.push(pat);
+            Rule { pats, guard, body }
+          })
+          .collect();
+        new_defs.push(Definition {
+          name: new_nam.clone(),
+          typ: None,
+          rules,
+          builtin: false,
+          span: None,
+          doc: None,
+        });
+
+        // Call the new function
+        let call = Term::call(Term::Ref { nam: new_nam }, free_vars.into_iter().map(|nam| Term::Var { nam }));
+        *self = Term::app(call, std::mem::take(arg.as_mut()));
+      }
+      Ok(())
+    })
+  }
+
+  /// Collects the names of all the variables bound inside the term.
+  fn bound_names(&self, names: &mut HashSet<Name>) {
+    maybe_grow(|| {
+      for (child, binds) in self.children_with_binds() {
+        names.extend(binds.flatten().cloned());
+        child.bound_names(names);
+      }
+    })
+  }
+}
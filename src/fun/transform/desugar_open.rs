@@ -1,5 +1,5 @@
 use crate::{
-  diagnostics::Diagnostics,
+  diagnostics::{Diagnostics, ErrorType},
   fun::{Adts, Ctx, Term},
   maybe_grow,
 };
@@ -11,7 +11,7 @@ impl Ctx<'_> {
     for def in self.book.defs.values_mut() {
       for rule in def.rules.iter_mut() {
         if let Err(err) = rule.body.desugar_open(&self.book.adts) {
-          self.info.add_rule_error(err, def.name.clone());
+          self.info.add_rule_error(err, ErrorType::InvalidOpen, def.name.clone());
         }
       }
     }
@@ -0,0 +1,139 @@
+use crate::{
+  diagnostics::{Diagnostics, ErrorType},
+  fun::{Adts, CtrField, Ctx, Name, Term},
+  maybe_grow,
+};
+use std::collections::HashMap;
+
+impl Ctx<'_> {
+  /// Desugars the access and update of the fields of records into matches on their constructor.
+  /// A record is a type with a single constructor, and it's found by the names of the fields.
+  /// ```bend
+  /// data Point = (New x y)
+  /// (get_point p).x
+  /// {p | y = 0}
+  /// ```
+  /// Desugars to:
+  /// ```bend
+  /// match (get_point p) { Point/New %rec.x %rec.y: %rec.x }
+  /// match p { Point/New %rec.x %rec.y: (Point/New %rec.x 0) }
+  /// ```
+  /// An unbound variable like `p.x` is also read as a field access if `p` is bound.
+  ///
+  /// Precondition: Match terms are fixed and refs are resolved.
+  pub fn desugar_records(&mut self) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    for def in self.book.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        let mut scope = HashMap::new();
+        for bind in rule.pats.iter().flat_map(|pat| pat.binds()).flatten() {
+          *scope.entry(bind.clone()).or_default() += 1;
+        }
+        if let Err(err) = rule.body.desugar_records(&self.book.adts, &mut scope) {
+          self.info.add_rule_error(err, ErrorType::InvalidRecord, def.name.clone());
+        }
+      }
+    }
+
+    self.info.fatal(())
+  }
+}
+
+impl Term {
+  fn desugar_records(&mut self, adts: &Adts, scope: &mut HashMap<Name, usize>) -> Result<(), String> {
+    maybe_grow(|| {
+      if let Term::Var { nam } = self
+        && !is_bound(nam, scope)
+        && let Some(access) = var_field_access(nam, adts, scope)
+      {
+        *self = access;
+      }
+
+      for (child, binds) in self.children_mut_with_binds() {
+        let binds = binds.flatten().cloned().collect::<Vec<_>>();
+        for bind in &binds {
+          *scope.entry(bind.clone()).or_default() += 1;
+        }
+        child.desugar_records(adts, scope)?;
+        for bind in &binds {
+          *scope.entry(bind.clone()).or_default() -= 1;
+        }
+      }
+
+      match self {
+        Term::Get { rec, fld } => {
+          let (ctr, fields) = find_record(adts, &[&*fld])?;
+          let body = Term::Var { nam: field_var(fld) };
+          *self = match_record(std::mem::take(rec), ctr, fields, body);
+        }
+        Term::Upd { rec, flds } => {
+          for (i, (fld, _)) in flds.iter().enumerate() {
+            if flds[.. i].iter().any(|(other, _)| other == fld) {
+              return Err(format!("Repeated field '{fld}' in record update."));
+            }
+          }
+          let (ctr, fields) = find_record(adts, &flds.iter().map(|(fld, _)| fld).collect::<Vec<_>>())?;
+          let mut vals = std::mem::take(flds).into_iter().collect::<HashMap<_, _>>();
+          let args = fields
+            .iter()
+            .map(|f| vals.remove(&f.nam).unwrap_or_else(|| Term::Var { nam: field_var(&f.nam) }));
+          let body = Term::call(Term::Ref { nam: ctr.clone() }, args);
+          *self = match_record(std::mem::take(rec), ctr, fields, body);
+        }
+        _ => (),
+      }
+      Ok(())
+    })
+  }
+}
+
+fn is_bound(nam: &Name, scope: &HashMap<Name, usize>) -> bool {
+  scope.get(nam).is_some_and(|n| *n > 0)
+}
+
+/// Reads a variable like `p.x.y` as field accesses on the longest bound prefix of its name.
+fn var_field_access(nam: &Name, adts: &Adts, scope: &HashMap<Name, usize>) -> Option<Term> {
+  let (rec, flds) = nam
+    .rmatch_indices('.')
+    .map(|(i, _)| (&nam[.. i], &nam[i + 1 ..]))
+    .find(|(rec, _)| is_bound(&Name::new(*rec), scope))?;
+
+  let flds = flds.split('.').map(Name::new).collect::<Vec<_>>();
+  let is_field = |fld: &Name| records(adts).any(|(_, fields)| fields.iter().any(|f| f.nam == *fld));
+  if !flds.iter().all(is_field) {
+    return None;
+  }
+
+  let rec = Term::Var { nam: Name::new(rec) };
+  Some(flds.into_iter().fold(rec, |rec, fld| Term::Get { rec: Box::new(rec), fld }))
+}
+
+/// The constructors of the types with a single constructor.
+fn records(adts: &Adts) -> impl Iterator<Item = (&Name, &Vec<CtrField>)> {
+  adts.values().filter(|adt| adt.ctrs.len() == 1).filter_map(|adt| adt.ctrs.first())
+}
+
+/// Finds the constructor of the only record type that has all the given fields.
+fn find_record<'a>(adts: &'a Adts, flds: &[&Name]) -> Result<(&'a Name, &'a [CtrField]), String> {
+  let mut found =
+    records(adts).filter(|(_, fields)| flds.iter().all(|fld| fields.iter().any(|f| f.nam == **fld)));
+  let flds = flds.iter().map(|fld| format!("'{fld}'")).collect::<Vec<_>>().join(", ");
+  match (found.next(), found.next()) {
+    (Some((ctr, fields)), None) => Ok((ctr, fields)),
+    (None, _) => Err(format!("No record type has the fields {flds}.")),
+    (Some((fst, _)), Some((snd, _))) => {
+      Err(format!("Ambiguous record fields {flds}, found in both '{fst}' and '{snd}'."))
+    }
+  }
+}
+
+fn field_var(fld: &Name) -> Name {
+  Name::new(format!("%rec.{fld}"))
+}
+
+/// Matches on the record, binding its fields to `%rec.field` variables.
+fn match_record(rec: Term, ctr: &Name, fields: &[CtrField], body: Term) -> Term {
+  let binds = fields.iter().map(|f| Some(field_var(&f.nam))).collect();
+  Term::Mat { arg: Box::new(rec), bnd: None, with: vec![], arms: vec![(Some(ctr.clone()), binds, body)] }
+}
@@ -10,8 +10,15 @@ impl Book {
 
         let body = encode_ctr(fields.iter().map(|f| &f.nam), ctrs, ctr_name);
 
-        let rules = vec![Rule { pats: vec![], body }];
-        let def = Definition { name: ctr_name.clone(), rules, builtin: adt.builtin };
+        let rules = vec![Rule { pats: vec![], guard: None, body }];
+        let def = Definition {
+          name: ctr_name.clone(),
+          typ: None,
+          rules,
+          builtin: adt.builtin,
+          span: None,
+          doc: None,
+        };
         defs.push((ctr_name.clone(), def));
       }
     }
@@ -19,7 +26,7 @@ impl Book {
   }
 }
 
-fn encode_ctr<'a>(
+pub fn encode_ctr<'a>(
   ctr_args: impl DoubleEndedIterator<Item = &'a Name> + Clone,
   ctrs: Vec<Name>,
   ctr_name: &Name,
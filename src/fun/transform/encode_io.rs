@@ -0,0 +1,22 @@
+use crate::fun::{builtins::MK_IO, Book, Name, Term};
+
+impl Book {
+  /// Converts the `IO` action returned by the entrypoint into the
+  /// format that the HVM runtime reads to perform it when running with `--io`.
+  ///
+  /// Example:
+  /// ```hvm
+  /// main = (IO/print "hi")
+  /// ```
+  /// Becomes:
+  /// ```hvm
+  /// main = (MkIO (IO/print "hi"))
+  /// ```
+  pub fn encode_io(&mut self) {
+    if let Some(entrypoint) = &self.entrypoint {
+      // `apply_args` already left the entrypoint with a single rule.
+      let body = &mut self.defs[entrypoint].rules[0].body;
+      *body = Term::call(Term::Ref { nam: Name::new(MK_IO) }, [std::mem::take(body)]);
+    }
+  }
+}
@@ -1,6 +1,6 @@
 use crate::{
   fun::{Book, MatchRule, Name, Pattern, Tag, Term},
-  maybe_grow,
+  map_values_mut, maybe_grow,
 };
 
 impl Book {
@@ -13,11 +13,11 @@ impl Book {
   ///
   /// Var and pair matches become a let expression.
   pub fn encode_matches(&mut self) {
-    for def in self.defs.values_mut() {
+    map_values_mut(&mut self.defs, |_, def| {
       for rule in &mut def.rules {
         rule.body.encode_matches();
       }
-    }
+    });
   }
 }
 
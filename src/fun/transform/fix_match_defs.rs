@@ -1,5 +1,5 @@
 use crate::{
-  diagnostics::Diagnostics,
+  diagnostics::{Diagnostics, ErrorType},
   fun::{Adts, Constructors, Ctx, Pattern},
 };
 
@@ -30,7 +30,7 @@ impl Ctx<'_> {
       }
 
       for err in errs {
-        self.info.add_rule_error(err, def.name.clone());
+        self.info.add_rule_error(err, ErrorType::InvalidPattern, def.name.clone());
       }
     }
 
@@ -40,7 +40,7 @@ impl Ctx<'_> {
 
 impl Pattern {
   /// If a var pattern actually refers to an ADT constructor, convert it into a constructor pattern.
-  fn resolve_pat(&mut self, ctrs: &Constructors) {
+  pub fn resolve_pat(&mut self, ctrs: &Constructors) {
     if let Pattern::Var(Some(nam)) = self {
       if ctrs.contains_key(nam) {
         *self = Pattern::Ctr(std::mem::take(nam), vec![]);
@@ -52,7 +52,7 @@ impl Pattern {
   }
 
   /// Check that ADT constructor pats are correct, meaning defined in a `data` and with correct arity.
-  fn check_good_ctr(&self, ctrs: &Constructors, adts: &Adts, errs: &mut Vec<String>) {
+  pub fn check_good_ctr(&self, ctrs: &Constructors, adts: &Adts, errs: &mut Vec<String>) {
     if let Pattern::Ctr(nam, args) = self {
       if let Some(adt) = ctrs.get(nam) {
         let expected_arity = adts[adt].ctrs[nam].len();
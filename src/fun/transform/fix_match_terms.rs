@@ -1,7 +1,7 @@
 use crate::{
-  diagnostics::{Diagnostics, WarningType, ERR_INDENT_SIZE},
+  diagnostics::{Diagnostics, ErrorType, WarningType, ERR_INDENT_SIZE},
   fun::{Adts, Constructors, CtrField, Ctx, MatchRule, Name, Num, Term},
-  maybe_grow,
+  map_values_mut, maybe_grow,
 };
 use std::collections::HashMap;
 
@@ -44,24 +44,27 @@ impl Ctx<'_> {
   pub fn fix_match_terms(&mut self) -> Result<(), Diagnostics> {
     self.info.start_pass();
 
-    for def in self.book.defs.values_mut() {
-      for rule in def.rules.iter_mut() {
-        let errs = rule.body.fix_match_terms(&self.book.ctrs, &self.book.adts);
-
-        for err in errs {
-          match err {
-            FixMatchErr::AdtMismatch { .. } | FixMatchErr::NonExhaustiveMatch { .. } => {
-              self.info.add_rule_error(err, def.name.clone())
-            }
-            FixMatchErr::IrrefutableMatch { .. } => {
-              self.info.add_rule_warning(err, WarningType::IrrefutableMatch, def.name.clone())
-            }
-            FixMatchErr::UnreachableMatchArms { .. } => {
-              self.info.add_rule_warning(err, WarningType::UnreachableMatch, def.name.clone())
-            }
-            FixMatchErr::RedundantArm { .. } => {
-              self.info.add_rule_warning(err, WarningType::RedundantMatch, def.name.clone())
-            }
+    let (ctrs, adts) = (&self.book.ctrs, &self.book.adts);
+    let errs = map_values_mut(&mut self.book.defs, |_, def| {
+      def.rules.iter_mut().flat_map(|rule| rule.body.fix_match_terms(ctrs, adts)).collect::<Vec<_>>()
+    });
+    for (def, errs) in self.book.defs.values().zip(errs) {
+      for err in errs {
+        match err {
+          FixMatchErr::AdtMismatch { .. } => {
+            self.info.add_rule_error(err, ErrorType::MatchTypeMismatch, def.name.clone())
+          }
+          FixMatchErr::NonExhaustiveMatch { .. } => {
+            self.info.add_rule_error(err, ErrorType::NonExhaustiveMatch, def.name.clone())
+          }
+          FixMatchErr::IrrefutableMatch { .. } => {
+            self.info.add_rule_warning(err, WarningType::IrrefutableMatch, def.name.clone())
+          }
+          FixMatchErr::UnreachableMatchArms { .. } => {
+            self.info.add_rule_warning(err, WarningType::UnreachableMatch, def.name.clone())
+          }
+          FixMatchErr::RedundantArm { .. } => {
+            self.info.add_rule_warning(err, WarningType::RedundantMatch, def.name.clone())
           }
         }
       }
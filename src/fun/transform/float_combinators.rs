@@ -1,5 +1,5 @@
 use crate::{
-  fun::{Book, Definition, Name, Pattern, Rule, Term},
+  fun::{Book, DefOpt, Definition, Name, Pattern, Rule, Term},
   maybe_grow, multi_iterator,
 };
 use std::collections::{BTreeMap, HashSet};
@@ -28,11 +28,22 @@ impl Book {
   ///
   /// See [`Term::size`] for the measurement of size.
   /// It should more or less correspond to the compiled inet size.
+  ///
+  /// The definitions with a [`FloatMode`] are floated as it says, including the ones generated from them,
+  /// and the ones that disable `float-combinators` with an `#!opt(..)` pragma are not floated.
   pub fn float_combinators(&mut self, max_size: usize) {
     let book = self.clone();
     let mut ctx = FloatCombinatorsCtx::new(&book, max_size);
 
     for (def_name, def) in self.defs.iter_mut() {
+      if !self.def_opts.allows(def_name, DefOpt::FloatCombinators) {
+        continue;
+      }
+      ctx.max_size = match self.float_modes.get(&def_name.def_name_from_generated()) {
+        Some(FloatMode::NoFloat) => continue,
+        Some(FloatMode::Inline) => usize::MAX,
+        None => max_size,
+      };
       let builtin = def.builtin;
       let body = &mut def.rule_mut().body;
       ctx.reset();
@@ -42,6 +53,24 @@ impl Book {
 
     self.defs.extend(ctx.combinators.into_iter().map(|(nam, (_, def))| (nam, def)));
   }
+
+  /// Whether a reference to this definition is safe to keep in the combinators that use it,
+  /// as [`Term::is_safe`] decides before floating them.
+  pub(crate) fn is_safe_ref(&self, def_name: &Name) -> bool {
+    let mut ctx = FloatCombinatorsCtx::new(self, 0);
+    Term::Ref { nam: def_name.clone() }.is_safe(&mut ctx)
+  }
+}
+
+/// How the combinators of a definition are floated, as chosen with a pragma above it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatMode {
+  /// `#!inline`: Only the combinators that are not safe to keep in the definition are floated,
+  /// no matter how large it gets, so that more of its body can be shared.
+  Inline,
+  /// `#!no_float`: No combinators are floated, so the definition is compiled to a single net,
+  /// which may expand forever in strict mode if it has unsafe ones.
+  NoFloat,
 }
 
 struct FloatCombinatorsCtx<'b> {
@@ -107,8 +136,8 @@ impl Term {
     let comb_ref = Term::Ref { nam: comb_name.clone() };
     let extracted_term = std::mem::replace(self, comb_ref);
 
-    let rules = vec![Rule { body: extracted_term, pats: Vec::new() }];
-    let rule = Definition { name: comb_name.clone(), rules, builtin };
+    let rules = vec![Rule { pats: Vec::new(), guard: None, body: extracted_term }];
+    let rule = Definition { name: comb_name.clone(), typ: None, rules, builtin, span: None, doc: None };
     ctx.combinators.insert(comb_name, (is_safe, rule));
   }
 }
@@ -204,12 +233,16 @@ impl Term {
       Term::Era => 0,
       Term::Bend { .. }
       | Term::Fold { .. }
+      | Term::PatMat { .. }
       | Term::Nat { .. }
       | Term::Str { .. }
       | Term::List { .. }
       | Term::Do { .. }
       | Term::Ask { .. }
       | Term::Open { .. }
+      | Term::Def { .. }
+      | Term::Get { .. }
+      | Term::Upd { .. }
       | Term::Err => unreachable!(),
     }
   }
@@ -255,7 +288,15 @@ impl Term {
       | Term::Ref { .. }
       | Term::Era
       | Term::Err => FloatIter::Zero([]),
-      Term::Do { .. } | Term::Ask { .. } | Term::Bend { .. } | Term::Fold { .. } | Term::Open { .. } => {
+      Term::Do { .. }
+      | Term::Ask { .. }
+      | Term::Bend { .. }
+      | Term::Fold { .. }
+      | Term::PatMat { .. }
+      | Term::Open { .. }
+      | Term::Def { .. }
+      | Term::Get { .. }
+      | Term::Upd { .. } => {
         unreachable!()
       }
     }
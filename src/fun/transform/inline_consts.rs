@@ -0,0 +1,112 @@
+use crate::{
+  compile_book,
+  diagnostics::{Diagnostics, DiagnosticsConfig, ErrorType, Severity},
+  fun::{Book, Ctx, Name, Num, Rule, Term},
+  hvm::interpreter::{self, Limits},
+  maybe_grow, readback_hvm_net, CompileOpts,
+};
+use std::collections::HashMap;
+
+impl Ctx<'_> {
+  /// Evaluates the body of each `const` definition, replacing it by the number it reduces to.
+  ///
+  /// Each constant is compiled as the entrypoint of a copy of the program and normalized by
+  /// the interpreter, with the same limits as the pre-reduce pass.
+  /// ```bend
+  /// const Size = (<< 1 20)
+  /// ```
+  /// Becomes:
+  /// ```bend
+  /// const Size = 1048576
+  /// ```
+  /// The constants of a program that fails to compile are left as they are,
+  /// since compiling the program itself reports its errors.
+  pub fn eval_consts(&mut self, opts: &CompileOpts) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    let consts =
+      self.book.defs.keys().filter(|nam| self.book.consts.contains(*nam)).cloned().collect::<Vec<_>>();
+    for nam in consts {
+      match eval_const(self.book, &nam, opts) {
+        Ok(Some(val)) => {
+          self.book.defs[&nam].rules = vec![Rule { pats: vec![], guard: None, body: Term::Num { val } }];
+        }
+        Ok(None) => {}
+        Err(err) => self.info.add_rule_error(err, ErrorType::ConstEval, nam),
+      }
+    }
+
+    self.info.fatal(())
+  }
+
+  /// Replaces the references to the constants evaluated by [`Ctx::eval_consts`] by their values,
+  /// removing their definitions unless one of them is the entrypoint.
+  pub fn inline_consts(&mut self) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    let mut vals = HashMap::new();
+    for def in self.book.defs.values().filter(|def| self.book.consts.contains(&def.name)) {
+      match def.rules.as_slice() {
+        [Rule { body: Term::Num { val }, .. }] => {
+          vals.insert(def.name.clone(), *val);
+        }
+        _ => self.info.add_rule_error(
+          "The constant can't be evaluated apart from the rest of the program.",
+          ErrorType::ConstEval,
+          def.name.clone(),
+        ),
+      }
+    }
+
+    for def in self.book.defs.values_mut() {
+      for rule in def.rules.iter_mut() {
+        rule.body.subst_consts(&vals);
+      }
+    }
+    let entrypoint = self.book.entrypoint.clone();
+    self.book.defs.retain(|nam, _| !vals.contains_key(nam) || entrypoint.as_ref() == Some(nam));
+
+    self.info.fatal(())
+  }
+}
+
+/// Compiles a copy of the program with the constant as its entrypoint and normalizes it,
+/// returning `None` if the program can't be compiled.
+fn eval_const(book: &Book, nam: &Name, opts: &CompileOpts) -> Result<Option<Num>, String> {
+  let mut book = book.clone();
+  // The copy is compiled as a program without constants, which would be evaluated again.
+  book.consts.clear();
+  let opts = CompileOpts { entrypoint: Some(nam.clone()), io: false, cache_dir: None, ..opts.clone() };
+  let limits = Limits {
+    max_rewrites: Some(opts.pre_reduce_rewrite_limit),
+    timeout: None,
+    max_memory: Some(opts.pre_reduce_memory),
+  };
+  let Ok(res) = compile_book(&mut book, opts, DiagnosticsConfig::new(Severity::Allow, false), None) else {
+    return Ok(None);
+  };
+
+  let (net, stats) = interpreter::normalize_with_stats(&res.core_book, limits)?;
+  if let Some(limit) = stats.stopped {
+    return Err(format!("The constant didn't reduce to a number before reaching {limit}."));
+  }
+  match readback_hvm_net(&net, &book, &res.labels, false, false) {
+    (Term::Num { val }, _) => Ok(Some(val)),
+    (term, _) => Err(format!("The constant must reduce to a number, but it reduced to '{term}'.")),
+  }
+}
+
+impl Term {
+  fn subst_consts(&mut self, vals: &HashMap<Name, Num>) {
+    maybe_grow(|| {
+      if let Term::Ref { nam } = self
+        && let Some(val) = vals.get(nam)
+      {
+        *self = Term::Num { val: *val };
+      }
+      for child in self.children_mut() {
+        child.subst_consts(vals);
+      }
+    })
+  }
+}
@@ -0,0 +1,137 @@
+use crate::{
+  diagnostics::{Diagnostics, ErrorType},
+  fun::{Ctx, Definition, Name, Pattern, Term},
+  maybe_grow,
+};
+use std::collections::{BTreeSet, HashSet};
+
+const NEW_FN_SEP: &str = "__local";
+
+impl Ctx<'_> {
+  /// Lifts the local functions to new top-level definitions.
+  ///
+  /// The variables that a local function captures from its surroundings become
+  /// its first arguments, and the local name is bound to the partial application
+  /// of the new definition, both where it's visible and in its own rules.
+  ///
+  /// Example:
+  /// ```hvm
+  /// main = let x = 1; def add y = (+ x y); (add 2)
+  /// ```
+  /// Becomes:
+  /// ```hvm
+  /// main = let x = 1; use add = (main__local0_add x); (add 2)
+  /// main__local0_add x y = use add = (main__local0_add x); (+ x y)
+  /// ```
+  pub fn lift_local_defs(&mut self) -> Result<(), Diagnostics> {
+    self.info.start_pass();
+
+    let globals = self.book.defs.keys().chain(self.book.ctrs.keys()).cloned().collect::<HashSet<_>>();
+    let mut new_defs = vec![];
+    for def in self.book.defs.values_mut() {
+      let mut fresh = 0;
+      for rule in def.rules.iter_mut() {
+        let mut scope = rule.pats.iter().flat_map(|pat| pat.binds().flatten().cloned()).collect();
+        let res = rule.guard.iter_mut().chain([&mut rule.body]).try_for_each(|term| {
+          term.lift_local_defs(&def.name, &globals, &mut scope, &mut fresh, &mut new_defs)
+        });
+        if let Err(err) = res {
+          self.info.add_rule_error(err, ErrorType::UnscopedExtraction, def.name.clone());
+          break;
+        }
+      }
+    }
+
+    for def in new_defs {
+      self.book.defs.insert(def.name.clone(), def);
+    }
+
+    self.info.fatal(())
+  }
+}
+
+impl Term {
+  /// `scope` has the variables bound around the term, which take precedence over the global names.
+  fn lift_local_defs(
+    &mut self,
+    def_name: &Name,
+    globals: &HashSet<Name>,
+    scope: &mut Vec<Name>,
+    fresh: &mut usize,
+    new_defs: &mut Vec<Definition>,
+  ) -> Result<(), String> {
+    maybe_grow(|| {
+      let Term::Def { nam, rules, nxt } = self else {
+        for (child, binds) in self.children_mut_with_binds() {
+          let len = scope.len();
+          scope.extend(binds.flatten().cloned());
+          child.lift_local_defs(def_name, globals, scope, fresh, new_defs)?;
+          scope.truncate(len);
+        }
+        return Ok(());
+      };
+
+      // Lift the local functions inside this one first, so that what they capture is also captured by it.
+      for rule in rules.iter_mut() {
+        let len = scope.len();
+        scope.push(nam.clone());
+        scope.extend(rule.pats.iter().flat_map(|pat| pat.binds().flatten().cloned()));
+        for child in rule.guard.iter_mut().chain([&mut rule.body]) {
+          child.lift_local_defs(def_name, globals, scope, fresh, new_defs)?;
+        }
+        scope.truncate(len);
+      }
+
+      // Can't have unmatched unscoped because this'll be extracted
+      if rules.iter().any(|rule| rule.guard.iter().chain([&rule.body]).any(Term::has_unscoped_diff)) {
+        return Err(format!(
+          "Can't have non self-contained unscoped variables in the local function '{nam}'"
+        ));
+      }
+
+      // Gather the captured variables.
+      // Free variables that aren't global names are assumed to come from the surroundings,
+      // since some binds, like the fields of a match, are only known in later passes.
+      let mut captured = BTreeSet::new();
+      for rule in rules.iter() {
+        let binds = rule.pats.iter().flat_map(|pat| pat.binds().flatten()).collect::<HashSet<_>>();
+        for child in rule.guard.iter().chain([&rule.body]) {
+          for var in child.free_vars().into_keys() {
+            if var != *nam && !binds.contains(&var) && (scope.contains(&var) || !globals.contains(&var)) {
+              captured.insert(var);
+            }
+          }
+        }
+      }
+
+      let new_nam = Name::new(format!("{}{}{}_{}", def_name, NEW_FN_SEP, fresh, nam));
+      *fresh += 1;
+      let call =
+        Term::call(Term::Ref { nam: new_nam.clone() }, captured.iter().map(|v| Term::Var { nam: v.clone() }));
+
+      // Create the new function, where recursive calls also pass the captured variables.
+      let mut rules = std::mem::take(rules);
+      for rule in rules.iter_mut() {
+        if !rule.pats.iter().any(|pat| pat.binds().flatten().any(|bind| bind == nam)) {
+          let use_call = |term: &mut Term| {
+            let nxt = Box::new(std::mem::take(term));
+            *term = Term::Use { nam: Some(nam.clone()), val: Box::new(call.clone()), nxt };
+          };
+          rule.guard.iter_mut().for_each(use_call);
+          use_call(&mut rule.body);
+        }
+        rule.pats.splice(0 .. 0, captured.iter().map(|var| Pattern::Var(Some(var.clone()))));
+      }
+      new_defs.push(Definition { name: new_nam, typ: None, rules, builtin: false, span: None, doc: None });
+
+      // Replace the definition by its partial application in the rest of the term.
+      let nam = std::mem::take(nam);
+      let mut nxt = std::mem::take(nxt.as_mut());
+      scope.push(nam.clone());
+      let res = nxt.lift_local_defs(def_name, globals, scope, fresh, new_defs);
+      scope.pop();
+      *self = Term::Use { nam: Some(nam), val: Box::new(call), nxt: Box::new(nxt) };
+      res
+    })
+  }
+}
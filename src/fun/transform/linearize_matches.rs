@@ -1,6 +1,6 @@
 use crate::{
-  fun::{Book, Name, Pattern, Term},
-  maybe_grow,
+  fun::{Book, DefOpt, Name, Pattern, Term},
+  map_values_mut, maybe_grow,
 };
 use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 
@@ -24,11 +24,14 @@ impl Book {
   /// }
   /// ```
   pub fn linearize_match_binds(&mut self) {
-    for def in self.defs.values_mut() {
+    map_values_mut(&mut self.defs, |_, def| {
+      if !self.def_opts.allows(&def.name, DefOpt::LinearizeMatches) {
+        return;
+      }
       for rule in def.rules.iter_mut() {
         rule.body.linearize_match_binds();
       }
-    }
+    });
   }
 }
 
@@ -320,11 +323,14 @@ fn binds_fixed_by_dependency(used_in_arg: HashSet<Name>, bind_terms: &[Term]) ->
 impl Book {
   /// Linearizes all variables used in a matches' arms.
   pub fn linearize_matches(&mut self) {
-    for def in self.defs.values_mut() {
+    map_values_mut(&mut self.defs, |_, def| {
+      if !self.def_opts.allows(&def.name, DefOpt::LinearizeMatches) {
+        return;
+      }
       for rule in def.rules.iter_mut() {
         rule.body.linearize_matches();
       }
-    }
+    });
   }
 }
 
@@ -423,11 +429,11 @@ fn get_match_reference(mut match_term: &mut Term) -> &mut Term {
 impl Book {
   /// Linearizes all variables specified in the `with` clauses of match terms.
   pub fn linearize_match_with(&mut self) {
-    for def in self.defs.values_mut() {
+    map_values_mut(&mut self.defs, |_, def| {
       for rule in def.rules.iter_mut() {
         rule.body.linearize_match_with();
       }
-    }
+    });
   }
 }
 
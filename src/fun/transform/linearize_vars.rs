@@ -1,6 +1,6 @@
 use crate::{
   fun::{Book, FanKind, Name, Pattern, Tag, Term},
-  maybe_grow,
+  map_values_mut, maybe_grow,
 };
 use std::collections::HashMap;
 
@@ -18,9 +18,7 @@ use std::collections::HashMap;
 /// Precondition: All variables are bound and have unique names within each definition.
 impl Book {
   pub fn linearize_vars(&mut self) {
-    for def in self.defs.values_mut() {
-      def.rule_mut().body.linearize_vars();
-    }
+    map_values_mut(&mut self.defs, |_, def| def.rule_mut().body.linearize_vars());
   }
 }
 
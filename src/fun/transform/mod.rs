@@ -1,21 +1,32 @@
 pub mod apply_args;
 pub mod apply_use;
+pub mod const_fold;
 pub mod definition_merge;
 pub mod definition_pruning;
 pub mod desugar_bend;
 pub mod desugar_do_blocks;
 pub mod desugar_fold;
+pub mod desugar_let_ctrs;
 pub mod desugar_match_defs;
+pub mod desugar_nested_matches;
 pub mod desugar_open;
+pub mod desugar_records;
 pub mod encode_adts;
+pub mod encode_io;
 pub mod encode_match_terms;
 pub mod expand_generated;
 pub mod fix_match_defs;
 pub mod fix_match_terms;
 pub mod float_combinators;
+pub mod inline_consts;
+pub mod lift_local_defs;
 pub mod linearize_matches;
 pub mod linearize_vars;
+pub mod qualify_names;
 pub mod resolve_refs;
+pub mod resugar_adts;
 pub mod resugar_list;
 pub mod resugar_string;
+pub mod resugar_tuple;
+pub mod share_common_terms;
 pub mod unique_names;
@@ -0,0 +1,180 @@
+use crate::{
+  fun::{Book, Name, Pattern, Term, Type},
+  maybe_grow,
+};
+use std::collections::HashMap;
+
+impl Book {
+  /// Prefixes the names of all the definitions, datatypes and constructors of the book with `prefix/`,
+  /// updating the references to them.
+  ///
+  /// Used to place the contents of an imported file in its own namespace.
+  ///
+  /// Precondition: Refs are encoded as vars.
+  pub fn qualify_names(&mut self, prefix: &Name) {
+    let qualify = |nam: &Name| Name::new(format!("{prefix}/{nam}"));
+
+    let ctrs = self.ctrs.keys().map(|ctr| (ctr.clone(), qualify(ctr))).collect::<HashMap<_, _>>();
+    let adts = self.adts.keys().map(|adt| (adt.clone(), qualify(adt))).collect::<HashMap<_, _>>();
+    let mut names = ctrs.clone();
+    for nam in self.defs.keys().chain(self.adts.keys()) {
+      names.insert(nam.clone(), qualify(nam));
+    }
+
+    self.defs = std::mem::take(&mut self.defs)
+      .into_iter()
+      .map(|(nam, mut def)| {
+        def.name = names[&nam].clone();
+        if let Some(typ) = &mut def.typ {
+          typ.qualify_names(&adts);
+        }
+        for rule in def.rules.iter_mut() {
+          let mut scope = HashMap::new();
+          for pat in rule.pats.iter_mut() {
+            pat.qualify_names(&ctrs);
+            for bind in pat.binds() {
+              push_scope(bind, &mut scope);
+            }
+          }
+          rule.body.qualify_names(&names, &ctrs, &mut scope);
+        }
+        (def.name.clone(), def)
+      })
+      .collect();
+    self.consts = self.consts.iter().map(|nam| names[nam].clone()).collect();
+
+    self.adts = std::mem::take(&mut self.adts)
+      .into_iter()
+      .map(|(nam, mut adt)| {
+        adt.ctrs = std::mem::take(&mut adt.ctrs)
+          .into_iter()
+          .map(|(ctr, fields)| (names[&ctr].clone(), fields))
+          .collect();
+        (names[&nam].clone(), adt)
+      })
+      .collect();
+
+    self.ctrs = std::mem::take(&mut self.ctrs)
+      .into_iter()
+      .map(|(ctr, adt)| (names[&ctr].clone(), names[&adt].clone()))
+      .collect();
+  }
+}
+
+impl Term {
+  fn qualify_names(
+    &mut self,
+    names: &HashMap<Name, Name>,
+    ctrs: &HashMap<Name, Name>,
+    scope: &mut HashMap<Name, usize>,
+  ) {
+    maybe_grow(|| {
+      match self {
+        Term::Var { nam } if !scope.get(nam).is_some_and(|n| *n > 0) => {
+          if let Some(new) = names.get(nam) {
+            *nam = new.clone();
+          }
+        }
+        Term::Mat { arms, .. } | Term::Fold { arms, .. } => {
+          for (ctr, _, _) in arms {
+            if let Some(new) = ctr.as_ref().and_then(|ctr| ctrs.get(ctr)) {
+              *ctr = Some(new.clone());
+            }
+          }
+        }
+        Term::PatMat { arms, .. } => {
+          for (pat, _, _) in arms {
+            pat.qualify_names(ctrs);
+          }
+        }
+        Term::Open { typ, .. } | Term::Do { typ, .. } => {
+          if let Some(new) = names.get(typ) {
+            *typ = new.clone();
+          }
+        }
+        // The local function is in scope in its own rules and in the rest of the term.
+        Term::Def { nam, rules, nxt } => {
+          *scope.entry(nam.clone()).or_default() += 1;
+          for rule in rules {
+            let binds = rule.pats.iter().flat_map(|pat| pat.binds().flatten().cloned()).collect::<Vec<_>>();
+            for pat in &mut rule.pats {
+              pat.qualify_names(ctrs);
+            }
+            for bind in &binds {
+              *scope.entry(bind.clone()).or_default() += 1;
+            }
+            for child in rule.guard.iter_mut().chain([&mut rule.body]) {
+              child.qualify_names(names, ctrs, scope);
+            }
+            for bind in &binds {
+              *scope.entry(bind.clone()).or_default() -= 1;
+            }
+          }
+          nxt.qualify_names(names, ctrs, scope);
+          *scope.entry(nam.clone()).or_default() -= 1;
+          return;
+        }
+        _ => {
+          if let Some(pat) = self.pattern_mut() {
+            pat.qualify_names(ctrs);
+          }
+        }
+      }
+
+      for (child, binds) in self.children_mut_with_binds() {
+        let binds = binds.flatten().cloned().collect::<Vec<_>>();
+        for bind in &binds {
+          *scope.entry(bind.clone()).or_default() += 1;
+        }
+        child.qualify_names(names, ctrs, scope);
+        for bind in &binds {
+          *scope.entry(bind.clone()).or_default() -= 1;
+        }
+      }
+    })
+  }
+}
+
+impl Pattern {
+  /// Qualifies the constructors in the pattern.
+  ///
+  /// Variable patterns are also qualified if they have the name of a constructor,
+  /// since they're only later converted into constructor patterns.
+  fn qualify_names(&mut self, ctrs: &HashMap<Name, Name>) {
+    maybe_grow(|| {
+      match self {
+        Pattern::Ctr(nam, _) | Pattern::Var(Some(nam)) => {
+          if let Some(new) = ctrs.get(nam) {
+            *nam = new.clone();
+          }
+        }
+        _ => (),
+      }
+      for child in self.children_mut() {
+        child.qualify_names(ctrs);
+      }
+    })
+  }
+}
+
+impl Type {
+  /// Qualifies the datatypes in the type.
+  fn qualify_names(&mut self, adts: &HashMap<Name, Name>) {
+    maybe_grow(|| {
+      if let Type::Var(nam) | Type::Ctr(nam, _) = self
+        && let Some(new) = adts.get(nam)
+      {
+        *nam = new.clone();
+      }
+      for child in self.children_mut() {
+        child.qualify_names(adts);
+      }
+    })
+  }
+}
+
+fn push_scope(bind: &Option<Name>, scope: &mut HashMap<Name, usize>) {
+  if let Some(bind) = bind {
+    *scope.entry(bind.clone()).or_default() += 1;
+  }
+}
@@ -1,5 +1,5 @@
 use crate::{
-  diagnostics::Diagnostics,
+  diagnostics::{Diagnostics, ErrorType},
   fun::{Ctx, Name, Pattern, Term},
   maybe_grow,
 };
@@ -29,8 +29,12 @@ impl Ctx<'_> {
           push_scope(name.as_ref(), &mut scope);
         }
 
+        if let Some(guard) = &mut rule.guard {
+          let res = guard.resolve_refs(&def_names, self.book.entrypoint.as_ref(), &mut scope);
+          self.info.take_rule_err(res, ErrorType::Entrypoint, def_name.clone());
+        }
         let res = rule.body.resolve_refs(&def_names, self.book.entrypoint.as_ref(), &mut scope);
-        self.info.take_rule_err(res, def_name.clone());
+        self.info.take_rule_err(res, ErrorType::Entrypoint, def_name.clone());
       }
     }
 
@@ -0,0 +1,73 @@
+use crate::{
+  fun::{Adt, Book, Name, Pattern, Tag, Term},
+  maybe_grow,
+};
+
+impl Term {
+  /// Converts the Scott-encoded constructors of the datatypes declared by the program
+  /// back to calls of their constructors.
+  ///
+  /// Example:
+  /// ```bend
+  /// data Tree = (Node left right) | (Leaf val)
+  ///
+  /// λ* λa (a 1)
+  /// // Transforms to:
+  /// (Tree/Leaf 1)
+  /// ```
+  ///
+  /// The builtin datatypes are left to the other resugarings, like lists and strings.
+  pub fn resugar_adts(&mut self, book: &Book) {
+    maybe_grow(|| {
+      if let Some((ctr, arity)) = scott_ctr(self, book.adts.values().filter(|adt| !adt.builtin)) {
+        let mut bod = std::mem::take(self);
+        for _ in 0 .. arity {
+          let Term::Lam { bod: nxt, .. } = &mut bod else { unreachable!() };
+          bod = std::mem::take(nxt.as_mut());
+        }
+        let mut head = &mut bod;
+        while let Term::App { fun, .. } = head {
+          head = fun;
+        }
+        *head = Term::Ref { nam: ctr.clone() };
+        *self = bod;
+      }
+
+      for child in self.children_mut() {
+        child.resugar_adts(book);
+      }
+    })
+  }
+}
+
+/// Matches a term with the Scott encoding of a constructor, `λc1 .. λcn (ci field1 .. fieldk)`,
+/// returning the constructor and the number `n` of constructors of its datatype.
+///
+/// Since the encoding doesn't say which datatype the value is from, it's taken to be the first
+/// of `adts` with `n` constructors whose `i`-th constructor has `k` fields.
+pub(crate) fn scott_ctr<'a>(
+  term: &Term,
+  adts: impl IntoIterator<Item = &'a Adt>,
+) -> Option<(&'a Name, usize)> {
+  let mut binds = vec![];
+  let mut bod = term;
+  while let Term::Lam { tag: Tag::Static, pat: box Pattern::Var(bind), bod: nxt } = bod {
+    binds.push(bind);
+    bod = nxt;
+  }
+  let mut fields = 0;
+  while let Term::App { tag: Tag::Static, fun, .. } = bod {
+    fields += 1;
+    bod = fun;
+  }
+  let Term::Var { nam } = bod else { return None };
+  let idx = binds.iter().position(|bind| bind.as_ref() == Some(nam))?;
+  if binds.iter().enumerate().any(|(i, bind)| i != idx && bind.is_some()) {
+    return None;
+  }
+
+  adts.into_iter().find_map(|adt| {
+    let (ctr, ctr_fields) = adt.ctrs.get_index(idx)?;
+    (adt.ctrs.len() == binds.len() && ctr_fields.len() == fields).then_some((ctr, binds.len()))
+  })
+}
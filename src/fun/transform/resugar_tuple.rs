@@ -0,0 +1,29 @@
+use crate::{
+  fun::{FanKind, Term},
+  maybe_grow,
+};
+
+impl Term {
+  /// Converts nested pairs back into the tuples that they encode.
+  ///
+  /// Tuples with more than two elements are compiled to pairs nested to the right,
+  /// so `(a, (b, c))` is read back as `(a, b, c)`.
+  pub fn resugar_tuples(&mut self) {
+    maybe_grow(|| {
+      for child in self.children_mut() {
+        child.resugar_tuples();
+      }
+
+      // The children were already resugared, so the last element is flattened already.
+      if let Term::Fan { fan: FanKind::Tup, tag, els } = self
+        && let Some(Term::Fan { fan: FanKind::Tup, tag: last_tag, .. }) = els.last()
+        && last_tag == tag
+      {
+        let Some(Term::Fan { els: last_els, .. }) = els.last_mut() else { unreachable!() };
+        let last_els = std::mem::take(last_els);
+        els.pop();
+        els.extend(last_els);
+      }
+    })
+  }
+}
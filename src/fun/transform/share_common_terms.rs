@@ -0,0 +1,91 @@
+use crate::{
+  fun::{Book, Constructors, DefOpt, FanKind, Name, Pattern, Tag, Term},
+  map_values_mut, maybe_grow,
+};
+use indexmap::IndexMap;
+
+impl Book {
+  /// Shares the data terms that appear more than once in a definition,
+  /// binding them once with a `let` at the start of its body and using the bound variable instead.
+  ///
+  /// Only closed terms made of numbers, operations, tuples and constructor calls are shared,
+  /// since duplicating them is always safe and evaluating them early can't loop.
+  ///
+  /// Example:
+  /// ```bend
+  /// λx (x (+ 1 2) [1, 2] (+ 1 2) [1, 2])
+  ///
+  /// // Transforms to:
+  /// λx let %shared0 = (+ 1 2); let %shared1 = [1, 2]; (x %shared0 %shared1 %shared0 %shared1)
+  /// ```
+  pub fn share_common_terms(&mut self) {
+    map_values_mut(&mut self.defs, |_, def| {
+      if !self.def_opts.allows(&def.name, DefOpt::ShareCommonTerms) {
+        return;
+      }
+      for rule in def.rules.iter_mut() {
+        rule.body.share_common_terms(&self.ctrs);
+      }
+    });
+  }
+}
+
+impl Term {
+  fn share_common_terms(&mut self, ctrs: &Constructors) {
+    let mut counts = IndexMap::new();
+    self.count_data_terms(ctrs, &mut counts);
+    let shared = counts
+      .into_iter()
+      .filter(|(_, count)| *count > 1)
+      .enumerate()
+      .map(|(i, (term, _))| (term.clone(), Name::new(format!("%shared{i}"))))
+      .collect::<IndexMap<_, _>>();
+    if shared.is_empty() {
+      return;
+    }
+
+    let mut body = &mut *self;
+    while let Term::Lam { bod, .. } = body {
+      body = bod;
+    }
+    body.replace_shared_terms(&shared);
+    for (val, nam) in shared.into_iter().rev() {
+      let nxt = std::mem::take(body);
+      *body = Term::Let { pat: Box::new(Pattern::Var(Some(nam))), val: Box::new(val), nxt: Box::new(nxt) };
+    }
+  }
+
+  /// Counts the occurrences of the biggest data terms that are not just a number or a reference.
+  /// Returns whether this term is a data term.
+  fn count_data_terms<'a>(&'a self, ctrs: &Constructors, counts: &mut IndexMap<&'a Term, usize>) -> bool {
+    maybe_grow(|| {
+      let children =
+        self.children().map(|child| (child, child.count_data_terms(ctrs, counts))).collect::<Vec<_>>();
+      let is_data = match self {
+        Term::Num { .. } | Term::Era | Term::Oper { .. } => true,
+        Term::App { tag: Tag::Static, .. } | Term::Fan { fan: FanKind::Tup, tag: Tag::Static, .. } => true,
+        Term::Ref { nam } => ctrs.contains_key(nam),
+        _ => false,
+      } && children.iter().all(|(_, is_data)| *is_data);
+
+      if !is_data {
+        for (child, is_data) in children {
+          if is_data && child.children().next().is_some() {
+            *counts.entry(child).or_default() += 1;
+          }
+        }
+      }
+      is_data
+    })
+  }
+
+  fn replace_shared_terms(&mut self, shared: &IndexMap<Term, Name>) {
+    maybe_grow(|| {
+      if let Some(nam) = shared.get(&*self) {
+        *self = Term::Var { nam: nam.clone() };
+      } else {
+        self.children_mut().for_each(|child| child.replace_shared_terms(shared));
+      }
+    })
+  }
+}
@@ -2,7 +2,7 @@
 
 use crate::{
   fun::{Book, Name, Term},
-  maybe_grow,
+  map_values_mut, maybe_grow,
 };
 use std::collections::HashMap;
 
@@ -11,9 +11,7 @@ impl Book {
   /// Skips unbound variables.
   /// Precondition: Definition references have been resolved.
   pub fn make_var_names_unique(&mut self) {
-    for def in self.defs.values_mut() {
-      def.rule_mut().body.make_var_names_unique();
-    }
+    map_values_mut(&mut self.defs, |_, def| def.rule_mut().body.make_var_names_unique());
   }
 }
 
@@ -0,0 +1,212 @@
+//! A compact binary encoding of compiled books, written by `bend gen-hvm --format bin`,
+//! so that running a program again doesn't need to parse, desugar and compile it.
+//!
+//! The file starts with [`MAGIC`] and the [`VERSION`] of the encoding, followed by the nets.
+//! Numbers are written as LEB128 varints and names as their length followed by their UTF-8 bytes.
+
+use crate::maybe_grow;
+use hvmc::ast::{Book, Net, Tree};
+
+/// The bytes that every binary book starts with.
+pub const MAGIC: &[u8; 4] = b"HVMB";
+
+/// The version of the encoding, increased whenever it changes in an incompatible way.
+pub const VERSION: u32 = 1;
+
+const ERA: u8 = 0;
+const VAR: u8 = 1;
+const REF: u8 = 2;
+const NUM: u8 = 3;
+const CTR: u8 = 4;
+const OPR: u8 = 5;
+const MAT: u8 = 6;
+
+/// Whether the contents of a file are a binary book, instead of a book in the text format.
+pub fn is_binary_book(bytes: &[u8]) -> bool {
+  bytes.starts_with(MAGIC)
+}
+
+/// Encodes a compiled book, with the header of the current version.
+pub fn book_to_bytes(book: &Book) -> Vec<u8> {
+  let mut out = MAGIC.to_vec();
+  write_uint(&mut out, VERSION as u64);
+  write_uint(&mut out, book.len() as u64);
+  for (name, net) in book.iter() {
+    write_str(&mut out, name);
+    write_tree(&mut out, &net.root);
+    write_uint(&mut out, net.redexes.len() as u64);
+    for (pri, a, b) in &net.redexes {
+      out.push(*pri as u8);
+      write_tree(&mut out, a);
+      write_tree(&mut out, b);
+    }
+  }
+  out
+}
+
+/// Decodes a book written by [`book_to_bytes`], failing if it was written with another version of the encoding.
+pub fn book_from_bytes(bytes: &[u8]) -> Result<Book, String> {
+  let Some(bytes) = bytes.strip_prefix(MAGIC) else {
+    return Err("The file is not a compiled hvm book in the binary format.".to_string());
+  };
+  let mut reader = Reader { bytes, pos: 0 };
+  let corrupted = |_| "The compiled hvm book is truncated or corrupted.".to_string();
+  let version = reader.uint().map_err(corrupted)?;
+  if version != VERSION as u64 {
+    return Err(format!(
+      "The compiled hvm book uses version {version} of the binary format, but this version of bend reads version {VERSION}. Compile the program again with 'bend gen-hvm --format bin'."
+    ));
+  }
+  reader.book().map_err(corrupted)
+}
+
+fn write_uint(out: &mut Vec<u8>, mut val: u64) {
+  while val >= 0x80 {
+    out.push((val as u8) | 0x80);
+    val >>= 7;
+  }
+  out.push(val as u8);
+}
+
+fn write_str(out: &mut Vec<u8>, str: &str) {
+  write_uint(out, str.len() as u64);
+  out.extend_from_slice(str.as_bytes());
+}
+
+fn write_tree(out: &mut Vec<u8>, tree: &Tree) {
+  maybe_grow(|| match tree {
+    Tree::Era => out.push(ERA),
+    Tree::Var { nam } => {
+      out.push(VAR);
+      write_str(out, nam);
+    }
+    Tree::Ref { nam } => {
+      out.push(REF);
+      write_str(out, nam);
+    }
+    Tree::Num { val } => {
+      out.push(NUM);
+      write_uint(out, *val as u64);
+    }
+    Tree::Ctr { lab, ports } => {
+      out.push(CTR);
+      write_uint(out, *lab as u64);
+      write_uint(out, ports.len() as u64);
+      ports.iter().for_each(|port| write_tree(out, port));
+    }
+    Tree::Op { fst, snd } => {
+      out.push(OPR);
+      write_tree(out, fst);
+      write_tree(out, snd);
+    }
+    Tree::Mat { zero, succ, out: ret } => {
+      out.push(MAT);
+      write_tree(out, zero);
+      write_tree(out, succ);
+      write_tree(out, ret);
+    }
+  })
+}
+
+/// The error of a [`Reader`], which is the same for any malformed input.
+struct Corrupted;
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+}
+
+impl Reader<'_> {
+  fn book(&mut self) -> Result<Book, Corrupted> {
+    let mut book = Book::default();
+    for _ in 0 .. self.uint()? {
+      let name = self.str()?;
+      let root = self.tree()?;
+      let mut redexes = vec![];
+      for _ in 0 .. self.uint()? {
+        let pri = self.byte()? != 0;
+        redexes.push((pri, self.tree()?, self.tree()?));
+      }
+      book.insert(name, Net { root, redexes });
+    }
+    if self.pos != self.bytes.len() {
+      return Err(Corrupted);
+    }
+    Ok(book)
+  }
+
+  fn tree(&mut self) -> Result<Tree, Corrupted> {
+    maybe_grow(|| match self.byte()? {
+      ERA => Ok(Tree::Era),
+      VAR => Ok(Tree::Var { nam: self.str()? }),
+      REF => Ok(Tree::Ref { nam: self.str()? }),
+      NUM => Ok(Tree::Num { val: self.uint()?.try_into().map_err(|_| Corrupted)? }),
+      CTR => {
+        let lab = self.uint()?.try_into().map_err(|_| Corrupted)?;
+        let ports = (0 .. self.uint()?).map(|_| self.tree()).collect::<Result<_, _>>()?;
+        Ok(Tree::Ctr { lab, ports })
+      }
+      OPR => Ok(Tree::Op { fst: Box::new(self.tree()?), snd: Box::new(self.tree()?) }),
+      MAT => {
+        let zero = Box::new(self.tree()?);
+        let succ = Box::new(self.tree()?);
+        let out = Box::new(self.tree()?);
+        Ok(Tree::Mat { zero, succ, out })
+      }
+      _ => Err(Corrupted),
+    })
+  }
+
+  fn byte(&mut self) -> Result<u8, Corrupted> {
+    let byte = *self.bytes.get(self.pos).ok_or(Corrupted)?;
+    self.pos += 1;
+    Ok(byte)
+  }
+
+  fn uint(&mut self) -> Result<u64, Corrupted> {
+    let mut val = 0;
+    for shift in (0 .. 64).step_by(7) {
+      let byte = self.byte()?;
+      val |= ((byte & 0x7F) as u64) << shift;
+      if byte & 0x80 == 0 {
+        return Ok(val);
+      }
+    }
+    Err(Corrupted)
+  }
+
+  fn str(&mut self) -> Result<String, Corrupted> {
+    let len = usize::try_from(self.uint()?).map_err(|_| Corrupted)?;
+    let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len()).ok_or(Corrupted)?;
+    let str = std::str::from_utf8(&self.bytes[self.pos .. end]).map_err(|_| Corrupted)?;
+    self.pos = end;
+    Ok(str.to_string())
+  }
+}
+
+#[test]
+fn binary_book() {
+  use crate::compiler::{Compiler, Runtime};
+
+  let code = "
+data Tree = (Node left right) | (Leaf val)
+sum (Tree/Node l r) = (+ (sum l) (sum r))
+sum (Tree/Leaf v) = v
+main = (sum (Tree/Node (Tree/Leaf 20) (Tree/Node (Tree/Leaf 19) (Tree/Leaf 3))))
+";
+  let compiled = Compiler::new().load_source("binary", code).compile().unwrap().core_book;
+  let bytes = book_to_bytes(&compiled);
+  assert!(is_binary_book(&bytes));
+  assert!(bytes.len() < compiled.to_string().len());
+  let book = book_from_bytes(&bytes).unwrap();
+  assert_eq!(book.to_string(), compiled.to_string());
+
+  let res = Compiler::new().hvm_book(book).runtime(Runtime::Interpreter).run().unwrap();
+  assert_eq!(res.term.unwrap().to_string(), "42");
+
+  let mut old = MAGIC.to_vec();
+  old.push(0);
+  assert!(book_from_bytes(&old).unwrap_err().contains("version 0 of the binary format"));
+  assert!(book_from_bytes(&bytes[.. bytes.len() - 1]).unwrap_err().contains("truncated"));
+  assert!(book_from_bytes(compiled.to_string().as_bytes()).is_err());
+}
@@ -1,4 +1,7 @@
-use crate::{diagnostics::Diagnostics, fun::Name};
+use crate::{
+  diagnostics::{Diagnostics, ErrorType},
+  fun::Name,
+};
 use hvmc::ast::Book;
 
 pub const MAX_NET_SIZE: usize = 32;
@@ -11,6 +14,7 @@ pub fn check_net_sizes(book: &Book, diagnostics: &mut Diagnostics) -> Result<(),
     if nodes > MAX_NET_SIZE {
       diagnostics.add_rule_error(
         format!("Definition is too large for hvm (size={nodes}, max size={MAX_NET_SIZE}). Please break it into smaller pieces."),
+        ErrorType::NetTooLarge,
         Name::new(name),
       );
     }
@@ -0,0 +1,134 @@
+use super::check_net_size::count_nodes;
+use crate::{
+  fun::{num_to_name, DefOpt, DefOpts, Name},
+  maybe_grow,
+};
+use hvmc::ast::{Book, Net, Tree};
+use std::collections::{HashMap, HashSet};
+
+/// Inlines the definitions whose nets have at most `threshold` nodes and no redexes
+/// into the nets that reference them, saving the expansion of the reference at runtime.
+///
+/// Recursive definitions are never inlined, since that would never end,
+/// and neither are the ones that disable `inline` with an `#!opt(..)` pragma.
+///
+/// Returns an error if there's a cycle of definitions that are only references to each other,
+/// like `A = B` and `B = A`.
+pub fn inline(book: &mut Book, threshold: usize, def_opts: &DefOpts) -> Result<(), String> {
+  check_ref_cycles(book)?;
+
+  let inlinees = book
+    .nets
+    .iter()
+    .filter(|(nam, net)| net.redexes.is_empty() && count_nodes(net) <= threshold && !is_recursive(book, nam))
+    .filter(|(nam, _)| def_opts.allows(&Name::new(*nam), DefOpt::Inline))
+    .map(|(nam, net)| (nam.clone(), net.root.clone()))
+    .collect::<HashMap<_, _>>();
+
+  let mut copies = 0;
+  for net in book.nets.values_mut() {
+    let mut inlined = false;
+    for tree in net.trees_mut() {
+      inlined |= inline_into(tree, &inlinees, &mut copies);
+    }
+    if inlined {
+      rename_vars(net);
+    }
+  }
+
+  Ok(())
+}
+
+/// Replaces the references to the inlined definitions with a copy of their nets.
+fn inline_into(tree: &mut Tree, inlinees: &HashMap<String, Tree>, copies: &mut usize) -> bool {
+  maybe_grow(|| {
+    if let Tree::Ref { nam } = tree {
+      let Some(inlinee) = inlinees.get(nam) else { return false };
+      // The variables of each copy get a prefix that can't clash with the ones of the net.
+      let mut copy = inlinee.clone();
+      prefix_vars(&mut copy, &format!("{copies}_"));
+      *copies += 1;
+      // The inlined net can itself reference other inlined definitions.
+      inline_into(&mut copy, inlinees, copies);
+      *tree = copy;
+      true
+    } else {
+      tree.children_mut().fold(false, |inlined, child| inline_into(child, inlinees, copies) | inlined)
+    }
+  })
+}
+
+/// Whether the definition can reach itself by following the references of the nets.
+fn is_recursive(book: &Book, nam: &str) -> bool {
+  let mut visited = HashSet::new();
+  let mut stack = vec![nam];
+  while let Some(cur) = stack.pop() {
+    let Some(net) = book.nets.get(cur) else { continue };
+    for dep in net.trees().flat_map(refs) {
+      if dep == nam {
+        return true;
+      }
+      if visited.insert(dep) {
+        stack.push(dep);
+      }
+    }
+  }
+  false
+}
+
+/// Errors on the definitions that end up referencing themselves by only following references.
+fn check_ref_cycles(book: &Book) -> Result<(), String> {
+  for nam in book.nets.keys() {
+    let mut visited = HashSet::new();
+    let mut cur = nam;
+    while let Some(Net { root: Tree::Ref { nam: nxt }, redexes }) = book.nets.get(cur) {
+      if !redexes.is_empty() {
+        break;
+      }
+      if !visited.insert(nxt) {
+        return Err(format!("infinite reference cycle in `@{nxt}`"));
+      }
+      cur = nxt;
+    }
+  }
+  Ok(())
+}
+
+fn refs(tree: &Tree) -> Vec<&String> {
+  let mut refs = vec![];
+  let mut visit = vec![tree];
+  while let Some(tree) = visit.pop() {
+    if let Tree::Ref { nam } = tree {
+      refs.push(nam);
+    }
+    visit.extend(tree.children());
+  }
+  refs
+}
+
+pub(super) fn prefix_vars(tree: &mut Tree, prefix: &str) {
+  maybe_grow(|| {
+    if let Tree::Var { nam } = tree {
+      nam.insert_str(0, prefix);
+    }
+    tree.children_mut().for_each(|child| prefix_vars(child, prefix));
+  })
+}
+
+/// Gives back short names to the variables of the net, in the order they appear.
+pub(super) fn rename_vars(net: &mut Net) {
+  let mut names = HashMap::new();
+  for tree in net.trees_mut() {
+    rename_vars_tree(tree, &mut names);
+  }
+}
+
+fn rename_vars_tree(tree: &mut Tree, names: &mut HashMap<String, String>) {
+  maybe_grow(|| {
+    if let Tree::Var { nam } = tree {
+      let len = names.len() as u64;
+      *nam = names.entry(std::mem::take(nam)).or_insert_with(|| num_to_name(len)).clone();
+    }
+    tree.children_mut().for_each(|child| rename_vars_tree(child, names));
+  })
+}
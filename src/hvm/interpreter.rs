@@ -0,0 +1,1268 @@
+use crate::{
+  codegen::{flatten_book, port, FlatBook, CON, DUP, ERA, NUM, OPR, REF, SWI, VAR},
+  maybe_grow,
+};
+use hvmc::ast::{Book, Net, Tree};
+use std::{
+  collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+  time::{Duration, Instant},
+};
+
+/// An empty variable.
+const NONE: u32 = u32::MAX;
+
+// Number types and operations, in the encoding of hvm32.
+const TY_SYM: u32 = 0x0;
+const TY_U24: u32 = 0x1;
+const TY_I24: u32 = 0x2;
+const TY_F24: u32 = 0x3;
+const OP_ADD: u32 = 0x4;
+const OP_SUB: u32 = 0x5;
+const OP_MUL: u32 = 0x6;
+const OP_DIV: u32 = 0x7;
+const OP_REM: u32 = 0x8;
+const OP_EQ: u32 = 0x9;
+const OP_NEQ: u32 = 0xA;
+const OP_LT: u32 = 0xB;
+const OP_GT: u32 = 0xC;
+const OP_AND: u32 = 0xD;
+const OP_OR: u32 = 0xE;
+const OP_XOR: u32 = 0xF;
+const FLIP: u32 = 0x1000_0000;
+
+/// How many interactions are performed between checks for new list elements when streaming.
+const STREAM_STEP: usize = 1 << 16;
+
+/// How many interactions are performed between checks of the time limit.
+const TIME_STEP: usize = 1 << 12;
+
+/// How many nodes the lazy evaluation allocates before it first frees the ones that aren't
+/// connected to the root anymore.
+const GC_MIN_NODES: usize = 1 << 12;
+
+/// Normalizes the entrypoint of an hvmc book in the current process.
+///
+/// This is a sequential runtime with the same semantics as the one generated by `gen-c`,
+/// for targets that can't run hvm, like WebAssembly.
+/// Returns the normal form and the number of interactions performed.
+pub fn normalize(book: &Book) -> Result<(Net, u64), String> {
+  let (net, stats, _) = run(book, Limits::default(), false)?;
+  Ok((net, stats.rewrites.total()))
+}
+
+/// Normalizes the entrypoint like [`normalize`], returning the statistics of the evaluation.
+///
+/// When one of the `limits` is reached, the evaluation stops and the net is returned as it was,
+/// with the reached limit in [`Stats::stopped`].
+pub fn normalize_with_stats(book: &Book, limits: Limits) -> Result<(Net, Stats), String> {
+  let (net, stats, _) = run(book, limits, false)?;
+  Ok((net, stats))
+}
+
+/// Normalizes the entrypoint like [`normalize_with_stats`], also counting the rewrites performed in
+/// the net of each definition and the time spent on them. The profiles are sorted by number of rewrites.
+pub fn profile(book: &Book, limits: Limits) -> Result<(Net, Stats, Vec<DefProfile>), String> {
+  let (net, stats, profile) = run(book, limits, true)?;
+  Ok((net, stats, profile.unwrap_or_default()))
+}
+
+/// Bounds on an evaluation, for programs that may not terminate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+  /// The maximum number of rewrites.
+  pub max_rewrites: Option<u64>,
+  /// The maximum time spent rewriting.
+  pub timeout: Option<Duration>,
+  /// The maximum bytes used by the nodes and variables of the net.
+  pub max_memory: Option<usize>,
+}
+
+/// A limit that stopped an evaluation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+  Rewrites(u64),
+  Timeout(Duration),
+  Memory(usize),
+}
+
+impl std::fmt::Display for Limit {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Limit::Rewrites(max) => write!(f, "the limit of {max} rewrites"),
+      Limit::Timeout(timeout) => write!(f, "the time limit of {}s", timeout.as_secs_f64()),
+      Limit::Memory(max) => write!(f, "the memory limit of {max} bytes"),
+    }
+  }
+}
+
+/// The number of rewrites of each kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rewrites {
+  /// Annihilations, between nodes of the same kind.
+  pub anni: u64,
+  /// Commutations, between nodes of different kinds.
+  pub comm: u64,
+  /// Erasures and copies of erasers, numbers and references.
+  pub eras: u64,
+  /// Expansions of references.
+  pub dref: u64,
+  /// Numeric operations and switches.
+  pub oper: u64,
+}
+
+impl Rewrites {
+  pub fn total(&self) -> u64 {
+    self.anni + self.comm + self.eras + self.dref + self.oper
+  }
+
+  /// Counts the interaction between two principal ports, ordered by tag.
+  fn add(&mut self, a: u32, b: u32, safe_ref: bool) {
+    match (tag(a), tag(b)) {
+      (REF, DUP) if safe_ref => self.eras += 1,
+      (REF, _) if is_node(b) => self.dref += 1,
+      (NUM, OPR | SWI) => self.oper += 1,
+      _ if !is_node(a) => self.eras += 1,
+      (ta, tb) if ta == tb => self.anni += 1,
+      _ => self.comm += 1,
+    }
+  }
+}
+
+/// The statistics of a normalization.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+  pub rewrites: Rewrites,
+  /// The bytes used by the nodes and variables of the net, at its largest.
+  pub memory: usize,
+  /// The limit that stopped the evaluation before reaching the normal form, if any.
+  pub stopped: Option<Limit>,
+}
+
+/// The rewrites performed in the net of a definition, and the time spent on them.
+///
+/// A rewrite belongs to the definition whose expansion created the node being rewritten,
+/// or to the expanded definition for the `DREF`s.
+#[derive(Clone, Debug, Default)]
+pub struct DefProfile {
+  pub name: String,
+  pub rewrites: Rewrites,
+  pub time: Duration,
+}
+
+fn run(book: &Book, limits: Limits, profile: bool) -> Result<(Net, Stats, Option<Vec<DefProfile>>), String> {
+  let book = flatten_book(book)?;
+  let mut net = Interpreter::new(&book, limits);
+  if profile {
+    let defs = book.defs.iter().map(|def| DefProfile { name: def.name.clone(), ..Default::default() });
+    net.profiler = Some(Profiler { owners: vec![], current: book.main, defs: defs.collect() });
+  }
+  let root = net.alloc_var();
+  net.call(book.main, port(VAR, root));
+  loop {
+    net.reduce();
+    if net.stopped.is_some() || !net.expand(Slot::Var(root)) {
+      break;
+    }
+  }
+  let tree = net.readback_slot(Slot::Var(root));
+  let stats = net.stats();
+  let profile = net.profiler.map(|profiler| {
+    let mut defs: Vec<_> = profiler.defs.into_iter().filter(|def| def.rewrites.total() > 0).collect();
+    defs.sort_by(|a, b| b.rewrites.total().cmp(&a.rewrites.total()).then_with(|| a.name.cmp(&b.name)));
+    defs
+  });
+  Ok((Net { root: tree, redexes: vec![] }, stats, profile))
+}
+
+/// Reduces the redexes of the definition with the given id, expanding only the references
+/// that take part in them, and returns the resulting net.
+///
+/// Returns `None` if one of the `limits` is reached before there's nothing left to reduce,
+/// or if the result can't be represented as an hvmc net.
+pub(crate) fn reduce_def(book: &FlatBook, id: u32, limits: Limits) -> Option<Net> {
+  let mut net = Interpreter::new(book, limits);
+  let root = net.alloc_var();
+  net.call(id, port(VAR, root));
+  net.reduce();
+  let (port, _) = net.follow(Slot::Var(root));
+  let mut wires = HashMap::new();
+  if net.stopped.is_some() || port == NONE || !net.is_readable(port, &mut wires) {
+    return None;
+  }
+  // Both ends of each wire have to be in the net.
+  if wires.values().any(|ends| *ends != 2) {
+    return None;
+  }
+  Some(Net { root: net.readback(port), redexes: vec![] })
+}
+
+/// Normalizes the entrypoint like [`normalize`], but when the result is a list, passes each
+/// element to `on_elem` as soon as it's in normal form, instead of waiting for the whole list.
+///
+/// Redexes are reduced breadth-first, so that the first elements are built before the deeper
+/// recursive calls.
+/// Returns what remains of the result after the streamed elements, which is the `Nil` that
+/// ends the list or the whole result if it isn't a list, and the statistics of the evaluation.
+/// The evaluation stops at the `limits` like in [`normalize_with_stats`].
+pub fn normalize_stream(
+  book: &Book,
+  limits: Limits,
+  mut on_elem: impl FnMut(Net),
+) -> Result<(Net, Stats), String> {
+  let book = flatten_book(book)?;
+  let mut net = Interpreter::new(&book, limits);
+  net.breadth_first = true;
+  let root = net.alloc_var();
+  net.call(book.main, port(VAR, root));
+  let mut rest = Slot::Var(root);
+  loop {
+    let done = net.reduce_steps(STREAM_STEP);
+    let pending = net.pending();
+    while let Some((head, tail)) = net.list_cons(net.follow(rest).0, &pending) {
+      on_elem(Net { root: net.readback(head), redexes: vec![] });
+      rest = tail;
+    }
+    if net.stopped.is_some() || done && !(rest == Slot::Var(root) && net.expand(rest)) {
+      break;
+    }
+  }
+  let tree = net.readback_slot(rest);
+  Ok((Net { root: tree, redexes: vec![] }, net.stats()))
+}
+
+/// Normalizes the entrypoint like [`normalize_with_stats`], but lazily, reducing only the redexes
+/// that the normal form of the result needs instead of all of them.
+///
+/// Each round follows the principal ports from the root to the redexes that block the result,
+/// and reduces only those. References are only expanded when something needs them,
+/// so a program that builds an infinite structure terminates as long as its result uses only
+/// part of it, and the redexes that the result doesn't need are dropped without being reduced.
+/// The places that hold each wire are indexed as the net is rewritten, and the nodes that aren't
+/// connected to the root anymore are freed each time the number of nodes doubles.
+pub fn normalize_lazy(book: &Book, limits: Limits) -> Result<(Net, Stats), String> {
+  let book = flatten_book(book)?;
+  let mut net = Interpreter::new(&book, limits);
+  net.wires = Some(Wires::default());
+  let root = net.alloc_var();
+  net.call(book.main, port(VAR, root));
+  let mut collect_at = GC_MIN_NODES;
+  let mut step = 0;
+  while !net.reached_limit(step) {
+    if net.expand(Slot::Var(root)) {
+      continue;
+    }
+    if net.live_nodes() >= collect_at {
+      net.collect_garbage(root);
+      collect_at = GC_MIN_NODES.max(2 * net.live_nodes());
+    }
+    let needed = net.needed_redexes(root);
+    if needed.is_empty() {
+      break;
+    }
+    for (a, b) in net.take_redexes(&needed) {
+      if net.reached_limit(step) {
+        break;
+      }
+      net.interact(a, b);
+      step += 1;
+    }
+  }
+  let tree = net.readback_slot(Slot::Var(root));
+  Ok((Net { root: tree, redexes: vec![] }, net.stats()))
+}
+
+/// Why a [`Debugger`] paused.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Pause {
+  /// The requested number of rewrites was performed.
+  Step,
+  /// The next rewrite expands a definition with a breakpoint.
+  Breakpoint(String),
+  /// The net is in normal form.
+  Done,
+}
+
+/// Normalizes the entrypoint of a book a few rewrites at a time, pausing before the expansions
+/// of chosen definitions, to inspect the net while it's reduced.
+pub struct Debugger<'a> {
+  net: Interpreter<'a>,
+  root: u32,
+  /// The definitions whose expansions pause the evaluation.
+  breakpoints: HashSet<u32>,
+  /// Whether it's paused at a breakpoint, which is passed over when resuming.
+  at_breakpoint: bool,
+}
+
+impl<'a> Debugger<'a> {
+  pub fn new(book: &'a FlatBook) -> Self {
+    let mut net = Interpreter::new(book, Limits::default());
+    let root = net.alloc_var();
+    net.call(book.main, port(VAR, root));
+    Debugger { net, root, breakpoints: HashSet::new(), at_breakpoint: false }
+  }
+
+  /// Pauses before each expansion of the definition `name`.
+  pub fn add_breakpoint(&mut self, name: &str) -> Result<(), String> {
+    let id = self.def_id(name)?;
+    self.breakpoints.insert(id);
+    Ok(())
+  }
+
+  pub fn remove_breakpoint(&mut self, name: &str) -> Result<(), String> {
+    let id = self.def_id(name)?;
+    self.breakpoints.remove(&id);
+    Ok(())
+  }
+
+  /// Performs `steps` rewrites, pausing earlier at a breakpoint or at the normal form.
+  pub fn step(&mut self, steps: u64) -> Pause {
+    self.run(Some(steps))
+  }
+
+  /// Rewrites until reaching a breakpoint or the normal form.
+  pub fn resume(&mut self) -> Pause {
+    self.run(None)
+  }
+
+  /// The rewrites performed so far.
+  pub fn rewrites(&self) -> Rewrites {
+    self.net.rewrites
+  }
+
+  /// The whole net in its current state, with the redexes waiting to be reduced, the next one last.
+  pub fn net(&self) -> Net {
+    let root = self.net.readback(port(VAR, self.root));
+    let redexes = self.net.rbag.iter().map(|&(a, b)| (false, self.net.readback(a), self.net.readback(b)));
+    Net { root, redexes: redexes.collect() }
+  }
+
+  /// The two trees of the next redex to be reduced.
+  pub fn next_redex(&self) -> Option<(Tree, Tree)> {
+    self.net.rbag.back().map(|&(a, b)| (self.net.readback(a), self.net.readback(b)))
+  }
+
+  fn run(&mut self, steps: Option<u64>) -> Pause {
+    let mut done = 0;
+    loop {
+      let Some(&(a, b)) = self.net.rbag.back() else {
+        if self.net.expand(Slot::Var(self.root)) {
+          continue;
+        }
+        return Pause::Done;
+      };
+      if !std::mem::take(&mut self.at_breakpoint)
+        && let Some(def) = self.expanded_def(a, b)
+        && self.breakpoints.contains(&def)
+      {
+        self.at_breakpoint = true;
+        return Pause::Breakpoint(self.net.book.defs[def as usize].name.clone());
+      }
+      if steps.is_some_and(|steps| done >= steps) {
+        return Pause::Step;
+      }
+      let (a, b) = self.net.pop_redex().unwrap();
+      self.net.interact(a, b);
+      done += 1;
+    }
+  }
+
+  /// The definition that a redex expands, if it does.
+  fn expanded_def(&self, a: u32, b: u32) -> Option<u32> {
+    let (a, b) = if tag(a) > tag(b) { (b, a) } else { (a, b) };
+    if tag(a) != REF || !is_node(b) {
+      return None;
+    }
+    let copied = tag(b) == DUP && self.net.book.defs[val(a) as usize].safe;
+    (!copied).then_some(val(a))
+  }
+
+  fn def_id(&self, name: &str) -> Result<u32, String> {
+    let id = self.net.book.defs.iter().position(|def| def.name == name);
+    id.map(|id| id as u32).ok_or_else(|| format!("There's no definition '{name}' in the compiled program."))
+  }
+}
+
+/// The nodes and variables reachable from the redexes, which can still be rewritten.
+struct Pending {
+  nodes: HashSet<u32>,
+  vars: HashSet<u32>,
+}
+
+/// A place that holds a port, either a variable or an auxiliary port of a node.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Slot {
+  Var(u32),
+  Aux(u32),
+}
+
+impl Slot {
+  /// A port to the variable or the node that the slot belongs to.
+  fn owner(self) -> u32 {
+    match self {
+      Slot::Var(loc) => port(VAR, loc),
+      Slot::Aux(idx) => port(CON, idx / 2),
+    }
+  }
+}
+
+/// Where the principal port of a node is held.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Principal {
+  Slot(Slot),
+  /// In a redex, with the other port of the redex.
+  Redex(u32),
+}
+
+/// The places that hold each variable and each principal port, to follow the wires of the net backwards.
+/// It's kept up to date by the interpreter as the net is rewritten.
+#[derive(Default)]
+struct Wires {
+  holders: HashMap<u32, Vec<Slot>>,
+  principals: HashMap<u32, Principal>,
+}
+
+impl Wires {
+  /// Records that a slot holds a port.
+  fn hold(&mut self, slot: Slot, port: u32) {
+    if tag(port) == VAR {
+      self.holders.entry(val(port)).or_default().push(slot);
+    } else if port != NONE && is_node(port) {
+      self.principals.insert(val(port), Principal::Slot(slot));
+    }
+  }
+
+  /// Forgets that a slot holds a port, when the port is taken out of it.
+  fn release(&mut self, slot: Slot, port: u32) {
+    let principal = Some(&Principal::Slot(slot));
+    if tag(port) == VAR {
+      if let Entry::Occupied(mut entry) = self.holders.entry(val(port)) {
+        entry.get_mut().retain(|held| *held != slot);
+        if entry.get().is_empty() {
+          entry.remove();
+        }
+      }
+    } else if port != NONE && is_node(port) && self.principals.get(&val(port)) == principal {
+      self.principals.remove(&val(port));
+    }
+  }
+
+  fn add_redex(&mut self, a: u32, b: u32) {
+    for (port, other) in [(a, b), (b, a)].into_iter().filter(|(port, _)| is_node(*port)) {
+      self.principals.insert(val(port), Principal::Redex(other));
+    }
+  }
+
+  fn remove_redex(&mut self, a: u32, b: u32) {
+    for port in [a, b].into_iter().filter(|port| is_node(*port)) {
+      self.principals.remove(&val(port));
+    }
+  }
+
+  /// The node at the other end of the wire of a variable, held by one of its auxiliary ports,
+  /// going through the variables that were linked to it. `from` is the end the wire is followed from.
+  /// Returns `None` if the wire ends at the root or nothing holds it.
+  fn aux_node(&self, mut var: u32, mut from: Option<Slot>) -> Option<u32> {
+    loop {
+      match *self.holders.get(&var)?.iter().find(|slot| Some(**slot) != from)? {
+        Slot::Aux(loc) => return Some(loc / 2),
+        Slot::Var(loc) => {
+          var = loc;
+          from = None;
+        }
+      }
+    }
+  }
+}
+
+struct Interpreter<'a> {
+  book: &'a FlatBook,
+  /// The two auxiliary ports of each node.
+  nodes: Vec<u32>,
+  free_nodes: Vec<u32>,
+  vars: Vec<u32>,
+  free_vars: Vec<u32>,
+  /// Pairs of principal ports waiting to interact.
+  rbag: VecDeque<(u32, u32)>,
+  /// Whether the oldest redex is reduced first, instead of the newest.
+  breadth_first: bool,
+  rewrites: Rewrites,
+  profiler: Option<Profiler>,
+  limits: Limits,
+  /// When the evaluation started, only taken with a timeout, since there's no clock on `wasm32-unknown-unknown`.
+  start: Option<Instant>,
+  /// The limit that was reached, after which nothing else is reduced.
+  stopped: Option<Limit>,
+  /// The index of the wires, only kept by the lazy evaluation.
+  wires: Option<Wires>,
+}
+
+struct Profiler {
+  /// The definition that created each node.
+  owners: Vec<u32>,
+  /// The definition of the interaction being performed, which owns the nodes it creates.
+  current: u32,
+  defs: Vec<DefProfile>,
+}
+
+impl Profiler {
+  fn set_owner(&mut self, loc: u32, def: u32) {
+    if self.owners.len() <= loc as usize {
+      self.owners.resize(loc as usize + 1, 0);
+    }
+    self.owners[loc as usize] = def;
+  }
+}
+
+impl<'a> Interpreter<'a> {
+  fn new(book: &'a FlatBook, limits: Limits) -> Self {
+    Interpreter {
+      book,
+      nodes: vec![],
+      free_nodes: vec![],
+      vars: vec![],
+      free_vars: vec![],
+      rbag: VecDeque::new(),
+      breadth_first: false,
+      rewrites: Rewrites::default(),
+      profiler: None,
+      limits,
+      start: limits.timeout.map(|_| Instant::now()),
+      stopped: None,
+      wires: None,
+    }
+  }
+
+  fn stats(&self) -> Stats {
+    let memory = (self.nodes.len() + self.vars.len()) * std::mem::size_of::<u32>();
+    Stats { rewrites: self.rewrites, memory, stopped: self.stopped }
+  }
+
+  fn alloc_node(&mut self) -> u32 {
+    if let Some(loc) = self.free_nodes.pop() {
+      return loc;
+    }
+    self.nodes.extend([0, 0]);
+    (self.nodes.len() / 2 - 1) as u32
+  }
+
+  fn new_node(&mut self, tag: u32, fst: u32, snd: u32) -> u32 {
+    let loc = self.alloc_node();
+    if let Some(profiler) = &mut self.profiler {
+      profiler.set_owner(loc, profiler.current);
+    }
+    self.nodes[2 * loc as usize] = fst;
+    self.nodes[2 * loc as usize + 1] = snd;
+    self.hold(Slot::Aux(2 * loc), fst);
+    self.hold(Slot::Aux(2 * loc + 1), snd);
+    port(tag, loc)
+  }
+
+  fn take_node(&mut self, port: u32) -> (u32, u32) {
+    let loc = val(port);
+    self.free_nodes.push(loc);
+    let (fst, snd) = (self.nodes[2 * loc as usize], self.nodes[2 * loc as usize + 1]);
+    if let Some(wires) = &mut self.wires {
+      wires.release(Slot::Aux(2 * loc), fst);
+      wires.release(Slot::Aux(2 * loc + 1), snd);
+      wires.principals.remove(&loc);
+    }
+    (fst, snd)
+  }
+
+  fn live_nodes(&self) -> usize {
+    self.nodes.len() / 2 - self.free_nodes.len()
+  }
+
+  fn alloc_var(&mut self) -> u32 {
+    if let Some(loc) = self.free_vars.pop() {
+      self.vars[loc as usize] = NONE;
+      return loc;
+    }
+    self.vars.push(NONE);
+    (self.vars.len() - 1) as u32
+  }
+
+  fn free_var(&mut self, loc: u32) {
+    if let Some(wires) = &mut self.wires {
+      wires.release(Slot::Var(loc), self.vars[loc as usize]);
+      wires.holders.remove(&loc);
+    }
+    self.vars[loc as usize] = NONE;
+    self.free_vars.push(loc);
+  }
+
+  /// Records that a slot holds a port in the index of the wires, if it's kept.
+  fn hold(&mut self, slot: Slot, port: u32) {
+    if let Some(wires) = &mut self.wires {
+      wires.hold(slot, port);
+    }
+  }
+
+  /// Follows a variable to what was linked to its other end, consuming it.
+  fn enter(&mut self, mut port: u32) -> u32 {
+    while tag(port) == VAR && self.vars[val(port) as usize] != NONE {
+      let got = self.vars[val(port) as usize];
+      self.free_var(val(port));
+      port = got;
+    }
+    port
+  }
+
+  /// Connects two ports, creating a redex if both are principal.
+  fn link(&mut self, mut a: u32, mut b: u32) {
+    loop {
+      if tag(a) != VAR && tag(b) == VAR {
+        std::mem::swap(&mut a, &mut b);
+      }
+      if tag(a) != VAR {
+        if let Some(wires) = &mut self.wires {
+          wires.add_redex(a, b);
+        }
+        self.rbag.push_back((a, b));
+        return;
+      }
+      b = self.enter(b);
+      let loc = val(a);
+      let got = self.vars[loc as usize];
+      if got == NONE {
+        self.vars[loc as usize] = b;
+        self.hold(Slot::Var(loc), b);
+        return;
+      }
+      self.free_var(loc);
+      a = got;
+    }
+  }
+
+  /// Expands a definition, connecting its root to `port`.
+  fn call(&mut self, id: u32, port: u32) {
+    let book = self.book;
+    let def = &book.defs[id as usize];
+    let nodes: Vec<u32> = (0 .. def.nodes.len() / 2).map(|_| self.alloc_node()).collect();
+    let vars: Vec<u32> = (0 .. def.vars).map(|_| self.alloc_var()).collect();
+    let adjust = |p: u32| match tag(p) {
+      VAR => self::port(VAR, vars[val(p) as usize]),
+      CON | DUP | OPR | SWI => self::port(tag(p), nodes[val(p) as usize]),
+      _ => p,
+    };
+    if let Some(profiler) = &mut self.profiler {
+      nodes.iter().for_each(|loc| profiler.set_owner(*loc, id));
+    }
+    for (i, loc) in nodes.iter().enumerate() {
+      for j in 0 .. 2 {
+        let (idx, aux) = (2 * *loc + j as u32, adjust(def.nodes[2 * i + j]));
+        self.nodes[idx as usize] = aux;
+        self.hold(Slot::Aux(idx), aux);
+      }
+    }
+    for redex in def.rbag.chunks(2) {
+      self.link(adjust(redex[0]), adjust(redex[1]));
+    }
+    self.link(adjust(def.root), port);
+  }
+
+  fn reduce(&mut self) {
+    self.reduce_steps(usize::MAX);
+  }
+
+  /// Performs at most `steps` interactions, stopping earlier if a limit is reached.
+  /// Returns whether there's nothing left to reduce.
+  fn reduce_steps(&mut self, steps: usize) -> bool {
+    for step in 0 .. steps {
+      if self.rbag.is_empty() {
+        return true;
+      }
+      if self.reached_limit(step) {
+        return false;
+      }
+      let (a, b) = self.pop_redex().unwrap();
+      self.interact(a, b);
+    }
+    self.rbag.is_empty()
+  }
+
+  /// Checks the limits of the evaluation, the time one only every [`TIME_STEP`] steps.
+  fn reached_limit(&mut self, step: usize) -> bool {
+    if self.stopped.is_none() {
+      let Limits { max_rewrites, timeout, max_memory } = self.limits;
+      if let Some(max) = max_rewrites.filter(|max| self.rewrites.total() >= *max) {
+        self.stopped = Some(Limit::Rewrites(max));
+      } else if let Some(timeout) =
+        timeout.filter(|t| step % TIME_STEP == 0 && self.start.is_some_and(|start| start.elapsed() >= *t))
+      {
+        self.stopped = Some(Limit::Timeout(timeout));
+      } else if let Some(max) = max_memory.filter(|max| self.stats().memory > *max) {
+        self.stopped = Some(Limit::Memory(max));
+      }
+    }
+    self.stopped.is_some()
+  }
+
+  fn pop_redex(&mut self) -> Option<(u32, u32)> {
+    let redex = if self.breadth_first { self.rbag.pop_front() } else { self.rbag.pop_back() };
+    let (a, b) = redex?;
+    if let Some(wires) = &mut self.wires {
+      wires.remove_redex(a, b);
+    }
+    Some((a, b))
+  }
+
+  fn interact(&mut self, mut a: u32, mut b: u32) {
+    if tag(a) > tag(b) {
+      std::mem::swap(&mut a, &mut b);
+    }
+    let safe = tag(a) == REF && self.book.defs[val(a) as usize].safe;
+    self.rewrites.add(a, b, safe);
+
+    let Some(profiler) = &mut self.profiler else { return self.rewrite(a, b) };
+    // Redexes between two nullary ports have no node to take the owner from,
+    // so they go to the definition of the interaction that created them.
+    let def = match tag(a) {
+      REF => val(a),
+      _ if is_node(b) => profiler.owners[val(b) as usize],
+      _ => profiler.current,
+    };
+    profiler.current = def;
+    let start = Instant::now();
+    self.rewrite(a, b);
+    let def = &mut self.profiler.as_mut().unwrap().defs[def as usize];
+    def.time += start.elapsed();
+    def.rewrites.add(a, b, safe);
+  }
+
+  fn rewrite(&mut self, a: u32, b: u32) {
+    // References, erasers and numbers meeting each other are simply discarded.
+    if !is_node(b) {
+      return;
+    }
+
+    // References are expanded, except safe ones that meet a duplicator, which are copied.
+    if tag(a) == REF {
+      if tag(b) == DUP && self.book.defs[val(a) as usize].safe {
+        let (b1, b2) = self.take_node(b);
+        self.link(b1, a);
+        self.link(b2, a);
+      } else {
+        self.call(val(a), b);
+      }
+      return;
+    }
+
+    let (b1, b2) = self.take_node(b);
+
+    if tag(a) == NUM && tag(b) == OPR {
+      let fst = self.enter(b1);
+      if tag(fst) == NUM {
+        self.link(b2, port(NUM, operate(val(a), val(fst))));
+      } else {
+        // The number waits for the other operand, with the order of the operation flipped.
+        let node = self.new_node(OPR, port(NUM, val(a) ^ FLIP), b2);
+        self.link(fst, node);
+      }
+      return;
+    }
+
+    if tag(a) == NUM && tag(b) == SWI {
+      let numb = val(a);
+      let n = numb_u24(numb);
+      if n == 0 {
+        let node = self.new_node(CON, b2, port(ERA, 0));
+        self.link(b1, node);
+      } else {
+        let pred = port(NUM, (numb & !(0xFFFFFF << 4)) | ((n - 1) << 4));
+        let succ = self.new_node(CON, pred, b2);
+        let node = self.new_node(CON, port(ERA, 0), succ);
+        self.link(b1, node);
+      }
+      return;
+    }
+
+    // Erasers and numbers are copied into both auxiliary ports.
+    if !is_node(a) {
+      self.link(b1, a);
+      self.link(b2, a);
+      return;
+    }
+
+    let (a1, a2) = self.take_node(a);
+
+    if tag(a) == tag(b) {
+      self.link(a1, b1);
+      self.link(a2, b2);
+      return;
+    }
+
+    let [x1, x2, y1, y2] = [(); 4].map(|_| port(VAR, self.alloc_var()));
+    let ta1 = self.new_node(tag(b), x1, x2);
+    let ta2 = self.new_node(tag(b), y1, y2);
+    let tb1 = self.new_node(tag(a), x1, y1);
+    let tb2 = self.new_node(tag(a), x2, y2);
+    self.link(a1, ta1);
+    self.link(a2, ta2);
+    self.link(b1, tb1);
+    self.link(b2, tb2);
+  }
+
+  /// Follows the variables of a port to what it's connected to, without consuming them.
+  fn resolve(&self, mut port: u32) -> u32 {
+    while tag(port) == VAR && self.vars[val(port) as usize] != NONE {
+      port = self.vars[val(port) as usize];
+    }
+    port
+  }
+
+  /// Follows the variables from a slot to the last slot of the chain and the port it holds.
+  fn follow(&self, mut slot: Slot) -> (u32, Slot) {
+    loop {
+      let port = match slot {
+        Slot::Var(loc) => self.vars[loc as usize],
+        Slot::Aux(loc) => self.nodes[loc as usize],
+      };
+      if tag(port) == VAR && self.vars[val(port) as usize] != NONE {
+        slot = Slot::Var(val(port));
+      } else {
+        return (port, slot);
+      }
+    }
+  }
+
+  /// Expands what a slot points to if it is a reference, like the root when the entrypoint is an alias.
+  /// Returns whether it was expanded.
+  fn expand(&mut self, slot: Slot) -> bool {
+    let (port, slot) = self.follow(slot);
+    if tag(port) != REF {
+      return false;
+    }
+    let var = self.alloc_var();
+    match slot {
+      Slot::Var(loc) => self.vars[loc as usize] = self::port(VAR, var),
+      Slot::Aux(loc) => self.nodes[loc as usize] = self::port(VAR, var),
+    }
+    self.hold(slot, self::port(VAR, var));
+    self.call(val(port), self::port(VAR, var));
+    true
+  }
+
+  /// Finds the redexes that block the normal form of the root, returning the nodes that take part in them.
+  ///
+  /// A node connected to a position of the result by its principal port is in weak head normal form,
+  /// so its auxiliary ports are positions of the result too. A node connected by an auxiliary port
+  /// waits for what its principal port is connected to, so the principal ports are followed until
+  /// a redex, which is needed, or until a node connected to the root, to a free variable or to
+  /// itself, which can't be rewritten and is part of the result.
+  fn needed_redexes(&self, root: u32) -> HashSet<u32> {
+    let wires = self.wires.as_ref().expect("the wires are indexed");
+    let mut needed = HashSet::new();
+    // Whether each visited node waits for a needed redex.
+    let mut blocked = HashMap::new();
+    let mut stack = vec![Slot::Var(root)];
+    while let Some(slot) = stack.pop() {
+      let (var, from) = match self.follow(slot) {
+        (NONE, Slot::Var(loc)) => (loc, None),
+        (port, last) if tag(port) == VAR => (val(port), Some(last)),
+        (port, _) if port != NONE && is_node(port) => {
+          if let Entry::Vacant(entry) = blocked.entry(val(port)) {
+            entry.insert(false);
+            stack.extend(aux_slots(val(port)));
+          }
+          continue;
+        }
+        _ => continue,
+      };
+      let Some(mut node) = wires.aux_node(var, from) else { continue };
+      let mut path = vec![];
+      let is_blocked = loop {
+        if let Some(is_blocked) = blocked.get(&node) {
+          break *is_blocked;
+        }
+        blocked.insert(node, false);
+        path.push(node);
+        match wires.principals.get(&node) {
+          Some(Principal::Redex(_)) => {
+            needed.insert(node);
+            break true;
+          }
+          Some(Principal::Slot(Slot::Aux(loc))) => node = loc / 2,
+          Some(Principal::Slot(Slot::Var(loc))) => match wires.aux_node(*loc, None) {
+            Some(next) => node = next,
+            None => break false,
+          },
+          None => break false,
+        }
+      };
+      for node in path {
+        blocked.insert(node, is_blocked);
+        if !is_blocked {
+          stack.extend(aux_slots(node));
+        }
+      }
+    }
+    needed
+  }
+
+  /// Removes the redexes that one of the given nodes takes part in from the redex bag, returning them.
+  fn take_redexes(&mut self, nodes: &HashSet<u32>) -> Vec<(u32, u32)> {
+    let (taken, rest): (Vec<_>, VecDeque<_>) = std::mem::take(&mut self.rbag)
+      .into_iter()
+      .partition(|&(a, b)| [a, b].into_iter().any(|port| is_node(port) && nodes.contains(&val(port))));
+    self.rbag = rest;
+    if let Some(wires) = &mut self.wires {
+      taken.iter().for_each(|&(a, b)| wires.remove_redex(a, b));
+    }
+    taken
+  }
+
+  /// Frees the nodes and the variables that aren't connected to the root anymore, which can't change
+  /// the result, dropping the redexes between them.
+  fn collect_garbage(&mut self, root: u32) {
+    let wires = self.wires.as_ref().expect("the wires are indexed");
+    let mut nodes = HashSet::new();
+    let mut vars = HashSet::new();
+    let mut stack = vec![port(VAR, root)];
+    while let Some(port) = stack.pop() {
+      if tag(port) == VAR {
+        if vars.insert(val(port)) {
+          stack.push(self.vars[val(port) as usize]);
+          stack.extend(wires.holders.get(&val(port)).into_iter().flatten().map(|slot| slot.owner()));
+        }
+      } else if port != NONE && is_node(port) && nodes.insert(val(port)) {
+        stack.extend(self.aux(port));
+        match wires.principals.get(&val(port)) {
+          Some(Principal::Slot(slot)) => stack.push(slot.owner()),
+          Some(Principal::Redex(other)) => stack.push(*other),
+          None => {}
+        }
+      }
+    }
+    self.rbag.retain(|&(a, b)| [a, b].into_iter().any(|port| is_node(port) && nodes.contains(&val(port))));
+    let free_nodes: HashSet<u32> = self.free_nodes.iter().copied().collect();
+    for loc in 0 .. self.nodes.len() as u32 / 2 {
+      if !nodes.contains(&loc) && !free_nodes.contains(&loc) {
+        self.take_node(port(CON, loc));
+      }
+    }
+    let free_vars: HashSet<u32> = self.free_vars.iter().copied().collect();
+    for loc in 0 .. self.vars.len() as u32 {
+      if !vars.contains(&loc) && !free_vars.contains(&loc) {
+        self.free_var(loc);
+      }
+    }
+  }
+
+  fn pending(&self) -> Pending {
+    let mut nodes = HashSet::new();
+    let mut vars = HashSet::new();
+    let mut stack: Vec<u32> = self.rbag.iter().flat_map(|&(a, b)| [a, b]).collect();
+    while let Some(port) = stack.pop() {
+      if tag(port) == VAR {
+        if vars.insert(val(port)) && self.vars[val(port) as usize] != NONE {
+          stack.push(self.vars[val(port) as usize]);
+        }
+      } else if is_node(port) && nodes.insert(val(port)) {
+        stack.extend(self.aux(port));
+      }
+    }
+    Pending { nodes, vars }
+  }
+
+  /// Checks that nothing reachable from a port can still be rewritten.
+  fn is_settled(&self, port: u32, pending: &Pending) -> bool {
+    let mut stack = vec![port];
+    while let Some(port) = stack.pop() {
+      if tag(port) == VAR {
+        if pending.vars.contains(&val(port)) {
+          return false;
+        }
+        if self.vars[val(port) as usize] != NONE {
+          stack.push(self.vars[val(port) as usize]);
+        }
+      } else if is_node(port) {
+        if pending.nodes.contains(&val(port)) {
+          return false;
+        }
+        stack.extend(self.aux(port));
+      }
+    }
+    true
+  }
+
+  /// Matches a port with a `List/Cons` whose head is in normal form, in its Scott encoding
+  /// `((head (tail r)) (* r))`. Returns the head and the slot that holds the tail.
+  fn list_cons(&self, port: u32, pending: &Pending) -> Option<(u32, Slot)> {
+    let con = |port: u32| {
+      (tag(port) == CON && !pending.nodes.contains(&val(port)))
+        .then(|| self.aux(port).map(|p| self.resolve(p)))
+    };
+    let [cons, nil] = con(port)?;
+    let [head, next] = con(cons)?;
+    let [era, ret1] = con(nil)?;
+    let [_, ret2] = con(next)?;
+    let is_cons = tag(era) == ERA && tag(ret1) == VAR && ret1 == ret2;
+    (is_cons && self.is_settled(head, pending)).then_some((head, Slot::Aux(2 * val(next))))
+  }
+
+  /// The two auxiliary ports of a node.
+  fn aux(&self, port: u32) -> [u32; 2] {
+    [self.nodes[2 * val(port) as usize], self.nodes[2 * val(port) as usize + 1]]
+  }
+
+  /// Reads back what a slot holds, or an eraser if nothing was connected to it yet,
+  /// which happens when a limit stopped the evaluation before the result was built.
+  fn readback_slot(&self, slot: Slot) -> Tree {
+    match self.follow(slot).0 {
+      NONE => Tree::Era,
+      port => self.readback(port),
+    }
+  }
+
+  /// Whether the net reachable from a port has no switches whose branches aren't a constructor,
+  /// which can't be represented in the ast. Counts the ends of each wire found in `wires`.
+  fn is_readable(&self, port: u32, wires: &mut HashMap<u32, usize>) -> bool {
+    maybe_grow(|| {
+      let port = self.resolve(port);
+      match tag(port) {
+        VAR => {
+          *wires.entry(val(port)).or_default() += 1;
+          true
+        }
+        SWI if tag(self.resolve(self.nodes[2 * val(port) as usize])) != CON => false,
+        _ if is_node(port) => self.aux(port).into_iter().all(|aux| self.is_readable(aux, wires)),
+        _ => true,
+      }
+    })
+  }
+
+  fn readback(&self, port: u32) -> Tree {
+    maybe_grow(|| {
+      let port = self.resolve(port);
+      match tag(port) {
+        VAR => Tree::Var { nam: var_name(val(port)) },
+        REF => Tree::Ref { nam: self.book.defs[val(port) as usize].name.clone() },
+        ERA => Tree::Era,
+        NUM => Tree::Num { val: val(port) },
+        SWI => {
+          let arms = self.resolve(self.nodes[2 * val(port) as usize]);
+          let out = Box::new(self.readback(self.nodes[2 * val(port) as usize + 1]));
+          if tag(arms) != CON {
+            // A switch whose branches aren't a constructor can't be represented in the ast.
+            return Tree::Era;
+          }
+          let zero = Box::new(self.readback(self.nodes[2 * val(arms) as usize]));
+          let succ = Box::new(self.readback(self.nodes[2 * val(arms) as usize + 1]));
+          Tree::Mat { zero, succ, out }
+        }
+        tag => {
+          let fst = self.readback(self.nodes[2 * val(port) as usize]);
+          let snd = self.readback(self.nodes[2 * val(port) as usize + 1]);
+          match tag {
+            CON => Tree::Ctr { lab: 0, ports: vec![fst, snd] },
+            DUP => Tree::Ctr { lab: 1, ports: vec![fst, snd] },
+            _ => Tree::Op { fst: Box::new(fst), snd: Box::new(snd) },
+          }
+        }
+      }
+    })
+  }
+}
+
+/// The slots of the two auxiliary ports of a node.
+fn aux_slots(loc: u32) -> [Slot; 2] {
+  [Slot::Aux(2 * loc), Slot::Aux(2 * loc + 1)]
+}
+
+fn tag(port: u32) -> u32 {
+  port & 0x7
+}
+
+fn val(port: u32) -> u32 {
+  port >> 3
+}
+
+fn is_node(port: u32) -> bool {
+  tag(port) >= CON
+}
+
+/// Names a variable by its slot, written in letters.
+fn var_name(mut val: u32) -> String {
+  let mut name = vec![];
+  loop {
+    name.push(b'a' + (val % 26) as u8);
+    val /= 26;
+    if val == 0 {
+      break;
+    }
+  }
+  name.reverse();
+  String::from_utf8(name).unwrap()
+}
+
+fn numb_tag(numb: u32) -> u32 {
+  numb & 0xF
+}
+
+fn numb_u24(numb: u32) -> u32 {
+  (numb >> 4) & 0xFFFFFF
+}
+
+fn numb_i24(numb: u32) -> i32 {
+  ((numb_u24(numb) << 8) as i32) >> 8
+}
+
+/// 24-bit floats have a sign bit, a 7-bit exponent and a 16-bit mantissa.
+fn numb_f24(numb: u32) -> f32 {
+  let bits = numb_u24(numb);
+  let sign = (bits >> 23) & 0x1;
+  let expo = (bits >> 16) & 0x7F;
+  let mant = bits & 0xFFFF;
+  let mut f32 = sign << 31;
+  if expo != 0 || mant != 0 {
+    f32 |= ((expo + 64) << 23) | (mant << 7);
+  }
+  f32::from_bits(f32)
+}
+
+fn new_u24(val: u32) -> u32 {
+  ((val & 0xFFFFFF) << 4) | TY_U24
+}
+
+fn new_i24(val: i32) -> u32 {
+  (((val as u32) & 0xFFFFFF) << 4) | TY_I24
+}
+
+fn new_f24(val: f32) -> u32 {
+  let f32 = val.to_bits();
+  let sign = f32 >> 31;
+  let expo = ((f32 >> 23) & 0xFF) as i32 - 64;
+  let mant = (f32 & 0x7FFFFF) >> 7;
+  let bits = if expo < 0 {
+    sign << 23
+  } else if expo > 0x7F {
+    (sign << 23) | (0x7F << 16) | mant
+  } else {
+    (sign << 23) | ((expo as u32) << 16) | mant
+  };
+  (bits << 4) | TY_F24
+}
+
+fn operate_u24(op: u32, a: u32, b: u32) -> u32 {
+  match op {
+    OP_ADD => new_u24(a.wrapping_add(b)),
+    OP_SUB => new_u24(a.wrapping_sub(b)),
+    OP_MUL => new_u24(a.wrapping_mul(b)),
+    OP_DIV => new_u24(a.checked_div(b).unwrap_or(0)),
+    OP_REM => new_u24(a.checked_rem(b).unwrap_or(0)),
+    OP_EQ => new_u24((a == b) as u32),
+    OP_NEQ => new_u24((a != b) as u32),
+    OP_LT => new_u24((a < b) as u32),
+    OP_GT => new_u24((a > b) as u32),
+    OP_AND => new_u24(a & b),
+    OP_OR => new_u24(a | b),
+    OP_XOR => new_u24(a ^ b),
+    _ => new_u24(0),
+  }
+}
+
+fn operate_i24(op: u32, a: i32, b: i32) -> u32 {
+  match op {
+    OP_ADD => new_i24(a.wrapping_add(b)),
+    OP_SUB => new_i24(a.wrapping_sub(b)),
+    OP_MUL => new_i24(a.wrapping_mul(b)),
+    OP_DIV => new_i24(a.checked_div(b).unwrap_or(0)),
+    OP_REM => new_i24(a.checked_rem(b).unwrap_or(0)),
+    OP_EQ => new_u24((a == b) as u32),
+    OP_NEQ => new_u24((a != b) as u32),
+    OP_LT => new_u24((a < b) as u32),
+    OP_GT => new_u24((a > b) as u32),
+    OP_AND => new_i24(a & b),
+    OP_OR => new_i24(a | b),
+    OP_XOR => new_i24(a ^ b),
+    _ => new_i24(0),
+  }
+}
+
+fn operate_f24(op: u32, a: f32, b: f32) -> u32 {
+  match op {
+    OP_ADD => new_f24(a + b),
+    OP_SUB => new_f24(a - b),
+    OP_MUL => new_f24(a * b),
+    OP_DIV => new_f24(a / b),
+    OP_REM => new_f24(a % b),
+    OP_EQ => new_u24((a == b) as u32),
+    OP_NEQ => new_u24((a != b) as u32),
+    OP_LT => new_u24((a < b) as u32),
+    OP_GT => new_u24((a > b) as u32),
+    OP_AND => new_f24(a.atan2(b)),
+    OP_OR => new_f24(b.ln() / a.ln()),
+    OP_XOR => new_f24(a.powf(b)),
+    _ => new_f24(0.0),
+  }
+}
+
+/// Applies a number to another.
+/// An operation symbol applied to a number makes a partial operation,
+/// and a partial operation applied to a number computes the result.
+pub(crate) fn operate(mut a: u32, mut b: u32) -> u32 {
+  if (a ^ b) & FLIP != 0 {
+    std::mem::swap(&mut a, &mut b);
+  }
+  let (at, bt) = (numb_tag(a), numb_tag(b));
+  if at == TY_SYM && bt == TY_SYM {
+    return new_u24(0);
+  }
+  if at == TY_SYM {
+    return (b & !0xF) | (numb_u24(a) & 0xF);
+  }
+  if bt == TY_SYM {
+    return (a & !0xF) | (numb_u24(b) & 0xF);
+  }
+  if (at >= OP_ADD) == (bt >= OP_ADD) {
+    return new_u24(0);
+  }
+  let (op, ty) = if at >= OP_ADD { (at, bt) } else { (bt, at) };
+  match ty {
+    TY_U24 => operate_u24(op, numb_u24(a), numb_u24(b)),
+    TY_I24 => operate_i24(op, numb_i24(a), numb_i24(b)),
+    TY_F24 => operate_f24(op, numb_f24(a), numb_f24(b)),
+    _ => new_u24(0),
+  }
+}
+
+#[test]
+fn interpreter_debugger() {
+  use crate::{
+    compile_book,
+    diagnostics::DiagnosticsConfig,
+    fun::{load_book::do_parse_book, Book},
+    CompileOpts,
+  };
+  use std::path::Path;
+
+  let code = "
+fib 0 = 0
+fib 1 = 1
+fib n = (+ (fib (- n 1)) (fib (- n 2)))
+main = (fib 10)
+";
+  let mut book = do_parse_book(code, Path::new("debugger"), Book::builtins()).unwrap();
+  let res = compile_book(&mut book, CompileOpts::default(), DiagnosticsConfig::default(), None).unwrap();
+  let flat = flatten_book(&res.core_book).unwrap();
+  let mut debugger = Debugger::new(&flat);
+  assert!(debugger.add_breakpoint("missing").is_err());
+  debugger.add_breakpoint("fib").unwrap();
+
+  assert_eq!(debugger.resume(), Pause::Breakpoint("fib".to_string()));
+  assert_eq!(debugger.rewrites().total(), 0);
+  assert_eq!(debugger.next_redex().unwrap().0.to_string(), "@fib");
+  assert_eq!(debugger.step(1), Pause::Step);
+  assert_eq!(debugger.rewrites().total(), 1);
+
+  let mut breaks = 1;
+  while let Pause::Breakpoint(name) = debugger.resume() {
+    assert_eq!(name, "fib");
+    breaks += 1;
+  }
+  assert!(breaks > 1);
+  let (net, itrs) = normalize(&res.core_book).unwrap();
+  assert_eq!(debugger.net().to_string(), net.to_string());
+  assert_eq!(debugger.rewrites().total(), itrs);
+}
@@ -0,0 +1,59 @@
+use super::inline::rename_vars;
+use crate::{
+  fun::{transform::definition_merge::MERGE_SEPARATOR, DefOpt, DefOpts, Name},
+  maybe_grow, ENTRY_POINT,
+};
+use hvmc::ast::{Book, Net, Tree};
+use indexmap::IndexMap;
+use std::collections::BTreeMap;
+
+/// Merges the definitions whose nets are the same up to the names of their variables,
+/// keeping a single net and pointing the references to the others to it.
+///
+/// Unlike [`crate::fun::transform::definition_merge`], this also merges the definitions
+/// that are different as terms but compile to the same net, like many of the floated combinators.
+/// The entrypoint is never merged, and neither are the definitions that disable `merge-defs`
+/// with an `#!opt(..)` pragma.
+pub fn merge_defs(book: &mut Book, def_opts: &DefOpts) {
+  loop {
+    let mut equal_nets = IndexMap::<Net, Vec<String>>::new();
+    let can_merge = |nam: &String| *nam != ENTRY_POINT && def_opts.allows(&Name::new(nam), DefOpt::MergeDefs);
+    for (nam, net) in book.nets.iter().filter(|(nam, _)| can_merge(nam)) {
+      let mut net = net.clone();
+      rename_vars(&mut net);
+      equal_nets.entry(net).or_default().push(nam.clone());
+    }
+
+    let mut name_map = BTreeMap::new();
+    for names in equal_nets.into_values().filter(|names| names.len() > 1) {
+      let new_name = names.join(MERGE_SEPARATOR);
+      let net = book.nets[&names[0]].clone();
+      for nam in names {
+        book.nets.remove(&nam);
+        name_map.insert(nam, new_name.clone());
+      }
+      book.nets.insert(new_name, net);
+    }
+
+    // Merging can make the nets that referenced the merged ones equal too.
+    if name_map.is_empty() {
+      break;
+    }
+    for net in book.nets.values_mut() {
+      for tree in net.trees_mut() {
+        subst_refs(tree, &name_map);
+      }
+    }
+  }
+}
+
+fn subst_refs(tree: &mut Tree, name_map: &BTreeMap<String, String>) {
+  maybe_grow(|| {
+    if let Tree::Ref { nam } = tree {
+      if let Some(new_name) = name_map.get(nam) {
+        *nam = new_name.clone();
+      }
+    }
+    tree.children_mut().for_each(|child| subst_refs(child, name_map));
+  })
+}
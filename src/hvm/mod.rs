@@ -1,3 +1,11 @@
 pub mod add_recursive_priority;
+pub mod binary;
 pub mod check_net_size;
+pub mod inline;
+pub mod interpreter;
+pub mod merge_defs;
 pub mod mutual_recursion;
+pub mod pre_reduce;
+pub mod roundtrip;
+pub mod source_map;
+pub mod tail_calls;
@@ -0,0 +1,41 @@
+use super::{
+  check_net_size::{count_nodes, MAX_NET_SIZE},
+  inline::rename_vars,
+  interpreter::{reduce_def, Limits},
+};
+use crate::{
+  codegen::flatten_book,
+  fun::{DefOpt, DefOpts, Name},
+  ENTRY_POINT,
+};
+use hvmc::ast::Book;
+
+/// Reduces the redexes of each definition at compile time, so that they're not reduced
+/// again every time the definition is expanded at runtime.
+///
+/// References are only expanded when they take part in a redex, so each definition is reduced
+/// as far as it can be without knowing its arguments. Every definition is reduced against the
+/// nets of the original book, within its own budget of `max_rewrites` and `max_memory` bytes,
+/// and is left as it was when it runs out of it, since it probably unfolds a recursive reference.
+///
+/// The entrypoint is never pre-reduced, since that would run the whole program,
+/// and neither are the definitions that disable `pre-reduce` with `#!no_prereduce`
+/// or an `#!opt(..)` pragma.
+pub fn pre_reduce(book: &mut Book, max_rewrites: u64, max_memory: usize, def_opts: &DefOpts) {
+  // Books that the interpreter can't run, like ones with unknown references, are left for hvm to report.
+  let Ok(flat) = flatten_book(book) else { return };
+  let limits = Limits { max_rewrites: Some(max_rewrites), timeout: None, max_memory: Some(max_memory) };
+
+  // The flattened definitions are in the same order as the ones of the book.
+  for (id, (nam, net)) in book.nets.iter_mut().enumerate() {
+    if net.redexes.is_empty() || nam == ENTRY_POINT || !def_opts.allows(&Name::new(nam), DefOpt::PreReduce) {
+      continue;
+    }
+    let Some(mut reduced) = reduce_def(&flat, id as u32, limits) else { continue };
+    if count_nodes(&reduced) > MAX_NET_SIZE {
+      continue;
+    }
+    rename_vars(&mut reduced);
+    *net = reduced;
+  }
+}
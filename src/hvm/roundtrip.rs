@@ -0,0 +1,37 @@
+//! Checks that a compiled book is read back the same by the hvm parser, so that a mismatch
+//! between how the nets are printed and how they are parsed is found before hvm runs the program.
+
+use super::inline::rename_vars;
+use hvmc::ast::{Book, Net};
+use std::str::FromStr;
+
+/// Prints the book and parses it back, returning the parsed book if it has the same nets,
+/// up to the names of their variables, or a message for each net that is different.
+pub fn check_roundtrip(book: &Book) -> Result<Book, Vec<String>> {
+  let printed = book.to_string();
+  let parsed =
+    Book::from_str(&printed).map_err(|e| vec![format!("The printed book can't be parsed back: {e}")])?;
+
+  let mut errs = vec![];
+  for (nam, net) in book.iter() {
+    match parsed.get(nam) {
+      None => errs.push(format!("'{nam}' is missing from the parsed book.")),
+      Some(parsed_net) if normalized(net) != normalized(parsed_net) => errs.push(format!(
+        "'{nam}' is different after parsing it back:\n  printed: {net}\n  parsed:  {parsed_net}"
+      )),
+      Some(_) => {}
+    }
+  }
+  for nam in parsed.keys().filter(|nam| !book.contains_key(*nam)) {
+    errs.push(format!("'{nam}' is in the parsed book, but wasn't printed."));
+  }
+
+  if errs.is_empty() { Ok(parsed) } else { Err(errs) }
+}
+
+/// The net with its variables renamed in the order they appear, to compare it with others.
+fn normalized(net: &Net) -> Net {
+  let mut net = net.clone();
+  rename_vars(&mut net);
+  net
+}
@@ -0,0 +1,134 @@
+//! Maps the nets of a compiled book back to the definitions of the program they came from, so that
+//! the floated combinators and the other generated nets can be reported by the names the user wrote.
+
+use crate::{
+  diagnostics::{JsonStr, Span},
+  fun::{display::DisplayFn, transform::definition_merge::MERGE_SEPARATOR, Book, Name},
+  ENTRY_POINT,
+};
+use std::collections::{BTreeMap, HashMap};
+
+/// A definition of the source program that a net was compiled from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceDef {
+  /// The definition as it was written, like `Foo` for the combinator `Foo__C0` floated out of it.
+  pub name: Name,
+  /// Where the definition was written, or `None` for the builtins and the programs not loaded from a file.
+  pub span: Option<Span>,
+}
+
+/// The definitions that each net of a compiled book came from.
+///
+/// Most nets come from a single definition, but the ones of definitions that were merged,
+/// because they compiled to the same net, come from all of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+  pub nets: BTreeMap<String, Vec<SourceDef>>,
+}
+
+impl SourceMap {
+  /// Builds the map of the nets compiled from `book`, which is the program after the compiler passes,
+  /// with `def_spans` being the spans of the definitions as they were loaded.
+  pub fn new(nets: &hvmc::ast::Book, book: &Book, def_spans: &HashMap<Name, Span>) -> Self {
+    let nets = nets.keys().map(|net| {
+      let defs = net.split(MERGE_SEPARATOR).map(|nam| {
+        let name = source_name(nam, book);
+        let span = def_spans.get(&name).cloned();
+        SourceDef { name, span }
+      });
+      let mut defs = defs.collect::<Vec<_>>();
+      defs.dedup();
+      (net.clone(), defs)
+    });
+    SourceMap { nets: nets.collect() }
+  }
+
+  /// The definitions that a net came from, which are none if the net isn't in the map.
+  pub fn get(&self, net: &str) -> &[SourceDef] {
+    self.nets.get(net).map(Vec::as_slice).unwrap_or_default()
+  }
+
+  /// Shows the definitions that a net came from, with where they were written, like `Foo (main.bend:3:1)`.
+  pub fn display_source<'a>(&'a self, net: &'a str) -> impl std::fmt::Display + 'a {
+    DisplayFn(move |f| {
+      for (i, SourceDef { name, span }) in self.get(net).iter().enumerate() {
+        if i != 0 {
+          write!(f, ", ")?;
+        }
+        write!(f, "{name}")?;
+        match span {
+          Some(Span { file: Some(file), line, column, .. }) => write!(f, " ({file}:{line}:{column})")?,
+          Some(Span { file: None, line, column, .. }) => write!(f, " ({line}:{column})")?,
+          None => {}
+        }
+      }
+      Ok(())
+    })
+  }
+
+  /// Displays the map as a JSON object from the name of each net to the list of the definitions it came from.
+  pub fn display_json(&self) -> impl std::fmt::Display + '_ {
+    DisplayFn(move |f| {
+      write!(f, "{{")?;
+      for (i, (net, defs)) in self.nets.iter().enumerate() {
+        if i != 0 {
+          write!(f, ",")?;
+        }
+        write!(f, "{}:[", JsonStr(net))?;
+        for (j, SourceDef { name, span }) in defs.iter().enumerate() {
+          if j != 0 {
+            write!(f, ",")?;
+          }
+          write!(f, "{{\"name\":{},\"span\":", JsonStr(name))?;
+          match span {
+            Some(span) => write!(f, "{}}}", span.display_json())?,
+            None => write!(f, "null}}")?,
+          }
+        }
+        write!(f, "]")?;
+      }
+      write!(f, "}}")
+    })
+  }
+}
+
+/// The name of the definition that the net, or one of the parts of a merged net, came from.
+fn source_name(net: &str, book: &Book) -> Name {
+  let nam = Name::new(net);
+  if net == ENTRY_POINT
+    && let Some(entrypoint) = &book.entrypoint
+  {
+    entrypoint.clone()
+  } else if net.trim_end_matches('_') == ENTRY_POINT && !book.defs.contains_key(&nam) {
+    // When the entrypoint has another name, the definition called `main` is renamed to make room for it.
+    Name::new(ENTRY_POINT)
+  } else {
+    nam.def_name_from_generated()
+  }
+}
+
+#[test]
+fn compile_source_map() {
+  use crate::compiler::Compiler;
+  use itertools::Itertools;
+
+  let code = "Z = @s @z z\nS = @x @s @z (s (x s z))\nget = @Nat (Nat @x (+ x 1) 0)\nMain = (get (S (S Z)))";
+  let res = Compiler::new().load_source("source_map", code).compile().unwrap();
+  let source = |net: &str| {
+    let defs = res.source_map.get(net).iter();
+    defs.map(|def| (def.name.to_string(), def.span.as_ref().map(|span| span.line))).collect_vec()
+  };
+
+  // The entrypoint is compiled to `main`, and the floated combinators come from the definition they were in.
+  assert_eq!(source("main"), [("Main".to_string(), Some(4))]);
+  assert_eq!(source("Main__C0"), [("Main".to_string(), Some(4))]);
+  assert_eq!(source("get__C0"), [("get".to_string(), Some(3))]);
+  assert_eq!(res.source_map.display_source("get__C0").to_string(), "get (source_map:3:1)");
+  assert!(res.core_book.keys().all(|net| !res.source_map.get(net).is_empty()));
+
+  let json = res.source_map.display_json().to_string();
+  assert!(
+    json.contains(r#""get__C0":[{"name":"get","span":{"file":"source_map","line":3,"column":1,"#),
+    "{json}"
+  );
+}
@@ -0,0 +1,61 @@
+use super::{
+  check_net_size::{count_nodes, MAX_NET_SIZE},
+  inline::{prefix_vars, rename_vars},
+};
+use crate::fun::{DefOpt, DefOpts, Name};
+use hvmc::ast::{Book, Tree};
+
+/// Inlines the net of a recursive definition into the floated match arms that call it in tail position.
+///
+/// Match arms are floated to their own definitions to keep them lazy, so each iteration of a loop
+/// like `Count n = switch n { 0: 0; _: (Count n-1) }` expands two nets, `Count` and `Count__C0`.
+/// With the match of `Count` copied into `Count__C0`, the arm references itself and each iteration
+/// expands only one net.
+///
+/// Only applies to definitions without redexes, since the arm would expand them eagerly,
+/// to arms with a single recursive call, and when the resulting net is not too large for hvm.
+/// Skips the definitions that disable `tail-calls` with an `#!opt(..)` pragma.
+pub fn inline_tail_calls(book: &mut Book, def_opts: &DefOpts) {
+  let arms = book.nets.keys().filter(|nam| nam.contains("__C")).cloned().collect::<Vec<_>>();
+  for arm_name in arms {
+    let arm_def = Name::new(&arm_name);
+    if !def_opts.allows(&arm_def, DefOpt::TailCalls) {
+      continue;
+    }
+    let def_name = arm_def.def_name_from_generated().to_string();
+    let Some(def) = book.nets.get(&def_name) else { continue };
+    if !def.redexes.is_empty() {
+      continue;
+    }
+
+    let arm = &book.nets[&arm_name];
+    let mut calls = arm.redexes.iter().enumerate().filter_map(|(i, (_, a, b))| match (a, b) {
+      (Tree::Ref { nam }, call) | (call, Tree::Ref { nam }) if *nam == def_name => Some((i, call)),
+      _ => None,
+    });
+    let (Some((i, call)), None) = (calls.next(), calls.next()) else { continue };
+    // In tail position, the recursive call returns to the same wire as the arm.
+    if !matches!(output(call), Tree::Var { .. }) || output(call) != output(&arm.root) {
+      continue;
+    }
+
+    let mut body = def.root.clone();
+    prefix_vars(&mut body, "tail_");
+    let mut arm = arm.clone();
+    arm.redexes[i] = (arm.redexes[i].0, body, call.clone());
+    if count_nodes(&arm) > MAX_NET_SIZE {
+      continue;
+    }
+    rename_vars(&mut arm);
+    book.nets.insert(arm_name, arm);
+  }
+}
+
+/// The port where a function net returns its result, after all its arguments.
+fn output(mut tree: &Tree) -> &Tree {
+  while let Tree::Ctr { lab: 0, ports } = tree {
+    let Some(last) = ports.last() else { break };
+    tree = last;
+  }
+  tree
+}
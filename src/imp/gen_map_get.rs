@@ -104,6 +104,10 @@ impl Stmt {
       Stmt::Open { typ: _, var: _, nxt } => {
         nxt.gen_map_get(id);
       }
+      Stmt::LocalDef { def, nxt } => {
+        def.body.gen_map_get(id);
+        nxt.gen_map_get(id);
+      }
       Stmt::Use { nam: _, val: bod, nxt } => {
         nxt.gen_map_get(id);
         let substitutions = bod.substitute_map_gets(id);
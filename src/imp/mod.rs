@@ -3,7 +3,10 @@ mod order_kwargs;
 pub mod parser;
 pub mod to_fun;
 
-use crate::fun::{CtrField, Name, Num, Op};
+use crate::fun::{
+  builtins::{NUM_SHOW, SCONS},
+  CtrField, Name, Num, Op, STRINGS,
+};
 use interner::global::GlobalString;
 
 #[derive(Clone, Debug)]
@@ -19,7 +22,8 @@ pub enum Expr {
   // {fun}({args},{kwargs},)
   Call { fun: Box<Expr>, args: Vec<Expr>, kwargs: Vec<(Name, Expr)> },
   // "lambda" {names}* ":" {bod}
-  Lam { names: Vec<(Name, bool)>, bod: Box<Expr> },
+  // Each name is a variable, an unscoped variable or an eraser.
+  Lam { names: Vec<AssignPattern>, bod: Box<Expr> },
   // {lhs} {op} {rhs}
   Bin { op: Op, lhs: Box<Expr>, rhs: Box<Expr> },
   // "\"" ... "\""
@@ -173,6 +177,11 @@ pub enum Stmt {
     val: Box<Expr>,
     nxt: Box<Stmt>,
   },
+  // "def" {name} "(" {params} ")" ":" {body} {nxt}
+  LocalDef {
+    def: Box<Definition>,
+    nxt: Box<Stmt>,
+  },
   #[default]
   Err,
 }
@@ -188,7 +197,8 @@ pub struct Variant {
 #[derive(Clone, Debug)]
 pub struct Definition {
   pub name: Name,
-  pub params: Vec<Name>,
+  // The erased parameters, written as `*`, are `None`.
+  pub params: Vec<Option<Name>>,
   pub body: Stmt,
 }
 
@@ -212,3 +222,21 @@ impl InPlaceOp {
     }
   }
 }
+
+impl Expr {
+  /// Builds an interpolated string, like "result: {x}", from the text before each interpolated
+  /// expression and the text after the last one. See [`crate::fun::Term::interpolated_str`].
+  pub fn interpolated_str(parts: Vec<(String, Expr)>, end: String) -> Expr {
+    let call = |nam: &str, args: Vec<Expr>| Expr::Call {
+      fun: Box::new(Expr::Var { nam: Name::new(nam) }),
+      args,
+      kwargs: vec![],
+    };
+    parts.into_iter().rfold(Expr::Str { val: STRINGS.get(end) }, |acc, (text, expr)| {
+      let acc = call(NUM_SHOW, vec![expr, acc]);
+      text
+        .chars()
+        .rfold(acc, |acc, chr| call(SCONS, vec![Expr::Num { val: Num::U24(chr as u32 & 0x00ff_ffff) }, acc]))
+    })
+  }
+}
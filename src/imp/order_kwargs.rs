@@ -84,6 +84,10 @@ impl Stmt {
       Stmt::Open { typ: _, var: _, nxt } => {
         nxt.order_kwargs(book)?;
       }
+      Stmt::LocalDef { def, nxt } => {
+        def.order_kwargs(book)?;
+        nxt.order_kwargs(book)?;
+      }
       Stmt::Use { nam: _, val: bod, nxt } => {
         bod.order_kwargs(book)?;
         nxt.order_kwargs(book)?;
@@ -197,7 +201,9 @@ fn get_args_def_or_ctr(name: &Name, book: &Book) -> Option<Vec<Name>> {
   if let Some(adt_nam) = book.ctrs.get(name) {
     Some(book.adts[adt_nam].ctrs[name].iter().map(|f| f.nam.clone()).collect())
   } else if let Some(def) = book.defs.get(name) {
-    Some(def.rules[0].pats.iter().flat_map(|p| p.binds().flatten().cloned()).collect())
+    // The erased parameters still take a positional argument, but can't be named.
+    let binds = def.rules[0].pats.iter().flat_map(|p| p.binds());
+    Some(binds.map(|nam| nam.clone().unwrap_or_else(|| Name::new("*"))).collect())
   } else {
     None
   }
@@ -1,11 +1,12 @@
 use crate::{
   fun::{
-    parser::{is_num_char, Indent, ParseResult, ParserCommons},
-    Adt, Book, CtrField, Name, Num, Op, STRINGS,
+    parser::{is_num_char, Indent, ParseResult, ParserCommons, BITWISE_NOT_ERR},
+    Adt, Book, CtrField, Name, Num, Op,
   },
   imp::{AssignPattern, Definition, Enum, Expr, InPlaceOp, MatchArm, Stmt, Variant},
   maybe_grow,
 };
+use std::ops::Range;
 use TSPL::Parser;
 
 const PREC: &[&[Op]] = &[
@@ -15,19 +16,44 @@ const PREC: &[&[Op]] = &[
   &[Op::EQL, Op::NEQ],
   &[Op::LTN],
   &[Op::GTN],
+  &[Op::SHL, Op::SHR],
   &[Op::ADD, Op::SUB],
   &[Op::MUL, Op::DIV, Op::REM],
   &[Op::POW],
 ];
 
+/// The characters that user-defined infix operators are made of.
+pub const INFIX_OP_CHARS: &str = "+-*/%<>=!&|^~?@";
+
+/// The number of precedence levels of infix operators, from 0 (`|`) to 9 (`**`).
+pub const INFIX_PREC_LEVELS: usize = PREC.len();
+
+/// An infix operator declared by the user, like `infixl 7 <+> = My/add`.
+#[derive(Clone, Debug)]
+pub struct InfixOp {
+  pub sym: String,
+  pub assoc: Assoc,
+  pub prec: usize,
+  pub fun: Name,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Assoc {
+  Left,
+  Right,
+  None,
+}
+
 pub struct PyParser<'i> {
   pub input: &'i str,
   pub index: usize,
+  /// The infix operators declared before the current definition.
+  pub infix_ops: Vec<InfixOp>,
 }
 
 impl<'a> PyParser<'a> {
   pub fn new(input: &'a str) -> Self {
-    Self { input, index: 0 }
+    Self { input, index: 0, infix_ops: vec![] }
   }
 }
 
@@ -70,6 +96,9 @@ impl<'a> Parser<'a> for PyParser<'a> {
         self.advance_one();
         continue;
       }
+      if self.skip_block_comment() > 0 {
+        continue;
+      }
       if c == '#' {
         while let Some(c) = self.peek_one() {
           if c != '\n' {
@@ -132,9 +161,8 @@ impl<'a> PyParser<'a> {
       '`' => Expr::Num { val: Num::U24(self.parse_quoted_symbol()?) },
       // String
       '\"' => {
-        let str = self.parse_quoted_string()?;
-        let val = STRINGS.get(str);
-        Expr::Str { val }
+        let (parts, end) = self.parse_interpolated_string(|p| p.parse_expr(false))?;
+        Expr::interpolated_str(parts, end)
       }
       // Char
       '\'' => {
@@ -147,6 +175,18 @@ impl<'a> PyParser<'a> {
         let nam = self.parse_bend_name()?;
         Expr::Chn { nam }
       }
+      // Bitwise not
+      '~' => {
+        self.advance_one();
+        let ini_idx = *self.index();
+        let val = self.parse_simple_expr(inline)?;
+        let end_idx = *self.index();
+        if let Expr::Num { val: Num::I24(_) | Num::F24(_) } = val {
+          return self.with_ctx(Err(BITWISE_NOT_ERR), ini_idx, end_idx);
+        }
+        let ones = Expr::Num { val: Num::U24(0xFF_FFFF) };
+        return Ok(Expr::Bin { op: Op::XOR, lhs: Box::new(val), rhs: Box::new(ones) });
+      }
       // Era
       '*' => {
         self.advance_one();
@@ -298,16 +338,18 @@ impl<'a> PyParser<'a> {
     }
   }
 
-  /// "λ" (<name> ","?)+ ":" <expr>
+  /// "λ" ((<name> | "$"<name> | "*") ","?)+ ":" <expr>
   /// | "open" <type> ":" <var>
   /// | <infix>
   fn parse_expr(&mut self, inline: bool) -> ParseResult<Expr> {
-    fn parse_lam_var(p: &mut PyParser) -> ParseResult<(Name, bool)> {
+    fn parse_lam_var(p: &mut PyParser) -> ParseResult<AssignPattern> {
       if p.starts_with("$") {
         p.advance_one();
-        Ok((p.parse_bend_name()?, true))
+        Ok(AssignPattern::Chn(p.parse_bend_name()?))
+      } else if p.try_consume_exactly("*") {
+        Ok(AssignPattern::Eraser)
       } else {
-        Ok((p.parse_bend_name()?, false))
+        Ok(AssignPattern::Var(p.parse_bend_name()?))
       }
     }
 
@@ -351,8 +393,25 @@ impl<'a> PyParser<'a> {
       }
       let mut lhs = self.parse_infix_expr(prec + 1, inline)?;
       self.skip_trivia_maybe_inline(inline);
-      while let Some(op) = self.peek_oper() {
-        if PREC[prec].iter().any(|r| *r == op) {
+      loop {
+        if let Some(user_op) = self.peek_infix_op() {
+          if user_op.prec != prec {
+            break;
+          }
+          let ini_idx = *self.index();
+          self.advance_many(user_op.sym.len());
+          let rhs_prec = if user_op.assoc == Assoc::Right { prec } else { prec + 1 };
+          let rhs = self.parse_infix_expr(rhs_prec, inline)?;
+          let fun = Box::new(Expr::Var { nam: user_op.fun.clone() });
+          lhs = Expr::Call { fun, args: vec![lhs, rhs], kwargs: vec![] };
+          self.advance_trivia_inline();
+          if user_op.assoc == Assoc::None && self.peek_prec() == Some(prec) {
+            let msg = format!("Operator '{}' is non-associative, use parentheses to chain it.", user_op.sym);
+            return self.with_ctx(Err(msg), ini_idx, ini_idx + user_op.sym.len());
+          }
+        } else if let Some(op) = self.peek_oper()
+          && PREC[prec].contains(&op)
+        {
           self.parse_oper()?;
           let rhs = self.parse_infix_expr(prec + 1, inline)?;
           lhs = Expr::Bin { op, lhs: Box::new(lhs), rhs: Box::new(rhs) };
@@ -365,6 +424,25 @@ impl<'a> PyParser<'a> {
     })
   }
 
+  /// Returns the user-defined operator at the current position, if it's
+  /// longer than any builtin operator that the input also starts with.
+  fn peek_infix_op(&mut self) -> Option<InfixOp> {
+    let builtin_len = self.peek_oper().map_or(0, |op| op.to_string().len());
+    let input = &self.input[self.index ..];
+    let user_op =
+      self.infix_ops.iter().filter(|op| input.starts_with(&op.sym)).max_by_key(|op| op.sym.len())?;
+    (user_op.sym.len() > builtin_len).then(|| user_op.clone())
+  }
+
+  /// Returns the precedence of the infix operator at the current position, if any.
+  fn peek_prec(&mut self) -> Option<usize> {
+    if let Some(user_op) = self.peek_infix_op() {
+      return Some(user_op.prec);
+    }
+    let op = self.peek_oper()?;
+    PREC.iter().position(|ops| ops.contains(&op))
+  }
+
   fn consume_indent_at_most(&mut self, expected: Indent) -> ParseResult<Indent> {
     let got = self.advance_newlines();
     match (expected, got) {
@@ -404,6 +482,8 @@ impl<'a> PyParser<'a> {
         self.parse_open(indent)
       } else if self.try_parse_keyword("use") {
         self.parse_use(indent)
+      } else if self.try_parse_keyword("def") {
+        self.parse_local_def(indent)
       } else {
         self.parse_assign(indent)
       }
@@ -627,25 +707,25 @@ impl<'a> PyParser<'a> {
     let ini_idx = *self.index();
     let (fst_case, fst_stmt, mut nxt_indent) = self.parse_switch_case(indent)?;
     let end_idx = *self.index();
-    if fst_case != Some(0) {
+    let Some(fst_range) = fst_case.filter(|range| range.start == 0) else {
       return self.expected_spanned("case 0", ini_idx, end_idx);
-    }
-    let mut arms = vec![fst_stmt];
-    let mut should_continue = fst_case == Some(0);
-    let mut expected_num = 1;
+    };
+    let mut arms = vec![fst_stmt; fst_range.len()];
+    let mut should_continue = true;
+    let mut expected_num = fst_range.end;
     while should_continue {
       if nxt_indent != *indent {
         return self.expected_indent(*indent, nxt_indent);
       }
       let (case, stmt, nxt_indent_) = self.parse_switch_case(indent)?;
       nxt_indent = nxt_indent_;
-      if let Some(case) = case {
-        if case != expected_num {
+      if let Some(range) = case {
+        if range.start != expected_num {
           return self.expected(&format!("case {}", expected_num));
         }
         should_continue = true;
-        arms.push(stmt);
-        expected_num += 1;
+        expected_num = range.end;
+        arms.extend(std::iter::repeat(stmt).take(range.len()));
       } else {
         should_continue = false;
         arms.push(stmt);
@@ -662,7 +742,9 @@ impl<'a> PyParser<'a> {
     }
   }
 
-  fn parse_switch_case(&mut self, indent: &mut Indent) -> ParseResult<(Option<u32>, Stmt, Indent)> {
+  /// Parses a case of a switch, which is either a number, a range of numbers `lo..hi` or `_`.
+  /// Returns the range of numbers of the case, not including its end.
+  fn parse_switch_case(&mut self, indent: &mut Indent) -> ParseResult<(Option<Range<u32>>, Stmt, Indent)> {
     self.parse_keyword("case")?;
     self.skip_trivia_inline();
     let case = if let Some(c) = self.peek_one() {
@@ -671,7 +753,10 @@ impl<'a> PyParser<'a> {
           self.advance_one();
           None
         }
-        c if c.is_ascii_digit() => Some(self.parse_u32()?),
+        c if c.is_ascii_digit() => {
+          let val = self.parse_u24()?;
+          Some(val .. self.parse_switch_range_end(val)?)
+        }
         _ => return self.expected("number or '_'"),
       }
     } else {
@@ -900,18 +985,36 @@ impl<'a> PyParser<'a> {
     Ok((stmt, nxt_indent))
   }
 
-  pub fn parse_def(&mut self, mut indent: Indent) -> ParseResult<(Definition, Indent)> {
+  /// "def" <name> "(" <params> ")" ":" <body> <nxt>
+  fn parse_local_def(&mut self, indent: &mut Indent) -> ParseResult<(Stmt, Indent)> {
+    let ini_idx = *self.index();
+    let (def, nxt_indent) = self.parse_def_body(*indent)?;
+    if nxt_indent != *indent {
+      let msg = format!("Expected a statement after the local function '{}'.", def.name);
+      let end_idx = *self.index();
+      return self.with_ctx(Err(msg), ini_idx, end_idx);
+    }
+    let (nxt, nxt_indent) = self.parse_statement(indent)?;
+    let stmt = Stmt::LocalDef { def: Box::new(def), nxt: Box::new(nxt) };
+    Ok((stmt, nxt_indent))
+  }
+
+  pub fn parse_def(&mut self, indent: Indent) -> ParseResult<(Definition, Indent)> {
     if indent != Indent::Val(0) {
       let msg = "Indentation error. Functions defined with 'def' must be at the start of the line.";
       let idx = *self.index();
       return self.with_ctx(Err(msg), idx, idx + 1);
     }
+    self.parse_def_body(indent)
+  }
 
+  /// Parses a function definition after the `def` keyword, with its body indented one level more than `indent`.
+  fn parse_def_body(&mut self, mut indent: Indent) -> ParseResult<(Definition, Indent)> {
     self.skip_trivia_inline();
     let name = self.parse_bend_name()?;
     self.skip_trivia_inline();
     let params = if self.starts_with("(") {
-      self.list_like(|p| p.parse_bend_name(), "(", ")", ",", true, 0)?
+      self.list_like(|p| p.parse_name_or_era(), "(", ")", ",", true, 0)?
     } else {
       vec![]
     };
@@ -1011,7 +1114,8 @@ impl<'a> PyParser<'a> {
     }
     def.order_kwargs(book)?;
     def.gen_map_get();
-    let def = def.to_fun()?;
+    let mut def = def.to_fun()?;
+    def.span = Some(self.span(ini_idx, end_idx));
     book.defs.insert(def.name.clone(), def);
     Ok(())
   }
@@ -1028,7 +1132,7 @@ impl<'a> PyParser<'a> {
       let msg = format!("Redefinition of type '{}'.", r#enum.name);
       return self.with_ctx(Err(msg), ini_idx, end_idx);
     }
-    let mut adt = Adt { ctrs: Default::default(), builtin };
+    let mut adt = Adt { ctrs: Default::default(), builtin, span: None, doc: None };
     for variant in r#enum.variants {
       if book.defs.contains_key(&variant.name) {
         let msg = format!("Redefinition of function '{}'.", variant.name);
@@ -1057,7 +1161,7 @@ impl<'a> PyParser<'a> {
       let msg = format!("Redefinition of type '{}'.", obj.name);
       return self.with_ctx(Err(msg), ini_idx, end_idx);
     }
-    let mut adt = Adt { ctrs: Default::default(), builtin };
+    let mut adt = Adt { ctrs: Default::default(), builtin, span: None, doc: None };
     if book.defs.contains_key(&obj.name) {
       let msg = format!("Redefinition of function '{}'.", obj.name);
       return self.with_ctx(Err(msg), ini_idx, end_idx);
@@ -12,9 +12,16 @@ impl Definition {
     };
 
     let rule =
-      fun::Rule { pats: self.params.into_iter().map(|param| fun::Pattern::Var(Some(param))).collect(), body };
+      fun::Rule { pats: self.params.into_iter().map(fun::Pattern::Var).collect(), guard: None, body };
 
-    let def = fun::Definition { name: self.name, rules: vec![rule], builtin: false };
+    let def = fun::Definition {
+      name: self.name,
+      typ: None,
+      rules: vec![rule],
+      builtin: false,
+      span: None,
+      doc: None,
+    };
     Ok(def)
   }
 }
@@ -280,6 +287,15 @@ impl Stmt {
         let term = fun::Term::Use { nam: Some(nam), val: Box::new(val.to_fun()), nxt: Box::new(nxt) };
         if let Some(pat) = nxt_pat { StmtToFun::Assign(pat, term) } else { StmtToFun::Return(term) }
       }
+      Stmt::LocalDef { def, nxt } => {
+        let (nxt_pat, nxt) = match nxt.into_fun()? {
+          StmtToFun::Return(term) => (None, term),
+          StmtToFun::Assign(pat, term) => (Some(pat), term),
+        };
+        let def = def.to_fun()?;
+        let term = fun::Term::Def { nam: def.name, rules: def.rules, nxt: Box::new(nxt) };
+        if let Some(pat) = nxt_pat { StmtToFun::Assign(pat, term) } else { StmtToFun::Return(term) }
+      }
       Stmt::Return { term } => StmtToFun::Return(term.to_fun()),
       Stmt::Err => unreachable!(),
     };
@@ -299,9 +315,9 @@ impl Expr {
         let args = args.into_iter().map(Self::to_fun);
         fun::Term::call(fun.to_fun(), args)
       }
-      Expr::Lam { names, bod } => names.into_iter().rfold(bod.to_fun(), |acc, (name, link)| fun::Term::Lam {
+      Expr::Lam { names, bod } => names.into_iter().rfold(bod.to_fun(), |acc, name| fun::Term::Lam {
         tag: fun::Tag::Static,
-        pat: Box::new(if link { fun::Pattern::Chn(name) } else { fun::Pattern::Var(Some(name)) }),
+        pat: Box::new(name.into_fun()),
         bod: Box::new(acc),
       }),
       Expr::Bin { op, lhs, rhs } => {
@@ -0,0 +1,518 @@
+//! Incremental compilation, which reuses the nets of the definitions that didn't change since
+//! a previous compilation, saved in a cache directory.
+//!
+//! Each definition is identified by a hash of its source code, of the datatypes and definitions it
+//! refers to, transitively, and of the options that change how it's compiled. The definitions found
+//! in the cache are replaced by stubs, so that they're not desugared again, and their nets are put
+//! back right after the program is compiled to nets, for the optimizations of the other ones to see.
+//!
+//! The warnings of the cached definitions are not reported again. Programs compiled with `merge` or
+//! `merge-defs` are never cached, since those merge definitions with the same body anywhere in the program.
+
+use crate::{
+  diagnostics::{Diagnostics, DiagnosticsConfig, MessageFormat},
+  fun::{format::FormatOpts, Book, DefOpt, Name, Pattern, Rule, Tag, Term, Type},
+  maybe_grow,
+  pipeline::{pass, Pipeline},
+  CompileOpts, CompileResult, StageHook, ENTRY_POINT, HVM1_ENTRY_POINT,
+};
+use hvmc::ast::{Net, Tree};
+use std::{
+  cell::RefCell,
+  collections::{BTreeSet, HashMap, HashSet},
+  hash::{Hash, Hasher},
+  path::Path,
+  rc::Rc,
+  str::FromStr,
+};
+
+/// The directory where the compiled definitions are cached, at the root of a project.
+pub const CACHE_DIR: &str = ".bend-cache";
+
+/// The nets compiled for a definition, as saved in the cache.
+struct CachedDef {
+  /// Whether the definition can be kept in the ones that refer to it when floating combinators.
+  safe: bool,
+  nets: Vec<(String, Net)>,
+}
+
+/// Compiles a book with the default [`Pipeline`], reusing the definitions cached in `cache_dir`
+/// and caching the ones that had to be compiled.
+pub fn compile(
+  book: &mut Book,
+  opts: CompileOpts,
+  diagnostics_cfg: DiagnosticsConfig,
+  args: Option<Vec<Term>>,
+  hook: &mut StageHook,
+  cache_dir: &Path,
+) -> Result<CompileResult, Diagnostics> {
+  let mut pipeline = Pipeline::default();
+  if opts.merge || opts.merge_defs {
+    return pipeline.compile(book, opts, diagnostics_cfg, args, hook);
+  }
+  // The seed is part of the source of the builtins that use it, so it's set before hashing them.
+  if let Some(seed) = opts.seed {
+    book.set_rand_seed(seed);
+  }
+
+  let (hashes, deps) = def_hashes(book, &opts, &diagnostics_cfg);
+  let mut cached = HashMap::new();
+  for (nam, hash) in &hashes {
+    if let Some(def) = load(cache_dir, *hash) {
+      cached.insert(nam.clone(), def);
+    }
+  }
+  keep_inferred_defs(book, &deps, &mut cached);
+
+  let mut stubs = HashMap::new();
+  for (nam, def) in cached {
+    // The type checker and the passes before floating the combinators only need the name
+    // of the definition, so it's left as an eraser until then.
+    book.defs[&nam].rules = vec![Rule { pats: vec![], guard: None, body: Term::Era }];
+    let opts = book.def_opts.defs.entry(nam.clone()).or_default();
+    opts.extend([(DefOpt::PreReduce, false), (DefOpt::TailCalls, false)]);
+    stubs.insert(nam, def);
+  }
+  let stubs = Rc::new(stubs);
+  let safety = Rc::new(RefCell::new(HashMap::new()));
+
+  // The stubs get a body that is as safe as the definition was, so that the combinators of the
+  // definitions that use them are floated like before, and then one that refers to the same
+  // definitions, so that the ones only used by them are not pruned.
+  let (stubs_, safety_) = (stubs.clone(), safety.clone());
+  pipeline.insert_before(
+    "float-combinators",
+    pass("stub-cached-safety", move |p| {
+      for (nam, def) in stubs_.iter() {
+        if !def.safe {
+          p.ctx.book.defs[nam].rule_mut().body = unsafe_body();
+        }
+      }
+      let book = &*p.ctx.book;
+      let safety = book
+        .defs
+        .keys()
+        .filter(|nam| !stubs_.contains_key(*nam))
+        .map(|nam| (nam.clone(), book.is_safe_ref(nam)));
+      *safety_.borrow_mut() = safety.collect();
+      Ok(())
+    }),
+  );
+  let stubs_ = stubs.clone();
+  pipeline.insert_before(
+    "prune",
+    pass("stub-cached-refs", move |p| {
+      for (stub, def) in stubs_.iter() {
+        let refs = def.nets.iter().flat_map(|(_, net)| net_refs(net));
+        let refs = refs.map(|nam| Name::new(nam).def_name_from_generated()).collect::<BTreeSet<_>>();
+        let refs = refs.into_iter().filter(|nam| nam != stub && p.ctx.book.defs.contains_key(nam));
+        let body = refs.fold(Term::Era, |fun, nam| Term::App {
+          tag: Tag::Static,
+          fun: Box::new(fun),
+          arg: Box::new(Term::Ref { nam }),
+        });
+        p.ctx.book.defs[stub].rule_mut().body = body;
+      }
+      Ok(())
+    }),
+  );
+  let stubs_ = stubs.clone();
+  pipeline.insert_after(
+    "to-nets",
+    pass("load-cached-nets", move |p| {
+      // The cached nets can refer to the generated definitions of other cached ones,
+      // which are loaded even if the stub of their definition was pruned.
+      let mut stack =
+        stubs_.keys().filter(|nam| p.nets.contains_key(nam.as_ref())).cloned().collect::<Vec<_>>();
+      let mut loaded = HashSet::new();
+      while let Some(nam) = stack.pop() {
+        if !loaded.insert(nam.clone()) {
+          continue;
+        }
+        for (net_nam, net) in &stubs_[&nam].nets {
+          for r in net_refs(net) {
+            let def_name = Name::new(r).def_name_from_generated();
+            if !p.nets.contains_key(r) && stubs_.contains_key(&def_name) {
+              stack.push(def_name);
+            }
+          }
+          p.nets.insert(net_nam.clone(), net.clone());
+        }
+      }
+      Ok(())
+    }),
+  );
+
+  let res = pipeline.compile(book, opts, diagnostics_cfg, args, hook)?;
+
+  let mut compiled = HashMap::<Name, Vec<(String, Net)>>::new();
+  for (nam, net) in res.core_book.iter() {
+    let def_name = Name::new(nam).def_name_from_generated();
+    if hashes.contains_key(&def_name) && !stubs.contains_key(&def_name) {
+      compiled.entry(def_name).or_default().push((nam.clone(), net.clone()));
+    }
+  }
+  let safety = safety.borrow();
+  for (nam, nets) in compiled {
+    let safe = safety.get(&nam).copied().unwrap_or(false);
+    save(cache_dir, hashes[&nam], &CachedDef { safe, nets });
+  }
+  Ok(res)
+}
+
+/// Hashes each definition that can be cached, returning the hashes along with the
+/// definitions that each one refers to.
+///
+/// The hash of a definition includes the ones of the definitions it refers to, so it changes
+/// whenever any of them changes. The ones that refer to each other are hashed together.
+fn def_hashes(
+  book: &Book,
+  opts: &CompileOpts,
+  diagnostics_cfg: &DiagnosticsConfig,
+) -> (HashMap<Name, u64>, HashMap<Name, Vec<Name>>) {
+  // The entrypoint gets the arguments of the program, so it's always compiled again,
+  // and so are the definitions named like it, which are renamed depending on which one it is.
+  let entrypoints = [Name::new(ENTRY_POINT), Name::new(HVM1_ENTRY_POINT)];
+  let entrypoint = match &opts.entrypoint {
+    Some(entrypoint) => entrypoint.clone(),
+    None => entrypoints.iter().find(|nam| book.defs.contains_key(*nam)).cloned().unwrap_or_default(),
+  };
+
+  // What changes how every definition is compiled.
+  let opts = CompileOpts { seed: None, cache_dir: None, ..opts.clone() };
+  let diagnostics_cfg = DiagnosticsConfig {
+    verbose: false,
+    message_format: MessageFormat::Human,
+    color: false,
+    ..*diagnostics_cfg
+  };
+  let enabled_by_defs =
+    DefOpt::ALL.iter().filter(|opt| book.def_opts.defs.values().any(|opts| opts.contains(&(**opt, true))));
+  let global = format!(
+    "{}\n{opts:?}\n{diagnostics_cfg:?}\n{entrypoint}\n{:?}\n{:?}",
+    env!("CARGO_PKG_VERSION"),
+    book.lints.file,
+    enabled_by_defs.collect::<Vec<_>>(),
+  );
+
+  // Definitions like `Type/bind` can be used implicitly through the name of their type.
+  let mut by_type = HashMap::<&str, Vec<&Name>>::new();
+  for nam in book.defs.keys() {
+    if let Some((typ, _)) = nam.rsplit_once('/') {
+      by_type.entry(typ).or_default().push(nam);
+    }
+  }
+  // Records are found by the names of their fields.
+  let mut by_field = HashMap::<&Name, Vec<&Name>>::new();
+  for (adt_nam, adt) in &book.adts {
+    for field in adt.ctrs.values().flatten() {
+      by_field.entry(&field.nam).or_default().push(adt_nam);
+    }
+  }
+
+  let mut own = HashMap::new();
+  let mut deps = HashMap::new();
+  for (nam, def) in &book.defs {
+    let mut src = format!("{def}\n");
+    if let Some(typ) = &def.typ {
+      src.push_str(&format!("{typ}\n"));
+    }
+    src.push_str(&format!(
+      "{:?}\n{:?}\n{:?}\n{}\n",
+      book.def_opts.defs.get(nam),
+      book.float_modes.get(nam),
+      book.lints.defs.get(nam),
+      def.builtin
+    ));
+
+    let mut uses = Uses::default();
+    for rule in &def.rules {
+      rule.pats.iter().for_each(|pat| uses.pattern(pat));
+      rule.guard.iter().chain([&rule.body]).for_each(|term| uses.term(term));
+    }
+    if let Some(typ) = &def.typ {
+      uses.typ(typ);
+    }
+
+    let mut adts = BTreeSet::new();
+    let mut refs = BTreeSet::new();
+    for nam in uses.names {
+      if book.defs.contains_key(&nam) {
+        refs.insert(nam.clone());
+      }
+      if let Some(adt) = book.ctrs.get(&nam) {
+        adts.insert(adt.clone());
+      }
+      if book.adts.contains_key(&nam) {
+        refs.extend(by_type.get(nam.as_ref()).into_iter().flatten().map(|nam| (*nam).clone()));
+        adts.insert(nam);
+      }
+    }
+    for field in uses.fields {
+      adts.extend(by_field.get(&field).into_iter().flatten().map(|adt| (*adt).clone()));
+    }
+    for adt in adts {
+      src.push_str(&book.adts[&adt].format(&adt, FormatOpts::default()));
+      src.push('\n');
+    }
+    own.insert(nam.clone(), hash(&src));
+    deps.insert(nam.clone(), refs.into_iter().collect::<Vec<_>>());
+  }
+
+  let mut sccs =
+    Sccs { deps: &deps, own: &own, index: HashMap::new(), stack: vec![], hashes: HashMap::new() };
+  for nam in book.defs.keys() {
+    if !sccs.index.contains_key(nam) {
+      sccs.visit(nam);
+    }
+  }
+  let hashes = sccs
+    .hashes
+    .into_iter()
+    .filter(|(nam, _)| *nam != entrypoint && !entrypoints.contains(nam))
+    .map(|(nam, scc_hash)| {
+      // A `Name` hashes its index in the global string pool, which depends on the order the
+      // names were first seen in, so only the text goes into the cache keys.
+      let hash = hash(&(&global, scc_hash, nam.as_ref()));
+      (nam, hash)
+    })
+    .collect();
+  (hashes, deps)
+}
+
+/// Finds the strongly connected components of the definitions with Tarjan's algorithm,
+/// hashing each one together with the ones it refers to.
+struct Sccs<'a> {
+  deps: &'a HashMap<Name, Vec<Name>>,
+  own: &'a HashMap<Name, u64>,
+  /// The order in which each definition was visited, and the lowest one reachable from it.
+  index: HashMap<Name, (usize, usize)>,
+  stack: Vec<Name>,
+  /// The hash of the component of each definition, once it's been found.
+  hashes: HashMap<Name, u64>,
+}
+
+impl Sccs<'_> {
+  fn visit(&mut self, nam: &Name) -> usize {
+    maybe_grow(|| {
+      let idx = self.index.len();
+      self.index.insert(nam.clone(), (idx, idx));
+      self.stack.push(nam.clone());
+      let mut low = idx;
+      for dep in &self.deps[nam] {
+        match self.index.get(dep) {
+          None => low = low.min(self.visit(dep)),
+          Some(&(dep_idx, _)) if !self.hashes.contains_key(dep) => low = low.min(dep_idx),
+          Some(_) => {}
+        }
+      }
+      self.index.insert(nam.clone(), (idx, low));
+
+      if low == idx {
+        let pos = self.stack.iter().rposition(|n| n == nam).unwrap();
+        let members = self.stack.split_off(pos);
+        let mut own = members.iter().map(|nam| (nam.as_ref(), self.own[nam])).collect::<Vec<_>>();
+        own.sort();
+        // The components it refers to were all found before it.
+        let mut outer = BTreeSet::<u64>::new();
+        for member in &members {
+          outer.extend(self.deps[member].iter().filter_map(|dep| self.hashes.get(dep)));
+        }
+        let scc_hash = hash(&(own, outer));
+        for member in members {
+          self.hashes.insert(member, scc_hash);
+        }
+      }
+      low
+    })
+  }
+}
+
+/// The names used by a definition, which can be of other definitions, constructors or datatypes.
+#[derive(Default)]
+struct Uses {
+  names: Vec<Name>,
+  /// The record fields it accesses, from which the records it uses are found.
+  fields: Vec<Name>,
+}
+
+impl Uses {
+  fn term(&mut self, term: &Term) {
+    maybe_grow(|| {
+      match term {
+        // Before the references are resolved, they're still variables.
+        Term::Var { nam } => {
+          self.names.push(nam.clone());
+          // `p.x` may be an access to the field `x` of `p`.
+          self.fields.extend(nam.split('.').skip(1).map(Name::new));
+        }
+        Term::Ref { nam } => self.names.push(nam.clone()),
+        Term::Do { typ, .. } | Term::Open { typ, .. } => self.names.push(typ.clone()),
+        Term::Mat { arms, .. } | Term::Fold { arms, .. } => {
+          self.names.extend(arms.iter().filter_map(|arm| arm.0.clone()));
+        }
+        Term::Get { fld, .. } => self.fields.push(fld.clone()),
+        Term::Upd { flds, .. } => self.fields.extend(flds.iter().map(|(fld, _)| fld.clone())),
+        Term::Lam { pat, .. } | Term::Let { pat, .. } | Term::Ask { pat, .. } => self.pattern(pat),
+        Term::PatMat { arms, .. } => arms.iter().for_each(|arm| self.pattern(&arm.0)),
+        Term::Def { rules, .. } => rules.iter().flat_map(|rule| &rule.pats).for_each(|pat| self.pattern(pat)),
+        _ => {}
+      }
+      for child in term.children() {
+        self.term(child);
+      }
+    })
+  }
+
+  fn pattern(&mut self, pat: &Pattern) {
+    maybe_grow(|| {
+      match pat {
+        // A constructor without fields is still a variable before the patterns are resolved.
+        Pattern::Ctr(nam, _) | Pattern::Var(Some(nam)) => self.names.push(nam.clone()),
+        _ => {}
+      }
+      for child in pat.children() {
+        self.pattern(child);
+      }
+    })
+  }
+
+  fn typ(&mut self, typ: &Type) {
+    if let Type::Var(nam) | Type::Ctr(nam, _) = typ {
+      self.names.push(nam.clone());
+    }
+    for child in typ.children() {
+      self.typ(child);
+    }
+  }
+}
+
+/// Keeps the definitions without a signature that are inferred by the type checker, when checking the
+/// definitions with a signature that are compiled again, since they're inferred from their body.
+fn keep_inferred_defs(book: &Book, deps: &HashMap<Name, Vec<Name>>, cached: &mut HashMap<Name, CachedDef>) {
+  let checked = book.defs.values().filter(|def| def.typ.is_some() && !cached.contains_key(&def.name));
+  let mut stack = checked.map(|def| def.name.clone()).collect::<Vec<_>>();
+  let mut seen = HashSet::new();
+  while let Some(nam) = stack.pop() {
+    for dep in &deps[&nam] {
+      if book.defs[dep].typ.is_none() && seen.insert(dep.clone()) {
+        cached.remove(dep);
+        stack.push(dep.clone());
+      }
+    }
+  }
+}
+
+/// `λx (x *)`, a body that is never safe to keep in another definition.
+fn unsafe_body() -> Term {
+  let var = Name::new("x");
+  let app =
+    Term::App { tag: Tag::Static, fun: Box::new(Term::Var { nam: var.clone() }), arg: Box::new(Term::Era) };
+  Term::Lam { tag: Tag::Static, pat: Box::new(Pattern::Var(Some(var))), bod: Box::new(app) }
+}
+
+fn net_refs(net: &Net) -> Vec<&str> {
+  fn go<'a>(tree: &'a Tree, refs: &mut Vec<&'a str>) {
+    maybe_grow(|| {
+      if let Tree::Ref { nam } = tree {
+        refs.push(nam);
+      }
+      for child in tree.children() {
+        go(child, refs);
+      }
+    })
+  }
+  let mut refs = vec![];
+  for tree in net.trees() {
+    go(tree, &mut refs);
+  }
+  refs
+}
+
+fn hash(value: &impl Hash) -> u64 {
+  let mut hasher = StableHasher::default();
+  value.hash(&mut hasher);
+  hasher.finish()
+}
+
+/// A 64-bit FNV-1a hasher. Unlike [`std::collections::hash_map::DefaultHasher`], whose algorithm
+/// may change between Rust releases, it gives the same hashes to the cache of every build of Bend.
+struct StableHasher(u64);
+
+impl Default for StableHasher {
+  fn default() -> Self {
+    Self(0xcbf2_9ce4_8422_2325)
+  }
+}
+
+impl Hasher for StableHasher {
+  fn write(&mut self, bytes: &[u8]) {
+    for byte in bytes {
+      self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+    }
+  }
+
+  fn finish(&self) -> u64 {
+    self.0
+  }
+}
+
+/// Reads a cached definition. The ones that can't be read are compiled again.
+fn load(cache_dir: &Path, hash: u64) -> Option<CachedDef> {
+  let text = std::fs::read_to_string(cache_dir.join(format!("{hash:016x}.hvm"))).ok()?;
+  let (safe, nets) = text.split_once('\n')?;
+  let safe = match safe {
+    "safe" => true,
+    "unsafe" => false,
+    _ => return None,
+  };
+  let nets = hvmc::ast::Book::from_str(nets).ok()?;
+  Some(CachedDef { safe, nets: nets.nets.into_iter().collect() })
+}
+
+/// Writes a compiled definition to the cache. Failing to do it only means that it's compiled again next time.
+fn save(cache_dir: &Path, hash: u64, def: &CachedDef) {
+  let mut text = if def.safe { "safe\n" } else { "unsafe\n" }.to_string();
+  for (nam, net) in &def.nets {
+    text.push_str(&format!("@{nam} = {net}\n"));
+  }
+  let _ = std::fs::create_dir_all(cache_dir);
+  let _ = std::fs::write(cache_dir.join(format!("{hash:016x}.hvm")), text);
+}
+
+#[test]
+fn incremental_compile() {
+  use crate::compiler::Compiler;
+
+  let dir = std::env::temp_dir().join(format!("bend_incremental_{}", std::process::id()));
+  let _ = std::fs::remove_dir_all(&dir);
+  let code = |n: u32| {
+    format!(
+      "data Tree = (Node ~left ~right) | (Leaf val)\n\
+       sum (Tree/Node l r) = (+ (sum l) (sum r))\nsum (Tree/Leaf v) = v\n\
+       leaf = λv (Tree/Leaf v)\n\
+       main = (sum (Tree/Node (leaf {n}) (leaf 2)))"
+    )
+  };
+  let compile = |n: u32, cache_dir: Option<&Path>| {
+    let compiler = Compiler::new().load_source("incremental", code(n));
+    let compiler = match cache_dir {
+      Some(cache_dir) => compiler.incremental(cache_dir),
+      None => compiler,
+    };
+    compiler.compile().unwrap().core_book.to_string()
+  };
+  let cached = || std::fs::read_dir(&dir).map(|dir| dir.count()).unwrap_or(0);
+
+  // The cached definitions give the same program as compiling everything again.
+  assert_eq!(compile(1, Some(&dir)), compile(1, None));
+  let after_first = cached();
+  assert!(after_first > 0);
+  assert_eq!(compile(1, Some(&dir)), compile(1, None));
+  assert_eq!(cached(), after_first);
+  // Only `main` changed, and it's never cached, since it gets the arguments of the program.
+  assert_eq!(compile(3, Some(&dir)), compile(3, None));
+  assert_eq!(cached(), after_first);
+
+  std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,24 +1,41 @@
 #![feature(box_patterns)]
 #![feature(let_chains)]
 
-use crate::fun::{book_to_nets, net_to_term::net_to_term, term_to_net::Labels, Book, Ctx, Term};
-use diagnostics::{Diagnostics, DiagnosticsConfig, ERR_INDENT_SIZE};
-use hvm::{
-  add_recursive_priority::add_recursive_priority,
-  check_net_size::{check_net_sizes, MAX_NET_SIZE},
-  mutual_recursion,
-};
+use crate::fun::{display::DisplayOpts, net_to_term::net_to_term, term_to_net::Labels, Book, Name, Term};
+use diagnostics::{Diagnostics, DiagnosticsConfig};
+use hvm::{check_net_size::MAX_NET_SIZE, source_map::SourceMap};
 use hvmc::ast::Net;
+use indexmap::IndexMap;
 use net::hvmc_to_net::hvmc_to_net;
-use std::{process::Output, str::FromStr};
+use pipeline::{PassTiming, Pipeline};
+use std::{
+  fmt::Display,
+  path::{Path, PathBuf},
+  process::{Child, Output, Stdio},
+  str::FromStr,
+  sync::atomic::{AtomicUsize, Ordering},
+  time::{Duration, Instant},
+};
 
+pub mod codegen;
+pub mod compiler;
+pub mod config;
 pub mod diagnostics;
+pub mod embed;
 pub mod fun;
 pub mod hvm;
 pub mod imp;
+pub mod incremental;
+#[cfg(feature = "lsp")]
+pub mod lsp;
 pub mod net;
+pub mod pipeline;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-pub use fun::load_book::load_file_to_book;
+pub use fun::load_book::{
+  load_code_to_book, load_file_to_book, load_file_to_book_with_root, load_source_to_book,
+};
 
 pub const ENTRY_POINT: &str = "main";
 pub const HVM1_ENTRY_POINT: &str = "Main";
@@ -33,43 +50,54 @@ pub fn check_book(
   Ok(res.diagnostics)
 }
 
+/// A named point of the compilation pipeline, where the program can be inspected with a hook.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+  /// The program as it was parsed, before any transformation.
+  Ast,
+  /// The program once its variables are linearized, before the optimizing passes.
+  Linearized,
+  /// The program after all the lambda-term level passes.
+  Desugared,
+  /// The compiled hvmc book.
+  Hvm,
+}
+
+impl Display for Stage {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Stage::Ast => write!(f, "ast"),
+      Stage::Linearized => write!(f, "linearized"),
+      Stage::Desugared => write!(f, "desugared"),
+      Stage::Hvm => write!(f, "hvm"),
+    }
+  }
+}
+
+/// Called with the program at each [`Stage`] of the pipeline that is reached.
+pub type StageHook<'a> = dyn FnMut(Stage, &dyn Display) + 'a;
+
 pub fn compile_book(
   book: &mut Book,
   opts: CompileOpts,
   diagnostics_cfg: DiagnosticsConfig,
   args: Option<Vec<Term>>,
 ) -> Result<CompileResult, Diagnostics> {
-  let mut diagnostics = desugar_book(book, opts.clone(), diagnostics_cfg, args)?;
-
-  let (mut hvm_book, labels) = book_to_nets(book, &mut diagnostics)?;
-
-  if opts.eta {
-    hvm_book.values_mut().for_each(Net::eta_reduce);
-  }
-
-  mutual_recursion::check_cycles(&hvm_book, &mut diagnostics)?;
-  if opts.eta {
-    hvm_book.values_mut().for_each(Net::eta_reduce);
-  }
-
-  if opts.inline {
-    diagnostics.start_pass();
-    if let Err(e) = hvm_book.inline() {
-      diagnostics.add_book_error(format!("During inlining:\n{:ERR_INDENT_SIZE$}{}", "", e));
-    }
-    diagnostics.fatal(())?;
-  }
+  compile_book_with_hook(book, opts, diagnostics_cfg, args, &mut |_, _| {})
+}
 
-  if opts.prune {
-    let prune_entrypoints = vec![book.hvmc_entrypoint().to_string()];
-    hvm_book.prune(&prune_entrypoints);
+/// Like [`compile_book`], but passes the program to `hook` after each [`Stage`].
+pub fn compile_book_with_hook(
+  book: &mut Book,
+  opts: CompileOpts,
+  diagnostics_cfg: DiagnosticsConfig,
+  args: Option<Vec<Term>>,
+  hook: &mut StageHook,
+) -> Result<CompileResult, Diagnostics> {
+  if let Some(cache_dir) = opts.cache_dir.clone() {
+    return incremental::compile(book, opts, diagnostics_cfg, args, hook, &cache_dir);
   }
-
-  check_net_sizes(&hvm_book, &mut diagnostics)?;
-
-  add_recursive_priority(&mut hvm_book);
-
-  Ok(CompileResult { core_book: hvm_book, labels, diagnostics })
+  Pipeline::default().compile(book, opts, diagnostics_cfg, args, hook)
 }
 
 pub fn desugar_book(
@@ -78,72 +106,18 @@ pub fn desugar_book(
   diagnostics_cfg: DiagnosticsConfig,
   args: Option<Vec<Term>>,
 ) -> Result<Diagnostics, Diagnostics> {
-  let mut ctx = Ctx::new(book, diagnostics_cfg);
-
-  ctx.check_shared_names();
-
-  ctx.set_entrypoint();
-
-  ctx.book.encode_adts();
-
-  ctx.fix_match_defs()?;
-
-  ctx.apply_args(args)?;
-
-  ctx.desugar_open()?;
-
-  ctx.book.encode_builtins();
-
-  ctx.resolve_refs()?;
-
-  ctx.desugar_match_defs()?;
-
-  ctx.fix_match_terms()?;
-
-  ctx.desugar_bend()?;
-  ctx.desugar_fold()?;
-  ctx.desugar_do_blocks()?;
-
-  ctx.check_unbound_vars()?;
-
-  ctx.book.make_var_names_unique();
-
-  // Auto match linearization
-  match opts.linearize_matches {
-    OptLevel::Disabled => (),
-    OptLevel::Alt => ctx.book.linearize_match_binds(),
-    OptLevel::Enabled => ctx.book.linearize_matches(),
-  }
-  // Manual match linearization
-  ctx.book.linearize_match_with();
-
-  ctx.book.encode_matches();
-
-  // sanity check
-  ctx.check_unbound_vars()?;
-
-  ctx.book.make_var_names_unique();
-  ctx.book.apply_use();
-  ctx.book.make_var_names_unique();
-  ctx.book.linearize_vars();
-
-  // sanity check
-  ctx.check_unbound_vars()?;
-
-  // Optimizing passes
-  if opts.float_combinators {
-    ctx.book.float_combinators(MAX_NET_SIZE);
-  }
-
-  ctx.prune(opts.prune);
-
-  if opts.merge {
-    ctx.book.merge_definitions();
-  }
-
-  ctx.book.make_var_names_unique();
+  desugar_book_with_hook(book, opts, diagnostics_cfg, args, &mut |_, _| {})
+}
 
-  if !ctx.info.has_errors() { Ok(ctx.info) } else { Err(ctx.info) }
+/// Like [`desugar_book`], but passes the program to `hook` after each [`Stage`].
+pub fn desugar_book_with_hook(
+  book: &mut Book,
+  opts: CompileOpts,
+  diagnostics_cfg: DiagnosticsConfig,
+  args: Option<Vec<Term>>,
+  hook: &mut StageHook,
+) -> Result<Diagnostics, Diagnostics> {
+  Pipeline::default().desugar(book, opts, diagnostics_cfg, args, hook)
 }
 
 pub fn run_book_with_fn(
@@ -155,7 +129,7 @@ pub fn run_book_with_fn(
   cmd: &str,
   arg_io: bool,
 ) -> Result<Option<(Term, String, Diagnostics)>, Diagnostics> {
-  let CompileResult { core_book, labels, diagnostics } =
+  let CompileResult { core_book, labels, diagnostics, .. } =
     compile_book(&mut book, compile_opts.clone(), diagnostics_cfg, args)?;
 
   // TODO: Printing should be taken care by the cli module, but we'd
@@ -163,20 +137,57 @@ pub fn run_book_with_fn(
   // cancel the run if a problem is detected.
   eprint!("{diagnostics}");
 
+  run_hvm_book(&core_book, &book, &labels, run_opts, diagnostics_cfg, cmd, arg_io)
+}
+
+/// Runs a compiled program with hvm, reading back its result with the datatypes
+/// of `book` and the labels given to the nets when they were compiled.
+pub fn run_hvm_book(
+  core_book: &hvmc::ast::Book,
+  book: &Book,
+  labels: &Labels,
+  run_opts: RunOpts,
+  diagnostics_cfg: DiagnosticsConfig,
+  cmd: &str,
+  arg_io: bool,
+) -> Result<Option<(Term, String, Diagnostics)>, Diagnostics> {
   let out_path = ".out.hvm";
   std::fs::write(out_path, core_book.to_string()).map_err(|x| x.to_string())?;
-  let run_fn = |out_path: &str| {
-    let mut process = std::process::Command::new("hvm");
-    process.arg(cmd).arg(out_path);
+  let run_fn = |mut process: std::process::Command| {
     if arg_io {
       process.arg("--io");
-      process.stdout(std::process::Stdio::inherit());
-      process.spawn()?.wait_with_output()
+      process.stdout(Stdio::inherit());
     } else {
-      process.output()
+      process.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+    let child = process.spawn()?;
+    match run_opts.timeout {
+      Some(timeout) => wait_with_timeout(child, timeout),
+      None => child.wait_with_output().map(Some),
     }
   };
-  let Output { status, stdout, stderr } = run_fn(out_path).map_err(|e| format!("While running hvm: {e}"))?;
+  let output = match run_opts.threads {
+    Some(threads) if cmd == "run-c" => {
+      // Built in a directory of its own, which is removed once the program has run.
+      static BUILDS: AtomicUsize = AtomicUsize::new(0);
+      let build = BUILDS.fetch_add(1, Ordering::Relaxed);
+      let dir = std::env::temp_dir().join(format!("bend_c_runtime_{}_{build}", std::process::id()));
+      let output = build_c_runtime(out_path, threads, &dir).and_then(|bin_path| {
+        run_fn(std::process::Command::new(bin_path)).map_err(|e| format!("While running hvm: {e}"))
+      });
+      let _ = std::fs::remove_dir_all(&dir);
+      output?
+    }
+    _ => {
+      let mut process = std::process::Command::new("hvm");
+      process.arg(cmd).arg(out_path);
+      run_fn(process).map_err(|e| format!("While running hvm: {e}"))?
+    }
+  };
+  let Some(Output { status, stdout, stderr }) = output else {
+    let timeout = run_opts.timeout.unwrap_or_default().as_secs_f64();
+    return Err(format!("hvm was stopped after reaching the time limit of {timeout}s.").into());
+  };
 
   let out = String::from_utf8_lossy(&stdout);
   let err = String::from_utf8_lossy(&stderr);
@@ -196,10 +207,104 @@ pub fn run_book_with_fn(
     return Err(format!("Error reading result from hvm. Output :\n{}{}{}", err, status, out).into());
   };
 
-  let (term, diags) = readback_hvm_net(&net, &book, &labels, run_opts.linear_readback);
+  let (term, mut diags) =
+    readback_hvm_net(&net, book, labels, run_opts.linear_readback, run_opts.raw_readback);
+  diags.config = diagnostics_cfg;
   Ok(Some((term, stats.to_string(), diags)))
 }
 
+/// Parses a program that was already compiled to hvm, like the output of `bend gen-hvm`.
+pub fn parse_hvm_book(code: &str) -> Result<hvmc::ast::Book, String> {
+  hvmc::ast::Book::from_str(code).map_err(|e| format!("While parsing the hvm program: {e}"))
+}
+
+/// Builds the C runtime of hvm for the program at `out_path` in `dir`, running on at most `threads`
+/// threads, and returns the path of the executable.
+///
+/// hvm fixes the number of threads of its C runtime when it's built, as the log2 of a power of two in `TPC_L2`,
+/// so the C code that it generates for the program is compiled again with the requested number.
+fn build_c_runtime(out_path: &str, threads: usize, dir: &Path) -> Result<PathBuf, String> {
+  const TPC_L2: &str = "#define TPC_L2 ";
+  let c_path = dir.join("out.c");
+  let bin_path = dir.join("out.bin");
+
+  if !threads.is_power_of_two() {
+    return Err(format!("The number of threads must be a power of two, but it was {threads}."));
+  }
+  let out = std::process::Command::new("hvm")
+    .arg("gen-c")
+    .arg(out_path)
+    .output()
+    .map_err(|e| format!("While running hvm: {e}"))?;
+  if !out.status.success() {
+    let err = String::from_utf8_lossy(&out.stderr);
+    return Err(format!("Error generating the C runtime with hvm:\n{err}"));
+  }
+  let mut found = false;
+  let code = String::from_utf8_lossy(&out.stdout)
+    .lines()
+    .map(|line| {
+      if line.starts_with(TPC_L2) {
+        found = true;
+        format!("{TPC_L2}{}", threads.ilog2())
+      } else {
+        line.to_string()
+      }
+    })
+    .collect::<Vec<_>>()
+    .join("\n");
+  if !found {
+    return Err("The C runtime generated by hvm doesn't have a number of threads to set.".to_string());
+  }
+  std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+  std::fs::write(&c_path, code).map_err(|e| e.to_string())?;
+
+  let out = std::process::Command::new("cc")
+    .arg(&c_path)
+    .arg("-o")
+    .arg(&bin_path)
+    .args(["-O2", "-lm", "-lpthread"])
+    .output()
+    .map_err(|e| format!("While running the C compiler: {e}"))?;
+  if !out.status.success() {
+    let err = String::from_utf8_lossy(&out.stderr);
+    return Err(format!("Error compiling the C runtime:\n{err}"));
+  }
+  Ok(bin_path)
+}
+
+/// Waits for a child process like [`Child::wait_with_output`], killing it if it runs for longer
+/// than `timeout`. Returns `None` if it was killed.
+fn wait_with_timeout(mut child: Child, timeout: Duration) -> std::io::Result<Option<Output>> {
+  // The pipes are read while waiting, so that the child doesn't block when one of them fills up.
+  fn read_pipe(pipe: Option<impl std::io::Read + Send + 'static>) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+      let mut buf = vec![];
+      if let Some(mut pipe) = pipe {
+        let _ = pipe.read_to_end(&mut buf);
+      }
+      buf
+    })
+  }
+  let stdout = read_pipe(child.stdout.take());
+  let stderr = read_pipe(child.stderr.take());
+  let start = Instant::now();
+  let status = loop {
+    if let Some(status) = child.try_wait()? {
+      break status;
+    }
+    if start.elapsed() >= timeout {
+      child.kill()?;
+      child.wait()?;
+      return Ok(None);
+    }
+    std::thread::sleep(Duration::from_millis(10));
+  };
+  let stdout = stdout.join().unwrap_or_default();
+  let stderr = stderr.join().unwrap_or_default();
+  Ok(Some(Output { status, stdout, stderr }))
+}
+
 pub fn run_book(
   book: Book,
   run_opts: RunOpts,
@@ -210,20 +315,56 @@ pub fn run_book(
   run_book_with_fn(book, run_opts, compile_opts, diagnostics_cfg, args, "run", false).map(Option::unwrap)
 }
 
-pub fn readback_hvm_net(net: &Net, book: &Book, labels: &Labels, linear: bool) -> (Term, Diagnostics) {
+/// Reads back a net as a term, resugaring the strings, lists, tuples and, unless `raw`,
+/// the constructors of the datatypes declared by the program.
+pub fn readback_hvm_net(
+  net: &Net,
+  book: &Book,
+  labels: &Labels,
+  linear: bool,
+  raw: bool,
+) -> (Term, Diagnostics) {
   let mut diags = Diagnostics::default();
   let net = hvmc_to_net(net);
   let mut term = net_to_term(&net, book, labels, linear, &mut diags);
   term.expand_generated(book);
   term.resugar_strings();
   term.resugar_lists();
+  term.resugar_tuples();
+  if !raw {
+    term.resugar_adts(book);
+  }
   (term, diags)
 }
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct RunOpts {
   pub linear_readback: bool,
+  /// Reads back the constructors of the program's datatypes as their lambda encodings.
+  pub raw_readback: bool,
   pub pretty: bool,
+  /// How much of the result to show, and how.
+  pub display: DisplayOpts,
+  /// Stops the evaluation after this many rewrites. Only the built-in interpreter supports it.
+  pub max_rewrites: Option<u64>,
+  /// Stops the evaluation after this much time.
+  pub timeout: Option<Duration>,
+  /// Reduces only what the result needs, instead of the whole net. Only the built-in interpreter supports it.
+  pub lazy: bool,
+  /// The most threads that `run-c` can use, which must be a power of two. Uses every core if not given.
+  /// The other runtimes, and the built-in interpreter, always run on a single thread.
+  pub threads: Option<usize>,
+}
+
+impl RunOpts {
+  /// Shows a result of the program with the display options, or pretty printed with `pretty`.
+  pub fn display_result(&self, term: &Term) -> String {
+    if self.display == DisplayOpts::default() && self.pretty {
+      term.display_pretty(0).to_string()
+    } else {
+      term.display_with(&self.display)
+    }
+  }
 }
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -255,14 +396,56 @@ pub struct CompileOpts {
   /// Enables [fun::transform::linearize_matches].
   pub linearize_matches: OptLevel,
 
+  /// Enables [fun::transform::const_fold].
+  pub const_fold: bool,
+
+  /// Enables [fun::transform::share_common_terms].
+  pub share_common_terms: bool,
+
   /// Enables [fun::transform::float_combinators].
   pub float_combinators: bool,
 
+  /// The size above which combinators are floated out of a definition, for the ones that don't need to be.
+  /// Above the default, the definitions can become too large for hvm.
+  pub float_threshold: usize,
+
   /// Enables [fun::transform::definition_merge]
   pub merge: bool,
 
-  /// Enables [fun::transform::inline].
+  /// Enables [hvm::merge_defs].
+  pub merge_defs: bool,
+
+  /// Enables [hvm::inline].
   pub inline: bool,
+
+  /// Enables [hvm::tail_calls].
+  pub tail_calls: bool,
+
+  /// The most nodes that a definition can have to be inlined into the ones that use it.
+  pub inline_threshold: usize,
+
+  /// Enables [hvm::pre_reduce].
+  pub pre_reduce: bool,
+
+  /// The most rewrites that pre-reducing a single definition can perform before it's left as it was,
+  /// which is also the most that evaluating a `const` can take.
+  pub pre_reduce_rewrite_limit: u64,
+
+  /// The most bytes that pre-reducing a single definition can use before it's left as it was,
+  /// which is also the most that evaluating a `const` can use.
+  pub pre_reduce_memory: usize,
+
+  /// The definition that the program starts from, instead of `main` or `Main`.
+  pub entrypoint: Option<Name>,
+
+  /// Enables [fun::transform::encode_io], for programs whose entrypoint returns an `IO` action.
+  pub io: bool,
+
+  /// The seed of the pseudo-random number generator. Uses the default one of the builtins if not given.
+  pub seed: Option<u32>,
+
+  /// The directory where [incremental] compilation caches the compiled definitions. Disabled if not given.
+  pub cache_dir: Option<PathBuf>,
 }
 
 impl CompileOpts {
@@ -273,9 +456,22 @@ impl CompileOpts {
       eta: true,
       prune: true,
       float_combinators: true,
+      float_threshold: self.float_threshold,
       merge: true,
+      merge_defs: true,
       inline: true,
+      inline_threshold: self.inline_threshold,
+      tail_calls: true,
+      pre_reduce: true,
+      pre_reduce_rewrite_limit: self.pre_reduce_rewrite_limit,
+      pre_reduce_memory: self.pre_reduce_memory,
       linearize_matches: OptLevel::Enabled,
+      const_fold: true,
+      share_common_terms: true,
+      entrypoint: self.entrypoint,
+      io: self.io,
+      seed: self.seed,
+      cache_dir: self.cache_dir,
     }
   }
 
@@ -286,12 +482,56 @@ impl CompileOpts {
       eta: false,
       prune: false,
       linearize_matches: OptLevel::Disabled,
+      const_fold: false,
+      share_common_terms: false,
       float_combinators: false,
+      float_threshold: self.float_threshold,
       merge: false,
+      merge_defs: false,
       inline: false,
+      inline_threshold: self.inline_threshold,
+      tail_calls: false,
+      pre_reduce: false,
+      pre_reduce_rewrite_limit: self.pre_reduce_rewrite_limit,
+      pre_reduce_memory: self.pre_reduce_memory,
+      entrypoint: self.entrypoint,
+      io: self.io,
+      seed: self.seed,
+      cache_dir: self.cache_dir,
+    }
+  }
+
+  /// The light preset, `-O1`: the default passes, which keep the compilation fast.
+  #[must_use]
+  pub fn set_o1(self) -> Self {
+    let default = Self::default();
+    Self {
+      float_threshold: self.float_threshold,
+      inline_threshold: self.inline_threshold,
+      pre_reduce_rewrite_limit: self.pre_reduce_rewrite_limit,
+      pre_reduce_memory: self.pre_reduce_memory,
+      entrypoint: self.entrypoint,
+      io: self.io,
+      seed: self.seed,
+      cache_dir: self.cache_dir,
+      ..default
     }
   }
 
+  /// The heavy preset, `-O2`: also removes the unused code, merges the repeated definitions
+  /// and shortens the recursive loops.
+  #[must_use]
+  pub fn set_o2(self) -> Self {
+    Self { prune: true, merge: true, merge_defs: true, tail_calls: true, ..self.set_o1() }
+  }
+
+  /// The aggressive preset, `-O3`: also inlines small definitions, shares the repeated terms
+  /// and computes the constant expressions and redexes at compile time. The same as [`Self::set_all`].
+  #[must_use]
+  pub fn set_o3(self) -> Self {
+    Self { inline: true, share_common_terms: true, const_fold: true, pre_reduce: true, ..self.set_o2() }
+  }
+
   pub fn check_for_strict(&self) {
     if !self.float_combinators {
       println!(
@@ -313,9 +553,22 @@ impl Default for CompileOpts {
       eta: true,
       prune: false,
       linearize_matches: OptLevel::Enabled,
+      const_fold: false,
+      share_common_terms: false,
       float_combinators: true,
+      float_threshold: MAX_NET_SIZE,
       merge: false,
+      merge_defs: false,
       inline: false,
+      inline_threshold: 1,
+      tail_calls: false,
+      pre_reduce: false,
+      pre_reduce_rewrite_limit: 100_000,
+      pre_reduce_memory: 64 << 20,
+      entrypoint: None,
+      io: false,
+      seed: None,
+      cache_dir: None,
     }
   }
 }
@@ -324,6 +577,10 @@ pub struct CompileResult {
   pub diagnostics: Diagnostics,
   pub core_book: hvmc::ast::Book,
   pub labels: Labels,
+  /// How long each pass of the compilation took, in the order they ran.
+  pub timings: Vec<PassTiming>,
+  /// The definitions of the program that each net of the compiled book came from.
+  pub source_map: SourceMap,
 }
 
 fn maybe_grow<R, F>(f: F) -> R
@@ -332,3 +589,27 @@ where
 {
   stacker::maybe_grow(1024 * 32, 1024 * 1024, f)
 }
+
+/// Applies `f` to each entry of `map`, on every thread when the `parallel` feature is enabled,
+/// returning the results in the order of the map.
+///
+/// Used by the passes that transform each definition independently of the others.
+pub(crate) fn map_values_mut<K, V, T>(
+  map: &mut IndexMap<K, V>,
+  f: impl Fn(&K, &mut V) -> T + Sync + Send,
+) -> Vec<T>
+where
+  K: Sync + Send,
+  V: Send,
+  T: Send,
+{
+  #[cfg(feature = "parallel")]
+  {
+    use rayon::prelude::*;
+    map.par_iter_mut().map(|(k, v)| f(k, v)).collect()
+  }
+  #[cfg(not(feature = "parallel"))]
+  {
+    map.iter_mut().map(|(k, v)| f(k, v)).collect()
+  }
+}
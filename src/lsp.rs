@@ -0,0 +1,569 @@
+//! A language server for editors, speaking the Language Server Protocol over a pair of streams,
+//! enabled by the `lsp` feature.
+//!
+//! The server publishes the diagnostics of a document every time it changes, and answers requests
+//! for the definition of a name, for its signature and documentation on hover, and for the symbols
+//! declared in a document. Documents are always synchronized in full, and positions are counted
+//! in UTF-16 code units, which is what the protocol uses by default.
+
+use crate::{
+  check_book,
+  diagnostics::{strip_ansi, Diagnostic, DiagnosticOrigin, DiagnosticsConfig, Severity, Span},
+  embed::set_main,
+  fun::{format::FormatOpts, parser::is_name_char, Adt, Book, Definition, Name, Term},
+  load_code_to_book, CompileOpts, ENTRY_POINT, HVM1_ENTRY_POINT,
+};
+use serde_json::{json, Value};
+use std::{
+  collections::HashMap,
+  io::{self, BufRead, Write},
+  path::{Path, PathBuf},
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+const SEVERITY_ERROR: u8 = 1;
+const SEVERITY_WARNING: u8 = 2;
+
+const SYMBOL_FUNCTION: u8 = 12;
+const SYMBOL_ENUM: u8 = 10;
+const SYMBOL_STRUCT: u8 = 23;
+const SYMBOL_ENUM_MEMBER: u8 = 22;
+
+/// The code and message of the error of a request.
+type RequestError = (i64, String);
+type RequestResult = Result<Value, RequestError>;
+/// A name in a document, with its range.
+type Word = (Name, Value);
+
+pub struct Server {
+  compile_opts: CompileOpts,
+  diagnostics_cfg: DiagnosticsConfig,
+  /// Whether the prelude is added to the documents, like when they're compiled.
+  prelude: bool,
+  /// The documents open in the editor, by their uri.
+  documents: HashMap<String, Document>,
+  shutdown: bool,
+}
+
+struct Document {
+  path: PathBuf,
+  text: String,
+  /// The book of the last version of the text that could be parsed,
+  /// so that names can still be looked up while the text is being edited.
+  book: Option<Book>,
+}
+
+/// A name declared by the program.
+enum Symbol<'a> {
+  Def(&'a Definition),
+  Ctr { nam: &'a Name, adt_nam: &'a Name, adt: &'a Adt },
+  Adt { nam: &'a Name, adt: &'a Adt },
+}
+
+impl Server {
+  pub fn new(compile_opts: CompileOpts, diagnostics_cfg: DiagnosticsConfig, prelude: bool) -> Self {
+    Server { compile_opts, diagnostics_cfg, prelude, documents: HashMap::new(), shutdown: false }
+  }
+
+  /// Serves the messages read from `input`, writing the responses to `output`,
+  /// until the client sends the `exit` notification or closes the input.
+  pub fn run(mut self, mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    while let Some(msg) = read_message(&mut input)? {
+      let msg = match msg {
+        Ok(msg) => msg,
+        Err(e) => {
+          let err = json!({ "code": PARSE_ERROR, "message": e.to_string() });
+          write_message(&mut output, &json!({ "jsonrpc": "2.0", "id": null, "error": err }))?;
+          continue;
+        }
+      };
+      // Responses to requests of the server are ignored, since it doesn't send any.
+      let Some(method) = msg.get("method").and_then(Value::as_str) else { continue };
+      let params = msg.get("params").unwrap_or(&Value::Null);
+      match msg.get("id") {
+        Some(id) => {
+          let response = match self.handle_request(method, params) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => {
+              json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+            }
+          };
+          write_message(&mut output, &response)?;
+        }
+        None if method == "exit" => return Ok(()),
+        None => {
+          for notification in self.handle_notification(method, params) {
+            write_message(&mut output, &notification)?;
+          }
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn handle_request(&mut self, method: &str, params: &Value) -> RequestResult {
+    if self.shutdown {
+      return Err((INVALID_REQUEST, "The server is shutting down.".to_string()));
+    }
+    match method {
+      "initialize" => Ok(json!({
+        "capabilities": {
+          "textDocumentSync": 1,
+          "definitionProvider": true,
+          "hoverProvider": true,
+          "documentSymbolProvider": true,
+        },
+        "serverInfo": { "name": "bend", "version": env!("CARGO_PKG_VERSION") },
+      })),
+      "shutdown" => {
+        self.shutdown = true;
+        Ok(Value::Null)
+      }
+      "textDocument/definition" => self.definition(params),
+      "textDocument/hover" => self.hover(params),
+      "textDocument/documentSymbol" => self.document_symbols(params),
+      _ => Err((METHOD_NOT_FOUND, format!("Unsupported method '{method}'."))),
+    }
+  }
+
+  /// Handles a notification, returning the notifications to send back.
+  fn handle_notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+    let Some(uri) = params["textDocument"]["uri"].as_str() else { return vec![] };
+    let text = match method {
+      "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+      // With full synchronization, the last change has the whole text of the document.
+      "textDocument/didChange" => {
+        params["contentChanges"].as_array().and_then(|changes| changes.last()?["text"].as_str())
+      }
+      "textDocument/didClose" => {
+        self.documents.remove(uri);
+        return vec![publish_diagnostics(uri, vec![])];
+      }
+      _ => None,
+    };
+    let Some(text) = text else { return vec![] };
+
+    let path = uri_to_path(uri);
+    let mut document =
+      self.documents.remove(uri).unwrap_or(Document { path, text: String::new(), book: None });
+    document.text = text.to_string();
+    let diagnostics = match self.load(&document) {
+      Ok(book) => {
+        let diagnostics = self.check(&document, book.clone());
+        document.book = Some(book);
+        diagnostics
+      }
+      Err(err) => vec![parse_error(&document, &err)],
+    };
+    self.documents.insert(uri.to_string(), document);
+    vec![publish_diagnostics(uri, diagnostics)]
+  }
+
+  fn load(&self, document: &Document) -> Result<Book, String> {
+    let root = document.path.parent().unwrap_or(Path::new(""));
+    let mut book = load_code_to_book(&document.text, &document.path, root)?;
+    if self.prelude {
+      book.add_prelude();
+    }
+    Ok(book)
+  }
+
+  /// Checks the program of a document, returning its diagnostics in the format of the protocol.
+  fn check(&self, document: &Document, mut book: Book) -> Vec<Value> {
+    let mut diagnostics_cfg = self.diagnostics_cfg;
+    // Files without an entrypoint are modules meant to be imported,
+    // so their definitions are checked without expecting them to be used.
+    let entrypoints = match &self.compile_opts.entrypoint {
+      Some(entrypoint) => vec![entrypoint.clone()],
+      None => vec![Name::new(ENTRY_POINT), Name::new(HVM1_ENTRY_POINT)],
+    };
+    if !entrypoints.iter().any(|nam| book.defs.contains_key(nam)) {
+      set_main(&mut book, Term::Era);
+      diagnostics_cfg.unused_definition = Severity::Allow;
+    }
+    let spans = book.defs.values().filter_map(|def| Some((def.name.clone(), def.span.clone()?))).collect();
+
+    let diagnostics = match check_book(&mut book, diagnostics_cfg, self.compile_opts.clone()) {
+      Ok(diagnostics) | Err(diagnostics) => diagnostics,
+    };
+    let mut out = vec![];
+    for (orig, diags) in &diagnostics.diagnostics {
+      for diag in diags {
+        let span = diag.span.clone().or_else(|| origin_span(orig, &spans));
+        if let Some(diag) = lsp_diagnostic(diag, span.as_ref(), document) {
+          out.push(diag);
+        }
+      }
+    }
+    out
+  }
+
+  fn definition(&self, params: &Value) -> RequestResult {
+    let (document, word) = self.word_at(params)?;
+    let (Some((word, _)), Some(book)) = (word, document.book.as_ref()) else { return Ok(Value::Null) };
+    let Some(span) = lookup(book, &word).and_then(|symbol| symbol.span()) else {
+      return Ok(Value::Null);
+    };
+    let Some(file) = &span.file else { return Ok(Value::Null) };
+    let path = Path::new(file);
+    let (uri, text) = match self.documents.iter().find(|(_, doc)| doc.path == path) {
+      Some((uri, doc)) => (uri.clone(), Some(doc.text.clone())),
+      None => (path_to_uri(path), std::fs::read_to_string(path).ok()),
+    };
+    Ok(json!({ "uri": uri, "range": span_range(span, text.as_deref()) }))
+  }
+
+  fn hover(&self, params: &Value) -> RequestResult {
+    let (document, word) = self.word_at(params)?;
+    let (Some((word, range)), Some(book)) = (word, document.book.as_ref()) else { return Ok(Value::Null) };
+    let Some(symbol) = lookup(book, &word) else { return Ok(Value::Null) };
+    Ok(json!({ "contents": { "kind": "markdown", "value": symbol.hover() }, "range": range }))
+  }
+
+  fn document_symbols(&self, params: &Value) -> RequestResult {
+    let document = self.document(params)?;
+    let Some(book) = &document.book else { return Ok(json!([])) };
+    let file = document.path.display().to_string();
+    let in_document =
+      |span: &Option<Span>| span.as_ref().filter(|span| span.file.as_ref() == Some(&file)).cloned();
+
+    let mut symbols = vec![];
+    for def in book.defs.values() {
+      if let Some(span) = in_document(&def.span) {
+        symbols.push((span, json!({ "name": def.name.to_string(), "kind": SYMBOL_FUNCTION })));
+      }
+    }
+    for (nam, adt) in &book.adts {
+      let Some(span) = in_document(&adt.span) else { continue };
+      let range = span_range(&span, Some(&document.text));
+      let is_object = adt.ctrs.len() == 1 && adt.ctrs.contains_key(nam);
+      let kind = if is_object { SYMBOL_STRUCT } else { SYMBOL_ENUM };
+      let children = adt.ctrs.keys().map(|ctr| {
+        json!({ "name": ctr.to_string(), "kind": SYMBOL_ENUM_MEMBER, "range": range, "selectionRange": range })
+      });
+      let children = children.collect::<Vec<_>>();
+      symbols.push((span, json!({ "name": nam.to_string(), "kind": kind, "children": children })));
+    }
+    symbols.sort_by_key(|(span, _)| (span.line, span.column));
+
+    let symbols = symbols.into_iter().map(|(span, mut symbol)| {
+      let range = span_range(&span, Some(&document.text));
+      symbol["range"] = range.clone();
+      symbol["selectionRange"] = range;
+      symbol
+    });
+    Ok(Value::Array(symbols.collect()))
+  }
+
+  fn document(&self, params: &Value) -> Result<&Document, RequestError> {
+    let uri =
+      params["textDocument"]["uri"].as_str().ok_or((INVALID_PARAMS, "Missing document uri.".to_string()))?;
+    self.documents.get(uri).ok_or_else(|| (INVALID_PARAMS, format!("Unknown document '{uri}'.")))
+  }
+
+  /// Finds the name at the position of a request, along with its range.
+  fn word_at(&self, params: &Value) -> Result<(&Document, Option<Word>), RequestError> {
+    let document = self.document(params)?;
+    let (Some(line), Some(character)) =
+      (params["position"]["line"].as_u64(), params["position"]["character"].as_u64())
+    else {
+      return Err((INVALID_PARAMS, "Missing position.".to_string()));
+    };
+    let Some(text) = document.text.split('\n').nth(line as usize) else { return Ok((document, None)) };
+
+    // Converts the column in UTF-16 code units to an index in the line.
+    let mut units = 0;
+    let idx = text.char_indices().find(|(_, c)| {
+      units += c.len_utf16();
+      units > character as usize
+    });
+    let idx = idx.map_or(text.len(), |(idx, _)| idx);
+    let ini = text[.. idx].rfind(|c| !is_name_char(c)).map_or(0, |i| i + 1);
+    let end = text[idx ..].find(|c| !is_name_char(c)).map_or(text.len(), |i| idx + i);
+    if ini == end {
+      return Ok((document, None));
+    }
+
+    let start = json!({ "line": line, "character": utf16_len(&text[.. ini]) });
+    let end_pos = json!({ "line": line, "character": utf16_len(&text[.. end]) });
+    Ok((document, Some((Name::new(&text[ini .. end]), json!({ "start": start, "end": end_pos })))))
+  }
+}
+
+impl<'a> Symbol<'a> {
+  fn span(&self) -> Option<&'a Span> {
+    match self {
+      Symbol::Def(def) => def.span.as_ref(),
+      Symbol::Ctr { adt, .. } | Symbol::Adt { adt, .. } => adt.span.as_ref(),
+    }
+  }
+
+  /// The markdown shown when hovering the name.
+  fn hover(&self) -> String {
+    let (code, info, doc) = match self {
+      Symbol::Def(def) => {
+        let code = match &def.typ {
+          Some(typ) => format!("{} : {typ}", def.name),
+          None => def.name.to_string(),
+        };
+        let info = match def.arity() {
+          0 => "Definition without arguments.".to_string(),
+          1 => "Function with 1 argument.".to_string(),
+          n => format!("Function with {n} arguments."),
+        };
+        (code, info, &def.doc)
+      }
+      Symbol::Ctr { nam, adt_nam, adt } => {
+        let fields = &adt.ctrs[*nam];
+        let fields = fields.iter().map(|f| format!(" {}{}", if f.rec { "~" } else { "" }, f.nam));
+        let code = format!("({nam}{})", fields.collect::<String>());
+        let info = match adt.ctrs[*nam].len() {
+          1 => format!("Constructor of `{adt_nam}` with 1 field."),
+          n => format!("Constructor of `{adt_nam}` with {n} fields."),
+        };
+        (code, info, &adt.doc)
+      }
+      Symbol::Adt { nam, adt } => {
+        let info = match adt.ctrs.len() {
+          1 => "Datatype with 1 constructor.".to_string(),
+          n => format!("Datatype with {n} constructors."),
+        };
+        (adt.format(nam, FormatOpts::default()), info, &adt.doc)
+      }
+    };
+    let mut hover = format!("```bend\n{code}\n```\n\n{info}");
+    if let Some(doc) = doc {
+      hover.push_str(&format!("\n\n---\n\n{doc}"));
+    }
+    hover
+  }
+}
+
+/// Finds the constructor, definition or datatype with the given name.
+fn lookup<'a>(book: &'a Book, nam: &Name) -> Option<Symbol<'a>> {
+  if let Some((nam, adt_nam)) = book.ctrs.get_key_value(nam) {
+    return Some(Symbol::Ctr { nam, adt_nam, adt: &book.adts[adt_nam] });
+  }
+  if let Some(def) = book.defs.get(nam) {
+    return Some(Symbol::Def(def));
+  }
+  let (nam, adt) = book.adts.get_key_value(nam)?;
+  Some(Symbol::Adt { nam, adt })
+}
+
+/// The span of the definition that a diagnostic without one comes from.
+fn origin_span(orig: &DiagnosticOrigin, spans: &HashMap<Name, Span>) -> Option<Span> {
+  match orig {
+    DiagnosticOrigin::Rule(nam) => spans.get(nam).cloned(),
+    DiagnosticOrigin::Inet(nam) => spans.get(&Name::new(nam).def_name_from_generated()).cloned(),
+    DiagnosticOrigin::Book | DiagnosticOrigin::Readback => None,
+  }
+}
+
+/// Converts a diagnostic to the format of the protocol, placing it at the start of the
+/// document when it doesn't have a location in it. Returns `None` for the allowed ones.
+fn lsp_diagnostic(diag: &Diagnostic, span: Option<&Span>, document: &Document) -> Option<Value> {
+  let severity = match diag.severity {
+    Severity::Allow => return None,
+    Severity::Warning => SEVERITY_WARNING,
+    Severity::Error => SEVERITY_ERROR,
+  };
+  let file = document.path.display().to_string();
+  let range = match span {
+    Some(span) if span.file.as_ref() == Some(&file) => span_range(span, Some(&document.text)),
+    _ => line_range(0, ""),
+  };
+  let mut message = strip_ansi(&diag.message);
+  for note in &diag.notes {
+    message.push('\n');
+    message.push_str(&strip_ansi(note));
+  }
+  Some(
+    json!({ "range": range, "severity": severity, "code": diag.code, "source": "bend", "message": message }),
+  )
+}
+
+/// Converts the error of a document that couldn't be parsed to a diagnostic, placing it at the
+/// line shown in the highlighted code of the message, if it's from the document itself.
+fn parse_error(document: &Document, err: &str) -> Value {
+  let err = strip_ansi(err);
+  let header = format!("In {} :\n", document.path.display());
+  let (message, line) = match err.strip_prefix(&header) {
+    Some(message) => {
+      let line = message.lines().find_map(|line| line.trim_start().split_once(" |")?.0.parse::<usize>().ok());
+      (message.to_string(), line)
+    }
+    None => (err.clone(), None),
+  };
+  let range = match line {
+    Some(line) => line_range(line - 1, document.text.split('\n').nth(line - 1).unwrap_or_default()),
+    None => line_range(0, ""),
+  };
+  json!({ "range": range, "severity": SEVERITY_ERROR, "source": "bend", "message": message.trim_end() })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+  let params = json!({ "uri": uri, "diagnostics": diagnostics });
+  json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": params })
+}
+
+/// The range of a span, given the text of its file, if available, to count the columns in UTF-16 code units.
+fn span_range(span: &Span, text: Option<&str>) -> Value {
+  let (line, column) = (span.line.saturating_sub(1), span.column.saturating_sub(1));
+  let Some(text) = text else {
+    let start = json!({ "line": line, "character": column });
+    let end = json!({ "line": line, "character": column + span.length });
+    return json!({ "start": start, "end": end });
+  };
+
+  let lines = text.split('\n').collect::<Vec<_>>();
+  let line_text = |line: usize| lines.get(line).copied().unwrap_or_default();
+  let position = |line: usize, column: usize| {
+    let prefix = line_text(line).chars().take(column).map(char::len_utf16).sum::<usize>();
+    json!({ "line": line, "character": prefix })
+  };
+
+  // Spans can extend over many lines, with each line break counting as one character.
+  let (mut end_line, mut end_column, mut remaining) = (line, column, span.length);
+  loop {
+    let len = line_text(end_line).chars().count();
+    if end_column + remaining <= len || end_line + 1 >= lines.len() {
+      end_column = (end_column + remaining).min(len);
+      break;
+    }
+    remaining -= len - end_column + 1;
+    end_line += 1;
+    end_column = 0;
+  }
+  json!({ "start": position(line, column), "end": position(end_line, end_column) })
+}
+
+/// The range of a whole line, given its text.
+fn line_range(line: usize, text: &str) -> Value {
+  json!({ "start": { "line": line, "character": 0 }, "end": { "line": line, "character": utf16_len(text) } })
+}
+
+fn utf16_len(text: &str) -> usize {
+  text.chars().map(char::len_utf16).sum()
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+  let path = uri.strip_prefix("file://").unwrap_or(uri);
+  let mut bytes = vec![];
+  let mut chars = path.bytes();
+  while let Some(b) = chars.next() {
+    let hex = |b: Option<u8>| (b? as char).to_digit(16);
+    match b {
+      b'%' => {
+        let mut hex_chars = chars.clone();
+        match (hex(hex_chars.next()), hex(hex_chars.next())) {
+          (Some(hi), Some(lo)) => {
+            bytes.push((hi * 16 + lo) as u8);
+            chars = hex_chars;
+          }
+          _ => bytes.push(b),
+        }
+      }
+      b => bytes.push(b),
+    }
+  }
+  PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn path_to_uri(path: &Path) -> String {
+  let path = match std::env::current_dir() {
+    Ok(dir) if path.is_relative() => dir.join(path),
+    _ => path.to_path_buf(),
+  };
+  let mut uri = "file://".to_string();
+  for b in path.display().to_string().bytes() {
+    match b {
+      b'a' ..= b'z' | b'A' ..= b'Z' | b'0' ..= b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => uri.push(b as char),
+      b => uri.push_str(&format!("%{b:02X}")),
+    }
+  }
+  uri
+}
+
+/// Reads a message with its `Content-Length` header, returning `None` at the end of the input,
+/// or the error when its content is not valid JSON.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Result<Value, serde_json::Error>>> {
+  let mut len = None;
+  let mut line = String::new();
+  loop {
+    line.clear();
+    if input.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    let line = line.trim_end();
+    if line.is_empty() {
+      break;
+    }
+    if let Some((key, val)) = line.split_once(':')
+      && key.eq_ignore_ascii_case("Content-Length")
+    {
+      len = val.trim().parse::<usize>().ok();
+    }
+  }
+  let Some(len) = len else {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "Message without a Content-Length header."));
+  };
+  let mut content = vec![0; len];
+  input.read_exact(&mut content)?;
+  Ok(Some(serde_json::from_slice(&content)))
+}
+
+fn write_message(output: &mut impl Write, msg: &Value) -> io::Result<()> {
+  let content = msg.to_string();
+  write!(output, "Content-Length: {}\r\n\r\n{content}", content.len())?;
+  output.flush()
+}
+
+#[test]
+fn language_server() {
+  use itertools::Itertools;
+
+  let uri = "file:///project/main.bend";
+  let code = "## A binary tree.\\ndata Tree = (Node ~left ~right) | (Leaf value)\\n\\nmain = (Tree/Node (Tree/Leaf 1) unbound)\\n";
+  let document = format!(r#""textDocument":{{"uri":"{uri}"}}"#);
+  let messages = [
+    r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#.to_string(),
+    format!(
+      r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{uri}","text":"{code}"}}}}}}"#
+    ),
+    format!(
+      r#"{{"jsonrpc":"2.0","id":2,"method":"textDocument/definition","params":{{{document},"position":{{"line":3,"character":22}}}}}}"#
+    ),
+    format!(
+      r#"{{"jsonrpc":"2.0","id":3,"method":"textDocument/hover","params":{{{document},"position":{{"line":3,"character":10}}}}}}"#
+    ),
+    format!(r#"{{"jsonrpc":"2.0","id":4,"method":"textDocument/documentSymbol","params":{{{document}}}}}"#),
+    r#"{"jsonrpc":"2.0","id":5,"method":"shutdown"}"#.to_string(),
+    r#"{"jsonrpc":"2.0","method":"exit"}"#.to_string(),
+  ];
+  let input = messages.iter().map(|msg| format!("Content-Length: {}\r\n\r\n{msg}", msg.len())).join("");
+
+  let mut output = vec![];
+  let server = Server::new(CompileOpts::default(), DiagnosticsConfig::default(), true);
+  server.run(input.as_bytes(), &mut output).unwrap();
+  let output = String::from_utf8(output).unwrap();
+
+  let expected = [
+    r#""definitionProvider":true"#,
+    // The unbound variable is reported in the rule of `main`.
+    r#""message":"Unbound variable 'unbound'.","range":{"end":{"character":40,"line":3},"start":{"character":0,"line":3}}"#,
+    // The constructors are defined by the declaration of their datatype.
+    r#""id":2,"jsonrpc":"2.0","result":{"range":{"end":{"character":46,"line":1},"start":{"character":0,"line":1}},"uri":"file:///project/main.bend"}"#,
+    r#"(Tree/Node ~left ~right)\n```\n\nConstructor of `Tree` with 2 fields.\n\n---\n\nA binary tree."#,
+    r#""kind":10,"name":"Tree""#,
+    r#""kind":12,"name":"main""#,
+    r#""id":5,"jsonrpc":"2.0","result":null"#,
+  ];
+  for expected in expected {
+    assert!(output.contains(expected), "Expected '{expected}' in the output of the server:\n{output}");
+  }
+}
@@ -1,13 +1,42 @@
 use bend::{
-  check_book, compile_book, desugar_book,
-  diagnostics::{Diagnostics, DiagnosticsConfig, Severity},
-  fun::{Book, Name},
-  load_file_to_book, run_book_with_fn, CompileOpts, OptLevel, RunOpts,
+  check_book,
+  codegen::{c::book_to_c, dot::book_to_dot, flatten_book, FlatBook},
+  compile_book, compile_book_with_hook,
+  compiler::{Compiler, RunInfo, RunResult, Runtime},
+  config::Config,
+  desugar_book, desugar_book_with_hook,
+  diagnostics::{self, Diagnostics, DiagnosticsConfig, MessageFormat, Severity},
+  fun::{
+    display::DisplayOpts,
+    doc::DocFormat,
+    format::{format_source, FormatOpts},
+    load_book::do_parse_book,
+    parser::{parse_term, TermParser},
+    Book, Definition, Name, Num, Rule, Term,
+  },
+  hvm::{
+    binary::{book_from_bytes, book_to_bytes, is_binary_book},
+    check_net_size::count_nodes,
+    interpreter::{self, Debugger, DefProfile, Limits, Pause},
+    roundtrip::check_roundtrip,
+    source_map::SourceMap,
+  },
+  incremental, load_file_to_book, load_file_to_book_with_root, load_source_to_book,
+  lsp::Server,
+  parse_hvm_book,
+  pipeline::{PassTiming, Pipeline},
+  readback_hvm_net, run_book, CompileOpts, OptLevel, RunOpts, Stage,
 };
 use clap::{Args, CommandFactory, Parser, Subcommand};
-use std::path::{Path, PathBuf};
+use std::{
+  collections::{hash_map::RandomState, HashMap},
+  hash::{BuildHasher, Hasher},
+  io::Read,
+  path::{Path, PathBuf},
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Clone, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
   #[command(subcommand)]
@@ -16,8 +45,59 @@ struct Cli {
   #[arg(short, long, global = true)]
   pub verbose: bool,
 
-  #[arg(short = 'e', long, global = true, help = "Use other entrypoint rather than main or Main")]
+  #[arg(long, global = true, help = "Start the program from this definition instead of main or Main")]
   pub entrypoint: Option<String>,
+
+  #[arg(
+    long,
+    global = true,
+    help = "Seed of the pseudo-random number generator, random on every run if not given"
+  )]
+  pub seed: Option<u32>,
+
+  #[arg(
+    long,
+    global = true,
+    help = "Size above which the float_combinators pass floats combinators out of a definition"
+  )]
+  pub float_threshold: Option<usize>,
+
+  #[arg(
+    long,
+    global = true,
+    help = "Most nodes that a definition can have to be inlined by the inline pass"
+  )]
+  pub inline_threshold: Option<usize>,
+
+  #[arg(
+    long,
+    global = true,
+    help = "Most rewrites that the pre-reduce pass can perform on each definition, and evaluating each constant"
+  )]
+  pub pre_reduce_rewrite_limit: Option<u64>,
+
+  #[arg(
+    long,
+    global = true,
+    help = "Most bytes that the pre-reduce pass can use on each definition, and evaluating each constant"
+  )]
+  pub pre_reduce_memory: Option<usize>,
+
+  #[arg(long, global = true, value_enum, default_value_t = MessageFormatArgs::Human, help = "How to print errors and warnings")]
+  pub message_format: MessageFormatArgs,
+
+  #[arg(long, global = true, value_enum, default_value_t = ColorArgs::Auto, help = "When to color errors and warnings")]
+  pub color: ColorArgs,
+
+  #[arg(long, global = true, help = "Don't add the prelude datatypes and functions to the program")]
+  pub no_prelude: bool,
+
+  #[arg(
+    long,
+    global = true,
+    help = "Reuse the compiled definitions that didn't change, caching them in a .bend-cache directory"
+  )]
+  pub incremental: bool,
 }
 
 #[derive(Subcommand, Clone, Debug)]
@@ -36,8 +116,14 @@ enum Mode {
     #[command(flatten)]
     warn_opts: CliWarnOpts,
 
-    #[arg(help = "Path to the input file")]
-    path: PathBuf,
+    #[arg(long, help = "Checks the program again every time the input file changes")]
+    watch: bool,
+
+    #[arg(
+      required = true,
+      help = "Paths to the input files, or '-' to read one from stdin. Directories are searched for '.bend' files"
+    )]
+    paths: Vec<PathBuf>,
   },
   /// Compiles the program and runs it with the Rust HVM implementation.
   Run(RunArgs),
@@ -45,8 +131,8 @@ enum Mode {
   RunC(RunArgs),
   /// Compiles the program and runs it with the Cuda HVM implementation.
   RunCu(RunArgs),
-  /// Compiles the program to hvmc and prints to stdout.
-  GenHvm(GenArgs),
+  /// Compiles the program to hvmc and prints to stdout or writes it to a file.
+  GenHvm(GenHvmArgs),
   /// Compiles the program to standalone C and prints to stdout.
   GenC(GenArgs),
   /// Compiles the program to standalone Cuda and prints to stdout.
@@ -68,9 +154,157 @@ enum Mode {
     #[command(flatten)]
     warn_opts: CliWarnOpts,
 
-    #[arg(help = "Path to the input file")]
+    #[arg(help = "Path to the input file, or '-' to read it from stdin")]
     path: PathBuf,
   },
+  /// Compiles the program, then prints the compiled hvm and parses it back, checking that the nets are the same.
+  Verify {
+    #[arg(
+      short = 'O',
+      value_delimiter = ' ',
+      action = clap::ArgAction::Append,
+      long_help = r#"Enables or disables the given optimizations
+      float_combinators is enabled by default on strict mode."#,
+    )]
+    comp_opts: Vec<OptArgs>,
+
+    #[command(flatten)]
+    warn_opts: CliWarnOpts,
+
+    #[arg(
+      long,
+      help = "Also runs both programs on the built-in interpreter, checking that their results are the same"
+    )]
+    run: bool,
+
+    #[arg(long, value_name = "N", help = "Stops each run after N rewrites")]
+    max_rewrites: Option<u64>,
+
+    #[arg(help = "Path to the input file, or '-' to read it from stdin")]
+    path: PathBuf,
+  },
+  /// Formats the program in the canonical style and prints to stdout.
+  Fmt {
+    #[arg(long, default_value_t = FormatOpts::default().indent, help = "Number of spaces per indentation level")]
+    indent: usize,
+
+    #[arg(long, default_value_t = FormatOpts::default().max_width, help = "Maximum line width")]
+    max_width: usize,
+
+    #[arg(long, help = "Exits with an error if the file is not already formatted, instead of printing it")]
+    check: bool,
+
+    #[arg(help = "Path to the input file, or '-' to read it from stdin")]
+    path: PathBuf,
+  },
+  /// Generates the documentation of the program's datatypes and definitions from their `##` comments.
+  Doc {
+    #[arg(long, value_enum, default_value_t = DocFormatArgs::Markdown, help = "Format of the generated documentation")]
+    format: DocFormatArgs,
+
+    #[arg(short = 'o', long, help = "Writes the output to the given file instead of stdout")]
+    output: Option<PathBuf>,
+
+    #[arg(help = "Path to the input file, or '-' to read it from stdin")]
+    path: PathBuf,
+  },
+  /// Runs the definitions whose names start with `test_`, which pass if they return `1` or `True`.
+  Test {
+    #[arg(
+      short = 'O',
+      value_delimiter = ' ',
+      action = clap::ArgAction::Append,
+      long_help = r#"Enables or disables the given optimizations
+      float_combinators is enabled by default on strict mode."#,
+    )]
+    comp_opts: Vec<OptArgs>,
+
+    #[command(flatten)]
+    warn_opts: CliWarnOpts,
+
+    #[arg(help = "Path to the input file, or '-' to read it from stdin")]
+    path: PathBuf,
+  },
+  /// Evaluates a single expression with the Rust HVM implementation.
+  Eval {
+    #[arg(short = 'p', help = "Debug and normalization pretty printing")]
+    pretty: bool,
+
+    #[command(flatten)]
+    run_opts: CliRunOpts,
+
+    #[arg(
+      short = 'O',
+      value_delimiter = ' ',
+      action = clap::ArgAction::Append,
+      long_help = r#"Enables or disables the given optimizations
+      float_combinators is enabled by default on strict mode."#,
+    )]
+    comp_opts: Vec<OptArgs>,
+
+    #[command(flatten)]
+    warn_opts: CliWarnOpts,
+
+    #[arg(long = "with", help = "Path to a file with definitions that the expression can use")]
+    with: Option<PathBuf>,
+
+    #[arg(short = 'e', long = "expr", value_parser = parse_term, help = "The expression to evaluate")]
+    expr: Term,
+  },
+  /// Starts an interactive session that evaluates terms with the Rust HVM implementation.
+  Repl {
+    #[arg(short = 'p', help = "Debug and normalization pretty printing")]
+    pretty: bool,
+
+    #[command(flatten)]
+    run_opts: CliRunOpts,
+
+    #[arg(
+      short = 'O',
+      value_delimiter = ' ',
+      action = clap::ArgAction::Append,
+      long_help = r#"Enables or disables the given optimizations
+      float_combinators is enabled by default on strict mode."#,
+    )]
+    comp_opts: Vec<OptArgs>,
+
+    #[command(flatten)]
+    warn_opts: CliWarnOpts,
+
+    #[arg(help = "Path to a file with definitions to load into the session")]
+    path: Option<PathBuf>,
+  },
+  /// Prints the extended description of a diagnostic code, with examples and common fixes.
+  Explain {
+    #[arg(
+      help = "The code of the diagnostic, like 'BEND0010', or the name of a warning. Lists all codes if omitted"
+    )]
+    code: Option<String>,
+  },
+  /// Creates a new project, with a main file, a `bend.toml` configuration and a directory for tests.
+  Init {
+    #[arg(default_value = ".", help = "Directory of the project, which is created if it doesn't exist")]
+    path: PathBuf,
+  },
+  /// Prints a script that completes the subcommands, options and their values in the given shell.
+  Completions {
+    #[arg(value_enum, help = "The shell to complete in")]
+    shell: clap_complete::Shell,
+  },
+  /// Starts a language server for editors, speaking the Language Server Protocol over stdin and stdout.
+  Lsp {
+    #[arg(
+      short = 'O',
+      value_delimiter = ' ',
+      action = clap::ArgAction::Append,
+      long_help = r#"Enables or disables the given optimizations
+      float_combinators is enabled by default on strict mode."#,
+    )]
+    comp_opts: Vec<OptArgs>,
+
+    #[command(flatten)]
+    warn_opts: CliWarnOpts,
+  },
 }
 
 #[derive(Args, Clone, Debug)]
@@ -81,9 +315,89 @@ struct RunArgs {
   #[arg(long, help = "Run with IO enabled")]
   io: bool,
 
+  #[arg(long, help = "Run on the GPU with the CUDA runtime, the same as run-cu")]
+  gpu: bool,
+
+  #[arg(
+    long,
+    help = "Prints the elements of a list result as they are computed, running on the built-in interpreter"
+  )]
+  stream: bool,
+
+  #[arg(long, help = "Shows the rewrites and time of each definition, running on the built-in interpreter")]
+  profile: bool,
+
+  #[arg(long, help = "Evaluates lazily on the built-in interpreter, reducing only what the result needs")]
+  lazy: bool,
+
+  #[arg(
+    long,
+    help = "Steps through the evaluation on the built-in interpreter, with breakpoints on definitions"
+  )]
+  debug_interactive: bool,
+
+  #[arg(
+    long,
+    value_name = "NAME",
+    help = "Prints each redex that expands this definition, running on the built-in interpreter"
+  )]
+  trace_def: Vec<String>,
+
+  #[arg(
+    long,
+    value_name = "N",
+    value_parser = clap::value_parser!(u64).range(1 ..),
+    help = "Prints the whole net every N rewrites, running on the built-in interpreter"
+  )]
+  trace_every: Option<u64>,
+
+  #[arg(
+    long,
+    value_name = "FILE",
+    num_args = 0..=1,
+    require_equals = true,
+    help = "Prints the run statistics as a JSON object, or writes them to the file given with `--stats-json=<FILE>`"
+  )]
+  stats_json: Option<Option<PathBuf>>,
+
+  #[arg(
+    long,
+    value_name = "N",
+    help = "Stops after N rewrites and shows the partial result, running on the built-in interpreter"
+  )]
+  max_rewrites: Option<u64>,
+
+  #[arg(
+    long,
+    value_name = "SECS",
+    value_parser = parse_timeout,
+    help = "Stops after SECS seconds, showing the partial result when running on the built-in interpreter"
+  )]
+  timeout: Option<Duration>,
+
+  #[arg(
+    long,
+    value_name = "N",
+    help = "Runs on at most N threads with run-c, where N must be a power of two"
+  )]
+  threads: Option<usize>,
+
+  #[arg(long, value_enum, default_value_t = OutputFormatArgs::Human, help = "How to print the result of the program")]
+  output_format: OutputFormatArgs,
+
   #[command(flatten)]
   run_opts: CliRunOpts,
 
+  #[arg(long, help = "Runs the program again every time the input file changes")]
+  watch: bool,
+
+  #[arg(
+    long,
+    value_enum,
+    help = "The language of the input file. By default, files ending in '.hvmc' or '.hvmb' are read as compiled hvm"
+  )]
+  from: Option<FromArgs>,
+
   #[arg(
     short = 'O',
     value_delimiter = ' ',
@@ -96,10 +410,13 @@ struct RunArgs {
   #[command(flatten)]
   warn_opts: CliWarnOpts,
 
-  #[arg(help = "Path to the input file")]
+  #[arg(help = "Path to the input file, or '-' to read it from stdin")]
   path: PathBuf,
 
-  #[arg(value_parser = |arg: &str| bend::fun::parser::TermParser::new(arg).parse_term())]
+  #[arg(
+    value_parser = parse_argument,
+    help = "Arguments passed to the main function. Arguments that aren't valid terms are passed as strings"
+  )]
   arguments: Option<Vec<bend::fun::Term>>,
 }
 
@@ -120,17 +437,102 @@ struct GenArgs {
   #[command(flatten)]
   warn_opts: CliWarnOpts,
 
-  #[arg(help = "Path to the input file")]
+  #[arg(help = "Path to the input file, or '-' to read it from stdin")]
   path: PathBuf,
 }
 
+#[derive(Args, Clone, Debug)]
+struct GenHvmArgs {
+  #[command(flatten)]
+  gen_args: GenArgs,
+
+  #[arg(short = 'o', long, help = "Writes the output to the given file instead of stdout")]
+  output: Option<PathBuf>,
+
+  #[arg(
+    long,
+    value_enum,
+    value_delimiter = ',',
+    default_value = "hvm",
+    help = "Which representations of the program to output, in the order of the pipeline"
+  )]
+  emit: Vec<EmitArgs>,
+
+  #[arg(long, value_enum, default_value = "text", help = "How to write the compiled hvm book")]
+  format: HvmFormatArgs,
+
+  #[arg(long, help = "Compiles the program again every time the input file changes")]
+  watch: bool,
+
+  #[arg(long, help = "Lists the compiler passes in the order they run, instead of compiling the program")]
+  list_passes: bool,
+
+  #[arg(
+    long,
+    help = "Reports the time taken by each compiler pass, the size of each compiled definition and of the hvm book"
+  )]
+  timings: bool,
+
+  #[arg(
+    long,
+    value_name = "FILE",
+    help = "Writes the definition and location in the source that each compiled net came from to the given file, as JSON"
+  )]
+  source_map: Option<PathBuf>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum EmitArgs {
+  /// The compiled hvmc book.
+  #[value(alias = "hvmc")]
+  Hvm,
+  /// The program after the lambda-term level desugaring passes.
+  Desugared,
+  /// The program once its variables are linearized, before the optimizing passes.
+  Linearized,
+  /// The program as it was parsed, before any transformation.
+  Ast,
+  /// The nets of the compiled hvmc book as a Graphviz graph.
+  Dot,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HvmFormatArgs {
+  /// The text format of hvm, which can be read and edited.
+  Text,
+  /// A compact binary format, which `bend run` loads without compiling the program again.
+  Bin,
+}
+
+impl EmitArgs {
+  /// The stage of the pipeline that is emitted, or `None` for the graph of the compiled nets.
+  fn stage(self) -> Option<Stage> {
+    match self {
+      EmitArgs::Hvm => Some(Stage::Hvm),
+      EmitArgs::Desugared => Some(Stage::Desugared),
+      EmitArgs::Linearized => Some(Stage::Linearized),
+      EmitArgs::Ast => Some(Stage::Ast),
+      EmitArgs::Dot => None,
+    }
+  }
+}
+
 #[derive(Args, Clone, Debug)]
 struct CliRunOpts {
   #[arg(short = 'l', help = "Linear readback (show explicit dups)")]
   linear: bool,
 
+  #[arg(long, help = "Show the constructors of the program's datatypes as their lambda encodings")]
+  raw_readback: bool,
+
   #[arg(short = 's', long = "stats", help = "Shows runtime stats and rewrite counts")]
   print_stats: bool,
+
+  #[arg(
+    long,
+    help = "How to show the result, as a list of options like 'depth=5,list=10,width=100,numbers=hex'"
+  )]
+  display: Option<DisplayOpts>,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -168,6 +570,12 @@ struct CliWarnOpts {
 pub enum OptArgs {
   All,
   NoAll,
+  #[value(name = "1")]
+  O1,
+  #[value(name = "2")]
+  O2,
+  #[value(name = "3")]
+  O3,
   Eta,
   NoEta,
   Prune,
@@ -175,32 +583,55 @@ pub enum OptArgs {
   LinearizeMatches,
   LinearizeMatchesAlt,
   NoLinearizeMatches,
+  ConstFold,
+  NoConstFold,
+  ShareCommonTerms,
+  NoShareCommonTerms,
   FloatCombinators,
   NoFloatCombinators,
   Merge,
   NoMerge,
+  MergeDefs,
+  NoMergeDefs,
   Inline,
   NoInline,
+  TailCalls,
+  NoTailCalls,
+  PreReduce,
+  NoPreReduce,
 }
 
-fn compile_opts_from_cli(args: &Vec<OptArgs>) -> CompileOpts {
+/// Applies the optimization arguments to the options that the command line gives for every mode.
+fn compile_opts_from_cli(args: &Vec<OptArgs>, mut opts: CompileOpts) -> CompileOpts {
   use OptArgs::*;
-  let mut opts = CompileOpts::default();
 
   for arg in args {
     match arg {
       All => opts = opts.set_all(),
       NoAll => opts = opts.set_no_all(),
+      O1 => opts = opts.set_o1(),
+      O2 => opts = opts.set_o2(),
+      O3 => opts = opts.set_o3(),
       Eta => opts.eta = true,
       NoEta => opts.eta = false,
       Prune => opts.prune = true,
       NoPrune => opts.prune = false,
+      ConstFold => opts.const_fold = true,
+      NoConstFold => opts.const_fold = false,
+      ShareCommonTerms => opts.share_common_terms = true,
+      NoShareCommonTerms => opts.share_common_terms = false,
       FloatCombinators => opts.float_combinators = true,
       NoFloatCombinators => opts.float_combinators = false,
       Merge => opts.merge = true,
       NoMerge => opts.merge = false,
+      MergeDefs => opts.merge_defs = true,
+      NoMergeDefs => opts.merge_defs = false,
       Inline => opts.inline = true,
       NoInline => opts.inline = false,
+      TailCalls => opts.tail_calls = true,
+      NoTailCalls => opts.tail_calls = false,
+      PreReduce => opts.pre_reduce = true,
+      NoPreReduce => opts.pre_reduce = false,
 
       LinearizeMatches => opts.linearize_matches = OptLevel::Enabled,
       LinearizeMatchesAlt => opts.linearize_matches = OptLevel::Alt,
@@ -211,15 +642,92 @@ fn compile_opts_from_cli(args: &Vec<OptArgs>) -> CompileOpts {
   opts
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum MessageFormatArgs {
+  /// Colored text meant to be read in a terminal.
+  Human,
+  /// Newline-delimited JSON objects, meant to be read by other programs.
+  Json,
+}
+
+impl From<MessageFormatArgs> for MessageFormat {
+  fn from(value: MessageFormatArgs) -> Self {
+    match value {
+      MessageFormatArgs::Human => MessageFormat::Human,
+      MessageFormatArgs::Json => MessageFormat::Json,
+    }
+  }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FromArgs {
+  /// A Bend program.
+  Bend,
+  /// A program already compiled to hvm, like the output of `bend gen-hvm`, in the text or the binary format.
+  #[value(alias = "hvm")]
+  Hvmc,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormatArgs {
+  /// The result as a term, with the diagnostics and the statistics asked for.
+  Human,
+  /// A single JSON object with the result as data and as a term, the diagnostics and the statistics.
+  Json,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ColorArgs {
+  /// Colors them when printing to a terminal, unless the NO_COLOR environment variable is set.
+  Auto,
+  Always,
+  Never,
+}
+
+impl ColorArgs {
+  /// Whether the diagnostics printed to stderr should be colored.
+  fn enabled(self) -> bool {
+    use std::io::IsTerminal;
+    match self {
+      ColorArgs::Auto => {
+        !std::env::var_os("NO_COLOR").is_some_and(|val| !val.is_empty()) && std::io::stderr().is_terminal()
+      }
+      ColorArgs::Always => true,
+      ColorArgs::Never => false,
+    }
+  }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum DocFormatArgs {
+  Markdown,
+  Html,
+}
+
+impl From<DocFormatArgs> for DocFormat {
+  fn from(value: DocFormatArgs) -> Self {
+    match value {
+      DocFormatArgs::Markdown => DocFormat::Markdown,
+      DocFormatArgs::Html => DocFormat::Html,
+    }
+  }
+}
+
 #[derive(clap::ValueEnum, Clone, Debug)]
 pub enum WarningArgs {
   All,
   IrrefutableMatch,
   RedundantMatch,
   UnreachableMatch,
+  UnreachableArm,
+  ShadowedVariable,
+  UnusedVars,
+  ImplicitDup,
+  UnusedDup,
   UnusedDefinition,
   RepeatedBind,
   RecursionCycle,
+  UnguardedRecursion,
 }
 
 fn main() {
@@ -227,67 +735,572 @@ fn main() {
   compile_error!("The 'cli' feature is needed for the hvm-lang cli");
 
   let cli = Cli::parse();
+  let message_format = cli.message_format.into();
+  let color = cli.color.enabled();
+
+  if let Some(path) = cli.mode.watched_path() {
+    watch(cli, &path);
+  }
 
-  if let Err(diagnostics) = execute_cli_mode(cli) {
-    eprint!("{diagnostics}")
+  if let Err(mut diagnostics) = execute_cli_mode(cli) {
+    diagnostics.config.message_format = message_format;
+    diagnostics.config.color = color;
+    eprint!("{diagnostics}");
+    std::process::exit(1);
   }
 }
 
-fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
-  let arg_verbose = cli.verbose;
-  let entrypoint = cli.entrypoint.take();
+impl Mode {
+  /// Returns the directory from which the `bend.toml` of the project is searched for: the one of
+  /// the input file, or the current one when reading from stdin. Modes that don't read a program don't use one.
+  fn config_dir(&self) -> Option<PathBuf> {
+    let path = match self {
+      Mode::Check { paths, .. } => paths.first(),
+      Mode::Run(RunArgs { path, .. })
+      | Mode::RunC(RunArgs { path, .. })
+      | Mode::RunCu(RunArgs { path, .. })
+      | Mode::GenHvm(GenHvmArgs { gen_args: GenArgs { path, .. }, .. })
+      | Mode::GenC(GenArgs { path, .. })
+      | Mode::GenCu(GenArgs { path, .. })
+      | Mode::Desugar { path, .. }
+      | Mode::Verify { path, .. }
+      | Mode::Fmt { path, .. }
+      | Mode::Doc { path, .. }
+      | Mode::Test { path, .. } => Some(path),
+      Mode::Eval { with: path, .. } | Mode::Repl { path, .. } => path.as_ref(),
+      Mode::Explain { .. } | Mode::Init { .. } | Mode::Completions { .. } => return None,
+      // The server is started from the root of the project open in the editor.
+      Mode::Lsp { .. } => None,
+    };
+    match path {
+      Some(path) if path.is_dir() => Some(path.clone()),
+      Some(path) if path != Path::new(STDIN_PATH) => {
+        Some(path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf())
+      }
+      _ => Some(PathBuf::from(".")),
+    }
+  }
 
-  let load_book = |path: &Path| -> Result<Book, Diagnostics> {
-    let mut book = load_file_to_book(path)?;
-    book.entrypoint = entrypoint.map(Name::new);
+  /// Returns the input file if the mode was called with `--watch`.
+  fn watched_path(&self) -> Option<PathBuf> {
+    let (watch, path) = match self {
+      Mode::Check { watch, paths, .. } => match paths.as_slice() {
+        [path] if !path.is_dir() => (*watch, path),
+        _ => return None,
+      },
+      Mode::GenHvm(GenHvmArgs { watch, gen_args: GenArgs { path, .. }, .. })
+      | Mode::Run(RunArgs { watch, path, .. })
+      | Mode::RunC(RunArgs { watch, path, .. })
+      | Mode::RunCu(RunArgs { watch, path, .. }) => (*watch, path),
+      _ => return None,
+    };
+    (watch && path != Path::new(STDIN_PATH)).then(|| path.clone())
+  }
+}
+
+/// How often the watched file is checked for changes.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long the watched file must stay unchanged before running again, so that a burst of saves runs only once.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Executes the cli mode every time the file at `path` is modified, until the process is killed.
+fn watch(cli: Cli, path: &Path) -> ! {
+  let message_format = cli.message_format.into();
+  let color = cli.color.enabled();
+  let modified = || std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+  loop {
+    println!("\x1b[1m---------- [{}] {} ----------\x1b[0m", utc_time_now(), path.display());
+    if let Err(mut diagnostics) = execute_cli_mode(cli.clone()) {
+      diagnostics.config.message_format = message_format;
+      diagnostics.config.color = color;
+      eprint!("{diagnostics}");
+    }
+
+    let last = modified();
+    while modified() == last {
+      std::thread::sleep(WATCH_POLL_INTERVAL);
+    }
+    let mut last = modified();
+    loop {
+      std::thread::sleep(WATCH_DEBOUNCE);
+      let curr = modified();
+      if curr == last {
+        break;
+      }
+      last = curr;
+    }
+  }
+}
 
+const DEBUG_HELP: &str = "\
+Commands:
+  break <def>   Pauses before each expansion of a definition
+  delete <def>  Removes the breakpoint of a definition
+  step [n]      Performs one rewrite, or n of them
+  continue      Rewrites until the next breakpoint or the end
+  net           Shows the whole net, with the next redex last
+  redex         Shows the next redex
+  help          Shows this message
+  quit          Stops the evaluation";
+
+/// Steps through the evaluation of a book with the commands read from the standard input.
+/// Returns the normal form, or `None` if the session ended before reaching it.
+fn step_through(book: &FlatBook) -> Option<hvmc::ast::Net> {
+  use std::io::Write;
+
+  let mut debugger = Debugger::new(book);
+  println!("Paused at the start. Type help for the available commands.");
+  let mut line = String::new();
+  loop {
+    print!("(debug) ");
+    let _ = std::io::stdout().flush();
+    line.clear();
+    match std::io::stdin().read_line(&mut line) {
+      Ok(0) => return None,
+      Ok(_) => (),
+      Err(e) => {
+        eprintln!("{e}");
+        return None;
+      }
+    }
+    let input = line.trim();
+
+    let res = match input.split_once(char::is_whitespace).unwrap_or((input, "")) {
+      ("", _) => Ok(None),
+      ("quit" | "q", _) => return None,
+      ("help" | "h", _) => {
+        println!("{DEBUG_HELP}");
+        Ok(None)
+      }
+      ("break" | "b", name) => debugger.add_breakpoint(name.trim()).map(|()| None),
+      ("delete" | "d", name) => debugger.remove_breakpoint(name.trim()).map(|()| None),
+      ("step" | "s", steps) if steps.trim().is_empty() => Ok(Some(debugger.step(1))),
+      ("step" | "s", steps) => match steps.trim().parse() {
+        Ok(steps) => Ok(Some(debugger.step(steps))),
+        Err(_) => Err(format!("Invalid number of steps '{}'.", steps.trim())),
+      },
+      ("continue" | "c", _) => Ok(Some(debugger.resume())),
+      ("net" | "n", _) => {
+        println!("{}", debugger.net());
+        Ok(None)
+      }
+      ("redex" | "r", _) => {
+        match debugger.next_redex() {
+          Some((a, b)) => println!("{a} ~ {b}"),
+          None => println!("There are no redexes left."),
+        }
+        Ok(None)
+      }
+      (cmd, _) => Err(format!("Unknown command '{cmd}'. Type help for the available commands.")),
+    };
+
+    let rewrites = debugger.rewrites().total();
+    match res {
+      Ok(None) => (),
+      Ok(Some(Pause::Step)) => println!("Paused after {rewrites} rewrites."),
+      Ok(Some(Pause::Breakpoint(name))) => {
+        println!("Paused before expanding '{name}', after {rewrites} rewrites.")
+      }
+      Ok(Some(Pause::Done)) => return Some(debugger.net()),
+      Err(e) => eprintln!("{e}"),
+    }
+  }
+}
+
+/// Evaluates a book, printing the redexes that expand the definitions in `defs`,
+/// and the whole net whenever the number of rewrites is a multiple of `every`.
+fn trace(book: &FlatBook, defs: &[String], every: Option<u64>) -> Result<hvmc::ast::Net, String> {
+  let mut debugger = Debugger::new(book);
+  for def in defs {
+    debugger.add_breakpoint(def)?;
+  }
+  loop {
+    let pause = match every {
+      Some(every) => debugger.step(every - debugger.rewrites().total() % every),
+      None => debugger.resume(),
+    };
+    let rewrites = debugger.rewrites().total();
+    match pause {
+      Pause::Step => println!("---------- {rewrites} rewrites ----------\n{}", debugger.net()),
+      Pause::Breakpoint(_) => {
+        let (a, b) = debugger.next_redex().unwrap();
+        println!("[{rewrites}] {a} ~ {b}");
+      }
+      Pause::Done => return Ok(debugger.net()),
+    }
+  }
+}
+
+/// The files created by `bend init`, relative to the directory of the project.
+const INIT_MAIN_PATH: &str = "src/main.bend";
+const INIT_FILES: &[(&str, &str)] = &[
+  ("bend.toml", include_str!("templates/bend.toml")),
+  (INIT_MAIN_PATH, include_str!("templates/main.bend")),
+  ("tests/main.bend", include_str!("templates/tests.bend")),
+];
+
+/// Creates the files of a new project in `dir`, without overwriting any that already exist.
+fn init_project(dir: &Path) -> Result<(), String> {
+  if let Some((file, _)) = INIT_FILES.iter().find(|(file, _)| dir.join(file).exists()) {
+    return Err(format!("Can't create a project in '{}', since '{file}' already exists.", dir.display()));
+  }
+  for (file, contents) in INIT_FILES {
+    let path = dir.join(file);
+    let write = |path: &Path| {
+      std::fs::create_dir_all(path.parent().unwrap_or(Path::new("")))?;
+      std::fs::write(path, contents)
+    };
+    write(&path).map_err(|e| format!("While writing '{}': {e}", path.display()))?;
+  }
+  Ok(())
+}
+
+/// Lists the files to check, searching the directories recursively for '.bend' files.
+/// The files found in a directory are sorted by their path, and given with the directory
+/// as the root of their imports, since they're taken to be the modules of a project.
+fn find_source_files(paths: &[PathBuf]) -> Result<Vec<(PathBuf, Option<PathBuf>)>, String> {
+  fn search(dir: &Path, root: &Path, files: &mut Vec<(PathBuf, Option<PathBuf>)>) -> Result<(), String> {
+    let err = |e: std::io::Error| format!("While reading '{}': {e}", dir.display());
+    let mut paths = std::fs::read_dir(dir)
+      .map_err(err)?
+      .map(|entry| Ok(entry?.path()))
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(err)?;
+    paths.sort();
+    for path in paths {
+      if path.is_dir() {
+        search(&path, root, files)?;
+      } else if path.extension().is_some_and(|ext| ext == "bend") {
+        files.push((path, Some(root.to_path_buf())));
+      }
+    }
+    Ok(())
+  }
+
+  let mut files = vec![];
+  for path in paths {
+    if path.is_dir() {
+      search(path, path, &mut files)?;
+    } else {
+      files.push((path.clone(), None));
+    }
+  }
+  Ok(files)
+}
+
+/// Prints the result of a run, on the lines below the label when it takes more than one.
+fn print_result(term: &Term, run_opts: &RunOpts) {
+  let result = run_opts.display_result(term);
+  if run_opts.pretty || result.contains('\n') {
+    println!("Result:\n{result}");
+  } else {
+    println!("Result: {result}");
+  }
+}
+
+/// Writes the run statistics as JSON to `path`, or prints them if no path was given.
+fn write_stats_json(info: &RunInfo, path: Option<&Path>) -> Result<(), Diagnostics> {
+  match path {
+    Some(path) => std::fs::write(path, format!("{}\n", info.display_json()))
+      .map_err(|e| format!("While writing '{}': {e}", path.display()))?,
+    None => println!("{}", info.display_json()),
+  }
+  Ok(())
+}
+
+/// Prints the rewrites and time of each definition, as a table sorted by number of rewrites,
+/// with the definitions of the program that each one came from.
+fn print_profile(profile: &[DefProfile], source_map: &SourceMap) {
+  let width = profile.iter().map(|def| def.name.len()).chain(["DEFINITION".len()]).max().unwrap_or_default();
+  println!(
+    "{:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}  {:<width$}  SOURCE",
+    "REWRITES", "ANNI", "COMM", "ERAS", "DREF", "OPER", "TIME", "DEFINITION"
+  );
+  for def in profile {
+    let time = format!("{:.3}ms", def.time.as_secs_f64() * 1000.0);
+    println!(
+      "{:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}  {:<width$}  {}",
+      def.rewrites.total(),
+      def.rewrites.anni,
+      def.rewrites.comm,
+      def.rewrites.eras,
+      def.rewrites.dref,
+      def.rewrites.oper,
+      time,
+      def.name,
+      source_map.display_source(&def.name)
+    );
+  }
+}
+
+/// Prints the time taken by each compiler pass with the size of the program after it, the nodes of the nets
+/// of each definition, sorted from the largest, and the size of the compiled book.
+/// It goes to stderr, so that the compiled program can still be piped.
+fn print_timings(timings: &[PassTiming], book: &hvmc::ast::Book) {
+  let ms = |time: Duration| format!("{:.3}ms", time.as_secs_f64() * 1000.0);
+  eprintln!("{:>10} {:>10} {:>10}  PASS", "TIME", "DEFS", "NETS");
+  for pass in timings {
+    eprintln!("{:>10} {:>10} {:>10}  {}", ms(pass.time), pass.defs, pass.nets, pass.name);
+  }
+  eprintln!("{:>10} {:>10} {:>10}  total", ms(timings.iter().map(|pass| pass.time).sum()), "", "");
+
+  // The nets generated for a definition, like the ones of its floated combinators, are counted with it.
+  let mut defs = HashMap::<Name, (usize, usize)>::new();
+  for (nam, net) in book.iter() {
+    let (nodes, nets) = defs.entry(Name::new(nam).def_name_from_generated()).or_default();
+    *nodes += count_nodes(net);
+    *nets += 1;
+  }
+  let mut defs = defs.into_iter().collect::<Vec<_>>();
+  defs.sort_by(|(a_nam, (a_nodes, _)), (b_nam, (b_nodes, _))| b_nodes.cmp(a_nodes).then(a_nam.cmp(b_nam)));
+  eprintln!("\n{:>10} {:>10}  DEFINITION", "NODES", "NETS");
+  for (nam, (nodes, nets)) in &defs {
+    eprintln!("{nodes:>10} {nets:>10}  {nam}");
+  }
+
+  let nodes = defs.iter().map(|(_, (nodes, _))| nodes).sum::<usize>();
+  let bytes = book.to_string().len();
+  eprintln!("\nhvm book: {} nets, {nodes} nodes, {bytes} bytes", book.len());
+}
+
+/// Checks whether there's a CUDA device to run programs on, by listing the GPUs known to the Nvidia driver.
+fn cuda_device_available() -> bool {
+  std::process::Command::new("nvidia-smi").arg("-L").output().is_ok_and(|out| {
+    out.status.success() && String::from_utf8_lossy(&out.stdout).lines().any(|line| line.starts_with("GPU"))
+  })
+}
+
+/// Returns the current time of the day in UTC, formatted as `HH:MM:SS`.
+fn utc_time_now() -> String {
+  let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+  format!("{:02}:{:02}:{:02}", secs / 3600 % 24, secs / 60 % 60, secs % 60)
+}
+
+fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
+  let arg_verbose = cli.verbose;
+  let arg_prelude = !cli.no_prelude;
+  // The options of the project's `bend.toml` are the defaults that the command line overrides.
+  let config_dir = cli.mode.config_dir();
+  let config = match &config_dir {
+    Some(dir) => Config::find(dir)?.unwrap_or_default(),
+    None => Config::default(),
+  };
+  let entrypoint = cli.entrypoint.take().or(config.entrypoint.clone());
+  // Only the programs that are run get a random seed, so that compiling one always gives the same output.
+  let seed = cli.seed;
+  let run_seed = move || Some(seed.unwrap_or_else(|| RandomState::new().build_hasher().finish() as u32));
+  let mut base_opts = CompileOpts { entrypoint: entrypoint.map(Name::new), seed, ..CompileOpts::default() };
+  if let Some(float_threshold) = cli.float_threshold.or(config.float_threshold) {
+    base_opts.float_threshold = float_threshold;
+  }
+  if let Some(inline_threshold) = cli.inline_threshold.or(config.inline_threshold) {
+    base_opts.inline_threshold = inline_threshold;
+  }
+  if let Some(limit) = cli.pre_reduce_rewrite_limit.or(config.pre_reduce_rewrite_limit) {
+    base_opts.pre_reduce_rewrite_limit = limit;
+  }
+  if let Some(memory) = cli.pre_reduce_memory.or(config.pre_reduce_memory) {
+    base_opts.pre_reduce_memory = memory;
+  }
+  if cli.incremental {
+    // The cache is kept at the root of the project, next to its `bend.toml`.
+    let root =
+      config.path.parent().map(Path::to_path_buf).or(config_dir).unwrap_or_else(|| PathBuf::from("."));
+    base_opts.cache_dir = Some(root.join(incremental::CACHE_DIR));
+  }
+  let (config_opts, config_warnings) = config_args(&config)?;
+  base_opts = compile_opts_from_cli(&config_opts, base_opts);
+  // Eval, repl and test replace the entrypoint of the program, so they ignore the chosen one.
+  // They only draw a seed for the programs that use it, see `eval_seed`.
+  let eval_opts = CompileOpts { entrypoint: None, ..base_opts.clone() };
+  let message_format = cli.message_format.into();
+  let color = cli.color.enabled();
+  let default_cfg = DiagnosticsConfig { message_format, color, ..DiagnosticsConfig::default() };
+  let run_cfg =
+    DiagnosticsConfig { message_format, color, ..DiagnosticsConfig::new(Severity::Allow, arg_verbose) };
+  let default_cfg = set_warning_cfg_from_config(default_cfg, &config_warnings);
+  let run_cfg = set_warning_cfg_from_config(run_cfg, &config_warnings);
+
+  // The imports are resolved relative to `root`, or to the directory of the file without one.
+  let load_book_with_root = |path: &Path, root: Option<&Path>| -> Result<Book, Diagnostics> {
+    let mut book = match root {
+      _ if path == Path::new(STDIN_PATH) => load_source_to_book(STDIN_NAME, &read_input(path)?)?,
+      Some(root) => load_file_to_book_with_root(path, root)?,
+      None => load_file_to_book(path)?,
+    };
+    if arg_prelude {
+      book.add_prelude();
+    }
     if arg_verbose {
       println!("{book}");
     }
 
     Ok(book)
   };
+  let load_book = |path: &Path| load_book_with_root(path, None);
 
-  let (gen_cmd, gen_supports_io) = match &cli.mode {
-    Mode::GenC(..) => ("gen-c", true),
-    Mode::GenCu(..) => ("gen-cu", false),
-    _ => ("gen", false),
-  };
-
-  let (run_cmd, run_supports_io) = match &cli.mode {
-    Mode::RunC(..) => ("run-c", true),
-    Mode::RunCu(..) => ("run-cu", false),
-    _ => ("run", false),
+  let (runtime, run_supports_io) = match &cli.mode {
+    Mode::RunCu(..) | Mode::Run(RunArgs { gpu: true, .. }) | Mode::RunC(RunArgs { gpu: true, .. }) => {
+      (Runtime::Cuda, false)
+    }
+    Mode::RunC(..) => (Runtime::C, true),
+    _ => (Runtime::Rust, false),
   };
 
   match cli.mode {
-    Mode::Check { comp_opts, warn_opts, path } => {
-      let diagnostics_cfg = set_warning_cfg_from_cli(DiagnosticsConfig::default(), warn_opts);
-      let compile_opts = compile_opts_from_cli(&comp_opts);
+    Mode::Check { comp_opts, warn_opts, paths, .. } => {
+      let diagnostics_cfg = set_warning_cfg_from_cli(default_cfg, warn_opts);
+      let compile_opts = compile_opts_from_cli(&comp_opts, base_opts.clone());
+
+      let single_file = match paths.as_slice() {
+        [path] if !path.is_dir() => Some(path),
+        _ => None,
+      };
+      if let Some(path) = single_file {
+        let mut book = load_book(path)?;
+        let diagnostics = check_book(&mut book, diagnostics_cfg, compile_opts)?;
+        eprintln!("{}", diagnostics);
+        return Ok(());
+      }
+
+      let files = find_source_files(&paths)?;
+      let mut failed = 0;
+      for (file, root) in &files {
+        let res = load_book_with_root(file, root.as_deref()).and_then(|mut book| {
+          let mut diagnostics_cfg = diagnostics_cfg;
+          // Files without an entrypoint are modules meant to be imported,
+          // so their definitions are checked without expecting them to be used.
+          let entrypoints = match &compile_opts.entrypoint {
+            Some(entrypoint) => vec![entrypoint.clone()],
+            None => vec![Name::new(bend::ENTRY_POINT), Name::new(bend::HVM1_ENTRY_POINT)],
+          };
+          if !entrypoints.iter().any(|nam| book.defs.contains_key(nam)) {
+            set_main(&mut book, Term::Era);
+            diagnostics_cfg.unused_definition = Severity::Allow;
+          }
+          check_book(&mut book, diagnostics_cfg, compile_opts.clone())
+        });
+        match res {
+          Ok(diagnostics) => {
+            eprint!("{diagnostics}");
+            println!("check {} ... ok", file.display());
+          }
+          Err(mut diagnostics) => {
+            diagnostics.config.message_format = message_format;
+            diagnostics.config.color = color;
+            eprint!("{diagnostics}");
+            println!("check {} ... FAILED", file.display());
+            failed += 1;
+          }
+        }
+      }
+
+      let passed = files.len() - failed;
+      let result = if failed == 0 { "ok" } else { "FAILED" };
+      println!("\ncheck result: {result}. {passed} passed; {failed} failed");
+      if failed != 0 {
+        Err(format!("{failed} files failed the check."))?;
+      }
+    }
+
+    Mode::GenHvm(GenHvmArgs {
+      gen_args: GenArgs { comp_opts, warn_opts, path, .. },
+      output,
+      emit,
+      format,
+      list_passes,
+      timings,
+      source_map,
+      ..
+    }) => {
+      let diagnostics_cfg = set_warning_cfg_from_cli(default_cfg, warn_opts);
+      let opts = compile_opts_from_cli(&comp_opts, base_opts.clone());
+      if list_passes {
+        print!("{}", Pipeline::default().display(&opts));
+        return Ok(());
+      }
+
+      let binary = format == HvmFormatArgs::Bin;
+      if binary && !matches!(emit.as_slice(), [EmitArgs::Hvm]) {
+        Err("Only the compiled hvm book can be written in the binary format.".to_string())?;
+      }
 
       let mut book = load_book(&path)?;
-      let diagnostics = check_book(&mut book, diagnostics_cfg, compile_opts)?;
-      eprintln!("{}", diagnostics);
+      let dot = emit.iter().any(|emit| matches!(emit, EmitArgs::Dot));
+      let stages: Vec<Stage> = emit.into_iter().filter_map(EmitArgs::stage).collect();
+      let mut outs = vec![];
+      let mut bytes = None;
+      let mut hook = |stage: Stage, program: &dyn std::fmt::Display| {
+        if stages.contains(&stage) {
+          outs.push((stage.to_string(), program.to_string()));
+        }
+      };
+      // Only go as far in the pipeline as the last stage that was asked for.
+      match if dot || timings || source_map.is_some() { Some(&Stage::Hvm) } else { stages.iter().max() } {
+        Some(Stage::Hvm) => {
+          let compile_res = compile_book_with_hook(&mut book, opts, diagnostics_cfg, None, &mut hook)?;
+          eprint!("{}", compile_res.diagnostics);
+          if timings {
+            print_timings(&compile_res.timings, &compile_res.core_book);
+          }
+          if dot {
+            outs.push(("dot".to_string(), book_to_dot(&compile_res.core_book)));
+          }
+          if let Some(source_map) = &source_map {
+            std::fs::write(source_map, format!("{}\n", compile_res.source_map.display_json()))
+              .map_err(|e| format!("While writing '{}': {e}", source_map.display()))?;
+          }
+          if binary {
+            bytes = Some(book_to_bytes(&compile_res.core_book));
+          }
+        }
+        Some(Stage::Desugared | Stage::Linearized) => {
+          let diagnostics = desugar_book_with_hook(&mut book, opts, diagnostics_cfg, None, &mut hook)?;
+          eprint!("{diagnostics}");
+        }
+        Some(Stage::Ast) | None => hook(Stage::Ast, &book),
+      }
+      let out = match (bytes, outs.as_slice()) {
+        (Some(bytes), _) => bytes,
+        (None, [(_, out)]) => format!("{out}\n").into_bytes(),
+        (None, outs) => {
+          let outs = outs.iter().map(|(stage, out)| format!("---------- {stage} ----------\n{out}"));
+          format!("{}\n", outs.collect::<Vec<_>>().join("\n\n")).into_bytes()
+        }
+      };
+
+      if let Some(output) = output {
+        if let Some(parent) = output.parent() {
+          std::fs::create_dir_all(parent)
+            .map_err(|e| format!("While creating '{}': {e}", parent.display()))?;
+        }
+        std::fs::write(&output, out).map_err(|e| format!("While writing '{}': {e}", output.display()))?;
+      } else {
+        use std::io::Write;
+        std::io::stdout().write_all(&out).map_err(|e| format!("While writing the output: {e}"))?;
+      }
     }
 
-    Mode::GenHvm(GenArgs { comp_opts, warn_opts, path, .. }) => {
-      let diagnostics_cfg = set_warning_cfg_from_cli(DiagnosticsConfig::default(), warn_opts);
-      let opts = compile_opts_from_cli(&comp_opts);
+    Mode::GenC(GenArgs { comp_opts, io, warn_opts, path }) => {
+      if io {
+        Err("Selected mode does not support io.".to_string())?;
+      }
+      let diagnostics_cfg = set_warning_cfg_from_cli(default_cfg, warn_opts);
+      let opts = compile_opts_from_cli(&comp_opts, base_opts.clone());
 
       let mut book = load_book(&path)?;
       let compile_res = compile_book(&mut book, opts, diagnostics_cfg, None)?;
-
       eprint!("{}", compile_res.diagnostics);
-      println!("{}", compile_res.core_book);
+
+      println!("{}", book_to_c(&compile_res.core_book)?);
     }
 
-    Mode::GenC(GenArgs { comp_opts, io, warn_opts, path })
-    | Mode::GenCu(GenArgs { comp_opts, io, warn_opts, path }) => {
-      if io && !gen_supports_io {
+    Mode::GenCu(GenArgs { comp_opts, io, warn_opts, path }) => {
+      if io {
         Err("Selected mode does not support io.".to_string())?;
       }
-      let diagnostics_cfg = set_warning_cfg_from_cli(DiagnosticsConfig::default(), warn_opts);
-      let opts = compile_opts_from_cli(&comp_opts);
+      let diagnostics_cfg = set_warning_cfg_from_cli(default_cfg, warn_opts);
+      let opts = compile_opts_from_cli(&comp_opts, base_opts.clone());
 
       let mut book = load_book(&path)?;
       let compile_res = compile_book(&mut book, opts, diagnostics_cfg, None)?;
@@ -297,10 +1310,7 @@ fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
 
       let gen_fn = |out_path: &str| {
         let mut process = std::process::Command::new("hvm");
-        process.arg(gen_cmd).arg(out_path);
-        if io {
-          process.arg("--io");
-        }
+        process.arg("gen-cu").arg(out_path);
         process.output().map_err(|e| format!("While running hvm: {e}"))
       };
 
@@ -315,9 +1325,9 @@ fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
     }
 
     Mode::Desugar { path, comp_opts, warn_opts, pretty } => {
-      let diagnostics_cfg = set_warning_cfg_from_cli(DiagnosticsConfig::default(), warn_opts);
+      let diagnostics_cfg = set_warning_cfg_from_cli(default_cfg, warn_opts);
 
-      let opts = compile_opts_from_cli(&comp_opts);
+      let opts = compile_opts_from_cli(&comp_opts, base_opts.clone());
 
       let mut book = load_book(&path)?;
       let diagnostics = desugar_book(&mut book, opts, diagnostics_cfg, None)?;
@@ -330,63 +1340,725 @@ fn execute_cli_mode(mut cli: Cli) -> Result<(), Diagnostics> {
       }
     }
 
-    Mode::Run(RunArgs { pretty, io, run_opts, comp_opts, warn_opts, path, arguments })
-    | Mode::RunC(RunArgs { pretty, io, run_opts, comp_opts, warn_opts, path, arguments })
-    | Mode::RunCu(RunArgs { pretty, io, run_opts, comp_opts, warn_opts, path, arguments }) => {
-      let CliRunOpts { linear, print_stats } = run_opts;
+    Mode::Verify { comp_opts, warn_opts, run, max_rewrites, path } => {
+      let diagnostics_cfg = set_warning_cfg_from_cli(default_cfg, warn_opts);
+      let opts = compile_opts_from_cli(&comp_opts, base_opts.clone());
+
+      let mut book = load_book(&path)?;
+      let res = compile_book(&mut book, opts, diagnostics_cfg, None)?;
+      eprint!("{}", res.diagnostics);
+
+      let parsed = check_roundtrip(&res.core_book).map_err(|errs| {
+        format!("The compiled program is different after printing and parsing it back:\n{}", errs.join("\n"))
+      })?;
+      println!("The {} compiled nets are the same after printing and parsing them back.", parsed.len());
+
+      if run {
+        let limits = Limits { max_rewrites, ..Default::default() };
+        let result = |core_book: &hvmc::ast::Book| -> Result<String, String> {
+          let (net, _) = interpreter::normalize_with_stats(core_book, limits)?;
+          let (term, _) = readback_hvm_net(&net, &book, &res.labels, false, false);
+          Ok(term.to_string())
+        };
+        let (compiled, parsed) = (result(&res.core_book)?, result(&parsed)?);
+        if compiled != parsed {
+          Err(format!("The programs give different results:\n  compiled: {compiled}\n  parsed:   {parsed}"))?;
+        }
+        println!("Both programs give the same result: {compiled}");
+      }
+    }
+
+    Mode::Fmt { indent, max_width, check, path } => {
+      let code = read_input(&path)?;
+      let name = if path == Path::new(STDIN_PATH) { Path::new(STDIN_NAME) } else { &path };
+      let formatted = format_source(&code, name, FormatOpts { indent, max_width })?;
+
+      if !check {
+        print!("{formatted}");
+      } else if formatted != code {
+        Err(format!("File '{}' is not formatted.", path.display()))?;
+      }
+    }
+
+    Mode::Doc { format, output, path } => {
+      let code = read_input(&path)?;
+      let book = if path == Path::new(STDIN_PATH) {
+        load_source_to_book(STDIN_NAME, &code)?
+      } else {
+        do_parse_book(&code, &path, Book::builtins())?
+      };
+      let title = path.file_stem().map_or(STDIN_NAME.into(), |stem| stem.to_string_lossy());
+      let doc = book.document(&title, format.into());
+
+      if let Some(output) = output {
+        if let Some(parent) = output.parent() {
+          std::fs::create_dir_all(parent)
+            .map_err(|e| format!("While creating '{}': {e}", parent.display()))?;
+        }
+        std::fs::write(&output, doc).map_err(|e| format!("While writing '{}': {e}", output.display()))?;
+      } else {
+        print!("{doc}");
+      }
+    }
+
+    Mode::Explain { code } => match code {
+      Some(code) => match diagnostics::explain(&code) {
+        Some(explanation) => print!("{explanation}"),
+        None => Err(format!("Unknown diagnostic code '{code}'."))?,
+      },
+      None => print!("{}", diagnostics::list_codes()),
+    },
+
+    Mode::Completions { shell } => {
+      clap_complete::generate(shell, &mut Cli::command(), "bend", &mut std::io::stdout());
+    }
+
+    Mode::Lsp { comp_opts, warn_opts } => {
+      let diagnostics_cfg = set_warning_cfg_from_cli(default_cfg, warn_opts);
+      let compile_opts = compile_opts_from_cli(&comp_opts, base_opts);
+      let server = Server::new(compile_opts, diagnostics_cfg, arg_prelude);
+      server
+        .run(std::io::stdin().lock(), std::io::stdout().lock())
+        .map_err(|e| format!("Language server error: {e}"))?;
+    }
+
+    Mode::Init { path } => {
+      init_project(&path)?;
+      println!("Created a Bend project in '{}'.", path.display());
+      println!("Run it with `bend run {}`.", path.join(INIT_MAIN_PATH).display());
+    }
+
+    Mode::Run(RunArgs {
+      pretty,
+      io,
+      stream,
+      profile,
+      lazy,
+      debug_interactive,
+      trace_def,
+      trace_every,
+      stats_json,
+      max_rewrites,
+      timeout,
+      threads,
+      output_format,
+      run_opts,
+      comp_opts,
+      warn_opts,
+      path,
+      arguments,
+      from,
+      ..
+    })
+    | Mode::RunC(RunArgs {
+      pretty,
+      io,
+      stream,
+      profile,
+      lazy,
+      debug_interactive,
+      trace_def,
+      trace_every,
+      stats_json,
+      max_rewrites,
+      timeout,
+      threads,
+      output_format,
+      run_opts,
+      comp_opts,
+      warn_opts,
+      path,
+      arguments,
+      from,
+      ..
+    })
+    | Mode::RunCu(RunArgs {
+      pretty,
+      io,
+      stream,
+      profile,
+      lazy,
+      debug_interactive,
+      trace_def,
+      trace_every,
+      stats_json,
+      max_rewrites,
+      timeout,
+      threads,
+      output_format,
+      run_opts,
+      comp_opts,
+      warn_opts,
+      path,
+      arguments,
+      from,
+      ..
+    }) => {
+      let CliRunOpts { linear, raw_readback, print_stats, display } = run_opts;
+      let from_hvm = match from {
+        Some(from) => from == FromArgs::Hvmc,
+        None => path.extension().is_some_and(|ext| ext == "hvmc" || ext == "hvmb"),
+      };
+      if from_hvm && arguments.is_some() {
+        Err("Arguments can't be applied to a program that was already compiled to hvm.".to_string())?;
+      }
 
       if io && !run_supports_io {
         Err("Selected mode does not support io.".to_string())?;
       }
+      if stream && (io || runtime != Runtime::Rust) {
+        Err("Selected mode does not support streaming.".to_string())?;
+      }
+      if profile && (io || stream || runtime != Runtime::Rust) {
+        Err("Selected mode does not support profiling.".to_string())?;
+      }
+      if lazy && (io || stream || profile || runtime != Runtime::Rust) {
+        Err("Selected mode does not support lazy evaluation.".to_string())?;
+      }
+      if max_rewrites.is_some() && (io || runtime != Runtime::Rust) {
+        Err("Selected mode does not support limiting the rewrites.".to_string())?;
+      }
+      if threads.is_some_and(|threads| threads > 1) && (io || runtime != Runtime::C) {
+        Err("Selected mode does not support setting the number of threads.".to_string())?;
+      }
+      if debug_interactive && (io || stream || profile || lazy || runtime != Runtime::Rust) {
+        Err("Selected mode does not support the interactive debugger.".to_string())?;
+      }
+      let tracing = !trace_def.is_empty() || trace_every.is_some();
+      if tracing && (io || stream || profile || lazy || debug_interactive || runtime != Runtime::Rust) {
+        Err("Selected mode does not support tracing.".to_string())?;
+      }
+      let json = output_format == OutputFormatArgs::Json;
+      if json && (stream || profile || debug_interactive || tracing) {
+        Err("Selected mode does not support the JSON output format.".to_string())?;
+      }
+      if runtime == Runtime::Cuda && !cuda_device_available() {
+        Err("No CUDA device was found. Use 'bend run-c' to run the program on the CPU instead.".to_string())?;
+      }
 
-      let diagnostics_cfg =
-        set_warning_cfg_from_cli(DiagnosticsConfig::new(Severity::Allow, arg_verbose), warn_opts);
+      let diagnostics_cfg = set_warning_cfg_from_cli(run_cfg, warn_opts);
 
-      let compile_opts = compile_opts_from_cli(&comp_opts);
+      let compile_opts =
+        CompileOpts { io, seed: run_seed(), ..compile_opts_from_cli(&comp_opts, base_opts.clone()) };
 
       compile_opts.check_for_strict();
 
-      let run_opts = RunOpts { linear_readback: linear, pretty };
+      let run_opts = RunOpts {
+        linear_readback: linear,
+        raw_readback,
+        pretty,
+        display: display.unwrap_or_default(),
+        max_rewrites,
+        timeout,
+        lazy,
+        threads,
+      };
 
-      let book = load_book(&path)?;
-      if let Some((term, stats, diags)) =
-        run_book_with_fn(book, run_opts, compile_opts, diagnostics_cfg, arguments, run_cmd, io)?
-      {
-        eprint!("{diags}");
-        if pretty {
-          println!("Result:\n{}", term.display_pretty(0));
+      // Only the interpreter can stop at a limit and still read back the partial result.
+      let limited = max_rewrites.is_some() || timeout.is_some();
+      let interpret = runtime == Runtime::Rust && !io && (profile || lazy || limited);
+
+      // A program already compiled to hvm is run as it is, and read back with the builtin datatypes.
+      if debug_interactive || tracing {
+        let (book, res) = if from_hvm {
+          (
+            Book::builtins(),
+            Compiler::new().hvm_book(load_hvm_book(&path)?).warnings(diagnostics_cfg).compile()?,
+          )
         } else {
-          println!("Result: {}", term);
+          let mut book = load_book(&path)?;
+          let res = compile_book(&mut book, compile_opts, diagnostics_cfg, arguments)?;
+          (book, res)
+        };
+        eprint!("{}", res.diagnostics);
+        let flat = flatten_book(&res.core_book)?;
+        let net =
+          if debug_interactive { step_through(&flat) } else { Some(trace(&flat, &trace_def, trace_every)?) };
+        if let Some(net) = net {
+          let (term, mut diags) = readback_hvm_net(&net, &book, &res.labels, linear, raw_readback);
+          diags.config = diagnostics_cfg;
+          eprint!("{diags}");
+          print_result(&term, &run_opts);
+        }
+        return Ok(());
+      }
+
+      let (book, compiler) = if from_hvm {
+        (Book::builtins(), Compiler::new().hvm_book(load_hvm_book(&path)?))
+      } else {
+        let book = load_book(&path)?;
+        (book.clone(), Compiler::new().book(book))
+      };
+      let mut compiler = compiler
+        .opts(compile_opts)
+        .warnings(diagnostics_cfg)
+        .run_opts(run_opts)
+        .runtime(if interpret { Runtime::Interpreter } else { runtime })
+        .profile(profile);
+      if let Some(arguments) = arguments {
+        compiler = compiler.args(arguments);
+      }
+      if stream {
+        let RunResult { term, stats, info, diagnostics, .. } = compiler.stream(|elem, diagnostics| {
+          eprint!("{diagnostics}");
+          println!("{}", run_opts.display_result(&elem));
+        })?;
+        eprint!("{diagnostics}");
+        if let Some(term) = term.filter(|term| !matches!(term, Term::List { els } if els.is_empty())) {
+          print_result(&term, &run_opts);
         }
         if print_stats {
           println!("{stats}");
         }
+        if let Some(stats_json) = stats_json {
+          write_stats_json(&info, stats_json.as_deref())?;
+        }
+        return Ok(());
+      }
+      let res = compiler.run()?;
+      if json {
+        println!("{}", res.display_json(&book));
+        if let Some(stats_json) = stats_json {
+          write_stats_json(&res.info, stats_json.as_deref())?;
+        }
+        return Ok(());
+      }
+      let RunResult { term, stats, info, diagnostics, profile, source_map } = res;
+      if let Some(term) = term {
+        eprint!("{diagnostics}");
+        print_result(&term, &run_opts);
+        if print_stats {
+          println!("{stats}");
+        }
+      }
+      if let Some(profile) = profile {
+        print_profile(&profile, &source_map);
+      }
+      if let Some(stats_json) = stats_json {
+        write_stats_json(&info, stats_json.as_deref())?;
+      }
+    }
+
+    Mode::Test { comp_opts, warn_opts, path } => {
+      let diagnostics_cfg = set_warning_cfg_from_cli(run_cfg, warn_opts);
+      let compile_opts = compile_opts_from_cli(&comp_opts, eval_opts.clone());
+
+      let book = load_book(&path)?;
+      let tests = book.defs.keys().filter(|nam| nam.starts_with(TEST_PREFIX)).cloned().collect::<Vec<_>>();
+      let compile_opts = CompileOpts { seed: eval_seed(&book, compile_opts.seed), ..compile_opts };
+
+      // Check the whole program once, instead of reporting the same errors for every test.
+      if let Some(test) = tests.first() {
+        let mut check = book.clone();
+        set_main(&mut check, Term::Var { nam: test.clone() });
+        check_book(&mut check, diagnostics_cfg, compile_opts.clone())?;
+      }
+
+      println!("running {} tests", tests.len());
+      let mut failed = vec![];
+      for test in &tests {
+        let mut book = book.clone();
+        set_main(&mut book, Term::Var { nam: test.clone() });
+
+        let start = Instant::now();
+        let res = run_book(book, RunOpts::default(), compile_opts.clone(), diagnostics_cfg, None);
+        let time = start.elapsed().as_secs_f64() * 1000.0;
+        match res {
+          Ok((term, _, _)) if is_test_success(&term) => println!("test {test} ... ok ({time:.0}ms)"),
+          Ok((term, _, _)) => {
+            println!("test {test} ... FAILED ({time:.0}ms)");
+            failed.push((test.clone(), format!("Returned '{term}'.")));
+          }
+          Err(diagnostics) => {
+            println!("test {test} ... FAILED ({time:.0}ms)");
+            failed.push((test.clone(), diagnostics.to_string()));
+          }
+        }
+      }
+
+      if !failed.is_empty() {
+        println!("\nfailures:");
+        for (test, msg) in &failed {
+          println!("---- {test} ----\n{}\n", msg.trim_end());
+        }
+      }
+      let passed = tests.len() - failed.len();
+      let result = if failed.is_empty() { "ok" } else { "FAILED" };
+      println!("\ntest result: {result}. {passed} passed; {} failed", failed.len());
+      if !failed.is_empty() {
+        Err(format!("{} tests failed.", failed.len()))?;
       }
     }
+
+    Mode::Eval { pretty, run_opts, comp_opts, warn_opts, with, expr } => {
+      let CliRunOpts { linear, raw_readback, print_stats, display } = run_opts;
+      let diagnostics_cfg = set_warning_cfg_from_cli(run_cfg, warn_opts);
+      let compile_opts = compile_opts_from_cli(&comp_opts, eval_opts.clone());
+      let run_opts = RunOpts {
+        linear_readback: linear,
+        raw_readback,
+        pretty,
+        display: display.unwrap_or_default(),
+        ..RunOpts::default()
+      };
+
+      let mut book = match with {
+        Some(path) => load_book(&path)?,
+        None => {
+          let mut book = Book::builtins();
+          if arg_prelude {
+            book.add_prelude();
+          }
+          book
+        }
+      };
+      set_main(&mut book, expr);
+      let compile_opts = CompileOpts { seed: eval_seed(&book, compile_opts.seed), ..compile_opts };
+
+      let (term, stats, diags) = run_book(book, run_opts, compile_opts, diagnostics_cfg, None)?;
+      eprint!("{diags}");
+      println!("{}", run_opts.display_result(&term));
+      if print_stats {
+        println!("{stats}");
+      }
+    }
+
+    Mode::Repl { pretty, run_opts, comp_opts, warn_opts, path } => {
+      let CliRunOpts { linear, raw_readback, print_stats, display } = run_opts;
+      let diagnostics_cfg = set_warning_cfg_from_cli(run_cfg, warn_opts);
+      let compile_opts = compile_opts_from_cli(&comp_opts, eval_opts.clone());
+      let run_opts = RunOpts {
+        linear_readback: linear,
+        raw_readback,
+        pretty,
+        display: display.unwrap_or_default(),
+        ..RunOpts::default()
+      };
+
+      let mut file_book = Book::builtins();
+      if arg_prelude {
+        file_book.add_prelude();
+      }
+      let mut repl = Repl { path: None, file_book, prompt_book: Book::default(), prelude: arg_prelude };
+      if let Some(path) = path {
+        repl.load(path)?;
+      }
+      repl.run(run_opts, compile_opts, diagnostics_cfg, print_stats);
+    }
   };
   Ok(())
 }
 
-fn set_warning_cfg_from_cli(mut cfg: DiagnosticsConfig, warn_opts: CliWarnOpts) -> DiagnosticsConfig {
-  fn set(cfg: &mut DiagnosticsConfig, severity: Severity, cli_val: WarningArgs) {
-    match cli_val {
-      WarningArgs::All => {
-        cfg.irrefutable_match = severity;
-        cfg.redundant_match = severity;
-        cfg.unreachable_match = severity;
-        cfg.unused_definition = severity;
-        cfg.repeated_bind = severity;
-        cfg.recursion_cycle = severity;
+/// Passing this as the path of the input file reads the program from the standard input.
+const STDIN_PATH: &str = "-";
+
+/// How the standard input is called in error messages.
+const STDIN_NAME: &str = "<stdin>";
+
+/// Reads the contents of the input file, or of the standard input if the path is [`STDIN_PATH`].
+fn read_input(path: &Path) -> Result<String, String> {
+  if path == Path::new(STDIN_PATH) {
+    let mut code = String::new();
+    std::io::stdin()
+      .read_to_string(&mut code)
+      .map_err(|e| format!("While reading the standard input: {e}"))?;
+    Ok(code)
+  } else {
+    std::fs::read_to_string(path).map_err(|e| e.to_string())
+  }
+}
+
+/// Reads the input file as raw bytes, or the standard input if the path is [`STDIN_PATH`].
+fn read_input_bytes(path: &Path) -> Result<Vec<u8>, String> {
+  if path == Path::new(STDIN_PATH) {
+    let mut bytes = vec![];
+    std::io::stdin().read_to_end(&mut bytes).map_err(|e| format!("While reading the standard input: {e}"))?;
+    Ok(bytes)
+  } else {
+    std::fs::read(path).map_err(|e| e.to_string())
+  }
+}
+
+/// Reads a program already compiled to hvm, in the text format or in the binary one of `gen-hvm --format bin`.
+fn load_hvm_book(path: &Path) -> Result<hvmc::ast::Book, String> {
+  let bytes = read_input_bytes(path)?;
+  if is_binary_book(&bytes) {
+    return book_from_bytes(&bytes);
+  }
+  let code = String::from_utf8(bytes).map_err(|e| format!("While reading the hvm program: {e}"))?;
+  parse_hvm_book(&code)
+}
+
+/// Definitions whose name starts with this prefix are run by `bend test`.
+const TEST_PREFIX: &str = "test_";
+
+/// Tests pass if they return the number `1` or a constructor named `True`.
+fn is_test_success(term: &Term) -> bool {
+  match term {
+    Term::Num { val: Num::U24(1) } => true,
+    Term::Ref { nam } => nam == "True" || nam.ends_with("/True"),
+    _ => false,
+  }
+}
+
+/// The state of an interactive session started with `bend repl`.
+struct Repl {
+  /// The file loaded with `:load`, if any.
+  path: Option<PathBuf>,
+  /// The builtins and the definitions of the loaded file.
+  file_book: Book,
+  /// The definitions entered at the prompt, which shadow the ones from the file.
+  prompt_book: Book,
+  /// Whether the prelude is available, unless disabled with `--no-prelude`.
+  prelude: bool,
+}
+
+const REPL_HELP: &str = "\
+Enter a term to evaluate it, or a definition to add it to the session.
+Commands:
+  :load <path>  Loads the definitions of a file, discarding the ones entered at the prompt
+  :reload       Reloads the current file, keeping the definitions entered at the prompt
+  :help         Shows this message
+  :quit         Exits the session";
+
+impl Repl {
+  fn run(
+    &mut self,
+    run_opts: RunOpts,
+    compile_opts: CompileOpts,
+    diagnostics_cfg: DiagnosticsConfig,
+    print_stats: bool,
+  ) {
+    use std::io::Write;
+
+    println!("Bend {} interactive mode. Type :help for the available commands.", env!("CARGO_PKG_VERSION"));
+    let mut line = String::new();
+    loop {
+      print!("> ");
+      let _ = std::io::stdout().flush();
+      line.clear();
+      match std::io::stdin().read_line(&mut line) {
+        Ok(0) => break,
+        Ok(_) => (),
+        Err(e) => {
+          eprintln!("{e}");
+          break;
+        }
+      }
+      let input = line.trim();
+
+      let res = match input.split_once(char::is_whitespace).unwrap_or((input, "")) {
+        ("", _) => Ok(()),
+        (":quit" | ":q", _) => break,
+        (":help" | ":h", _) => {
+          println!("{REPL_HELP}");
+          Ok(())
+        }
+        (":load" | ":l", path) if !path.trim().is_empty() => self.load(PathBuf::from(path.trim())),
+        (":load" | ":l", _) => Err("Missing path for ':load'.".to_string().into()),
+        (":reload" | ":r", _) => self.reload(),
+        (cmd, _) if cmd.starts_with(':') => {
+          Err(format!("Unknown command '{cmd}'. Type :help for the available commands.").into())
+        }
+        _ => self.eval(input, run_opts, compile_opts.clone(), diagnostics_cfg, print_stats),
+      };
+
+      if let Err(mut diagnostics) = res {
+        diagnostics.config.color = diagnostics_cfg.color;
+        eprint!("{diagnostics}");
+      }
+    }
+  }
+
+  fn load(&mut self, path: PathBuf) -> Result<(), Diagnostics> {
+    self.file_book = self.load_file(&path)?;
+    self.prompt_book = Book::default();
+    println!("Loaded '{}'.", path.display());
+    self.path = Some(path);
+    Ok(())
+  }
+
+  fn reload(&mut self) -> Result<(), Diagnostics> {
+    let Some(path) = &self.path else {
+      return Err("No file loaded.".to_string().into());
+    };
+    self.file_book = self.load_file(path)?;
+    println!("Reloaded '{}'.", path.display());
+    Ok(())
+  }
+
+  fn load_file(&self, path: &Path) -> Result<Book, Diagnostics> {
+    let mut book = load_file_to_book(path)?;
+    if self.prelude {
+      book.add_prelude();
+    }
+    Ok(book)
+  }
+
+  /// Evaluates the input if it's a term, otherwise adds its definitions to the session.
+  fn eval(
+    &mut self,
+    input: &str,
+    run_opts: RunOpts,
+    compile_opts: CompileOpts,
+    diagnostics_cfg: DiagnosticsConfig,
+    print_stats: bool,
+  ) -> Result<(), Diagnostics> {
+    let term = match parse_term(input) {
+      Ok(term) => term,
+      Err(term_err) => {
+        // Not a term, so try reading it as definitions instead, reporting
+        // the error for the term if it's not valid as definitions either.
+        let Ok(defs) = TermParser::new(input).parse_book(Book::default(), false) else {
+          return Err(term_err.into());
+        };
+        let names = defs.adts.keys().chain(defs.defs.keys()).map(|nam| nam.to_string()).collect::<Vec<_>>();
+        merge_books(&mut self.prompt_book, defs);
+        println!("Defined {}.", names.join(", "));
+        return Ok(());
+      }
+    };
+
+    // The term is evaluated as the entrypoint of a copy of the session's book.
+    let mut book = self.file_book.clone();
+    merge_books(&mut book, self.prompt_book.clone());
+    set_main(&mut book, term);
+    let compile_opts = CompileOpts { seed: eval_seed(&book, compile_opts.seed), ..compile_opts };
+
+    let (term, stats, diags) = run_book(book, run_opts, compile_opts, diagnostics_cfg, None)?;
+    eprint!("{diags}");
+    println!("{}", run_opts.display_result(&term));
+    if print_stats {
+      println!("{stats}");
+    }
+    Ok(())
+  }
+}
+
+/// Parses a program argument given in the command line.
+/// Arguments that aren't a valid term, like text with spaces, are passed as a string.
+fn parse_argument(arg: &str) -> Result<Term, String> {
+  Ok(parse_term(arg).unwrap_or_else(|_| Term::str(arg)))
+}
+
+fn parse_timeout(arg: &str) -> Result<Duration, String> {
+  let secs: f64 = arg.parse().map_err(|e| format!("{e}"))?;
+  Duration::try_from_secs_f64(secs).map_err(|e| format!("{e}"))
+}
+
+/// The seed to evaluate `book` with: the one given with `--seed`, or a random one if the program
+/// uses the pseudo-random number generator, so that the others always compile the same way.
+fn eval_seed(book: &Book, seed: Option<u32>) -> Option<u32> {
+  seed.or_else(|| book.uses_rand_seed().then(|| RandomState::new().build_hasher().finish() as u32))
+}
+
+/// Replaces the entrypoint of the book with a `main` definition with the given body.
+fn set_main(book: &mut Book, body: Term) {
+  book.entrypoint = None;
+  book.defs.shift_remove(&Name::new(bend::HVM1_ENTRY_POINT));
+  let main = Name::new(bend::ENTRY_POINT);
+  let rule = Rule { pats: vec![], guard: None, body };
+  book.defs.insert(main.clone(), Definition {
+    name: main,
+    typ: None,
+    rules: vec![rule],
+    builtin: false,
+    span: None,
+    doc: None,
+  });
+}
+
+/// Adds the definitions and datatypes of `from` to `into`, replacing the ones with the same name.
+fn merge_books(into: &mut Book, from: Book) {
+  for (nam, adt) in from.adts {
+    if let Some(old) = into.adts.shift_remove(&nam) {
+      for ctr in old.ctrs.keys() {
+        into.ctrs.shift_remove(ctr);
       }
-      WarningArgs::IrrefutableMatch => cfg.irrefutable_match = severity,
-      WarningArgs::RedundantMatch => cfg.redundant_match = severity,
-      WarningArgs::UnreachableMatch => cfg.unreachable_match = severity,
-      WarningArgs::UnusedDefinition => cfg.unused_definition = severity,
-      WarningArgs::RepeatedBind => cfg.repeated_bind = severity,
-      WarningArgs::RecursionCycle => cfg.recursion_cycle = severity,
     }
+    for ctr in adt.ctrs.keys() {
+      into.ctrs.insert(ctr.clone(), nam.clone());
+    }
+    into.adts.insert(nam, adt);
+  }
+  for (nam, def) in from.defs {
+    into.defs.insert(nam, def);
   }
+}
 
+/// The level of each warning, in the order they're set.
+type WarningLevels = Vec<(WarningArgs, Severity)>;
+
+/// Reads the optimizations and warning levels of the project's configuration as their command line options.
+fn config_args(config: &Config) -> Result<(Vec<OptArgs>, WarningLevels), String> {
+  use clap::ValueEnum;
+
+  let in_config = |msg: String| format!("In {} :\n{msg}", config.path.display());
+  let opts = config
+    .opts
+    .iter()
+    .map(|opt| OptArgs::from_str(opt, false).map_err(|_| in_config(format!("Unknown optimization '{opt}'."))))
+    .collect::<Result<_, _>>()?;
+  let warnings = config
+    .warnings
+    .iter()
+    .map(|(warn, level)| {
+      let warn =
+        WarningArgs::from_str(warn, false).map_err(|_| in_config(format!("Unknown warning '{warn}'.")))?;
+      let severity = match level.as_str() {
+        "allow" => Severity::Allow,
+        "warn" => Severity::Warning,
+        "deny" => Severity::Error,
+        _ => Err(in_config(format!("Unknown warning level '{level}'. Expected 'allow', 'warn' or 'deny'.")))?,
+      };
+      Ok((warn, severity))
+    })
+    .collect::<Result<_, String>>()?;
+  Ok((opts, warnings))
+}
+
+fn set_warning_cfg_from_config(
+  mut cfg: DiagnosticsConfig,
+  warnings: &[(WarningArgs, Severity)],
+) -> DiagnosticsConfig {
+  for (warn, severity) in warnings {
+    set_warning(&mut cfg, *severity, warn.clone());
+  }
+  cfg
+}
+
+fn set_warning(cfg: &mut DiagnosticsConfig, severity: Severity, warn: WarningArgs) {
+  match warn {
+    WarningArgs::All => {
+      cfg.irrefutable_match = severity;
+      cfg.redundant_match = severity;
+      cfg.unreachable_match = severity;
+      cfg.unreachable_arm = severity;
+      cfg.shadowed_variable = severity;
+      cfg.unused_vars = severity;
+      cfg.implicit_dup = severity;
+      cfg.unused_dup = severity;
+      cfg.unused_definition = severity;
+      cfg.repeated_bind = severity;
+      cfg.recursion_cycle = severity;
+      cfg.unguarded_recursion = severity;
+    }
+    WarningArgs::IrrefutableMatch => cfg.irrefutable_match = severity,
+    WarningArgs::RedundantMatch => cfg.redundant_match = severity,
+    WarningArgs::UnreachableMatch => cfg.unreachable_match = severity,
+    WarningArgs::UnreachableArm => cfg.unreachable_arm = severity,
+    WarningArgs::ShadowedVariable => cfg.shadowed_variable = severity,
+    WarningArgs::UnusedVars => cfg.unused_vars = severity,
+    WarningArgs::ImplicitDup => cfg.implicit_dup = severity,
+    WarningArgs::UnusedDup => cfg.unused_dup = severity,
+    WarningArgs::UnusedDefinition => cfg.unused_definition = severity,
+    WarningArgs::RepeatedBind => cfg.repeated_bind = severity,
+    WarningArgs::RecursionCycle => cfg.recursion_cycle = severity,
+    WarningArgs::UnguardedRecursion => cfg.unguarded_recursion = severity,
+  }
+}
+
+fn set_warning_cfg_from_cli(mut cfg: DiagnosticsConfig, warn_opts: CliWarnOpts) -> DiagnosticsConfig {
   let cmd = Cli::command();
   let matches = cmd.get_matches();
   let subcmd_name = matches.subcommand_name().expect("To have a subcommand");
@@ -398,12 +2070,29 @@ fn set_warning_cfg_from_cli(mut cfg: DiagnosticsConfig, warn_opts: CliWarnOpts)
     let mut denies = warn_opts.denies.into_iter();
     for id in warn_opts_ids {
       match id.as_ref() {
-        "allows" => set(&mut cfg, Severity::Allow, allows.next().unwrap()),
-        "denies" => set(&mut cfg, Severity::Error, denies.next().unwrap()),
-        "warns" => set(&mut cfg, Severity::Warning, warns.next().unwrap()),
+        "allows" => set_warning(&mut cfg, Severity::Allow, allows.next().unwrap()),
+        "denies" => set_warning(&mut cfg, Severity::Error, denies.next().unwrap()),
+        "warns" => set_warning(&mut cfg, Severity::Warning, warns.next().unwrap()),
         _ => unreachable!(),
       }
     }
   }
   cfg
 }
+
+#[test]
+fn shell_completions() {
+  use clap_complete::Shell;
+
+  for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+    let mut script = vec![];
+    clap_complete::generate(shell, &mut Cli::command(), "bend", &mut script);
+    let script = String::from_utf8(script).unwrap();
+    // Completes the subcommands, and the values of the optimization and warning options where the shell can.
+    let values: &[&str] =
+      if shell == Shell::PowerShell { &[] } else { &["no-merge", "linearize-matches-alt", "unused-definition"] };
+    for word in ["run-c", "gen-hvm", "max-rewrites"].iter().chain(values) {
+      assert!(script.contains(word), "The {shell} completions don't have '{word}'");
+    }
+  }
+}
@@ -0,0 +1,632 @@
+use crate::{
+  diagnostics::{Diagnostics, DiagnosticsConfig, ErrorType, ERR_INDENT_SIZE},
+  fun::{book_to_nets, term_to_net::Labels, Book, Ctx, DefOpt, Name, Term},
+  hvm::{
+    add_recursive_priority::add_recursive_priority, check_net_size::check_net_sizes, inline::inline,
+    merge_defs::merge_defs, mutual_recursion, pre_reduce::pre_reduce, source_map::SourceMap,
+    tail_calls::inline_tail_calls,
+  },
+  CompileOpts, CompileResult, OptLevel, Stage, StageHook, ENTRY_POINT,
+};
+use std::{fmt::Display, time::Duration};
+
+/// A step of the compilation pipeline, that checks or transforms the program.
+///
+/// Library users can implement it to run their own passes, adding them to a [`Pipeline`].
+pub trait Pass {
+  /// The name that identifies the pass in the pipeline.
+  fn name(&self) -> &str;
+
+  /// Whether the pass runs when compiling with these options.
+  fn enabled(&self, _opts: &CompileOpts) -> bool {
+    true
+  }
+
+  /// Runs the pass. Returning an error stops the compilation.
+  fn run(&self, program: &mut Program) -> Result<(), Diagnostics>;
+}
+
+impl<P: Pass + ?Sized> Pass for Box<P> {
+  fn name(&self) -> &str {
+    P::name(self)
+  }
+
+  fn enabled(&self, opts: &CompileOpts) -> bool {
+    P::enabled(self, opts)
+  }
+
+  fn run(&self, program: &mut Program) -> Result<(), Diagnostics> {
+    P::run(self, program)
+  }
+}
+
+/// The program being compiled, as the passes of the [`Pipeline`] see it.
+pub struct Program<'a> {
+  /// The lambda-term level program and the diagnostics found so far.
+  pub ctx: Ctx<'a>,
+  /// The compiled nets, empty until the `to-nets` pass runs.
+  pub nets: hvmc::ast::Book,
+  /// The labels of the compiled nets, used to read back their results.
+  pub labels: Labels,
+  pub opts: CompileOpts,
+  /// The arguments of the entrypoint, taken by the `apply-args` pass.
+  pub args: Option<Vec<Term>>,
+  hook: &'a mut StageHook<'a>,
+  timings: Vec<PassTiming>,
+}
+
+/// How long a pass of the [`Pipeline`] took, and the size of the program right after it.
+#[derive(Debug, Clone)]
+pub struct PassTiming {
+  pub name: String,
+  pub time: Duration,
+  /// The number of definitions of the lambda-term level program.
+  pub defs: usize,
+  /// The number of compiled nets, zero until the `to-nets` pass runs.
+  pub nets: usize,
+}
+
+impl Program<'_> {
+  /// Passes the program to the stage hook of the compilation.
+  pub fn emit(&mut self, stage: Stage) {
+    let program: &dyn Display = if stage == Stage::Hvm { &self.nets } else { self.ctx.book };
+    (self.hook)(stage, program);
+  }
+}
+
+/// The ordered list of passes that compile a program.
+///
+/// The default pipeline is the one used by [`crate::compile_book`]. Its passes can be reordered,
+/// removed or extended with new ones before compiling with [`Pipeline::compile`].
+/// A name can appear more than once, when the same pass runs at different points.
+pub struct Pipeline {
+  /// The lambda-term level passes, the only ones run by [`crate::desugar_book`].
+  pub desugar: Vec<Box<dyn Pass>>,
+  /// The passes that compile the desugared program to nets and optimize them.
+  pub compile: Vec<Box<dyn Pass>>,
+}
+
+impl Pipeline {
+  /// All the passes, in the order they run.
+  pub fn passes(&self) -> impl Iterator<Item = &dyn Pass> {
+    self.desugar.iter().chain(&self.compile).map(Box::as_ref)
+  }
+
+  /// Removes the passes with this name. Returns whether there was any.
+  pub fn remove(&mut self, name: &str) -> bool {
+    let len = self.desugar.len() + self.compile.len();
+    self.desugar.retain(|pass| pass.name() != name);
+    self.compile.retain(|pass| pass.name() != name);
+    self.desugar.len() + self.compile.len() != len
+  }
+
+  /// Inserts a pass right before the first one with the given name. Returns whether it was found.
+  pub fn insert_before(&mut self, name: &str, pass: impl Pass + 'static) -> bool {
+    self.insert_at(name, 0, Box::new(pass))
+  }
+
+  /// Inserts a pass right after the first one with the given name. Returns whether it was found.
+  pub fn insert_after(&mut self, name: &str, pass: impl Pass + 'static) -> bool {
+    self.insert_at(name, 1, Box::new(pass))
+  }
+
+  fn insert_at(&mut self, name: &str, offset: usize, pass: Box<dyn Pass>) -> bool {
+    for passes in [&mut self.desugar, &mut self.compile] {
+      if let Some(idx) = passes.iter().position(|pass| pass.name() == name) {
+        passes.insert(idx + offset, pass);
+        return true;
+      }
+    }
+    false
+  }
+
+  /// Runs the desugaring passes, stopping before the program is compiled to nets.
+  pub fn desugar(
+    &self,
+    book: &mut Book,
+    opts: CompileOpts,
+    diagnostics_cfg: DiagnosticsConfig,
+    args: Option<Vec<Term>>,
+    hook: &mut StageHook,
+  ) -> Result<Diagnostics, Diagnostics> {
+    let mut program = Program::new(book, opts, diagnostics_cfg, args, hook);
+    run_passes(&self.desugar, &mut program)?;
+    Ok(program.ctx.info)
+  }
+
+  /// Runs all the passes, compiling the program to nets.
+  pub fn compile(
+    &self,
+    book: &mut Book,
+    opts: CompileOpts,
+    diagnostics_cfg: DiagnosticsConfig,
+    args: Option<Vec<Term>>,
+    hook: &mut StageHook,
+  ) -> Result<CompileResult, Diagnostics> {
+    let mut program = Program::new(book, opts, diagnostics_cfg, args, hook);
+    run_passes(&self.desugar, &mut program)?;
+    run_passes(&self.compile, &mut program)?;
+    Ok(CompileResult {
+      core_book: program.nets,
+      labels: program.labels,
+      diagnostics: program.ctx.info,
+      timings: program.timings,
+      source_map: SourceMap::new(&program.nets, program.ctx.book, &program.ctx.info.def_spans),
+    })
+  }
+
+  /// Lists the passes one per line, marking the ones that these options disable.
+  pub fn display(&self, opts: &CompileOpts) -> String {
+    let mut out = String::new();
+    for (title, passes) in [("desugar", &self.desugar), ("compile", &self.compile)] {
+      out.push_str(&format!("{title}:\n"));
+      for pass in passes {
+        let disabled = if pass.enabled(opts) { "" } else { " (disabled)" };
+        out.push_str(&format!("  {}{disabled}\n", pass.name()));
+      }
+    }
+    out
+  }
+}
+
+impl<'a> Program<'a> {
+  fn new(
+    book: &'a mut Book,
+    opts: CompileOpts,
+    diagnostics_cfg: DiagnosticsConfig,
+    args: Option<Vec<Term>>,
+    hook: &'a mut StageHook,
+  ) -> Self {
+    let ctx = Ctx::new(book, diagnostics_cfg);
+    Program { ctx, nets: Default::default(), labels: Labels::default(), opts, args, hook, timings: vec![] }
+  }
+}
+
+fn run_passes(passes: &[Box<dyn Pass>], program: &mut Program) -> Result<(), Diagnostics> {
+  for pass in passes {
+    if pass.enabled(&program.opts) {
+      let time = timed(|| pass.run(program))?;
+      program.timings.push(PassTiming {
+        name: pass.name().to_string(),
+        time,
+        defs: program.ctx.book.defs.len(),
+        nets: program.nets.len(),
+      });
+    }
+  }
+  Ok(())
+}
+
+/// Runs `f` and returns how long it took.
+///
+/// `Instant` panics on `wasm32-unknown-unknown`, which has no clock, so there the time is always zero.
+fn timed<E>(f: impl FnOnce() -> Result<(), E>) -> Result<Duration, E> {
+  #[cfg(not(target_arch = "wasm32"))]
+  {
+    let start = std::time::Instant::now();
+    f()?;
+    Ok(start.elapsed())
+  }
+  #[cfg(target_arch = "wasm32")]
+  {
+    f()?;
+    Ok(Duration::ZERO)
+  }
+}
+
+type RunFn = dyn Fn(&mut Program) -> Result<(), Diagnostics>;
+
+/// A pass of the default pipeline.
+struct BuiltinPass {
+  name: &'static str,
+  enabled: fn(&CompileOpts) -> bool,
+  run: Box<RunFn>,
+}
+
+impl Pass for BuiltinPass {
+  fn name(&self) -> &str {
+    self.name
+  }
+
+  fn enabled(&self, opts: &CompileOpts) -> bool {
+    (self.enabled)(opts)
+  }
+
+  fn run(&self, program: &mut Program) -> Result<(), Diagnostics> {
+    (self.run)(program)
+  }
+}
+
+pub(crate) fn pass(
+  name: &'static str,
+  run: impl Fn(&mut Program) -> Result<(), Diagnostics> + 'static,
+) -> Box<dyn Pass> {
+  opt_pass(name, |_| true, run)
+}
+
+/// A pass that only runs when `enabled` by the options.
+fn opt_pass(
+  name: &'static str,
+  enabled: fn(&CompileOpts) -> bool,
+  run: impl Fn(&mut Program) -> Result<(), Diagnostics> + 'static,
+) -> Box<dyn Pass> {
+  Box::new(BuiltinPass { name, enabled, run: Box::new(run) })
+}
+
+/// A checking pass that recovers from errors, so that we can
+/// report the errors of all the definitions at once.
+fn check(name: &'static str, check: fn(&mut Ctx) -> Result<(), Diagnostics>) -> Box<dyn Pass> {
+  pass(name, move |p| {
+    p.ctx.recover(check);
+    Ok(())
+  })
+}
+
+fn emit(name: &'static str, stage: Stage) -> Box<dyn Pass> {
+  pass(name, move |p| {
+    p.emit(stage);
+    Ok(())
+  })
+}
+
+/// Stops the compilation if any definition failed.
+fn stop_on_errors(p: &mut Program) -> Result<(), Diagnostics> {
+  if p.ctx.info.has_errors() {
+    return Err(std::mem::take(&mut p.ctx.info));
+  }
+  Ok(())
+}
+
+/// Enables for the whole program the optimizations that some definition enables with an `#!opt(..)`
+/// pragma, disabling them for the other user definitions, the ones that don't set them.
+fn apply_def_opts(p: &mut Program) -> Result<(), Diagnostics> {
+  let book = &mut *p.ctx.book;
+  for opt in DefOpt::ALL {
+    let enabled_by_def = book.defs.keys().any(|nam| book.def_opts.get(nam, opt) == Some(true));
+    if !enabled_by_def || is_enabled(&p.opts, opt) {
+      continue;
+    }
+    enable(&mut p.opts, opt);
+    for def in book.defs.values().filter(|def| !def.builtin) {
+      if book.def_opts.get(&def.name, opt).is_none() {
+        book.def_opts.defs.entry(def.name.clone()).or_default().push((opt, false));
+      }
+    }
+  }
+  Ok(())
+}
+
+fn is_enabled(opts: &CompileOpts, opt: DefOpt) -> bool {
+  match opt {
+    DefOpt::Eta => opts.eta,
+    DefOpt::Prune => opts.prune,
+    DefOpt::LinearizeMatches => opts.linearize_matches.enabled(),
+    DefOpt::ConstFold => opts.const_fold,
+    DefOpt::ShareCommonTerms => opts.share_common_terms,
+    DefOpt::FloatCombinators => opts.float_combinators,
+    DefOpt::Merge => opts.merge,
+    DefOpt::MergeDefs => opts.merge_defs,
+    DefOpt::Inline => opts.inline,
+    DefOpt::TailCalls => opts.tail_calls,
+    DefOpt::PreReduce => opts.pre_reduce,
+  }
+}
+
+fn enable(opts: &mut CompileOpts, opt: DefOpt) {
+  match opt {
+    DefOpt::Eta => opts.eta = true,
+    DefOpt::Prune => opts.prune = true,
+    DefOpt::LinearizeMatches => opts.linearize_matches = OptLevel::Enabled,
+    DefOpt::ConstFold => opts.const_fold = true,
+    DefOpt::ShareCommonTerms => opts.share_common_terms = true,
+    DefOpt::FloatCombinators => opts.float_combinators = true,
+    DefOpt::Merge => opts.merge = true,
+    DefOpt::MergeDefs => opts.merge_defs = true,
+    DefOpt::Inline => opts.inline = true,
+    DefOpt::TailCalls => opts.tail_calls = true,
+    DefOpt::PreReduce => opts.pre_reduce = true,
+  }
+}
+
+/// Eta-reduces the nets of the definitions that don't disable it with a pragma.
+fn eta_reduce(p: &mut Program) -> Result<(), Diagnostics> {
+  for (nam, net) in p.nets.iter_mut() {
+    if p.ctx.book.def_opts.allows(&Name::new(nam), DefOpt::Eta) {
+      net.eta_reduce();
+    }
+  }
+  Ok(())
+}
+
+impl Default for Pipeline {
+  fn default() -> Self {
+    let desugar = vec![
+      emit("emit-ast", Stage::Ast),
+      pass("apply-def-opts", apply_def_opts),
+      opt_pass(
+        "set-rand-seed",
+        |opts| opts.seed.is_some(),
+        |p| {
+          p.ctx.book.set_rand_seed(p.opts.seed.unwrap_or_default());
+          Ok(())
+        },
+      ),
+      pass("eval-consts", |p| {
+        let opts = &p.opts;
+        p.ctx.recover(|ctx| ctx.eval_consts(opts));
+        Ok(())
+      }),
+      pass("check-shared-names", |p| {
+        p.ctx.check_shared_names();
+        Ok(())
+      }),
+      pass("set-entrypoint", |p| {
+        if let Some(entrypoint) = &p.opts.entrypoint {
+          p.ctx.book.entrypoint = Some(entrypoint.clone());
+        }
+        p.ctx.set_entrypoint();
+        Ok(())
+      }),
+      pass("encode-adts", |p| {
+        p.ctx.book.encode_adts();
+        Ok(())
+      }),
+      check("lift-local-defs", |ctx| ctx.lift_local_defs()),
+      check("fix-match-defs", |ctx| ctx.fix_match_defs()),
+      check("check-shadowed-vars", |ctx| ctx.check_shadowed_vars()),
+      check("check-unused-vars", |ctx| ctx.check_unused_vars()),
+      check("check-implicit-dups", |ctx| ctx.check_implicit_dups()),
+      pass("apply-args", |p| {
+        let args = p.args.take();
+        p.ctx.recover(|ctx| ctx.apply_args(args));
+        Ok(())
+      }),
+      opt_pass(
+        "encode-io",
+        |opts| opts.io,
+        |p| {
+          p.ctx.book.encode_io();
+          Ok(())
+        },
+      ),
+      check("desugar-open", |ctx| ctx.desugar_open()),
+      check("desugar-let-ctrs", |ctx| ctx.desugar_let_ctrs()),
+      check("desugar-nested-matches", |ctx| ctx.desugar_nested_matches()),
+      pass("encode-builtins", |p| {
+        p.ctx.book.encode_builtins();
+        Ok(())
+      }),
+      check("resolve-refs", |ctx| ctx.resolve_refs()),
+      check("desugar-match-defs", |ctx| ctx.desugar_match_defs()),
+      check("fix-match-terms", |ctx| ctx.fix_match_terms()),
+      check("desugar-records", |ctx| ctx.desugar_records()),
+      check("desugar-bend", |ctx| ctx.desugar_bend()),
+      check("desugar-fold", |ctx| ctx.desugar_fold()),
+      check("desugar-do-blocks", |ctx| ctx.desugar_do_blocks()),
+      check("check-unguarded-recursion", |ctx| ctx.check_unguarded_recursion()),
+      check("check-unbound-vars", |ctx| ctx.check_unbound_vars()),
+      check("check-types", |ctx| ctx.check_types()),
+      // Stop before the transformations if any definition failed, since
+      // the placeholders would trigger spurious unused definition warnings.
+      pass("stop-on-errors", stop_on_errors),
+      pass("inline-consts", |p| p.ctx.inline_consts()),
+      opt_pass(
+        "prune-ctrs",
+        |opts| opts.prune,
+        |p| {
+          p.ctx.book.prune_ctrs();
+          Ok(())
+        },
+      ),
+      pass("make-var-names-unique", |p| {
+        p.ctx.book.make_var_names_unique();
+        Ok(())
+      }),
+      opt_pass(
+        "const-fold",
+        |opts| opts.const_fold,
+        |p| {
+          p.ctx.book.const_fold();
+          Ok(())
+        },
+      ),
+      opt_pass(
+        "share-common-terms",
+        |opts| opts.share_common_terms,
+        |p| {
+          p.ctx.book.share_common_terms();
+          Ok(())
+        },
+      ),
+      // Auto match linearization
+      opt_pass(
+        "linearize-matches",
+        |opts| opts.linearize_matches.enabled(),
+        |p| {
+          match p.opts.linearize_matches {
+            OptLevel::Disabled => (),
+            OptLevel::Alt => p.ctx.book.linearize_match_binds(),
+            OptLevel::Enabled => p.ctx.book.linearize_matches(),
+          }
+          Ok(())
+        },
+      ),
+      // Manual match linearization
+      pass("linearize-match-with", |p| {
+        p.ctx.book.linearize_match_with();
+        Ok(())
+      }),
+      pass("encode-matches", |p| {
+        p.ctx.book.encode_matches();
+        // sanity check
+        p.ctx.check_unbound_vars()
+      }),
+      pass("linearize-vars", |p| {
+        p.ctx.book.make_var_names_unique();
+        p.ctx.book.apply_use();
+        p.ctx.book.make_var_names_unique();
+        p.ctx.book.linearize_vars();
+        // sanity check
+        p.ctx.check_unbound_vars()
+      }),
+      emit("emit-linearized", Stage::Linearized),
+      // Optimizing passes
+      opt_pass(
+        "float-combinators",
+        |opts| opts.float_combinators,
+        |p| {
+          p.ctx.book.float_combinators(p.opts.float_threshold);
+          Ok(())
+        },
+      ),
+      pass("prune", |p| {
+        p.ctx.prune(p.opts.prune);
+        Ok(())
+      }),
+      opt_pass(
+        "merge",
+        |opts| opts.merge,
+        |p| {
+          p.ctx.book.merge_definitions();
+          Ok(())
+        },
+      ),
+      pass("make-var-names-unique", |p| {
+        p.ctx.book.make_var_names_unique();
+        Ok(())
+      }),
+      pass("stop-on-errors", stop_on_errors),
+      emit("emit-desugared", Stage::Desugared),
+    ];
+
+    let compile = vec![
+      pass("to-nets", |p| {
+        (p.nets, p.labels) = book_to_nets(p.ctx.book, &mut p.ctx.info)?;
+        Ok(())
+      }),
+      opt_pass("eta", |opts| opts.eta, eta_reduce),
+      pass("check-cycles", |p| mutual_recursion::check_cycles(&p.nets, &mut p.ctx.info)),
+      opt_pass("eta", |opts| opts.eta, eta_reduce),
+      opt_pass(
+        "pre-reduce",
+        |opts| opts.pre_reduce,
+        |p| {
+          let CompileOpts { pre_reduce_rewrite_limit, pre_reduce_memory, .. } = p.opts;
+          pre_reduce(&mut p.nets, pre_reduce_rewrite_limit, pre_reduce_memory, &p.ctx.book.def_opts);
+          Ok(())
+        },
+      ),
+      opt_pass(
+        "tail-calls",
+        |opts| opts.tail_calls,
+        |p| {
+          inline_tail_calls(&mut p.nets, &p.ctx.book.def_opts);
+          Ok(())
+        },
+      ),
+      opt_pass(
+        "inline",
+        |opts| opts.inline,
+        |p| {
+          p.ctx.info.start_pass();
+          if let Err(e) = inline(&mut p.nets, p.opts.inline_threshold, &p.ctx.book.def_opts) {
+            p.ctx
+              .info
+              .add_book_error(format!("During inlining:\n{:ERR_INDENT_SIZE$}{}", "", e), ErrorType::Inlining);
+          }
+          p.ctx.info.fatal(())
+        },
+      ),
+      opt_pass(
+        "merge-defs",
+        |opts| opts.merge_defs,
+        |p| {
+          merge_defs(&mut p.nets, &p.ctx.book.def_opts);
+          Ok(())
+        },
+      ),
+      opt_pass(
+        "prune-nets",
+        |opts| opts.prune,
+        |p| {
+          // The definitions kept by a pragma are pruned like the entrypoint.
+          let def_opts = &p.ctx.book.def_opts;
+          let kept = p.nets.keys().filter(|nam| def_opts.get(&Name::new(*nam), DefOpt::Prune) == Some(false));
+          let entrypoints = std::iter::once(ENTRY_POINT.to_string()).chain(kept.cloned()).collect::<Vec<_>>();
+          p.nets.prune(&entrypoints);
+          Ok(())
+        },
+      ),
+      pass("check-net-sizes", |p| check_net_sizes(&p.nets, &mut p.ctx.info)),
+      pass("add-recursive-priority", |p| {
+        add_recursive_priority(&mut p.nets);
+        Ok(())
+      }),
+      emit("emit-hvm", Stage::Hvm),
+    ];
+
+    Pipeline { desugar, compile }
+  }
+}
+
+#[test]
+fn custom_pipeline() {
+  use crate::fun::load_book::do_parse_book;
+  use std::path::Path;
+
+  struct RenameMain;
+  impl Pass for RenameMain {
+    fn name(&self) -> &str {
+      "rename-main"
+    }
+
+    fn run(&self, program: &mut Program) -> Result<(), Diagnostics> {
+      let net = program.nets.remove("main").unwrap();
+      program.nets.insert("start".to_string(), net);
+      Ok(())
+    }
+  }
+
+  let compile = |pipeline: &Pipeline| {
+    let mut book = do_parse_book("main = (+ 1 2)", Path::new("pipeline"), Book::builtins()).unwrap();
+    let opts = CompileOpts::default();
+    pipeline.compile(&mut book, opts, DiagnosticsConfig::default(), None, &mut |_, _| {}).unwrap().core_book
+  };
+
+  let mut pipeline = Pipeline::default();
+  assert!(pipeline.passes().any(|pass| pass.name() == "float-combinators"));
+  assert_eq!(compile(&pipeline).to_string(), "@main = a\n  & $(2 a) ~ [+1]");
+
+  assert!(pipeline.insert_after("add-recursive-priority", RenameMain));
+  assert!(pipeline.remove("eta"));
+  assert!(!pipeline.remove("missing"));
+  assert!(!pipeline.passes().any(|pass| pass.name() == "eta"));
+  assert_eq!(compile(&pipeline).to_string(), "@start = a\n  & $(2 a) ~ [+1]");
+
+  // Without the builtin functions, the shifts can't be compiled.
+  let mut pipeline = Pipeline::default();
+  assert!(pipeline.remove("encode-builtins"));
+  let mut book = do_parse_book("main = (<< 1 4)", Path::new("pipeline"), Book::builtins()).unwrap();
+  let opts = CompileOpts::default();
+  let res = pipeline.compile(&mut book, opts, DiagnosticsConfig::default(), None, &mut |_, _| {});
+  assert!(res.err().unwrap().to_string().contains("BEND0016"));
+}
+
+#[test]
+fn compile_timings() {
+  use crate::{compile_book, fun::load_book::do_parse_book};
+  use itertools::Itertools;
+  use std::path::Path;
+
+  let mut book = do_parse_book("main = λx (+ x 1)", Path::new("timings"), Book::builtins()).unwrap();
+  let opts = CompileOpts::default();
+  let res = compile_book(&mut book, opts.clone(), DiagnosticsConfig::default(), None).unwrap();
+
+  // Only the enabled passes are timed, in the order they ran.
+  let enabled = Pipeline::default();
+  let enabled = enabled.passes().filter(|pass| pass.enabled(&opts)).map(|pass| pass.name().to_string());
+  assert_eq!(res.timings.iter().map(|pass| pass.name.clone()).collect_vec(), enabled.collect_vec());
+  let to_nets = res.timings.iter().position(|pass| pass.name == "to-nets").unwrap();
+  assert!(res.timings[.. to_nets].iter().all(|pass| pass.nets == 0));
+  assert_eq!(res.timings.last().unwrap().nets, res.core_book.len());
+}
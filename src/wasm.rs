@@ -0,0 +1,89 @@
+//! JavaScript bindings for compiling and running programs in the browser, enabled by the `wasm` feature.
+//!
+//! Diagnostics are given in the JSON message format, one object per line.
+//! When a program can't be compiled, the functions throw its error diagnostics.
+
+use crate::{
+  compiler::{Compiler, RunResult, Runtime},
+  diagnostics::{Diagnostics, DiagnosticsConfig, MessageFormat, Severity},
+  RunOpts,
+};
+use wasm_bindgen::prelude::*;
+
+/// The name given to the source code in diagnostics.
+const SOURCE_NAME: &str = "<playground>";
+
+#[wasm_bindgen(getter_with_clone)]
+pub struct CompileOutput {
+  /// The compiled program, in the hvm textual format.
+  pub hvmc: String,
+  pub diagnostics: String,
+}
+
+#[wasm_bindgen(getter_with_clone)]
+pub struct RunOutput {
+  /// The normal form of the program.
+  pub result: String,
+  /// The statistics of the evaluation.
+  pub stats: String,
+  pub diagnostics: String,
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Default)]
+pub struct RunOptions {
+  /// Don't duplicate the readback of terms shared by superpositions.
+  pub linear: bool,
+  /// Show the result with indentation.
+  pub pretty: bool,
+  /// The definition to start from, instead of main.
+  pub entrypoint: Option<String>,
+}
+
+#[wasm_bindgen]
+impl RunOptions {
+  #[wasm_bindgen(constructor)]
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
+
+/// Compiles a program to hvm.
+#[wasm_bindgen]
+pub fn compile(source: &str) -> Result<CompileOutput, String> {
+  let warnings = DiagnosticsConfig { message_format: MessageFormat::Json, ..DiagnosticsConfig::default() };
+  let res = Compiler::new()
+    .warnings(warnings)
+    .load_source(SOURCE_NAME, source)
+    .compile()
+    .map_err(|e| json_error(e, warnings))?;
+  Ok(CompileOutput { hvmc: res.core_book.to_string(), diagnostics: res.diagnostics.to_string() })
+}
+
+/// Compiles and runs a program, with a sequential evaluator that runs inside of WebAssembly.
+#[wasm_bindgen]
+pub fn run(source: &str, opts: RunOptions) -> Result<RunOutput, String> {
+  let warnings = DiagnosticsConfig {
+    message_format: MessageFormat::Json,
+    ..DiagnosticsConfig::new(Severity::Allow, false)
+  };
+  let mut compiler = Compiler::new()
+    .warnings(warnings)
+    .run_opts(RunOpts { linear_readback: opts.linear, pretty: opts.pretty, ..RunOpts::default() })
+    .runtime(Runtime::Interpreter)
+    .load_source(SOURCE_NAME, source);
+  if let Some(entrypoint) = opts.entrypoint {
+    compiler = compiler.entrypoint(entrypoint);
+  }
+  let RunResult { term, stats, diagnostics, .. } = compiler.run().map_err(|e| json_error(e, warnings))?;
+
+  let term = term.unwrap();
+  let result = if opts.pretty { term.display_pretty(0).to_string() } else { term.to_string() };
+  Ok(RunOutput { result, stats, diagnostics: diagnostics.to_string() })
+}
+
+fn json_error(err: impl Into<Diagnostics>, config: DiagnosticsConfig) -> String {
+  let mut diagnostics = err.into();
+  diagnostics.config = config;
+  diagnostics.to_string()
+}
@@ -1,9 +1,11 @@
 use bend::{
-  compile_book, desugar_book,
+  codegen::{c::book_to_c, dot::book_to_dot},
+  compile_book,
+  compiler::Compiler,
+  desugar_book,
   diagnostics::{Diagnostics, DiagnosticsConfig, Severity},
-  fun::{load_book::do_parse_book, net_to_term::net_to_term, term_to_net::Labels, Book, Ctx, Name},
-  net::hvmc_to_net::hvmc_to_net,
-  run_book, CompileOpts, RunOpts,
+  fun::{load_book::do_parse_book, term_to_net::Labels, Book, Ctx, Name},
+  readback_hvm_net, run_book, CompileOpts, RunOpts,
 };
 use insta::assert_snapshot;
 use itertools::Itertools;
@@ -167,23 +169,27 @@ fn run_file() {
 }
 
 #[test]
-#[ignore = "while lazy execution is not implemented for hvm32"]
 fn run_lazy() {
-  run_golden_test_dir(function_name!(), &|_code, _path| {
-    todo!()
-    /* let _guard = RUN_MUTEX.lock().unwrap();
-    let book = do_parse_book(code, path)?;
-    let compile_opts = CompileOpts::default_lazy();
+  use bend::compiler::Runtime;
+
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let _guard = RUN_MUTEX.lock().unwrap();
+    let book = do_parse_book(code, path, Book::builtins())?;
     let diagnostics_cfg = DiagnosticsConfig {
       recursion_cycle: Severity::Allow,
+      unguarded_recursion: Severity::Allow,
       unused_definition: Severity::Allow,
       ..DiagnosticsConfig::new(Severity::Error, true)
     };
-    let run_opts = RunOpts::lazy();
-
-    let (term, _, diags) = run_book(book, run_opts, compile_opts, diagnostics_cfg, None)?;
-    let res = format!("{diags}{term}");
-    Ok(res) */
+    let run_opts = RunOpts { lazy: true, ..RunOpts::default() };
+
+    let res = Compiler::new()
+      .book(book)
+      .warnings(diagnostics_cfg)
+      .runtime(Runtime::Interpreter)
+      .run_opts(run_opts)
+      .run()?;
+    Ok(format!("{}{}", res.diagnostics, res.term.unwrap()))
   })
 }
 
@@ -192,9 +198,7 @@ fn readback_lnet() {
   run_golden_test_dir(function_name!(), &|code, _| {
     let net = hvmc::ast::Net::from_str(code)?;
     let book = Book::default();
-    let compat_net = hvmc_to_net(&net);
-    let mut diags = Diagnostics::default();
-    let term = net_to_term(&compat_net, &book, &Labels::default(), false, &mut diags);
+    let (term, diags) = readback_hvm_net(&net, &book, &Labels::default(), false, false);
     Ok(format!("{}{}", diags, term))
   })
 }
@@ -202,7 +206,8 @@ fn readback_lnet() {
 #[test]
 fn simplify_matches() {
   run_golden_test_dir(function_name!(), &|code, path| {
-    let diagnostics_cfg = DiagnosticsConfig::new(Severity::Error, true);
+    let diagnostics_cfg =
+      DiagnosticsConfig { unreachable_arm: Severity::Allow, ..DiagnosticsConfig::new(Severity::Error, true) };
     let mut book = do_parse_book(code, path, Book::builtins())?;
     let mut ctx = Ctx::new(&mut book, diagnostics_cfg);
 
@@ -281,6 +286,7 @@ fn desugar_file() {
     let compile_opts = CompileOpts::default();
     let diagnostics_cfg = DiagnosticsConfig {
       unused_definition: Severity::Allow,
+      unguarded_recursion: Severity::Allow,
       ..DiagnosticsConfig::new(Severity::Error, true)
     };
     let mut book = do_parse_book(code, path, Book::builtins())?;
@@ -318,21 +324,19 @@ fn hangs() {
 fn compile_entrypoint() {
   run_golden_test_dir(function_name!(), &|code, path| {
     let mut book = do_parse_book(code, path, Book::builtins())?;
-    book.entrypoint = Some(Name::new("foo"));
+    let compile_opts = CompileOpts { entrypoint: Some(Name::new("foo")), ..CompileOpts::default() };
     let diagnostics_cfg = DiagnosticsConfig { ..DiagnosticsConfig::new(Severity::Error, true) };
-    let res = compile_book(&mut book, CompileOpts::default(), diagnostics_cfg, None)?;
+    let res = compile_book(&mut book, compile_opts, diagnostics_cfg, None)?;
     Ok(format!("{}{}", res.diagnostics, res.core_book))
   })
 }
 
 #[test]
-#[ignore = "while execution with different entrypoints is not implemented for hvm32"]
 fn run_entrypoint() {
   run_golden_test_dir(function_name!(), &|code, path| {
     let _guard = RUN_MUTEX.lock().unwrap();
-    let mut book = do_parse_book(code, path, Book::builtins())?;
-    book.entrypoint = Some(Name::new("foo"));
-    let compile_opts = CompileOpts::default().set_all();
+    let book = do_parse_book(code, path, Book::builtins())?;
+    let compile_opts = CompileOpts { entrypoint: Some(Name::new("foo")), ..CompileOpts::default() }.set_all();
     let diagnostics_cfg = DiagnosticsConfig { ..DiagnosticsConfig::new(Severity::Error, true) };
     let (term, _, diags) = run_book(book, RunOpts::default(), compile_opts, diagnostics_cfg, None)?;
     let res = format!("{diags}{term}");
@@ -360,6 +364,80 @@ fn cli() {
   })
 }
 
+#[test]
+fn gen_c() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let _guard = RUN_MUTEX.lock().unwrap();
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let compile_opts = CompileOpts::default();
+    let diagnostics_cfg = DiagnosticsConfig { ..DiagnosticsConfig::new(Severity::Error, true) };
+    let res = compile_book(&mut book, compile_opts, diagnostics_cfg, None)?;
+    let c_code = book_to_c(&res.core_book)?;
+
+    let out_dir = std::env::temp_dir().join("bend_gen_c");
+    std::fs::create_dir_all(&out_dir).map_err(|e| e.to_string())?;
+    let c_path = out_dir.join("main.c");
+    let bin_path = out_dir.join("main");
+    std::fs::write(&c_path, c_code).map_err(|e| e.to_string())?;
+    let cc = std::process::Command::new("cc")
+      .arg("-O2")
+      .arg("-o")
+      .arg(&bin_path)
+      .arg(&c_path)
+      .arg("-lm")
+      .output()
+      .map_err(|e| format!("While running cc: {e}"))?;
+    assert!(cc.status.success(), "{}", String::from_utf8_lossy(&cc.stderr));
+
+    let out = std::process::Command::new(&bin_path).output().map_err(|e| e.to_string())?;
+    let out = String::from_utf8_lossy(&out.stdout);
+    let result = out
+      .lines()
+      .find_map(|line| line.strip_prefix("Result: "))
+      .ok_or(format!("No result in output:\n{out}"))?;
+    let net = hvmc::ast::Net::from_str(result)?;
+    let (term, diags) = readback_hvm_net(&net, &book, &res.labels, false, false);
+    Ok(format!("{}{}{}", res.diagnostics, diags, term))
+  })
+}
+
+#[test]
+fn gen_dot() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let compile_opts = CompileOpts::default();
+    let diagnostics_cfg = DiagnosticsConfig::new(Severity::Error, true);
+    let res = compile_book(&mut book, compile_opts, diagnostics_cfg, None)?;
+    Ok(format!("{}{}", res.diagnostics, book_to_dot(&res.core_book)))
+  })
+}
+
+#[test]
+fn run_interpreter() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let mut book = do_parse_book(code, path, Book::builtins())?;
+    let compile_opts = CompileOpts::default();
+    let diagnostics_cfg = DiagnosticsConfig { ..DiagnosticsConfig::new(Severity::Error, true) };
+    let res = compile_book(&mut book, compile_opts, diagnostics_cfg, None)?;
+    let (net, _) = bend::hvm::interpreter::normalize(&res.core_book)?;
+    let (term, diags) = readback_hvm_net(&net, &book, &res.labels, false, false);
+    Ok(format!("{}{}{}", res.diagnostics, diags, term))
+  })
+}
+
+#[test]
+fn run_stream() {
+  run_golden_test_dir(function_name!(), &|code, path| {
+    let book = do_parse_book(code, path, Book::builtins())?;
+    let diagnostics_cfg = DiagnosticsConfig::new(Severity::Error, true);
+    let mut out = String::new();
+    let res = Compiler::new().warnings(diagnostics_cfg).book(book).stream(|elem, diags| {
+      writeln!(out, "{diags}{elem}").unwrap();
+    })?;
+    Ok(format!("{out}{}Rest: {}", res.diagnostics, res.term.unwrap()))
+  })
+}
+
 #[test]
 fn mutual_recursion() {
   run_golden_test_dir(function_name!(), &|code, path| {
@@ -445,3 +523,23 @@ fn scott_triggers_unused() {
     Ok(format!("{}{}", res.diagnostics, res.core_book))
   })
 }
+
+#[test]
+fn init_project() {
+  let dir = std::env::temp_dir().join(format!("bend_init_{}", std::process::id()));
+  let _ = std::fs::remove_dir_all(&dir);
+  let bend = |args: &[&std::ffi::OsStr]| {
+    std::process::Command::new(env!("CARGO_BIN_EXE_bend")).args(args).output().expect("Run command")
+  };
+
+  assert!(bend(&["init".as_ref(), dir.as_ref()]).status.success());
+  for file in ["bend.toml", "src/main.bend", "tests/main.bend"] {
+    assert!(dir.join(file).is_file(), "{file} was not created");
+  }
+  // The new project passes the checks, and is never overwritten.
+  let check = bend(&["check".as_ref(), dir.as_ref()]);
+  assert!(check.status.success(), "{}", String::from_utf8_lossy(&check.stderr));
+  assert!(!bend(&["init".as_ref(), dir.as_ref()]).status.success());
+
+  std::fs::remove_dir_all(&dir).unwrap();
+}